@@ -0,0 +1,142 @@
+//! Runtime loading of shared libraries: the C libraries behind the optional engines
+//! and `lflog`'s native plugins.
+
+use std::ffi::c_void;
+
+use crate::error::{Error, Result};
+
+/// A shared library opened with `dlopen`, closed when dropped.
+#[derive(Debug)]
+pub struct Library {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    handle: *mut c_void,
+}
+
+// The handle is only used for dlsym and dlclose, which are thread-safe
+unsafe impl Send for Library {}
+unsafe impl Sync for Library {}
+
+impl Library {
+    /// Open the first of `names` that loads, e.g. a versioned soname then the bare one.
+    pub fn open_first(names: &[&str]) -> Result<Self> {
+        let mut errors = Vec::with_capacity(names.len());
+        for name in names {
+            match Self::open(name) {
+                Ok(library) => return Ok(library),
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(Error::Other(errors.join("; ")))
+    }
+}
+
+#[cfg(unix)]
+impl Library {
+    /// Open the library at path `name`, or that the loader finds by that name.
+    pub fn open(name: &str) -> Result<Self> {
+        let c_name = std::ffi::CString::new(name)
+            .map_err(|_| Error::Other(format!("invalid library name: {:?}", name)))?;
+        let handle = unsafe { libc::dlopen(c_name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(Error::Other(format!(
+                "cannot load {}: {}",
+                name,
+                dl_error()
+            )));
+        }
+        Ok(Self { handle })
+    }
+
+    /// Look up the symbol `name` as a value of type `F`, a function pointer.
+    ///
+    /// # Safety
+    ///
+    /// `F` must be the type of the symbol, e.g. the `unsafe extern "C" fn` of its C
+    /// declaration, and the value must not be used once the library is dropped.
+    pub unsafe fn symbol<F: Copy>(&self, name: &str) -> Result<F> {
+        if size_of::<F>() != size_of::<*mut c_void>() {
+            return Err(Error::Other(format!(
+                "symbol {} cannot be read as a {}",
+                name,
+                std::any::type_name::<F>()
+            )));
+        }
+        let c_name = std::ffi::CString::new(name)
+            .map_err(|_| Error::Other(format!("invalid symbol name: {:?}", name)))?;
+        let symbol = unsafe { libc::dlsym(self.handle, c_name.as_ptr()) };
+        if symbol.is_null() {
+            return Err(Error::Other(format!(
+                "symbol {} not found: {}",
+                name,
+                dl_error()
+            )));
+        }
+        Ok(unsafe { std::mem::transmute_copy(&symbol) })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.handle) };
+    }
+}
+
+#[cfg(unix)]
+fn dl_error() -> String {
+    let error = unsafe { libc::dlerror() };
+    if error.is_null() {
+        "unknown error".to_string()
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(error) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+#[cfg(not(unix))]
+impl Library {
+    /// Fails: shared libraries are only loaded on Unix.
+    pub fn open(name: &str) -> Result<Self> {
+        Err(Error::Other(format!(
+            "cannot load {}: shared libraries are only loaded on Unix",
+            name
+        )))
+    }
+
+    /// # Safety
+    ///
+    /// See the Unix version; no library can be opened here.
+    pub unsafe fn symbol<F: Copy>(&self, name: &str) -> Result<F> {
+        Err(Error::Other(format!("symbol {} not found", name)))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_errors() {
+        let err = Library::open("lib\0c.so").unwrap_err();
+        assert!(err.to_string().contains("invalid library name"), "{}", err);
+        let err = Library::open_first(&["liblflog-missing.so.9", "liblflog-missing.so"])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("liblflog-missing.so.9") && err.contains("liblflog-missing.so:"),
+            "{}",
+            err
+        );
+
+        // The running process, which links libc
+        let library = Library::open_first(&["libc.so.6", "libc.so"]).unwrap();
+        type StrlenFn = unsafe extern "C" fn(*const std::ffi::c_char) -> usize;
+        let strlen: StrlenFn = unsafe { library.symbol("strlen") }.unwrap();
+        assert_eq!(unsafe { strlen(c"four".as_ptr()) }, 4);
+        let err = unsafe { library.symbol::<StrlenFn>("str\0len") }.unwrap_err();
+        assert!(err.to_string().contains("invalid symbol name"), "{}", err);
+        assert!(unsafe { library.symbol::<StrlenFn>("lflog_no_such_symbol") }.is_err());
+        assert!(unsafe { library.symbol::<[usize; 2]>("strlen") }.is_err());
+    }
+}
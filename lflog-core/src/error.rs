@@ -0,0 +1,54 @@
+#![allow(missing_docs)]
+//! Error types for lflog-core.
+//!
+//! The `lflog` crate converts these into its own `Error`, keeping the variant.
+
+use thiserror::Error;
+
+/// Parser-level error enum.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("UTF-8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+
+    #[error("Regex error: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("TOML parse error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("TOML serialize error: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    // Domain-specific errors
+    #[error("Macro parse error: {0}")]
+    MacroParse(String),
+
+    #[error("Macro expansion error: {0}")]
+    Macro(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Convenience result alias using the crate's `Error`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Convenience constructor for `Other` variant.
+    pub fn other<S: Into<String>>(s: S) -> Self {
+        Error::Other(s.into())
+    }
+}
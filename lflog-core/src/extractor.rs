@@ -0,0 +1,507 @@
+//! Per-line record extraction.
+//!
+//! A [`RecordExtractor`] turns one log line into field values. The regex [`Scanner`]
+//! is the default; profiles can select JSON, logfmt or delimited extraction with
+//! `format`, and library users can plug in a closure with [`FnExtractor`].
+//!
+//! [`Scanner`]: crate::scanner::Scanner
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::stats::ScanStats;
+use crate::types::FieldType;
+
+/// Extracts field values from a log line.
+pub trait RecordExtractor: fmt::Debug + Send + Sync {
+    /// Ordered names of the fields this extractor produces.
+    fn field_names(&self) -> &[String];
+
+    /// Type hints for fields, used for schema generation.
+    fn type_hints(&self) -> &HashMap<String, FieldType>;
+
+    /// Extract the value of every field, in [`field_names`](Self::field_names) order.
+    ///
+    /// `out` is cleared and filled on success. Missing fields are empty strings.
+    /// Returns `false` if the line is not a record of this format.
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool;
+
+    /// Extract like [`extract`](Self::extract), returning the index of the pattern
+    /// that matched the line.
+    ///
+    /// Extractors with a single line shape always report pattern 0.
+    fn extract_pattern<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        self.extract(line, out).then_some(0)
+    }
+
+    /// Extract like [`extract_pattern`](Self::extract_pattern) from a raw line.
+    ///
+    /// Lines that are not valid UTF-8 are not records by default; extractors that
+    /// can search bytes override this to skip validating the whole line.
+    fn extract_bytes<'a>(&self, line: &'a [u8], out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        self.extract_pattern(std::str::from_utf8(line).ok()?, out)
+    }
+
+    /// Check whether a line is a record without keeping its values.
+    fn is_match(&self, line: &str) -> bool {
+        self.extract(line, &mut Vec::new())
+    }
+
+    /// Whether `line` is noise to drop, neither a record nor an unmatched line.
+    fn is_excluded(&self, _line: &str) -> bool {
+        false
+    }
+
+    /// Counters that scans using this extractor add to, if it keeps any.
+    fn scan_stats(&self) -> Option<&ScanStats> {
+        None
+    }
+}
+
+/// Line format of a profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    /// The profile `pattern`, with macros expanded.
+    #[default]
+    Regex,
+    /// One JSON object per line.
+    Json,
+    /// `key=value` pairs, values optionally double-quoted.
+    Logfmt,
+    /// Columns separated by the profile `delimiter` (`,` by default).
+    Delimited,
+}
+
+/// A field read by a non-regex extractor.
+///
+/// For JSON, dotted names (`http.status`) address nested objects when no key with
+/// the full name exists. For delimited lines fields are taken by position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSpec {
+    pub name: String,
+    pub type_hint: Option<FieldType>,
+}
+
+impl FieldSpec {
+    /// A string field.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            type_hint: None,
+        }
+    }
+
+    /// A field with a type hint.
+    pub fn typed(name: impl Into<String>, type_hint: FieldType) -> Self {
+        Self {
+            name: name.into(),
+            type_hint: Some(type_hint),
+        }
+    }
+}
+
+/// Field names and type hints shared by the non-regex extractors.
+#[derive(Debug, Clone)]
+struct Fields {
+    names: Vec<String>,
+    type_hints: HashMap<String, FieldType>,
+}
+
+impl Fields {
+    fn new(specs: Vec<FieldSpec>) -> Self {
+        let mut names = Vec::with_capacity(specs.len());
+        let mut type_hints = HashMap::new();
+        for spec in specs {
+            if let Some(hint) = spec.type_hint {
+                type_hints.insert(spec.name.clone(), hint);
+            }
+            names.push(spec.name);
+        }
+        Self { names, type_hints }
+    }
+}
+
+/// Extracts fields from JSON object lines.
+///
+/// Strings are taken unescaped, `null` and missing keys are empty, and other values
+/// (numbers, booleans, nested objects) are kept as JSON text.
+#[derive(Debug, Clone)]
+pub struct JsonExtractor {
+    fields: Fields,
+}
+
+impl JsonExtractor {
+    pub fn new(fields: Vec<FieldSpec>) -> Self {
+        Self {
+            fields: Fields::new(fields),
+        }
+    }
+}
+
+impl RecordExtractor for JsonExtractor {
+    fn field_names(&self) -> &[String] {
+        &self.fields.names
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        &self.fields.type_hints
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        let Ok(serde_json::Value::Object(object)) = serde_json::from_str(line.trim()) else {
+            return false;
+        };
+        out.clear();
+        for name in &self.fields.names {
+            let value = object.get(name).or_else(|| {
+                let (first, rest) = name.split_once('.')?;
+                rest.split('.')
+                    .try_fold(object.get(first)?, |value, key| value.get(key))
+            });
+            out.push(Cow::Owned(match value {
+                None | Some(serde_json::Value::Null) => String::new(),
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            }));
+        }
+        true
+    }
+}
+
+/// Extracts fields from logfmt lines (`level=info msg="request done" took=12ms`).
+///
+/// A key without `=` is read as `true`. A line is a record if it has at least one
+/// of the configured keys.
+#[derive(Debug, Clone)]
+pub struct LogfmtExtractor {
+    fields: Fields,
+}
+
+impl LogfmtExtractor {
+    pub fn new(fields: Vec<FieldSpec>) -> Self {
+        Self {
+            fields: Fields::new(fields),
+        }
+    }
+}
+
+impl RecordExtractor for LogfmtExtractor {
+    fn field_names(&self) -> &[String] {
+        &self.fields.names
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        &self.fields.type_hints
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        out.clear();
+        out.resize(self.fields.names.len(), Cow::Borrowed(""));
+        let mut found = false;
+        for (key, value) in logfmt_pairs(line) {
+            if let Some(i) = self.fields.names.iter().position(|name| name == key) {
+                out[i] = value;
+                found = true;
+            }
+        }
+        found
+    }
+}
+
+/// Split a logfmt line into key/value pairs.
+fn logfmt_pairs(line: &str) -> Vec<(&str, Cow<'_, str>)> {
+    let bytes = line.as_bytes();
+    let mut pairs = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let key_start = i;
+        while i < bytes.len() && bytes[i] != b'=' && !bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let key = &line[key_start..i];
+        if i >= bytes.len() || bytes[i] != b'=' {
+            if !key.is_empty() {
+                pairs.push((key, Cow::Borrowed("true")));
+            }
+            continue;
+        }
+        i += 1;
+        let value = if bytes.get(i) == Some(&b'"') {
+            i += 1;
+            let value_start = i;
+            let mut escaped = false;
+            while i < bytes.len() && bytes[i] != b'"' {
+                if bytes[i] == b'\\' {
+                    escaped = true;
+                    i += 1;
+                }
+                i += 1;
+            }
+            let raw = &line[value_start..i.min(bytes.len())];
+            i += 1;
+            if escaped {
+                Cow::Owned(unescape(raw))
+            } else {
+                Cow::Borrowed(raw)
+            }
+        } else {
+            let value_start = i;
+            while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            Cow::Borrowed(&line[value_start..i])
+        };
+        if !key.is_empty() {
+            pairs.push((key, value));
+        }
+    }
+    pairs
+}
+
+/// Resolve backslash escapes in a quoted logfmt value.
+fn unescape(raw: &str) -> String {
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some(other) => value.push(other),
+            None => value.push('\\'),
+        }
+    }
+    value
+}
+
+/// Extracts fields by position from delimiter-separated lines.
+///
+/// Columns may be double-quoted, with `""` for a literal quote. Lines with fewer
+/// columns than fields are not records; extra columns are ignored.
+#[derive(Debug, Clone)]
+pub struct DelimitedExtractor {
+    delimiter: char,
+    fields: Fields,
+}
+
+impl DelimitedExtractor {
+    pub fn new(delimiter: char, fields: Vec<FieldSpec>) -> Self {
+        Self {
+            delimiter,
+            fields: Fields::new(fields),
+        }
+    }
+}
+
+impl RecordExtractor for DelimitedExtractor {
+    fn field_names(&self) -> &[String] {
+        &self.fields.names
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        &self.fields.type_hints
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        out.clear();
+        let wanted = self.fields.names.len();
+        let mut rest = line;
+        while out.len() < wanted {
+            let (column, next) = split_column(rest, self.delimiter);
+            out.push(column);
+            match next {
+                Some(next) => rest = next,
+                None => break,
+            }
+        }
+        out.len() == wanted
+    }
+}
+
+/// Split the first column off `line`, returning it and the rest after the delimiter.
+fn split_column(line: &str, delimiter: char) -> (Cow<'_, str>, Option<&str>) {
+    let Some(quoted) = line.strip_prefix('"') else {
+        return match line.split_once(delimiter) {
+            Some((column, rest)) => (Cow::Borrowed(column), Some(rest)),
+            None => (Cow::Borrowed(line), None),
+        };
+    };
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c != '"' {
+            value.push(c);
+            continue;
+        }
+        if quoted[i + 1..].starts_with('"') {
+            value.push('"');
+            chars.next();
+            continue;
+        }
+        let after = &quoted[i + 1..];
+        let rest = after
+            .find(delimiter)
+            .map(|pos| &after[pos + delimiter.len_utf8()..]);
+        return (Cow::Owned(value), rest);
+    }
+    // Unterminated quote: keep the remainder as the value
+    (Cow::Owned(value), None)
+}
+
+/// Line extraction function of an [`FnExtractor`].
+pub type ExtractFn = dyn Fn(&str) -> Option<Vec<String>> + Send + Sync;
+
+/// Extracts fields with a user-provided closure.
+///
+/// The closure returns the values in field order, or `None` if the line is not a
+/// record. Missing trailing values are empty.
+#[derive(Clone)]
+pub struct FnExtractor {
+    fields: Fields,
+    extract: Arc<ExtractFn>,
+}
+
+impl FnExtractor {
+    pub fn new(
+        fields: Vec<FieldSpec>,
+        extract: impl Fn(&str) -> Option<Vec<String>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            fields: Fields::new(fields),
+            extract: Arc::new(extract),
+        }
+    }
+}
+
+impl fmt::Debug for FnExtractor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnExtractor")
+            .field("fields", &self.fields.names)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RecordExtractor for FnExtractor {
+    fn field_names(&self) -> &[String] {
+        &self.fields.names
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        &self.fields.type_hints
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        let Some(values) = (self.extract)(line) else {
+            return false;
+        };
+        out.clear();
+        out.extend(values.into_iter().map(Cow::Owned));
+        out.resize(self.fields.names.len(), Cow::Borrowed(""));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(extractor: &dyn RecordExtractor, line: &str) -> Option<Vec<String>> {
+        let mut out = Vec::new();
+        extractor
+            .extract(line, &mut out)
+            .then(|| out.into_iter().map(Cow::into_owned).collect())
+    }
+
+    #[test]
+    fn test_json_extractor() {
+        let extractor = JsonExtractor::new(vec![
+            FieldSpec::new("level"),
+            FieldSpec::typed("status", FieldType::Int),
+            FieldSpec::new("http.path"),
+            FieldSpec::new("missing"),
+        ]);
+        assert_eq!(
+            extract(
+                &extractor,
+                r#"{"level":"warn","status":503,"http":{"path":"/a\"b"}}"#
+            ),
+            Some(vec![
+                "warn".to_string(),
+                "503".to_string(),
+                "/a\"b".to_string(),
+                String::new()
+            ])
+        );
+        assert_eq!(extract(&extractor, "not json"), None);
+        assert_eq!(extractor.type_hints().get("status"), Some(&FieldType::Int));
+    }
+
+    #[test]
+    fn test_logfmt_extractor() {
+        let extractor = LogfmtExtractor::new(vec![
+            FieldSpec::new("level"),
+            FieldSpec::new("msg"),
+            FieldSpec::new("debug"),
+            FieldSpec::new("took"),
+        ]);
+        assert_eq!(
+            extract(&extractor, r#"level=info msg="say \"hi\"" debug took=12ms"#),
+            Some(vec![
+                "info".to_string(),
+                "say \"hi\"".to_string(),
+                "true".to_string(),
+                "12ms".to_string()
+            ])
+        );
+        assert_eq!(extract(&extractor, "plain text line"), None);
+    }
+
+    #[test]
+    fn test_delimited_extractor() {
+        let extractor = DelimitedExtractor::new(
+            ',',
+            vec![
+                FieldSpec::new("a"),
+                FieldSpec::new("b"),
+                FieldSpec::new("c"),
+            ],
+        );
+        assert_eq!(
+            extract(&extractor, r#"1,"x, ""y""",3,extra"#),
+            Some(vec![
+                "1".to_string(),
+                "x, \"y\"".to_string(),
+                "3".to_string()
+            ])
+        );
+        assert_eq!(extract(&extractor, "1,2"), None);
+
+        let tabs = DelimitedExtractor::new('\t', vec![FieldSpec::new("a"), FieldSpec::new("b")]);
+        assert_eq!(
+            extract(&tabs, "x\ty"),
+            Some(vec!["x".to_string(), "y".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_fn_extractor() {
+        let extractor = FnExtractor::new(vec![FieldSpec::new("k"), FieldSpec::new("v")], |line| {
+            line.split_once(':').map(|(k, _)| vec![k.to_string()])
+        });
+        assert_eq!(
+            extract(&extractor, "key: value"),
+            Some(vec!["key".to_string(), String::new()])
+        );
+        assert!(!extractor.is_match("no separator"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Multi-pattern line matching with Hyperscan or Vectorscan.
+//!
+//! The expanded patterns of a [`Scanner`](crate::Scanner) are compiled into one
+//! Hyperscan database. Scanning a line with it yields every pattern that may match
+//! in a single pass, so the `regex` crate only runs, for captures, on those
+//! candidates. Patterns are compiled in prefilter mode: constructs Hyperscan does not
+//! support are approximated, which can add candidates but never loses a match.
+//!
+//! `libhs` is loaded at runtime (`libhs.so.5`, then `libhs.so`), so the feature
+//! builds without Hyperscan installed and fails only when a scanner selects it.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_uint, c_ulonglong, c_void};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::dl::Library;
+use crate::error::{Error, Result};
+
+/// Library names tried in order.
+const LIBRARIES: [&str; 2] = ["libhs.so.5", "libhs.so"];
+
+/// Number of databases whose scratch space each thread keeps.
+const SCRATCH_CACHE_SIZE: usize = 8;
+
+const HS_SUCCESS: c_int = 0;
+const HS_MODE_BLOCK: c_uint = 1;
+const HS_FLAG_SINGLEMATCH: c_uint = 8;
+const HS_FLAG_ALLOWEMPTY: c_uint = 16;
+const HS_FLAG_UTF8: c_uint = 32;
+const HS_FLAG_UCP: c_uint = 64;
+const HS_FLAG_PREFILTER: c_uint = 128;
+
+static NEXT_DATABASE_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Scratch space and candidate buffers, keyed by database id.
+    static SCRATCH: RefCell<Vec<Scratch>> = const { RefCell::new(Vec::new()) };
+}
+
+#[repr(C)]
+struct CompileError {
+    message: *const c_char,
+    expression: c_int,
+}
+
+type MatchHandler = unsafe extern "C" fn(
+    id: c_uint,
+    from: c_ulonglong,
+    to: c_ulonglong,
+    flags: c_uint,
+    context: *mut c_void,
+) -> c_int;
+
+/// The `libhs` functions used, resolved once.
+struct Api {
+    /// Kept loaded while the functions below are in use.
+    _library: Library,
+    compile_multi: unsafe extern "C" fn(
+        expressions: *const *const c_char,
+        flags: *const c_uint,
+        ids: *const c_uint,
+        elements: c_uint,
+        mode: c_uint,
+        platform: *const c_void,
+        database: *mut *mut c_void,
+        error: *mut *mut CompileError,
+    ) -> c_int,
+    free_compile_error: unsafe extern "C" fn(error: *mut CompileError) -> c_int,
+    free_database: unsafe extern "C" fn(database: *mut c_void) -> c_int,
+    alloc_scratch:
+        unsafe extern "C" fn(database: *const c_void, scratch: *mut *mut c_void) -> c_int,
+    free_scratch: unsafe extern "C" fn(scratch: *mut c_void) -> c_int,
+    scan: unsafe extern "C" fn(
+        database: *const c_void,
+        data: *const c_char,
+        length: c_uint,
+        flags: c_uint,
+        scratch: *mut c_void,
+        on_event: MatchHandler,
+        context: *mut c_void,
+    ) -> c_int,
+}
+
+/// Load `libhs`, or the reason it is unavailable.
+fn api() -> Result<&'static Api> {
+    static API: OnceLock<std::result::Result<Api, String>> = OnceLock::new();
+    API.get_or_init(load)
+        .as_ref()
+        .map_err(|e| Error::Config(format!("Hyperscan is unavailable: {}", e)))
+}
+
+fn load() -> std::result::Result<Api, String> {
+    let library = Library::open_first(&LIBRARIES).map_err(|e| e.to_string())?;
+    // Safety: the types are those of the C declarations, and the library is kept
+    // alongside the functions
+    fn symbol<F: Copy>(library: &Library, name: &str) -> std::result::Result<F, String> {
+        unsafe { library.symbol(name) }.map_err(|e| e.to_string())
+    }
+    Ok(Api {
+        compile_multi: symbol(&library, "hs_compile_multi")?,
+        free_compile_error: symbol(&library, "hs_free_compile_error")?,
+        free_database: symbol(&library, "hs_free_database")?,
+        alloc_scratch: symbol(&library, "hs_alloc_scratch")?,
+        free_scratch: symbol(&library, "hs_free_scratch")?,
+        scan: symbol(&library, "hs_scan")?,
+        _library: library,
+    })
+}
+
+/// Whether `libhs` can be loaded.
+pub fn is_available() -> bool {
+    api().is_ok()
+}
+
+/// A compiled Hyperscan database of a scanner's patterns.
+#[derive(Debug)]
+pub(crate) struct Database {
+    database: *mut c_void,
+    patterns: usize,
+    /// Identifies this database in the per-thread scratch cache.
+    id: usize,
+}
+
+// Databases are immutable once compiled; scanning only needs per-thread scratch
+unsafe impl Send for Database {}
+unsafe impl Sync for Database {}
+
+impl Database {
+    /// Compile `patterns` in block mode, pattern `i` getting id `i`.
+    pub(crate) fn compile(patterns: &[&str]) -> Result<Self> {
+        let api = api()?;
+        let expressions = patterns
+            .iter()
+            .map(|p| CString::new(*p))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|_| Error::Config("Hyperscan patterns cannot contain NUL".into()))?;
+        let pointers: Vec<*const c_char> = expressions.iter().map(|e| e.as_ptr()).collect();
+        let flags = vec![
+            HS_FLAG_SINGLEMATCH
+                | HS_FLAG_ALLOWEMPTY
+                | HS_FLAG_UTF8
+                | HS_FLAG_UCP
+                | HS_FLAG_PREFILTER;
+            patterns.len()
+        ];
+        let ids: Vec<c_uint> = (0..patterns.len() as c_uint).collect();
+
+        let mut database = std::ptr::null_mut();
+        let mut error = std::ptr::null_mut();
+        let status = unsafe {
+            (api.compile_multi)(
+                pointers.as_ptr(),
+                flags.as_ptr(),
+                ids.as_ptr(),
+                patterns.len() as c_uint,
+                HS_MODE_BLOCK,
+                std::ptr::null(),
+                &mut database,
+                &mut error,
+            )
+        };
+        if status != HS_SUCCESS {
+            let message = match error.is_null() {
+                true => format!("error {}", status),
+                false => unsafe {
+                    let message = CStr::from_ptr((*error).message)
+                        .to_string_lossy()
+                        .into_owned();
+                    let expression = (*error).expression;
+                    (api.free_compile_error)(error);
+                    match expression {
+                        e if e >= 0 => format!("pattern {}: {}", e, message),
+                        _ => message,
+                    }
+                },
+            };
+            return Err(Error::Config(format!(
+                "Cannot compile Hyperscan database: {}",
+                message
+            )));
+        }
+        Ok(Self {
+            database,
+            patterns: patterns.len(),
+            id: NEXT_DATABASE_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+
+    /// Run `f` on the candidate flags of `line`: `true` for every pattern that may match.
+    ///
+    /// If the scan itself fails every pattern is a candidate, so the regexes decide.
+    pub(crate) fn with_candidates<R>(&self, line: &str, f: impl FnOnce(&[bool]) -> R) -> R {
+        let api = api().expect("a compiled database implies a loaded library");
+        SCRATCH.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let index = match cache.iter().position(|s| s.database == self.id) {
+                Some(index) => index,
+                None => {
+                    let mut scratch = std::ptr::null_mut();
+                    let status = unsafe { (api.alloc_scratch)(self.database, &mut scratch) };
+                    if status != HS_SUCCESS {
+                        return f(&vec![true; self.patterns]);
+                    }
+                    if cache.len() >= SCRATCH_CACHE_SIZE {
+                        cache.remove(0);
+                    }
+                    cache.push(Scratch {
+                        database: self.id,
+                        scratch,
+                        candidates: vec![false; self.patterns],
+                    });
+                    cache.len() - 1
+                }
+            };
+            let scratch = &mut cache[index];
+            scratch.candidates.fill(false);
+            let status = match u32::try_from(line.len()) {
+                Ok(length) => unsafe {
+                    (api.scan)(
+                        self.database,
+                        line.as_ptr() as *const c_char,
+                        length,
+                        0,
+                        scratch.scratch,
+                        on_match,
+                        &mut scratch.candidates as *mut Vec<bool> as *mut c_void,
+                    )
+                },
+                Err(_) => -1,
+            };
+            if status != HS_SUCCESS {
+                scratch.candidates.fill(true);
+            }
+            f(&scratch.candidates)
+        })
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe { (api.free_database)(self.database) };
+        }
+    }
+}
+
+/// Match handler flagging pattern `id` in the `Vec<bool>` behind `context`.
+unsafe extern "C" fn on_match(
+    id: c_uint,
+    _from: c_ulonglong,
+    _to: c_ulonglong,
+    _flags: c_uint,
+    context: *mut c_void,
+) -> c_int {
+    let candidates = unsafe { &mut *(context as *mut Vec<bool>) };
+    if let Some(candidate) = candidates.get_mut(id as usize) {
+        *candidate = true;
+    }
+    0
+}
+
+/// Per-thread scratch space for one database.
+struct Scratch {
+    database: usize,
+    scratch: *mut c_void,
+    candidates: Vec<bool>,
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe { (api.free_scratch)(self.scratch) };
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! lflog-core - Log line parsing with regex pattern macros.
+//!
+//! This crate holds the parser half of lflog: pattern macros, the regex
+//! [`Scanner`], record extractors and profiles. It has no DataFusion or tokio
+//! dependencies, so it can be used where the query engine is not wanted.
+
+#[cfg(feature = "dl")]
+pub mod dl;
+pub mod error;
+pub mod extractor;
+#[cfg(feature = "hyperscan")]
+pub mod hyperscan;
+mod locale;
+pub mod macros;
+#[cfg(feature = "pcre2")]
+pub mod pcre2;
+pub mod record;
+pub mod redact;
+pub mod rules;
+pub mod scanner;
+pub mod stats;
+pub mod transform;
+pub mod types;
+pub mod validate;
+
+pub use error::{Error, Result};
+pub use extractor::{RecordExtractor, RecordFormat};
+pub use macros::parser::{ProfileFormat, Profiles};
+pub use record::{Record, ScanIter};
+pub use scanner::{CaptureLocations, Engine, Scanner};
+pub use stats::ScanCounts;
+pub use types::{BinaryEncoding, FieldType, TimestampUnit, YearStrategy};
+pub use validate::{LineReport, NearMiss, Validation};
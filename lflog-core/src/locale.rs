@@ -0,0 +1,109 @@
+//! Month and weekday names of the locales `datetime(..., locale=xx)` reads.
+//!
+//! Names are matched case-insensitively, full or abbreviated, whichever directive
+//! the format uses, so `%b` reads both `Mär` and `März`.
+
+use chrono::Weekday;
+
+/// Names of one locale; weekdays start on Monday.
+pub(crate) struct Locale {
+    pub(crate) code: &'static str,
+    months: [&'static str; 12],
+    short_months: [&'static str; 12],
+    weekdays: [&'static str; 7],
+    short_weekdays: [&'static str; 7],
+}
+
+#[rustfmt::skip]
+const LOCALES: &[Locale] = &[
+    Locale {
+        code: "en",
+        months: ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"],
+        short_months: ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"],
+        weekdays: ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"],
+        short_weekdays: ["mon", "tue", "wed", "thu", "fri", "sat", "sun"],
+    },
+    Locale {
+        code: "de",
+        months: ["januar", "februar", "märz", "april", "mai", "juni", "juli", "august", "september", "oktober", "november", "dezember"],
+        short_months: ["jan", "feb", "mär", "apr", "mai", "jun", "jul", "aug", "sep", "okt", "nov", "dez"],
+        weekdays: ["montag", "dienstag", "mittwoch", "donnerstag", "freitag", "samstag", "sonntag"],
+        short_weekdays: ["mo", "di", "mi", "do", "fr", "sa", "so"],
+    },
+    Locale {
+        code: "fr",
+        months: ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+        short_months: ["janv", "févr", "mars", "avr", "mai", "juin", "juil", "août", "sept", "oct", "nov", "déc"],
+        weekdays: ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+        short_weekdays: ["lun", "mar", "mer", "jeu", "ven", "sam", "dim"],
+    },
+    Locale {
+        code: "es",
+        months: ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+        short_months: ["ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sep", "oct", "nov", "dic"],
+        weekdays: ["lunes", "martes", "miércoles", "jueves", "viernes", "sábado", "domingo"],
+        short_weekdays: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    },
+    Locale {
+        code: "it",
+        months: ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio", "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+        short_months: ["gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic"],
+        weekdays: ["lunedì", "martedì", "mercoledì", "giovedì", "venerdì", "sabato", "domenica"],
+        short_weekdays: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    },
+    Locale {
+        code: "pt",
+        months: ["janeiro", "fevereiro", "março", "abril", "maio", "junho", "julho", "agosto", "setembro", "outubro", "novembro", "dezembro"],
+        short_months: ["jan", "fev", "mar", "abr", "mai", "jun", "jul", "ago", "set", "out", "nov", "dez"],
+        weekdays: ["segunda-feira", "terça-feira", "quarta-feira", "quinta-feira", "sexta-feira", "sábado", "domingo"],
+        short_weekdays: ["seg", "ter", "qua", "qui", "sex", "sáb", "dom"],
+    },
+    Locale {
+        code: "nl",
+        months: ["januari", "februari", "maart", "april", "mei", "juni", "juli", "augustus", "september", "oktober", "november", "december"],
+        short_months: ["jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec"],
+        weekdays: ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+        short_weekdays: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+    },
+];
+
+/// Codes of the supported locales.
+pub(crate) fn codes() -> impl Iterator<Item = &'static str> {
+    LOCALES.iter().map(|locale| locale.code)
+}
+
+/// The locale with code `code`, e.g. `de`.
+pub(crate) fn find(code: &str) -> Option<&'static Locale> {
+    LOCALES
+        .iter()
+        .find(|locale| locale.code.eq_ignore_ascii_case(code))
+}
+
+impl Locale {
+    /// The month, from 1, whose name starts `s`, and the length of the name.
+    pub(crate) fn month(&self, s: &str) -> Option<(u32, usize)> {
+        let (index, len) = longest_prefix(s, self.months.iter().chain(&self.short_months))?;
+        Some((index as u32 % 12 + 1, len))
+    }
+
+    /// The weekday whose name starts `s`, and the length of the name.
+    pub(crate) fn weekday(&self, s: &str) -> Option<(Weekday, usize)> {
+        let (index, len) = longest_prefix(s, self.weekdays.iter().chain(&self.short_weekdays))?;
+        Some((Weekday::try_from((index % 7) as u8).ok()?, len))
+    }
+}
+
+/// Index and length of the longest of `names` that `s` starts with, ignoring case.
+fn longest_prefix<'a>(
+    s: &str,
+    names: impl Iterator<Item = &'a &'static str>,
+) -> Option<(usize, usize)> {
+    names
+        .enumerate()
+        .filter(|(_, name)| {
+            s.get(..name.len())
+                .is_some_and(|prefix| prefix.to_lowercase() == **name)
+        })
+        .max_by_key(|(_, name)| name.len())
+        .map(|(index, name)| (index, name.len()))
+}
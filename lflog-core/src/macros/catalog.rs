@@ -0,0 +1,218 @@
+//! Reference of the available macros, for help texts and editors.
+
+use serde::Serialize;
+
+use crate::macros::expander::expand_macro;
+use crate::macros::parser::CustomMacro;
+use crate::types::FieldType;
+
+/// Builtin macros: names, the first one canonical, arguments and description.
+const BUILTINS: &[(&[&str], &str, &str)] = &[
+    (&["number", "num"], "[digits | min-max]", "Integer"),
+    (&["float", "double"], "", "Floating point number"),
+    (
+        &["decimal", "dec"],
+        "[precision[, scale]]",
+        "Exact decimal number, decimal(38, 10) by default",
+    ),
+    (&["string", "str"], "[greedy]", "Non-greedy string"),
+    (&["any"], "[greedy]", "Non-greedy match of anything"),
+    (&["rest"], "", "Everything to the end of the line"),
+    (&["word", "token"], "", "Run of non-whitespace characters"),
+    (&["var_name", "ident"], "", "Identifier"),
+    (
+        &["datetime", "ts"],
+        "[format, ...][, locale=][, unit=][, tz=]",
+        "Datetime with chrono strftime formats",
+    ),
+    (
+        &["enum"],
+        "value, ...",
+        "One of the listed values, into a dictionary column",
+    ),
+    (
+        &["uuid"],
+        "[dashless | any][, upper | lower]",
+        "UUID, dashed by default",
+    ),
+    (&["ip", "ipv4"], "", "IPv4 address"),
+    (&["ipv6"], "", "IPv6 address"),
+    (&["email"], "", "Email address"),
+    (&["url"], "", "http, https or ftp URL"),
+    (
+        &["path"],
+        "[unix | windows]",
+        "File path without whitespace",
+    ),
+    (&["hostname", "fqdn"], "", "Hostname or FQDN"),
+    (&["loglevel"], "[normalize]", "Log level in any case"),
+    (&["quoted"], "", "Quoted string, captured unescaped"),
+    (
+        &["bracketed"],
+        "[[] | () | {} | <>]",
+        "Text between brackets, captured without them",
+    ),
+    (&["hex"], "[int | bytes]", "Hex number"),
+    (&["base64"], "[url][, bytes]", "Base64 token"),
+    (&["http_method"], "", "HTTP method"),
+    (
+        &["http_status"],
+        "[class, ...]",
+        "Three-digit HTTP status, e.g. 4xx",
+    ),
+    (
+        &["useragent", "user_agent"],
+        "",
+        "Everything up to an unescaped quote",
+    ),
+    (&["size"], "", "Byte size like 1.5GB, in bytes"),
+    (
+        &["duration"],
+        "[typed]",
+        "Duration like 3m12s, in milliseconds",
+    ),
+    (&["json"], "", "JSON object or array"),
+    (
+        &["pid", "tid"],
+        "",
+        "Process or thread ID, bare or bracketed",
+    ),
+    (&["mac"], "", "MAC address"),
+    (
+        &["list"],
+        "[separator[, item]]",
+        "Items of macro item joined by the separator",
+    ),
+    (&["kv"], "", "Space-separated key=value pairs"),
+];
+
+/// Where a macro is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MacroSource {
+    Builtin,
+    /// A custom macro, from a profile file or a [`MacroRegistry`](super::MacroRegistry).
+    Custom,
+}
+
+/// A macro as listed by [`available_macros`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MacroInfo {
+    pub name: String,
+    /// Other names of the macro, e.g. `ipv4` for `ip`.
+    pub aliases: Vec<String>,
+    /// Arguments it takes, e.g. `[separator[, item]]` with optional ones in brackets;
+    /// empty when it takes none.
+    pub args: String,
+    /// Type of its fields without arguments; arguments may change it, e.g. `hex(int)`.
+    pub type_hint: Option<FieldType>,
+    pub description: Option<String>,
+    pub source: MacroSource,
+}
+
+impl MacroInfo {
+    /// How the macro is written, e.g. `list([separator[, item]])` or `ip`.
+    pub fn signature(&self) -> String {
+        match self.args.is_empty() {
+            true => self.name.clone(),
+            false => format!("{}({})", self.name, self.args),
+        }
+    }
+}
+
+/// The builtin macros.
+pub fn builtin_macros() -> Vec<MacroInfo> {
+    BUILTINS
+        .iter()
+        .map(|(names, args, description)| MacroInfo {
+            name: names[0].to_string(),
+            aliases: names[1..].iter().map(|name| name.to_string()).collect(),
+            args: args.to_string(),
+            type_hint: expand_macro(names[0], &[], None)
+                .ok()
+                .and_then(|(_, hint)| hint),
+            description: Some(description.to_string()),
+            source: MacroSource::Builtin,
+        })
+        .collect()
+}
+
+/// The macros patterns can use with `custom_macros`: those, in order, then the
+/// builtins they do not override.
+pub fn available_macros(custom_macros: Option<&[CustomMacro]>) -> Vec<MacroInfo> {
+    let customs = custom_macros.unwrap_or_default();
+    let mut macros: Vec<MacroInfo> = customs
+        .iter()
+        .map(|custom| MacroInfo {
+            name: custom.name.clone(),
+            aliases: Vec::new(),
+            args: match custom.expander {
+                Some(_) => "...".to_string(),
+                None => String::new(),
+            },
+            type_hint: custom.type_hint.clone(),
+            description: custom.description.clone(),
+            source: MacroSource::Custom,
+        })
+        .collect();
+    for mut builtin in builtin_macros() {
+        builtin
+            .aliases
+            .retain(|alias| !customs.iter().any(|custom| &custom.name == alias));
+        if !customs.iter().any(|custom| custom.name == builtin.name) {
+            macros.push(builtin);
+        }
+    }
+    macros
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macros::expand_macros;
+
+    #[test]
+    fn test_builtin_macros() {
+        let builtins = builtin_macros();
+        // Every listed name is one the expander knows
+        for info in &builtins {
+            for name in std::iter::once(&info.name).chain(&info.aliases) {
+                let pattern = match name.as_str() {
+                    "enum" => "{{enum(a)}}".to_string(),
+                    _ => format!("{{{{{}}}}}", name),
+                };
+                assert!(expand_macros(&pattern, None).is_ok(), "{}", name);
+            }
+        }
+        let get = |name: &str| builtins.iter().find(|info| info.name == name).unwrap();
+        assert_eq!(get("number").type_hint, Some(FieldType::Int));
+        assert_eq!(get("size").type_hint, Some(FieldType::Int64));
+        assert_eq!(get("enum").type_hint, None);
+        assert_eq!(get("ip").aliases, ["ipv4"]);
+        assert_eq!(get("list").signature(), "list([separator[, item]])");
+        assert_eq!(get("mac").signature(), "mac");
+    }
+
+    #[test]
+    fn test_available_macros() {
+        let customs = [
+            CustomMacro::new("ticket", r"[A-Z]+-\d+").with_description("Jira ticket"),
+            CustomMacro::new("ipv4", r"\d+(?:\.\d+){3}"),
+            CustomMacro::from_fn("mac", |_| Ok(".".into())).with_type_hint(FieldType::Int),
+        ];
+        let macros = available_macros(Some(&customs));
+        assert_eq!(macros.len(), builtin_macros().len() + 2);
+        let names: Vec<&str> = macros.iter().map(|info| info.name.as_str()).collect();
+        assert_eq!(names[..4], ["ticket", "ipv4", "mac", "number"]);
+        assert_eq!(macros[0].description.as_deref(), Some("Jira ticket"));
+        assert_eq!(macros[0].source, MacroSource::Custom);
+        assert_eq!(macros[2].signature(), "mac(...)");
+        assert_eq!(macros[2].type_hint, Some(FieldType::Int));
+        // The custom `ipv4` takes the alias of the builtin `ip`
+        let ip = macros.iter().find(|info| info.name == "ip").unwrap();
+        assert!(ip.aliases.is_empty());
+        assert_eq!(ip.source, MacroSource::Builtin);
+
+        assert_eq!(available_macros(None), builtin_macros());
+    }
+}
@@ -0,0 +1,1696 @@
+//! Macro expansion logic.
+//!
+//! Expands macro invocations into regex patterns with field type hints.
+
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+
+use crate::locale;
+use crate::macros::grok;
+use crate::macros::parser::{CustomMacro, parse_macro_invocation};
+use crate::transform::{LOG_LEVELS, Transform};
+use crate::types::{
+    BinaryEncoding, DateTime, FieldType, MAX_DECIMAL_PRECISION, MAX_DICTIONARY_VALUES,
+    TimestampUnit, Zone,
+};
+
+/// Expand a single macro - checks custom macros first, then falls back to builtins.
+pub(crate) fn expand_macro(
+    name: &str,
+    args: &[String],
+    custom_macros: Option<&[CustomMacro]>,
+) -> Result<(String, Option<FieldType>)> {
+    // Check custom macros first
+    if let Some(customs) = custom_macros
+        && let Some(custom) = customs.iter().find(|m| m.name == name)
+    {
+        return Ok((custom.regex(args)?, custom.type_hint.clone()));
+    }
+    if name.eq_ignore_ascii_case("list") {
+        return expand_list(args, custom_macros);
+    }
+    // Fall back to builtin
+    expand_builtin_macro(name, args)
+}
+
+/// Expand `list(separator, item)`: zero or more items matching macro `item` joined
+/// by `separator` (`,` by default), which blanks may follow, as in `a, b`. Without
+/// `item`, an item is any run of non-blank characters not in the separator.
+fn expand_list(
+    args: &[String],
+    custom_macros: Option<&[CustomMacro]>,
+) -> Result<(String, Option<FieldType>)> {
+    if args.len() > 2 {
+        return Err(Error::Macro(
+            "list macro takes a separator and an item macro".into(),
+        ));
+    }
+    let separator = args
+        .first()
+        .map(String::as_str)
+        .filter(|separator| !separator.is_empty())
+        .unwrap_or(",");
+    let item = match args.get(1) {
+        Some(item) => {
+            let inv = parse_macro_invocation(item)?;
+            if inv.field.is_some() {
+                return Err(Error::Macro(format!(
+                    "list item '{}' cannot name a field",
+                    item
+                )));
+            }
+            format!(
+                "(?:{})",
+                expand_macro(&inv.name, &inv.args, custom_macros)?.0
+            )
+        }
+        None => {
+            let excluded: String = separator
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(|c| regex::escape(&c.to_string()))
+                .collect();
+            format!(r"[^\s{}]+", excluded)
+        }
+    };
+    let mut joiner = regex::escape(separator);
+    if !separator.ends_with(char::is_whitespace) {
+        joiner.push_str(r"[ \t]*");
+    }
+    Ok((
+        format!("(?:{}(?:{}{})*)?", item, joiner, item),
+        Some(FieldType::List(separator.to_string())),
+    ))
+}
+
+/// Transform a builtin macro applies to the values it captures, such as
+/// `loglevel(normalize)` spelling levels the canonical way or `quoted` keeping only
+/// what is between the quotes.
+fn builtin_transform(name: &str, args: &[String]) -> Option<Transform> {
+    match (name.to_lowercase().as_str(), args) {
+        ("loglevel", [arg]) if arg == "normalize" => Some(Transform::LogLevel),
+        ("quoted", _) => Some(Transform::Unquote),
+        ("hex", [arg]) if arg == "int" => Some(Transform::FromHex),
+        ("pid" | "tid", _) => Some(Transform::Unwrap("[".into(), "]".into())),
+        ("size", _) => Some(Transform::Size),
+        ("duration", _) => Some(Transform::Duration),
+        ("bracketed", _) => {
+            let (open, close) = bracket_pair(args).ok()?;
+            Some(Transform::Unwrap(open.to_string(), close.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Deepest nesting of objects and arrays the `json` macro matches.
+const JSON_DEPTH: usize = 6;
+
+/// Regex matching a JSON object or array nested up to `depth` levels, skipping
+/// braces and brackets inside strings.
+fn json_regex(depth: usize) -> String {
+    let string = r#""(?:[^"\\]|\\.)*""#;
+    let mut inner = String::new();
+    for _ in 0..depth {
+        let content = match inner.is_empty() {
+            true => format!(r#"[^{{}}\[\]"]|{}"#, string),
+            false => format!(r#"[^{{}}\[\]"]|{}|{}"#, string, inner),
+        };
+        inner = format!(r"\{{(?:{0})*\}}|\[(?:{0})*\]", content);
+    }
+    inner
+}
+
+/// Regex of `uuid(args)`: dashed by default, `dashless` for 32 hex digits or `any`
+/// for either, in any case unless `upper` or `lower` is given.
+fn uuid_regex(args: &[String]) -> Result<String> {
+    let (mut dashed, mut dashless, mut hex) = (true, false, "[0-9a-fA-F]");
+    for arg in args {
+        match arg.to_lowercase().as_str() {
+            "dashless" => (dashed, dashless) = (false, true),
+            "any" => (dashed, dashless) = (true, true),
+            "upper" => hex = "[0-9A-F]",
+            "lower" => hex = "[0-9a-f]",
+            _ => {
+                return Err(Error::Macro(format!(
+                    "invalid uuid macro arg: {}, expected dashless, any, upper or lower",
+                    arg
+                )));
+            }
+        }
+    }
+    let groups = |separator: &str| {
+        [8, 4, 4, 4, 12]
+            .map(|digits| format!("{}{{{}}}", hex, digits))
+            .join(separator)
+    };
+    Ok(match (dashed, dashless) {
+        (true, true) => format!("{}|{}", groups("-"), groups("")),
+        (false, _) => groups(""),
+        (true, false) => groups("-"),
+    })
+}
+
+/// Delimiters of `bracketed(pair)`: `[]` by default, or `()`, `{}` or `<>`.
+fn bracket_pair(args: &[String]) -> Result<(char, char)> {
+    match args {
+        [] => Ok(('[', ']')),
+        [pair] if ["[]", "()", "{}", "<>"].contains(&pair.as_str()) => {
+            let mut chars = pair.chars();
+            Ok((chars.next().unwrap(), chars.next().unwrap()))
+        }
+        _ => Err(Error::Macro(format!(
+            "invalid bracketed macro args: {}, expected [], (), {{}} or <>",
+            args.join(", ")
+        ))),
+    }
+}
+
+/// Default value declared by custom macro `name`, if any.
+fn custom_default<'a>(name: &str, custom_macros: Option<&'a [CustomMacro]>) -> Option<&'a str> {
+    custom_macros?
+        .iter()
+        .find(|m| m.name == name)?
+        .default
+        .as_deref()
+}
+
+/// Names of the named groups of a regex, in order, e.g. `method` and `path` of
+/// `(?P<method>\w+) (?<path>\S+)`. Escaped parentheses are skipped.
+fn named_groups(regex: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut escaped = false;
+    for (i, c) in regex.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '(' => {
+                let rest = &regex[i + 1..];
+                let Some(rest) = rest.strip_prefix("?P<").or_else(|| rest.strip_prefix("?<"))
+                else {
+                    continue;
+                };
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                if end > 0 && rest[end..].starts_with('>') {
+                    names.push(&rest[..end]);
+                }
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Dotted-quad IPv4 address, each octet from 0 to 255.
+const IPV4: &str =
+    r"(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)";
+
+/// IPv6 address in any RFC 4291 text form, with `H` for a group and `V4` for an
+/// embedded IPv4 address. Forms ending in IPv4 come first, then those with more
+/// groups after `::`, so the first alternative that matches takes the whole address.
+const IPV6_TEMPLATE: &str = concat!(
+    r"(?:H:){6}V4|::(?:H:){0,5}V4|H::(?:H:){0,4}V4|(?:H:){1,2}:(?:H:){0,3}V4|",
+    r"(?:H:){1,3}:(?:H:){0,2}V4|(?:H:){1,4}:(?:H:)?V4|(?:H:){1,5}:V4|",
+    r"(?:H:){7}H|H:(?::H){1,6}|(?:H:){1,2}(?::H){1,5}|(?:H:){1,3}(?::H){1,4}|",
+    r"(?:H:){1,4}(?::H){1,3}|(?:H:){1,5}(?::H){1,2}|(?:H:){1,6}:H|",
+    r":(?:(?::H){1,7}|:)|(?:H:){1,7}:",
+);
+
+/// Regex matching an IPv6 address, compressed (`2001:db8::1`) or not, including
+/// IPv4-mapped and -embedded forms (`::ffff:192.0.2.1`).
+fn ipv6_regex() -> String {
+    let alternatives = IPV6_TEMPLATE
+        .replace("V4", IPV4)
+        .replace('H', "[0-9A-Fa-f]{1,4}");
+    format!("(?:{})", alternatives)
+}
+
+/// One label of a hostname: letters, digits and inner hyphens, up to 63 characters.
+const HOST_LABEL: &str = r"[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?";
+
+/// Absolute Unix path, or one relative to `~`, `.` or `..`, without whitespace.
+const UNIX_PATH: &str = r#"(?:~|\.\.?)?(?:/[^\s/"']*)+"#;
+
+/// Windows path on a drive (`C:\Temp`) or a UNC share (`\\server\share`), with
+/// either slash as separator.
+const WINDOWS_PATH: &str = r#"(?:[A-Za-z]:|\\\\[^\s\\/:*?"<>|]+)(?:[\\/][^\s\\/:*?"<>|]*)+"#;
+
+/// Regex of `any` and `string`: as little text as lets the pattern match, or with
+/// `greedy`, as much.
+fn lazy_or_greedy(name: &str, args: &[String]) -> Result<String> {
+    match args {
+        [] => Ok(r".+?".to_string()),
+        [arg] if arg == "greedy" => Ok(r".+".to_string()),
+        _ => Err(Error::Macro(format!(
+            "{} macro takes no argument or greedy, got '{}'",
+            name,
+            args.join(", ")
+        ))),
+    }
+}
+
+/// Expand a built-in macro into a regex fragment and optional field type hint.
+fn expand_builtin_macro(name: &str, args: &[String]) -> Result<(String, Option<FieldType>)> {
+    match name.to_lowercase().as_str() {
+        "number" | "num" => {
+            if args.is_empty() {
+                Ok((r"\d+".to_string(), Some(FieldType::Int)))
+            } else {
+                let a = &args[0];
+                if let Some(pos) = a.find('-') {
+                    let min = a[..pos].trim();
+                    let max = a[pos + 1..].trim();
+                    Ok((format!(r"\d{{{},{}}}", min, max), Some(FieldType::Int)))
+                } else if a.chars().all(|c| c.is_ascii_digit()) {
+                    Ok((format!(r"\d{{{}}}", a), Some(FieldType::Int)))
+                } else {
+                    Err(Error::Macro(format!("invalid number macro arg: {}", a)))
+                }
+            }
+        }
+        "string" | "str" => Ok((lazy_or_greedy(name, args)?, Some(FieldType::String))),
+        "float" | "double" => Ok((
+            r"[-+]?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][-+]?\d+)?".to_string(),
+            Some(FieldType::Float),
+        )),
+        "decimal" | "dec" => {
+            let arg = |arg: &String| {
+                arg.parse::<u8>()
+                    .map_err(|_| Error::Macro(format!("invalid decimal macro arg: {}", arg)))
+            };
+            let (precision, scale) = match args {
+                [] => (MAX_DECIMAL_PRECISION, 10),
+                [precision] => (arg(precision)?, 0),
+                [precision, scale] => (arg(precision)?, arg(scale)?),
+                _ => {
+                    return Err(Error::Macro(
+                        "decimal macro takes a precision and a scale".into(),
+                    ));
+                }
+            };
+            let field_type = FieldType::decimal(precision, scale).ok_or_else(|| {
+                Error::Macro(format!(
+                    "invalid decimal({}, {}): precision must be from 1 to {} and scale at most precision",
+                    precision, scale, MAX_DECIMAL_PRECISION
+                ))
+            })?;
+            Ok((
+                r"[-+]?(?:\d+(?:\.\d*)?|\.\d+)".to_string(),
+                Some(field_type),
+            ))
+        }
+        "word" | "token" => Ok((r"\S+".to_string(), Some(FieldType::String))),
+        "var_name" | "ident" => Ok((
+            r"[A-Za-z_][A-Za-z0-9_]*".to_string(),
+            Some(FieldType::String),
+        )),
+        "uuid" => Ok((uuid_regex(args)?, Some(FieldType::String))),
+        "pid" | "tid" => Ok((r"\[\d+\]|\d+".to_string(), Some(FieldType::Int))),
+        "ip" | "ipv4" => Ok((IPV4.to_string(), Some(FieldType::String))),
+        "ipv6" => Ok((ipv6_regex(), Some(FieldType::String))),
+        "email" => Ok((
+            format!(r"[A-Za-z0-9._%+-]+@{0}(?:\.{0})+", HOST_LABEL),
+            Some(FieldType::String),
+        )),
+        // Quotes and angle brackets are never raw in a URL, so a quoted URL stops there
+        "url" => Ok((
+            r#"(?i:https?|ftp)://[^\s/?#"'<>]+(?:[/?#][^\s"'<>]*)?"#.to_string(),
+            Some(FieldType::String),
+        )),
+        "path" => {
+            let regex = match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+                None => format!("{}|{}", UNIX_PATH, WINDOWS_PATH),
+                Some("unix") if args.len() == 1 => UNIX_PATH.to_string(),
+                Some("windows") if args.len() == 1 => WINDOWS_PATH.to_string(),
+                _ => {
+                    return Err(Error::Macro(format!(
+                        "invalid path macro args: {}, expected unix or windows",
+                        args.join(", ")
+                    )));
+                }
+            };
+            Ok((regex, Some(FieldType::String)))
+        }
+        "hostname" | "fqdn" => Ok((
+            format!(r"{0}(?:\.{0})*", HOST_LABEL),
+            Some(FieldType::String),
+        )),
+        "loglevel" => {
+            if !matches!(args, [] | [_]) || args.first().is_some_and(|arg| arg != "normalize") {
+                return Err(Error::Macro(format!(
+                    "invalid loglevel macro args: {}, expected normalize",
+                    args.join(", ")
+                )));
+            }
+            // Longer spellings first, so `warning` is not cut short at `warn`
+            let spellings: Vec<&str> = LOG_LEVELS.iter().map(|(spelling, _)| *spelling).collect();
+            Ok((
+                format!("(?i:{})", spellings.join("|")),
+                Some(FieldType::Enum),
+            ))
+        }
+        "quoted" => Ok((
+            r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#.to_string(),
+            Some(FieldType::String),
+        )),
+        // One level of nested delimiters, as in `[worker [3]]`
+        "bracketed" => {
+            let (open, close) = bracket_pair(args)?;
+            let (open, close) = (
+                regex::escape(&open.to_string()),
+                regex::escape(&close.to_string()),
+            );
+            let content = format!(r"[^{}{}\n]", open, close);
+            Ok((
+                format!(
+                    r"{open}(?:{content}|{open}{content}*{close})*{close}",
+                    open = open,
+                    close = close,
+                    content = content
+                ),
+                Some(FieldType::String),
+            ))
+        }
+        "hex" => match args {
+            [] => Ok((
+                r"(?:0[xX])?[0-9A-Fa-f]+".to_string(),
+                Some(FieldType::String),
+            )),
+            [arg] if arg == "int" => Ok((
+                r"(?:0[xX])?[0-9A-Fa-f]{1,16}".to_string(),
+                Some(FieldType::Int64),
+            )),
+            [arg] if arg == "bytes" => Ok((
+                r"(?:0[xX])?(?:[0-9A-Fa-f]{2})+".to_string(),
+                Some(FieldType::Binary(BinaryEncoding::Hex)),
+            )),
+            _ => Err(Error::Macro(format!(
+                "invalid hex macro args: {}, expected int or bytes",
+                args.join(", ")
+            ))),
+        },
+        "base64" => {
+            let (mut url, mut bytes) = (false, false);
+            for arg in args {
+                match arg.as_str() {
+                    "url" => url = true,
+                    "bytes" => bytes = true,
+                    _ => {
+                        return Err(Error::Macro(format!(
+                            "invalid base64 macro args: {}, expected url or bytes",
+                            args.join(", ")
+                        )));
+                    }
+                }
+            }
+            let (alphabet, encoding) = match url {
+                false => ("A-Za-z0-9+/", BinaryEncoding::Base64),
+                true => ("A-Za-z0-9_-", BinaryEncoding::Base64Url),
+            };
+            let hint = match bytes {
+                false => FieldType::String,
+                true => FieldType::Binary(encoding),
+            };
+            Ok((format!("[{}]+={{0,2}}", alphabet), Some(hint)))
+        }
+        "http_method" => Ok((
+            "(?:GET|HEAD|POST|PUT|DELETE|CONNECT|OPTIONS|TRACE|PATCH)".to_string(),
+            Some(FieldType::Enum),
+        )),
+        "http_status" => {
+            if args.is_empty() {
+                return Ok((r"\d{3}".to_string(), Some(FieldType::Int)));
+            }
+            let mut classes = String::new();
+            for arg in args {
+                match arg.to_lowercase().as_bytes() {
+                    [class @ b'1'..=b'5', b'x', b'x'] => classes.push(*class as char),
+                    _ => {
+                        return Err(Error::Macro(format!(
+                            "invalid http_status macro arg: {}, expected a class from 1xx to 5xx",
+                            arg
+                        )));
+                    }
+                }
+            }
+            Ok((format!(r"[{}]\d{{2}}", classes), Some(FieldType::Int)))
+        }
+        // Everything up to an unescaped `"`, so it stops at the closing quote of
+        // `"{{agent:useragent}}"` however many fields follow, or else at the line end
+        "useragent" | "user_agent" => {
+            Ok((r#"(?:[^"\\\n]|\\.)*"#.to_string(), Some(FieldType::String)))
+        }
+        "size" => Ok((
+            r"\d+(?:\.\d+)?(?i:[kmgtpe]i?b?|b)?".to_string(),
+            Some(FieldType::Int64),
+        )),
+        // `ms` before `m`, so `250ms` is not read as minutes
+        "duration" => {
+            let hint = match args {
+                [] => FieldType::Float,
+                [arg] if arg == "typed" => FieldType::Duration,
+                _ => {
+                    return Err(Error::Macro(format!(
+                        "invalid duration macro args: {}, expected typed",
+                        args.join(", ")
+                    )));
+                }
+            };
+            Ok((
+                r"(?:\d+(?:\.\d+)?(?:ns|us|µs|ms|s|m|h|d))+".to_string(),
+                Some(hint),
+            ))
+        }
+        "json" => Ok((json_regex(JSON_DEPTH), Some(FieldType::Json))),
+        "kv" => {
+            let pair = r#"[\w.-]+=(?:"(?:[^"\\]|\\.)*"|[^\s"]*)"#;
+            Ok((format!(r"{0}(?:[ \t]+{0})*", pair), Some(FieldType::Map)))
+        }
+        "mac" => Ok((
+            r"[0-9A-Fa-f]{2}(?::[0-9A-Fa-f]{2}){5}|[0-9A-Fa-f]{2}(?:-[0-9A-Fa-f]{2}){5}"
+                .to_string(),
+            Some(FieldType::String),
+        )),
+        "enum" => {
+            if args.is_empty() {
+                return Err(Error::Macro(
+                    "enum macro requires comma-separated values".into(),
+                ));
+            }
+            let vals = args.join(",");
+            let mut values: Vec<String> = Vec::new();
+            for value in vals.split(',').map(str::trim) {
+                if !values.iter().any(|v| v == value) {
+                    values.push(value.to_string());
+                }
+            }
+            if values.len() > MAX_DICTIONARY_VALUES {
+                return Err(Error::Macro(format!(
+                    "enum macro takes at most {} values, got {}",
+                    MAX_DICTIONARY_VALUES,
+                    values.len()
+                )));
+            }
+            let items: Vec<String> = values.iter().map(|v| regex::escape(v)).collect();
+            Ok((
+                format!(r"(?:{})", items.join("|")),
+                Some(FieldType::Dictionary(values)),
+            ))
+        }
+        "datetime" | "ts" => {
+            if args.is_empty() {
+                Ok((
+                    r"\S+".to_string(),
+                    Some(FieldType::DateTime(DateTime::new(Some(vec![
+                        "%Y-%m-%dT%H:%M:%S%.f".to_string(),
+                    ])))),
+                ))
+            } else {
+                let (options, formats): (Vec<&String>, Vec<&String>) =
+                    args.iter().partition(|arg| {
+                        ["locale=", "unit=", "tz="]
+                            .iter()
+                            .any(|option| arg.starts_with(option))
+                    });
+                let (mut locale, mut unit, mut timezone) = (None, None, None);
+                for option in options {
+                    if let Some(code) = option.strip_prefix("locale=") {
+                        if locale::find(code).is_none() {
+                            return Err(Error::Macro(format!(
+                                "unknown datetime locale '{}', expected one of {}",
+                                code,
+                                locale::codes().collect::<Vec<_>>().join(", ")
+                            )));
+                        }
+                        if locale.replace(code.to_string()).is_some() {
+                            return Err(Error::Macro("datetime macro takes one locale".into()));
+                        }
+                    } else if let Some(name) = option.strip_prefix("unit=") {
+                        let parsed = name.parse::<TimestampUnit>().map_err(|_| {
+                            Error::Macro(format!(
+                                "unknown datetime unit '{}', expected s, ms, us or ns",
+                                name
+                            ))
+                        })?;
+                        if unit.replace(parsed).is_some() {
+                            return Err(Error::Macro("datetime macro takes one unit".into()));
+                        }
+                    } else if let Some(tz) = option.strip_prefix("tz=") {
+                        if Zone::parse(tz).is_none() {
+                            return Err(Error::Macro(format!(
+                                "unknown datetime time zone '{}', expected UTC, an offset like +02:00 or a name like Europe/Berlin",
+                                tz
+                            )));
+                        }
+                        if timezone.replace(tz.to_string()).is_some() {
+                            return Err(Error::Macro("datetime macro takes one time zone".into()));
+                        }
+                    }
+                }
+                if formats.is_empty() {
+                    return Err(Error::Macro("datetime macro needs a format".into()));
+                }
+                // translate strftime-like format string(s) into a regex fragment
+                let mut frags = Vec::new();
+                for fmt in &formats {
+                    let frag = format_to_regex(fmt, locale.is_some())?;
+                    frags.push(frag);
+                }
+                let datetime = DateTime::new(Some(formats.into_iter().cloned().collect()))
+                    .with_locale(locale)
+                    .with_unit(unit)
+                    .with_timezone(timezone);
+                if frags.len() == 1 {
+                    Ok((
+                        frags.into_iter().next().unwrap(),
+                        Some(FieldType::DateTime(datetime)),
+                    ))
+                } else {
+                    Ok((
+                        format!("(?:{})", frags.join("|")),
+                        Some(FieldType::DateTime(datetime)),
+                    ))
+                }
+            }
+        }
+        "any" => Ok((lazy_or_greedy(name, args)?, Some(FieldType::String))),
+        "rest" => Ok((r".*".to_string(), Some(FieldType::String))),
+        _ => Err(Error::Macro(format!("unknown macro '{}'", name))),
+    }
+}
+
+/// Convert a strftime format string to a regex pattern.
+///
+/// Covers chrono's directives, including the `-`, `_` and `0` padding modifiers,
+/// fractional seconds like `%.3f` and `%6f`, and offsets from `%z` to `%:::z`. Composite
+/// directives such as `%T` or `%c` expand to the regex of what they stand for.
+/// Month and weekday names are ASCII unless `localized`, when any letters go.
+fn format_to_regex(fmt: &str, localized: bool) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            // escape regex metacharacters in literals
+            out.push_str(&regex::escape(&c.to_string()));
+            continue;
+        }
+        let pad = chars.next_if(|c| matches!(c, '-' | '_' | '0'));
+        let mut directive = String::new();
+        while let Some(c) = chars.next_if(|c| matches!(c, '.' | ':' | '#' | '3' | '6' | '9')) {
+            directive.push(c);
+        }
+        match chars.next() {
+            Some(c) => directive.push(c),
+            None => {
+                return Err(Error::Macro(
+                    "incomplete datetime format string: ends with %".into(),
+                ));
+            }
+        }
+        let number = |width: usize, default_pad: char| match pad.unwrap_or(default_pad) {
+            '-' => format!(r"\d{{1,{}}}", width),
+            '_' => format!(r"[ \d]{{{}}}\d", width - 1),
+            _ => format!(r"\d{{{}}}", width),
+        };
+        let frag = match directive.as_str() {
+            "Y" | "G" => r"\d{4}".to_string(),
+            "C" | "y" | "g" | "m" | "d" | "H" | "I" | "M" | "S" | "U" | "W" | "V" => number(2, '0'),
+            "e" | "k" | "l" => number(2, '_'),
+            "j" => number(3, '0'),
+            "q" => "[1-4]".to_string(),
+            "w" => "[0-6]".to_string(),
+            "u" => "[1-7]".to_string(),
+            "b" | "h" | "B" | "a" | "A" if localized => r"[^\W\d_]+(?:-[^\W\d_]+)?".to_string(),
+            "b" | "h" | "B" | "a" | "A" => r"[A-Za-z]+".to_string(),
+            "p" | "P" => "[AaPp][Mm]".to_string(),
+            "f" => r"\d+".to_string(),
+            ".f" => r"(?:\.\d+)?".to_string(),
+            ".3f" | ".6f" | ".9f" => format!(r"\.\d{{{}}}", &directive[1..2]),
+            "3f" | "6f" | "9f" => format!(r"\d{{{}}}", &directive[..1]),
+            "z" => r"[+-]\d{4}".to_string(),
+            ":z" => r"[+-]\d{2}:\d{2}".to_string(),
+            "::z" => r"[+-]\d{2}:\d{2}:\d{2}".to_string(),
+            ":::z" => r"[+-]\d{2}".to_string(),
+            "#z" => r"[+-]\d{2}(?::?\d{2})?".to_string(),
+            "Z" => r"[A-Za-z/_+-]+".to_string(),
+            "s" => r"-?\d+".to_string(),
+            "D" | "x" => format_to_regex("%m/%d/%y", localized)?,
+            "F" => format_to_regex("%Y-%m-%d", localized)?,
+            "v" => format_to_regex("%e-%b-%Y", localized)?,
+            "R" => format_to_regex("%H:%M", localized)?,
+            "T" | "X" => format_to_regex("%H:%M:%S", localized)?,
+            "r" => format_to_regex("%I:%M:%S %p", localized)?,
+            "c" => format_to_regex("%a %b %e %H:%M:%S %Y", localized)?,
+            "+" => format!(
+                "{}(?:Z|{})",
+                format_to_regex("%Y-%m-%dT%H:%M:%S%.f", localized)?,
+                format_to_regex("%#z", localized)?
+            ),
+            "t" => r"\t".to_string(),
+            "n" => r"\n".to_string(),
+            "%" => "%".to_string(),
+            other => {
+                return Err(Error::Macro(format!(
+                    "unsupported datetime directive: %{}",
+                    other
+                )));
+            }
+        };
+        out.push_str(&frag);
+    }
+    Ok(out)
+}
+
+/// Expand all macros in a pattern string.
+///
+/// Arguments:
+/// - `pattern`: The pattern string containing macros like `{{field:macro}}`
+/// - `custom_macros`: Optional slice of custom macros to check before builtins
+///
+/// Returns:
+/// - The expanded regex pattern
+/// - A list of field names in order
+/// - A map of field names to their type hints
+pub fn expand_macros(
+    pattern: &str,
+    custom_macros: Option<&[CustomMacro]>,
+) -> Result<(String, Vec<String>, HashMap<String, FieldType>)> {
+    let expansion = expand(pattern, custom_macros)?;
+    Ok((expansion.regex, expansion.fields, expansion.type_hints))
+}
+
+/// A pattern with its macros expanded.
+#[derive(Default)]
+pub(crate) struct Expansion {
+    pub(crate) regex: String,
+    /// Field names in order.
+    pub(crate) fields: Vec<String>,
+    pub(crate) type_hints: HashMap<String, FieldType>,
+    /// Defaults of fields whose custom macro declares one.
+    pub(crate) defaults: HashMap<String, String>,
+    /// Transforms of fields whose builtin macro normalizes its values.
+    pub(crate) transforms: HashMap<String, Transform>,
+}
+
+/// Widest stretch of the pattern quoted on either side of a failing macro.
+const SNIPPET_CONTEXT: usize = 30;
+
+/// Point a macro error at bytes `start..end` of `pattern`: the message gets the
+/// column of the macro and a snippet of the pattern with carets under it.
+fn with_span(err: Error, pattern: &str, start: usize, end: usize) -> Error {
+    let before: Vec<char> = pattern[..start].chars().collect();
+    let span = pattern[start..end].chars().count();
+    let from = before.len().saturating_sub(SNIPPET_CONTEXT);
+    let mut snippet: String = before[from..].iter().collect();
+    let mut indent = before.len() - from;
+    if from > 0 {
+        snippet.insert_str(0, "...");
+        indent += 3;
+    }
+    let mut after = pattern[end..].chars();
+    snippet.push_str(&pattern[start..end]);
+    snippet.extend(after.by_ref().take(SNIPPET_CONTEXT));
+    if after.next().is_some() {
+        snippet.push_str("...");
+    }
+    let annotate = |message: String| {
+        format!(
+            "{} at column {}\n  {}\n  {}{}",
+            message,
+            before.len() + 1,
+            snippet,
+            " ".repeat(indent),
+            "^".repeat(span)
+        )
+    };
+    match err {
+        Error::Macro(message) => Error::Macro(annotate(message)),
+        Error::MacroParse(message) => Error::MacroParse(annotate(message)),
+        other => other,
+    }
+}
+
+/// Find the `}}` closing the macro whose body starts at byte `start` of `pattern`.
+///
+/// A `}}` inside quotes or parentheses does not close the macro, so arguments like
+/// `list("}}")` or `enum(a{2}})` keep their braces; elsewhere `\{` and `\}` stand for
+/// a literal brace. Returns the position of the `}}` and the body with those escapes
+/// resolved, or `None` when the macro is not closed.
+fn macro_body(pattern: &str, start: usize) -> Option<(usize, String)> {
+    let mut body = String::new();
+    let mut quote = None;
+    let mut depth = 0usize;
+    let mut chars = pattern[start..].char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                body.push(c);
+                if let Some((_, next)) = chars.next() {
+                    body.push(next);
+                }
+            }
+            (Some(q), _) => {
+                if c == q {
+                    quote = None;
+                }
+                body.push(c);
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                body.push(c);
+            }
+            (None, '\\') if matches!(chars.peek(), Some((_, '{' | '}'))) => {
+                body.push(chars.next().unwrap().1);
+            }
+            (None, '(') => {
+                depth += 1;
+                body.push(c);
+            }
+            (None, ')') => {
+                depth = depth.saturating_sub(1);
+                body.push(c);
+            }
+            (None, '}') if depth == 0 && matches!(chars.peek(), Some((_, '}'))) => {
+                return Some((start + offset, body));
+            }
+            _ => body.push(c),
+        }
+    }
+    None
+}
+
+/// Expand all macros in a pattern string, like [`expand_macros`].
+pub(crate) fn expand(pattern: &str, custom_macros: Option<&[CustomMacro]>) -> Result<Expansion> {
+    let mut out = String::with_capacity(pattern.len());
+    let mut i = 0usize;
+    let mut auto_idx = 0usize;
+    let mut expansion = Expansion::default();
+
+    while i < pattern.len() {
+        let rest = &pattern[i..];
+        if let Some(reference) = rest
+            .starts_with('%')
+            .then(|| grok::parse_ref(rest))
+            .flatten()
+        {
+            let regex = grok::expand_ref(&reference, custom_macros, &mut expansion, 0)
+                .map_err(|e| with_span(e, pattern, i, i + reference.len))?;
+            out.push_str(&regex);
+            i += reference.len;
+            continue;
+        }
+        if rest.starts_with(r"\{{") {
+            out.push_str(r"\{\{");
+            i += 3;
+            continue;
+        }
+        if let Some(escaped) = rest.strip_prefix('\\').and_then(|r| r.chars().next()) {
+            // Any other escape is the regex's own, like `\\` before a macro
+            out.push('\\');
+            out.push(escaped);
+            i += 1 + escaped.len_utf8();
+            continue;
+        }
+        if rest.starts_with("{{") {
+            // In a run of braces, the last two open the macro and the others are
+            // literal, as in `{{{count:number}}}` for `{42}`
+            let literal = rest.len() - rest.trim_start_matches('{').len() - 2;
+            out.push_str(&r"\{".repeat(literal));
+            i += literal;
+            let Some((j, content)) = macro_body(pattern, i + 2) else {
+                let err = Error::Macro("unclosed '{{' in pattern".into());
+                return Err(with_span(err, pattern, i, i + 2));
+            };
+            let (inv, (frag, hint)) = parse_macro_invocation(&content)
+                .and_then(|inv| {
+                    let expanded = expand_macro(&inv.name, &inv.args, custom_macros)?;
+                    Ok((inv, expanded))
+                })
+                .map_err(|e| with_span(e, pattern, i, j + 2))?;
+            let custom =
+                custom_macros.and_then(|customs| customs.iter().find(|m| m.name == inv.name));
+            let inner_fields = custom.map_or_else(Vec::new, |_| named_groups(&frag));
+            i = j + 2;
+            // Likewise braces right after the macro are literal
+            let closing = pattern[i..].len() - pattern[i..].trim_start_matches('}').len();
+            let closing_braces = r"\}".repeat(closing);
+            i += closing;
+            // A macro of several fields needs no field of its own
+            if inv.field.is_none() && !inner_fields.is_empty() {
+                out.push_str(&format!("(?:{})", frag));
+            } else {
+                let field_name = if let Some(f) = inv.field {
+                    f
+                } else {
+                    auto_idx += 1;
+                    format!("auto_{}_{}", auto_idx, inv.name)
+                };
+                let capture = format!("(?P<{}>{})", field_name, frag);
+                out.push_str(&capture);
+                expansion.fields.push(field_name.clone());
+                if let Some(default) = custom_default(&inv.name, custom_macros) {
+                    expansion
+                        .defaults
+                        .insert(field_name.clone(), default.to_string());
+                }
+                if let Some(transform) =
+                    builtin_transform(&inv.name, &inv.args).filter(|_| custom.is_none())
+                {
+                    expansion.transforms.insert(field_name.clone(), transform);
+                }
+                if let Some(h) = hint {
+                    expansion.type_hints.insert(field_name, h);
+                }
+            }
+            for name in inner_fields {
+                expansion.fields.push(name.to_string());
+                if let Some(hint) = custom.and_then(|custom| custom.fields.get(name)) {
+                    expansion.type_hints.insert(name.to_string(), hint.clone());
+                }
+            }
+            out.push_str(&closing_braces);
+        } else {
+            let c = rest.chars().next().unwrap();
+            out.push(c);
+            i += c.len_utf8();
+        }
+    }
+    expansion.regex = out;
+    Ok(expansion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_shorthand_number() {
+        let pat = "qty: {{number:3-5}}";
+        let (expanded, fields, _) = expand_macros(pat, None).unwrap();
+        assert!(expanded.contains(r"\d{3,5}"));
+        assert_eq!(fields.len(), 1);
+    }
+
+    #[test]
+    fn test_auto_named_capture() {
+        let pat = "count={{number}} items";
+        let (expanded, fields, _) = expand_macros(pat, None).unwrap();
+        assert!(expanded.contains("(?P<auto_1_number>"));
+        assert_eq!(fields, vec!["auto_1_number".to_string()]);
+    }
+
+    #[test]
+    fn test_named_field_capture() {
+        let pat = "user {{name:var_name}} logged";
+        let (expanded, fields, _) = expand_macros(pat, None).unwrap();
+        assert!(expanded.contains("(?P<name>"));
+        assert_eq!(fields, vec!["name".to_string()]);
+    }
+
+    #[test]
+    fn test_datetime_macro_hint() {
+        let pat = "{{ts:datetime(\"%Y-%m-%d %H:%M:%S\")}} - msg";
+        let (_expanded, fields, hints) = expand_macros(pat, None).unwrap();
+        assert_eq!(fields.len(), 1);
+        let hint = hints.get(&fields[0]).unwrap();
+        assert_eq!(
+            *hint,
+            FieldType::DateTime(DateTime::new(Some(vec!["%Y-%m-%d %H:%M:%S".to_string()])))
+        );
+    }
+
+    #[test]
+    fn test_datetime_multiple_formats() {
+        let pat = "{{ts:datetime(\"%Y-%m-%d %H:%M:%S\",\"%d/%b/%Y:%H:%M:%S\")}} - msg";
+        let (expanded, fields, hints) = expand_macros(pat, None).unwrap();
+        assert!(expanded.contains("|"));
+        assert_eq!(fields.len(), 1);
+        let hint = hints.get(&fields[0]).unwrap();
+        assert_eq!(
+            *hint,
+            FieldType::DateTime(DateTime::new(Some(vec![
+                "%Y-%m-%d %H:%M:%S".to_string(),
+                "%d/%b/%Y:%H:%M:%S".to_string()
+            ])))
+        );
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert!(re.is_match("2023-05-03 12:34:56 - msg"));
+        assert!(re.is_match("03/May/2023:12:34:56 - msg"));
+    }
+
+    #[test]
+    fn test_custom_macro() {
+        let custom = CustomMacro {
+            name: "ip".to_string(),
+            pattern: r"\d{1,3}(?:\.\d{1,3}){3}".to_string(),
+            type_hint: Some(FieldType::String),
+            description: Some("IPv4 address".to_string()),
+            default: None,
+            fields: HashMap::new(),
+            expander: None,
+        };
+        let pat = "{{client:ip}} connected";
+        let (expanded, fields, hints) = expand_macros(pat, Some(&[custom])).unwrap();
+        assert!(expanded.contains(r"\d{1,3}(?:\.\d{1,3}){3}"));
+        assert_eq!(fields, vec!["client".to_string()]);
+        assert_eq!(*hints.get("client").unwrap(), FieldType::String);
+    }
+
+    #[test]
+    fn test_datetime_macro_default() {
+        let pat = "{{ts:datetime}} - msg";
+        let (_expanded, fields, hints) = expand_macros(pat, None).unwrap();
+        assert_eq!(fields.len(), 1);
+        let hint = hints.get(&fields[0]).unwrap();
+        assert_eq!(
+            *hint,
+            FieldType::DateTime(DateTime::new(Some(vec![
+                "%Y-%m-%dT%H:%M:%S%.f".to_string()
+            ])))
+        );
+    }
+
+    #[test]
+    fn test_float_macro() {
+        let pat = "value: {{val:float}}";
+        let (expanded, fields, hints) = expand_macros(pat, None).unwrap();
+
+        let re = regex::Regex::new(&expanded).unwrap();
+
+        // Integer compatible
+        assert!(re.is_match("value: 123"));
+        assert!(re.is_match("value: -5"));
+
+        // Standard floats
+        assert!(re.is_match("value: 123.456"));
+        assert!(re.is_match("value: 0.1"));
+        assert!(re.is_match("value: .5"));
+
+        // Scientific notation
+        assert!(re.is_match("value: 1.2e5"));
+        assert!(re.is_match("value: 1E-10"));
+
+        assert_eq!(fields, vec!["val".to_string()]);
+        assert_eq!(*hints.get("val").unwrap(), FieldType::Float);
+    }
+
+    #[test]
+    fn test_list_macro() {
+        let (expanded, _, hints) = expand_macros("^tags={{tags:list}} end$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert_eq!(&re.captures("tags=a,b-c,d end").unwrap()["tags"], "a,b-c,d");
+        assert_eq!(&re.captures("tags= end").unwrap()["tags"], "");
+        assert!(!re.is_match("tags=a,,b end"));
+        assert_eq!(&re.captures("tags=a, b end").unwrap()["tags"], "a, b");
+        assert_eq!(
+            FieldType::split_list("a, b", ",").collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(hints["tags"], FieldType::List(",".to_string()));
+
+        let (expanded, _, hints) = expand_macros(r#"^{{ports:list(";", number)}}$"#, None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert_eq!(&re.captures("80;443").unwrap()["ports"], "80;443");
+        assert!(!re.is_match("80;http"));
+        assert_eq!(hints["ports"], FieldType::List(";".to_string()));
+
+        assert!(expand_macros("{{x:list(a, number, b)}}", None).is_err());
+        assert!(expand_macros("{{x:list(',', nope)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_ip_macro() {
+        let (expanded, fields, hints) = expand_macros("^{{client:ip}} -$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        for ip in ["0.0.0.0", "10.0.0.1", "192.168.1.100", "255.255.255.255"] {
+            assert!(re.is_match(&format!("{} -", ip)), "{}", ip);
+        }
+        for ip in ["256.0.0.1", "1.2.3", "1.2.3.4.5", "01.2.3.4", "a.b.c.d"] {
+            assert!(!re.is_match(&format!("{} -", ip)), "{}", ip);
+        }
+        assert_eq!(fields, ["client"]);
+        assert_eq!(hints["client"], FieldType::String);
+        assert_eq!(
+            expand_macros("{{client:ipv4}}", None).unwrap().0,
+            format!("(?P<client>{})", IPV4)
+        );
+    }
+
+    #[test]
+    fn test_ipv6_macro() {
+        let (expanded, _, hints) = expand_macros("^{{client:ipv6}} -$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        for ip in [
+            "2001:0db8:85a3:0000:0000:8a2e:0370:7334",
+            "2001:db8::1",
+            "fe80::1:2:3:4",
+            "1:2:3:4:5:6::7",
+            "1::",
+            "::",
+            "::1",
+            "::ffff:192.0.2.128",
+            "64:ff9b::192.0.2.33",
+            "1:2:3:4:5:6:10.0.0.1",
+            "FF02::2",
+        ] {
+            assert!(re.is_match(&format!("{} -", ip)), "{}", ip);
+        }
+        for ip in [
+            "1:2:3:4:5:6:7:8:9",
+            "1::2::3",
+            "12345::1",
+            "1:2:3:4:5:6:7",
+            "::ffff:256.0.0.1",
+            "g::1",
+            "10.0.0.1",
+        ] {
+            assert!(!re.is_match(&format!("{} -", ip)), "{}", ip);
+        }
+        assert_eq!(hints["client"], FieldType::String);
+
+        // Without anchoring context the whole address is still captured
+        let (expanded, _, _) = expand_macros("{{client:ipv6}}", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        for ip in ["2001:db8::1:2", "::ffff:192.0.2.128", "1:2::3:4:5"] {
+            assert_eq!(&re.captures(ip).unwrap()["client"], ip);
+        }
+    }
+
+    #[test]
+    fn test_mac_macro() {
+        let (expanded, _, hints) = expand_macros("^DHCPACK to {{dev:mac}} via", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let dev = |line: &str| re.captures(line).map(|c| c["dev"].to_string());
+        assert_eq!(
+            dev("DHCPACK to 00:1A:2b:3c:4D:5e via").as_deref(),
+            Some("00:1A:2b:3c:4D:5e")
+        );
+        assert_eq!(
+            dev("DHCPACK to 00-1a-2b-3c-4d-5e via").as_deref(),
+            Some("00-1a-2b-3c-4d-5e")
+        );
+        // Separators may not be mixed
+        assert_eq!(dev("DHCPACK to 00:1a-2b:3c-4d:5e via"), None);
+        assert_eq!(dev("DHCPACK to 00:1a:2b:3c:4d via"), None);
+        assert_eq!(dev("DHCPACK to 00:1a:2b:3c:4d:5g via"), None);
+        assert_eq!(hints["dev"], FieldType::String);
+    }
+
+    #[test]
+    fn test_email_macro() {
+        let (expanded, _, hints) = expand_macros("user={{user:email}}[ ,]", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let user = |line: &str| re.captures(line).map(|c| c["user"].to_string());
+        assert_eq!(
+            user("user=jane.doe+ops@mail.example.co.uk,").as_deref(),
+            Some("jane.doe+ops@mail.example.co.uk")
+        );
+        assert_eq!(user("user=a_b@x-y.io ").as_deref(), Some("a_b@x-y.io"));
+        assert_eq!(user("user=root@localhost "), None);
+        assert_eq!(user("user=@example.com "), None);
+        assert_eq!(user("user=a@-bad.com "), None);
+        assert_eq!(hints["user"], FieldType::String);
+    }
+
+    #[test]
+    fn test_url_macro() {
+        let (expanded, _, hints) = expand_macros(r#""{{target:url}}""#, None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let target = |line: &str| re.captures(line).map(|c| c["target"].to_string());
+        assert_eq!(
+            target(r#"GET "https://user@example.com:8443/a/b.php?q=1&x=%20#top" 200"#).as_deref(),
+            Some("https://user@example.com:8443/a/b.php?q=1&x=%20#top")
+        );
+        assert_eq!(
+            target(r#""HTTP://example.com""#).as_deref(),
+            Some("HTTP://example.com")
+        );
+        assert_eq!(
+            target(r#""ftp://files.example.org/pub/""#).as_deref(),
+            Some("ftp://files.example.org/pub/")
+        );
+        assert_eq!(target(r#""file:///etc/passwd""#), None);
+        assert_eq!(target(r#""https://""#), None);
+        assert_eq!(hints["target"], FieldType::String);
+
+        // Unquoted, the URL stops at whitespace
+        let (expanded, _, _) = expand_macros("{{target:url}}", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert_eq!(
+            &re.captures("see http://a.io/x?y=1 now").unwrap()["target"],
+            "http://a.io/x?y=1"
+        );
+    }
+
+    #[test]
+    fn test_path_macro() {
+        let path = |pattern: &str, line: &str| {
+            let (expanded, _, _) = expand_macros(pattern, None).unwrap();
+            let re = regex::Regex::new(&expanded).unwrap();
+            re.captures(line).map(|c| c["file"].to_string())
+        };
+        let unix = "^open {{file:path(unix)}} ok$";
+        assert_eq!(
+            path(unix, "open /etc/passwd ok").as_deref(),
+            Some("/etc/passwd")
+        );
+        assert_eq!(path(unix, "open ~/.ssh/ ok").as_deref(), Some("~/.ssh/"));
+        assert_eq!(path(unix, "open ../a.txt ok").as_deref(), Some("../a.txt"));
+        assert_eq!(path(unix, "open etc/passwd ok"), None);
+        assert_eq!(path(unix, r"open C:\Temp ok"), None);
+
+        let windows = "^open {{file:path(windows)}} ok$";
+        assert_eq!(
+            path(windows, r"open C:\Users\bob\a.txt ok").as_deref(),
+            Some(r"C:\Users\bob\a.txt")
+        );
+        assert_eq!(
+            path(windows, "open d:/logs/ ok").as_deref(),
+            Some("d:/logs/")
+        );
+        assert_eq!(
+            path(windows, r"open \\srv\share\x ok").as_deref(),
+            Some(r"\\srv\share\x")
+        );
+        assert_eq!(path(windows, "open /etc/passwd ok"), None);
+
+        // Without an argument both kinds match, and a path stops at whitespace
+        let either = "open {{file:path}}";
+        assert_eq!(
+            path(either, "open /var/log/syslog now").as_deref(),
+            Some("/var/log/syslog")
+        );
+        assert_eq!(
+            path(either, r"open E:\x.log now").as_deref(),
+            Some(r"E:\x.log")
+        );
+        assert!(expand_macros("{{file:path(mac)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_hostname_macro() {
+        let (expanded, _, hints) = expand_macros("^{{host:hostname}} sshd$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        for host in ["web01", "db-2.eu-west.example.com", "A1.B2", "x"] {
+            assert!(re.is_match(&format!("{} sshd", host)), "{}", host);
+        }
+        for host in ["-web", "web-", "a..b", "a_b.com", &"a".repeat(64)] {
+            assert!(!re.is_match(&format!("{} sshd", host)), "{}", host);
+        }
+        assert_eq!(hints["host"], FieldType::String);
+        assert_eq!(
+            expand_macros("{{host:fqdn}}", None).unwrap().0,
+            expand_macros("{{host:hostname}}", None).unwrap().0
+        );
+    }
+
+    #[test]
+    fn test_base64_macro() {
+        let (expanded, _, hints) = expand_macros("^token={{token:base64}};$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        for token in ["aGVsbG8=", "aGVsbG8gd29ybGQ", "YQ==", "ab+/cd"] {
+            assert!(re.is_match(&format!("token={};", token)), "{}", token);
+        }
+        for token in ["", "aGVs bG8=", "YQ===", "a-b_c"] {
+            assert!(!re.is_match(&format!("token={};", token)), "{}", token);
+        }
+        assert_eq!(hints["token"], FieldType::String);
+
+        let (expanded, _, _) = expand_macros("^token={{token:base64(url)}};$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert!(re.is_match("token=a-b_c;"));
+        assert!(!re.is_match("token=a+b/c;"));
+        assert!(expand_macros("{{t:base64(mime)}}", None).is_err());
+
+        let (_, _, hints) =
+            expand_macros("{{a:base64(bytes)}} {{b:base64(url, bytes)}}", None).unwrap();
+        assert_eq!(hints["a"], FieldType::Binary(BinaryEncoding::Base64));
+        assert_eq!(hints["b"], FieldType::Binary(BinaryEncoding::Base64Url));
+        let (expanded, _, hints) = expand_macros("^{{sum:hex(bytes)}}$", None).unwrap();
+        assert_eq!(hints["sum"], FieldType::Binary(BinaryEncoding::Hex));
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert!(re.is_match("0xdeadBEEF"));
+        assert!(!re.is_match("abc"));
+    }
+
+    #[test]
+    fn test_uuid_macro() {
+        let matches = |pattern: &str, id: &str| {
+            let (expanded, _, _) = expand_macros(pattern, None).unwrap();
+            regex::Regex::new(&expanded)
+                .unwrap()
+                .is_match(&format!("id={} ok", id))
+        };
+        let dashed = "123e4567-E89b-12d3-a456-426614174000";
+        let dashless = "123e4567e89b12d3a456426614174000";
+        assert!(matches("^id={{id:uuid}} ok$", dashed));
+        assert!(!matches("^id={{id:uuid}} ok$", dashless));
+        assert!(matches("^id={{id:uuid(dashless)}} ok$", dashless));
+        assert!(!matches("^id={{id:uuid(dashless)}} ok$", dashed));
+        assert!(matches("^id={{id:uuid(any)}} ok$", dashed));
+        assert!(matches("^id={{id:uuid(any)}} ok$", dashless));
+        assert!(!matches("^id={{id:uuid(upper)}} ok$", dashed));
+        assert!(matches(
+            "^id={{id:uuid(dashless, upper)}} ok$",
+            &dashless.to_uppercase()
+        ));
+        assert!(matches(
+            "^id={{id:uuid(lower)}} ok$",
+            &dashed.to_lowercase()
+        ));
+        assert!(expand_macros("{{id:uuid(v4)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_http_method_macro() {
+        let (expanded, _, hints) = expand_macros(r#"^"{{method:http_method}} /"#, None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        for method in ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"] {
+            assert!(re.is_match(&format!(r#""{} /"#, method)), "{}", method);
+        }
+        for method in ["get", "FETCH", "GETS", "-"] {
+            assert!(!re.is_match(&format!(r#""{} /"#, method)), "{}", method);
+        }
+        assert_eq!(hints["method"], FieldType::Enum);
+    }
+
+    #[test]
+    fn test_http_status_macro() {
+        let matches = |pattern: &str, status: &str| {
+            let (expanded, _, _) = expand_macros(pattern, None).unwrap();
+            regex::Regex::new(&expanded)
+                .unwrap()
+                .is_match(&format!("{} 512", status))
+        };
+        assert!(matches("^{{status:http_status}} ", "200"));
+        assert!(matches("^{{status:http_status}} ", "999"));
+        assert!(!matches("^{{status:http_status}} ", "20"));
+        assert!(!matches("^{{status:http_status}} ", "2000"));
+        assert!(matches("^{{status:http_status(4xx, 5XX)}} ", "404"));
+        assert!(matches("^{{status:http_status(4xx, 5XX)}} ", "503"));
+        assert!(!matches("^{{status:http_status(4xx, 5XX)}} ", "200"));
+
+        let (_, _, hints) = expand_macros("{{status:http_status(2xx)}}", None).unwrap();
+        assert_eq!(hints["status"], FieldType::Int);
+        assert!(expand_macros("{{status:http_status(6xx)}}", None).is_err());
+        assert!(expand_macros("{{status:http_status(404)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_useragent_macro() {
+        let (expanded, _, hints) = expand_macros(
+            r#"^"{{referer:any}}" "{{agent:useragent}}" {{rest:any}}$"#,
+            None,
+        )
+        .unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let line = r#""-" "Mozilla/5.0 (Windows NT 10.0; Win64; x64) \"Edge\"" "x" 12"#;
+        let captures = re.captures(line).unwrap();
+        assert_eq!(
+            &captures["agent"],
+            r#"Mozilla/5.0 (Windows NT 10.0; Win64; x64) \"Edge\""#
+        );
+        assert_eq!(&captures["rest"], r#""x" 12"#);
+        assert_eq!(hints["agent"], FieldType::String);
+
+        // Unquoted, it runs to the end of the line
+        let (expanded, _, _) = expand_macros("ua={{agent:useragent}}", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert_eq!(
+            &re.captures("ua=curl/8.4.0 (x86_64)").unwrap()["agent"],
+            "curl/8.4.0 (x86_64)"
+        );
+    }
+
+    #[test]
+    fn test_json_macro() {
+        let (expanded, _, hints) = expand_macros("^{{msg:any}} {{payload:json}}$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let payload = |line: &str| re.captures(line).map(|c| c["payload"].to_string());
+        let object = r#"{"user":{"id":7,"tags":["a","b]"]},"note":"} {"}"#;
+        assert_eq!(
+            payload(&format!("request done {}", object)).as_deref(),
+            Some(object)
+        );
+        assert_eq!(
+            payload("batch [1, [2, 3], {}]").as_deref(),
+            Some("[1, [2, 3], {}]")
+        );
+        assert_eq!(payload("bad {\"a\": 1"), None);
+        assert_eq!(payload("bad [1}"), None);
+        assert_eq!(hints["payload"], FieldType::Json);
+
+        let nested = format!("x {}{}", "[".repeat(JSON_DEPTH), "]".repeat(JSON_DEPTH));
+        assert!(payload(&nested).is_some());
+        let deeper = format!(
+            "x {}{}",
+            "[".repeat(JSON_DEPTH + 1),
+            "]".repeat(JSON_DEPTH + 1)
+        );
+        assert!(payload(&deeper).is_none());
+    }
+
+    #[test]
+    fn test_kv_macro() {
+        let (expanded, _, hints) =
+            expand_macros("^{{level:var_name}} {{attrs:kv}}$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let line = r#"info user.id=7 msg="said \"hi\" twice" empty= http-status=200"#;
+        let attrs = re.captures(line).unwrap()["attrs"].to_string();
+        assert_eq!(hints["attrs"], FieldType::Map);
+        assert_eq!(
+            FieldType::split_pairs(&attrs).collect::<Vec<_>>(),
+            [
+                ("user.id", "7".into()),
+                ("msg", r#"said "hi" twice"#.into()),
+                ("empty", "".into()),
+                ("http-status", "200".into()),
+            ]
+        );
+        assert!(!re.is_match("info not pairs"));
+        assert_eq!(FieldType::split_pairs("").count(), 0);
+    }
+
+    #[test]
+    fn test_word_macro() {
+        let (expanded, fields, hints) =
+            expand_macros("{{user:word}} {{action:token}} done", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        // Unlike `any`, a word never reaches past whitespace to find what follows
+        let captures = re.captures("bob@x.io log-in done done").unwrap();
+        assert_eq!(&captures["user"], "bob@x.io");
+        assert_eq!(&captures["action"], "log-in");
+        assert!(!re.is_match("bob  done"));
+        assert_eq!(fields, ["user", "action"]);
+        assert_eq!(hints["user"], FieldType::String);
+    }
+
+    #[test]
+    fn test_greedy_any_macro() {
+        let capture = |pattern: &str, line: &str| {
+            let (expanded, _, _) = expand_macros(pattern, None).unwrap();
+            let re = regex::Regex::new(&expanded).unwrap();
+            re.captures(line).unwrap()["msg"].to_string()
+        };
+        // Unanchored, a lazy last field stops after one character
+        assert_eq!(capture(": {{msg:any}}", "x: disk full"), "d");
+        assert_eq!(
+            capture(": {{msg:any(greedy)}}", "x: disk full"),
+            "disk full"
+        );
+        assert_eq!(capture("- {{msg:string(greedy)}}", "a - b - c"), "b - c");
+        assert_eq!(capture(": {{msg:rest}}", "x: disk full"), "disk full");
+        assert_eq!(capture("x:{{msg:rest}}", "x:"), "");
+
+        assert!(expand_macros("{{msg:any(lazy)}}", None).is_err());
+        assert!(expand_macros("{{msg:string(greedy, 2)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_multi_field_custom_macro() {
+        let custom = CustomMacro {
+            name: "apache_request".to_string(),
+            pattern: r"(?P<method>[A-Z]+) (?P<path>\S+) HTTP/(?<proto>\d\.\d)".to_string(),
+            type_hint: None,
+            description: None,
+            default: None,
+            fields: HashMap::from([("proto".to_string(), FieldType::Float)]),
+            expander: None,
+        };
+        let customs = [custom];
+        let (expanded, fields, hints) = expand_macros(
+            r#"^"{{apache_request}}" {{status:number}}$"#,
+            Some(&customs),
+        )
+        .unwrap();
+        assert_eq!(fields, ["method", "path", "proto", "status"]);
+        assert_eq!(hints["proto"], FieldType::Float);
+        assert!(!hints.contains_key("method"));
+        let captures = regex::Regex::new(&expanded)
+            .unwrap()
+            .captures(r#""GET /a?b=1 HTTP/1.1" 200"#)
+            .unwrap();
+        assert_eq!(&captures["path"], "/a?b=1");
+        assert_eq!(&captures["proto"], "1.1");
+
+        // Naming the invocation captures the whole request too
+        let (_, fields, _) = expand_macros("{{request:apache_request}}", Some(&customs)).unwrap();
+        assert_eq!(fields, ["request", "method", "path", "proto"]);
+
+        assert_eq!(named_groups(r"\(?P<no>x\) (?:a)(?<=b)(?P<yes>c)"), ["yes"]);
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let err = expand_macros("^{{ip:ip}} {{user:nope}} done$", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Macro expansion error: unknown macro 'nope' at column 12\n  \
+             ^{{ip:ip}} {{user:nope}} done$\n  \
+             \x20          ^^^^^^^^^^^^^"
+        );
+
+        let err = expand_macros("a {{b:number} c", None).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("at column 3\n  a {{b:number} c\n    ^^")
+        );
+
+        // Long patterns are cut around the macro, and columns count characters
+        let pattern = format!("é{} {{{{x:nope}}}} {}", "a".repeat(40), "b".repeat(40));
+        let err = expand_macros(&pattern, None).unwrap_err().to_string();
+        let lines: Vec<&str> = err.lines().collect();
+        assert!(lines[0].ends_with("at column 43"));
+        assert_eq!(
+            lines[1],
+            format!(
+                "  ...{} {{{{x:nope}}}} {}...",
+                "a".repeat(29),
+                "b".repeat(29)
+            )
+        );
+        assert_eq!(lines[2], format!("  {}{}", " ".repeat(33), "^".repeat(10)));
+    }
+
+    #[test]
+    fn test_datetime_directives() {
+        for (format, value) in [
+            ("%b %e %H:%M:%S %Y", "May  3 12:34:56 2023"),
+            ("%b %e %H:%M:%S %Y", "May 13 12:34:56 2023"),
+            ("%Y-%m-%d %k:%M", "2023-05-03  9:05"),
+            ("%Y-%m-%d %l:%M %p", "2023-05-03  9:05 PM"),
+            ("%Y-%m-%d %I:%M:%S %P", "2023-05-03 09:05:00 am"),
+            ("%Y-%j %T", "2023-123 12:34:56"),
+            ("%s", "1683117296"),
+            ("%F %T%.3f", "2023-05-03 12:34:56.789"),
+            ("%F %T,%3f", "2023-05-03 12:34:56,789"),
+            ("%F %T%.6f %:z", "2023-05-03 12:34:56.789012 +02:00"),
+            ("%F %T%.9f", "2023-05-03 12:34:56.789012345"),
+            ("%F %T%.f", "2023-05-03 12:34:56"),
+            ("%FT%T%#z", "2023-05-03T12:34:56+02"),
+            ("%+", "2023-05-03T12:34:56.5Z"),
+            ("%c", "Wed May  3 12:34:56 2023"),
+            ("%D %r", "05/03/23 12:34:56 PM"),
+            ("%v %R", " 3-May-2023 12:34"),
+            ("%Y/%-m/%-d %T", "2023/5/3 12:34:56"),
+            ("%a, %d %b %Y %T %z", "Wed, 03 May 2023 12:34:56 +0000"),
+        ] {
+            let regex = format!("^{}$", format_to_regex(format, false).unwrap());
+            assert!(
+                regex::Regex::new(&regex).unwrap().is_match(value),
+                "{} !~ {}",
+                value,
+                regex
+            );
+            // The values the regex matches parse with the same format
+            let datetime = DateTime::new(Some(vec![format.to_string()]));
+            assert!(datetime.parse(value).is_some(), "{}: {}", format, value);
+        }
+        assert!(format_to_regex("%Q", false).is_err());
+        assert!(format_to_regex("%Y-%", false).is_err());
+    }
+
+    #[test]
+    fn test_datetime_locale() {
+        let (expanded, fields, hints) = expand_macros(
+            r#"^{{ts:datetime("%a, %d. %b %Y %H:%M", "%d %B %Y %H:%M", locale=de)}} {{msg:any}}$"#,
+            None,
+        )
+        .unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        let FieldType::DateTime(datetime) = &hints[&fields[0]] else {
+            panic!("{:?}", hints);
+        };
+        assert_eq!(datetime.locale.as_deref(), Some("de"));
+        assert_eq!(datetime.formats.as_ref().unwrap().len(), 2);
+        let parse = |line: &str| datetime.parse(&re.captures(line).unwrap()["ts"]);
+        let expected = |s| {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+                .unwrap()
+                .and_utc()
+                .timestamp_micros()
+        };
+        assert_eq!(
+            parse("Fr, 03. Mär 2023 12:34 up"),
+            Some(expected("2023-03-03 12:34"))
+        );
+        assert_eq!(
+            parse("24 Dezember 2023 08:00 up"),
+            Some(expected("2023-12-24 08:00"))
+        );
+        // 3 March 2023 was a Friday
+        assert_eq!(parse("Mo, 03. Mär 2023 12:34 up"), None);
+
+        let (expanded, fields, hints) =
+            expand_macros(r#"{{ts:datetime("%d %b %Y %H:%M", locale=fr)}}"#, None).unwrap();
+        let FieldType::DateTime(datetime) = &hints[&fields[0]] else {
+            panic!("{:?}", hints);
+        };
+        let value = &regex::Regex::new(&expanded)
+            .unwrap()
+            .captures("le 01 déc 2023 18:30")
+            .unwrap()["ts"];
+        assert_eq!(value, "01 déc 2023 18:30");
+        assert!(datetime.parse(value).is_some());
+
+        assert!(expand_macros(r#"{{ts:datetime("%b", locale=xx)}}"#, None).is_err());
+        assert!(expand_macros("{{ts:datetime(locale=de)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_datetime_unit() {
+        let (_, _, hints) = expand_macros(
+            r#"{{ts:datetime("%Y-%m-%d %H:%M:%S", unit=ms, locale=de)}}"#,
+            None,
+        )
+        .unwrap();
+        let Some(FieldType::DateTime(datetime)) = hints.get("ts") else {
+            panic!("ts is not a datetime: {:?}", hints);
+        };
+        assert_eq!(datetime.unit, Some(TimestampUnit::Millis));
+        assert_eq!(datetime.formats.as_ref().unwrap().len(), 1);
+
+        assert!(expand_macros(r#"{{ts:datetime("%s", unit=h)}}"#, None).is_err());
+
+        let (_, _, hints) =
+            expand_macros(r#"{{ts:datetime("%F %T", tz=Europe/Berlin)}}"#, None).unwrap();
+        let Some(FieldType::DateTime(datetime)) = hints.get("ts") else {
+            panic!("ts is not a datetime: {:?}", hints);
+        };
+        assert_eq!(datetime.timezone.as_deref(), Some("Europe/Berlin"));
+        assert!(expand_macros(r#"{{ts:datetime("%F %T", tz=Mars/Olympus)}}"#, None).is_err());
+        assert!(expand_macros(r#"{{ts:datetime("%s", unit=s, unit=ms)}}"#, None).is_err());
+    }
+
+    #[test]
+    fn test_decimal_macro() {
+        let (regex, _, hints) = expand_macros(
+            "^{{amount:decimal(18,4)}} {{fee:dec(5)}} {{rate:decimal}}$",
+            None,
+        )
+        .unwrap();
+        assert_eq!(hints["amount"], FieldType::Decimal(18, 4));
+        assert_eq!(hints["fee"], FieldType::Decimal(5, 0));
+        assert_eq!(hints["rate"], FieldType::Decimal(38, 10));
+        let re = regex::Regex::new(&regex).unwrap();
+        assert!(re.is_match("-1234.5678 12 .5"));
+        assert!(!re.is_match("1e3 12 .5"));
+
+        assert!(expand_macros("{{amount:decimal(39,2)}}", None).is_err());
+        assert!(expand_macros("{{amount:decimal(4,5)}}", None).is_err());
+        assert!(expand_macros("{{amount:decimal(x)}}", None).is_err());
+        assert!(expand_macros("{{amount:decimal(1,2,3)}}", None).is_err());
+    }
+
+    #[test]
+    fn test_enum_macro() {
+        let (expanded, _, hints) =
+            expand_macros("^{{level:enum(INFO, WARN,ERROR,INFO)}}$", None).unwrap();
+        assert_eq!(
+            hints["level"],
+            FieldType::Dictionary(vec!["INFO".into(), "WARN".into(), "ERROR".into()])
+        );
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert!(re.is_match("WARN"));
+        assert!(!re.is_match("DEBUG"));
+
+        let many = (0..=MAX_DICTIONARY_VALUES)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        assert!(expand_macros(&format!("{{{{n:enum({})}}}}", many), None).is_err());
+    }
+
+    #[test]
+    fn test_braces_in_macro_args() {
+        // `}}` inside quotes or parentheses does not close the macro
+        let (expanded, _, hints) = expand_macros(r#"^{{ids:list("}}", number)}}$"#, None).unwrap();
+        assert_eq!(hints.get("ids"), Some(&FieldType::List("}}".to_string())));
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert_eq!(&re.captures("1}}2}} 3").unwrap()["ids"], "1}}2}} 3");
+
+        let (expanded, _, _) = expand_macros("^{{v:enum(a{2}}, b)}}$", None).unwrap();
+        let re = regex::Regex::new(&expanded).unwrap();
+        assert!(re.is_match("a{2}}"));
+        assert!(re.is_match("b"));
+
+        // `\{` and `\}` are literal braces anywhere in the body
+        let (escaped, _, _) = expand_macros(r"^{{v:enum(\}\}, \{)}}$", None).unwrap();
+        let re = regex::Regex::new(&escaped).unwrap();
+        assert!(re.is_match("}}"));
+        assert!(re.is_match("{"));
+        let (quoted, _, _) = expand_macros(r#"^{{v:enum("}}", "\{")}}$"#, None).unwrap();
+        assert_eq!(escaped, quoted);
+
+        // Braces outside the macros are untouched
+        let (expanded, fields, _) = expand_macros(r"^\d{2,3} {{n:number}}x{1}$", None).unwrap();
+        assert_eq!(fields, ["n"]);
+        assert!(regex::Regex::new(&expanded).unwrap().is_match("12 7x"));
+
+        let err = expand_macros(r#"{{v:list("}}")"#, None).unwrap_err();
+        assert!(err.to_string().contains("unclosed"), "{}", err);
+        assert!(expand_macros(r"{{v:enum(a\}}", None).is_err());
+    }
+
+    #[test]
+    fn test_literal_braces_around_macros() {
+        let matches = |pattern: &str, line: &str| {
+            let (expanded, _, _) = expand_macros(pattern, None).unwrap();
+            let re = regex::Regex::new(&expanded).unwrap();
+            re.captures(line)
+                .map(|caps| caps.get(1).map_or("", |m| m.as_str()).to_string())
+        };
+        // Extra braces around a macro are literal
+        assert_eq!(
+            matches("^{{{count:number}}}$", "{42}").as_deref(),
+            Some("42")
+        );
+        assert_eq!(matches("^{{{{n:number}}}}$", "{{7}}").as_deref(), Some("7"));
+        assert_eq!(matches("^{{{count:number}}}$", "42"), None);
+        // `\{{` is a literal `{{`, `\\` before a macro a literal backslash
+        assert_eq!(
+            matches(r"^\{{x}} {{n:number}}$", "{{x}} 5").as_deref(),
+            Some("5")
+        );
+        assert_eq!(matches(r"^\\{{n:number}}$", r"\5").as_deref(), Some("5"));
+        // Adjacent macros and quantifiers next to macros
+        let (expanded, fields, _) =
+            expand_macros("^{{a:number}}{{b:var_name}}x{2}{{c:number}}$", None).unwrap();
+        assert_eq!(fields, ["a", "b", "c"]);
+        let caps = regex::Regex::new(&expanded)
+            .unwrap()
+            .captures("12abxx3")
+            .unwrap();
+        assert_eq!((&caps["a"], &caps["b"], &caps["c"]), ("12", "ab", "3"));
+        // Text around macros keeps its non-ASCII characters
+        assert_eq!(
+            matches("^größe={{n:number}} µs$", "größe=3 µs").as_deref(),
+            Some("3")
+        );
+
+        assert!(expand_macros("{{}}", None).is_err());
+        assert!(expand_macros("{{{n:number}", None).is_err());
+    }
+}
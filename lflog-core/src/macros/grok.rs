@@ -0,0 +1,240 @@
+//! Grok pattern references, e.g. `%{IPV4:client}`.
+//!
+//! A reference names a grok definition, an optional field to capture it into and an
+//! optional `int` or `float` type: `%{NUMBER:bytes:int}`. Definitions come from
+//! custom macros of the same name, then from the bundled standard set, and may refer
+//! to other definitions; their named references become fields too, as in Logstash.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::error::{Error, Result};
+use crate::macros::expander::Expansion;
+use crate::macros::parser::CustomMacro;
+use crate::types::FieldType;
+
+/// The bundled definitions, in Logstash's `NAME regex` format.
+const GROK_PATTERNS: &str = include_str!("grok-patterns");
+
+/// Deepest chain of definitions referring to one another.
+const MAX_DEPTH: usize = 32;
+
+/// Bundled definitions by name.
+fn bundled() -> &'static HashMap<&'static str, &'static str> {
+    static BUNDLED: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+    BUNDLED.get_or_init(|| {
+        GROK_PATTERNS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once(' '))
+            .collect()
+    })
+}
+
+/// A `%{NAME:field:type}` reference.
+pub(crate) struct GrokRef<'p> {
+    name: &'p str,
+    field: Option<&'p str>,
+    ty: Option<&'p str>,
+    /// Length of the reference in the pattern.
+    pub(crate) len: usize,
+}
+
+/// Parse the reference at the start of `s`, or `None` if `s` does not start with
+/// one, such as the regex `%{2}`.
+pub(crate) fn parse_ref(s: &str) -> Option<GrokRef<'_>> {
+    let body = s.strip_prefix("%{")?;
+    let end = body.find('}')?;
+    let mut parts = body[..end].split(':');
+    let name = parts.next()?;
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    let field = parts.next();
+    let ty = parts.next();
+    if parts.next().is_some() || field.is_some_and(str::is_empty) {
+        return None;
+    }
+    Some(GrokRef {
+        name,
+        field,
+        ty,
+        len: end + 3,
+    })
+}
+
+/// Expand `reference` into a regex fragment, adding its field and those of the
+/// definitions it refers to to `expansion`.
+pub(crate) fn expand_ref(
+    reference: &GrokRef<'_>,
+    custom_macros: Option<&[CustomMacro]>,
+    expansion: &mut Expansion,
+    depth: usize,
+) -> Result<String> {
+    if depth > MAX_DEPTH {
+        return Err(Error::Macro(format!(
+            "grok pattern '{}' refers to itself or nests too deeply",
+            reference.name
+        )));
+    }
+    let custom =
+        custom_macros.and_then(|customs| customs.iter().find(|m| m.name == reference.name));
+    let definition = match custom {
+        Some(custom) => custom.regex(&[])?,
+        None => bundled()
+            .get(reference.name)
+            .map(|definition| definition.to_string())
+            .ok_or_else(|| Error::Macro(format!("unknown grok pattern '{}'", reference.name)))?,
+    };
+    let Some(field) = reference.field else {
+        let regex = expand_definition(&definition, custom_macros, expansion, depth + 1)?;
+        return Ok(format!("(?:{})", regex));
+    };
+    let hint = match reference.ty {
+        None => custom.and_then(|custom| custom.type_hint.clone()),
+        Some("int") => Some(FieldType::Int),
+        Some("float") => Some(FieldType::Float),
+        Some(other) => {
+            return Err(Error::Macro(format!(
+                "unknown grok type '{}' for field '{}', expected int or float",
+                other, field
+            )));
+        }
+    };
+    expansion.fields.push(field.to_string());
+    if let Some(hint) = hint {
+        expansion.type_hints.insert(field.to_string(), hint);
+    }
+    if let Some(default) = custom.and_then(|custom| custom.default.clone()) {
+        expansion.defaults.insert(field.to_string(), default);
+    }
+    // Fields are in the order their groups open, so this one precedes its inner fields
+    let regex = expand_definition(&definition, custom_macros, expansion, depth + 1)?;
+    Ok(format!("(?P<{}>{})", field, regex))
+}
+
+/// Expand the grok references in a definition, keeping the rest as is.
+fn expand_definition(
+    definition: &str,
+    custom_macros: Option<&[CustomMacro]>,
+    expansion: &mut Expansion,
+    depth: usize,
+) -> Result<String> {
+    let mut out = String::with_capacity(definition.len());
+    let mut rest = definition;
+    while let Some(start) = rest.find("%{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match parse_ref(rest) {
+            Some(reference) => {
+                out.push_str(&expand_ref(&reference, custom_macros, expansion, depth)?);
+                rest = &rest[reference.len..];
+            }
+            None => {
+                out.push_str("%{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    #[test]
+    fn test_bundled_definitions_compile() {
+        for name in bundled().keys() {
+            let mut expansion = Expansion::default();
+            let reference = GrokRef {
+                name,
+                field: None,
+                ty: None,
+                len: 0,
+            };
+            let regex = expand_ref(&reference, None, &mut expansion, 0).unwrap();
+            assert!(Regex::new(&regex).is_ok(), "{}: {}", name, regex);
+        }
+    }
+
+    #[test]
+    fn test_parse_ref() {
+        let reference = parse_ref("%{NUMBER:bytes:int} rest").unwrap();
+        assert_eq!(
+            (reference.name, reference.field, reference.ty, reference.len),
+            ("NUMBER", Some("bytes"), Some("int"), 19)
+        );
+        assert!(parse_ref("%{2}").is_none());
+        assert!(parse_ref("%{IP:}").is_none());
+        assert!(parse_ref("%{IP").is_none());
+    }
+
+    #[test]
+    fn test_grok_scanner() {
+        use crate::extractor::RecordExtractor;
+        use crate::scanner::Scanner;
+
+        let scanner = Scanner::new("^%{COMBINEDAPACHELOG} {{took:number}}ms$".to_string()).unwrap();
+        assert_eq!(
+            scanner.field_names,
+            [
+                "clientip",
+                "ident",
+                "auth",
+                "timestamp",
+                "verb",
+                "request",
+                "httpversion",
+                "rawrequest",
+                "response",
+                "bytes",
+                "referrer",
+                "agent",
+                "took"
+            ]
+        );
+        let line = r#"10.0.0.7 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "-" "curl/8" 12ms"#;
+        let mut out = Vec::new();
+        assert!(scanner.extract(line, &mut out));
+        assert_eq!(out[0], "10.0.0.7");
+        assert_eq!(out[3], "10/Oct/2000:13:55:36 -0700");
+        assert_eq!(out[5], "/a.gif");
+        assert_eq!(out[11], "\"curl/8\"");
+        assert_eq!(out[12], "12");
+
+        let scanner =
+            Scanner::new("^%{IP:client} %{NUMBER:took:float} %{WORD}$".to_string()).unwrap();
+        assert_eq!(scanner.field_names, ["client", "took"]);
+        assert_eq!(scanner.type_hints["took"], FieldType::Float);
+        assert_eq!(
+            scanner.scan("fe80::1 0.5 ok"),
+            Some(vec!["fe80::1".to_string(), "0.5".to_string()])
+        );
+
+        // Custom macros define grok patterns too, and may refer to bundled ones
+        let customs = [CustomMacro {
+            name: "ENDPOINT".into(),
+            pattern: "%{IPV4:host}:%{POSINT:port:int}".into(),
+            type_hint: None,
+            default: None,
+            description: None,
+            fields: HashMap::new(),
+            expander: None,
+        }];
+        let scanner =
+            Scanner::with_patterns(vec!["^%{ENDPOINT:endpoint}$".into()], Some(&customs)).unwrap();
+        assert_eq!(scanner.field_names, ["endpoint", "host", "port"]);
+        assert_eq!(scanner.type_hints["port"], FieldType::Int);
+
+        assert!(Scanner::new("%{NOPE}".to_string()).is_err());
+        assert!(Scanner::new("%{INT:n:long}".to_string()).is_err());
+        // Not a reference: a repetition of `%`
+        assert!(Scanner::new("a%{2}".to_string()).is_ok());
+    }
+}
@@ -0,0 +1,17 @@
+//! Macro expansion module for log pattern parsing.
+//!
+//! This module provides functionality to parse and expand macros in log patterns,
+//! such as `{{field:datetime("%Y-%m-%d")}}` or `{{count:number}}`, and grok references
+//! like `%{IPV4:client}`.
+
+mod catalog;
+mod expander;
+mod grok;
+pub mod parser;
+mod registry;
+
+pub use catalog::{MacroInfo, MacroSource, available_macros, builtin_macros};
+pub(crate) use expander::expand;
+pub use expander::expand_macros;
+pub use parser::{CustomMacro, MacroExpander, MacroInvocation, Profile, ProfileFormat, Profiles};
+pub use registry::MacroRegistry;
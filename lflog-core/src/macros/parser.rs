@@ -0,0 +1,743 @@
+//! Macro invocation parser.
+//!
+//! Parses macro invocations like `field:datetime("%Y-%m-%d")` into structured data.
+
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::extractor::{
+    DelimitedExtractor, FieldSpec, JsonExtractor, LogfmtExtractor, RecordExtractor, RecordFormat,
+};
+use crate::redact::{RedactExtractor, parse_redactions};
+use crate::rules::{OnViolation, RuleExtractor, parse_rules};
+use crate::scanner::{Engine, Scanner};
+use crate::transform::{TransformExtractor, parse_transforms};
+use crate::types::{FieldType, TimestampUnit, YearStrategy};
+
+/// Represents a parsed macro invocation.
+#[derive(Debug, Clone)]
+pub struct MacroInvocation {
+    pub field: Option<String>,
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/// Split a comma-separated argument string, respecting quoted strings.
+pub fn split_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut in_quote: Option<char> = None;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if let Some(q) = in_quote {
+            if c == '\\' {
+                if let Some(&next) = chars.peek() {
+                    cur.push(next);
+                    chars.next();
+                }
+            } else if c == q {
+                in_quote = None;
+            } else {
+                cur.push(c);
+            }
+        } else if c == '\'' || c == '"' {
+            in_quote = Some(c);
+        } else if c == ',' {
+            args.push(cur.trim().to_string());
+            cur.clear();
+        } else {
+            cur.push(c);
+        }
+    }
+    if !cur.trim().is_empty() {
+        args.push(cur.trim().to_string());
+    }
+    args.into_iter()
+        .map(|a| {
+            if (a.starts_with('"') && a.ends_with('"'))
+                || (a.starts_with('\'') && a.ends_with('\''))
+            {
+                a[1..a.len() - 1].to_string()
+            } else {
+                a
+            }
+        })
+        .collect()
+}
+
+/// Parse a macro invocation string into a `MacroInvocation`.
+///
+/// Supports formats like:
+/// - `field:macro_name(arg1, arg2)`
+/// - `macro_name(arg1)`
+/// - `field:macro_name`
+/// - `macro_name`
+pub fn parse_macro_invocation(s: &str) -> Result<MacroInvocation> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(Error::MacroParse("empty macro token".into()));
+    }
+    if let Some(paren_start) = s.find('(') {
+        let before = &s[..paren_start];
+        let after = &s[paren_start + 1..];
+        if !after.ends_with(')') {
+            return Err(Error::MacroParse(format!(
+                "unclosed parenthesis in macro invocation: {}",
+                s
+            )));
+        }
+        let inside = &after[..after.len() - 1];
+        if let Some(colon_pos) = before.find(':') {
+            let field = before[..colon_pos].trim().to_string();
+            let name = before[colon_pos + 1..].trim().to_string();
+            let args = split_args(inside);
+            Ok(MacroInvocation {
+                field: Some(field),
+                name,
+                args,
+            })
+        } else {
+            let name = before.trim().to_string();
+            let args = split_args(inside);
+            Ok(MacroInvocation {
+                field: None,
+                name,
+                args,
+            })
+        }
+    } else if let Some(colon_pos) = s.find(':') {
+        let left = s[..colon_pos].trim();
+        let right = s[colon_pos + 1..].trim();
+        if right
+            .chars()
+            .next()
+            .map(|c| c.is_ascii_digit())
+            .unwrap_or(false)
+            || right.contains('-')
+            || right.contains(',')
+        {
+            let name = left.to_string();
+            let args = vec![right.to_string()];
+            Ok(MacroInvocation {
+                field: None,
+                name,
+                args,
+            })
+        } else {
+            let field = left.to_string();
+            let name = right.to_string();
+            Ok(MacroInvocation {
+                field: Some(field),
+                name,
+                args: vec![],
+            })
+        }
+    } else {
+        Ok(MacroInvocation {
+            field: None,
+            name: s.to_string(),
+            args: vec![],
+        })
+    }
+}
+
+/// Computes the regex of a macro from its arguments, like `3` in `{{digits(3)}}`.
+pub type MacroExpander = Arc<dyn Fn(&[String]) -> Result<String> + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CustomMacro {
+    pub name: String,
+    /// Regex the macro expands to, unless it has an `expander`.
+    #[serde(default)]
+    pub pattern: String,
+    pub type_hint: Option<FieldType>,
+    pub description: Option<String>,
+    /// Value of fields using this macro when their group does not participate.
+    pub default: Option<String>,
+    /// Type hints of the named groups of `pattern`, e.g. `{ status = "Int" }`. Every
+    /// named group becomes a field of the patterns using the macro.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fields: HashMap<String, FieldType>,
+    /// Computes the regex from the macro's arguments instead of `pattern`; only set
+    /// in code, see [`MacroRegistry`](super::MacroRegistry).
+    #[serde(skip)]
+    pub expander: Option<MacroExpander>,
+}
+
+impl CustomMacro {
+    /// A macro expanding to `pattern`.
+    pub fn new(name: impl Into<String>, pattern: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            pattern: pattern.into(),
+            type_hint: None,
+            description: None,
+            default: None,
+            fields: HashMap::new(),
+            expander: None,
+        }
+    }
+
+    /// A macro whose regex `expander` computes from its arguments, as the builtins
+    /// do; an error it returns fails the pattern using the macro.
+    pub fn from_fn(
+        name: impl Into<String>,
+        expander: impl Fn(&[String]) -> Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            expander: Some(Arc::new(expander)),
+            ..Self::new(name, "")
+        }
+    }
+
+    /// Set the type of the fields using the macro.
+    pub fn with_type_hint(mut self, type_hint: FieldType) -> Self {
+        self.type_hint = Some(type_hint);
+        self
+    }
+
+    /// Set the description of the macro.
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the value of fields using the macro when their group does not participate.
+    pub fn with_default(mut self, default: impl Into<String>) -> Self {
+        self.default = Some(default.into());
+        self
+    }
+
+    /// The regex of the macro invoked with `args`.
+    pub(crate) fn regex(&self, args: &[String]) -> Result<String> {
+        match &self.expander {
+            Some(expander) => expander(args),
+            None => Ok(self.pattern.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub custom_macros: Vec<CustomMacro>,
+    /// Regex pattern with macros; only used by the `regex` format.
+    #[serde(default)]
+    pub pattern: String,
+    /// Further patterns tried in order for lines `pattern` does not match.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Substring every line of this profile contains, checked before the patterns.
+    pub prefilter: Option<String>,
+    /// Regex engine for the patterns; engines other than `regex` need their feature.
+    #[serde(default)]
+    pub engine: Engine,
+    /// Regex flags applied to every pattern, e.g. `["i"]`; see [`Scanner::with_flags`].
+    #[serde(default)]
+    pub flags: Vec<String>,
+    pub description: Option<String>,
+    /// How lines are turned into records.
+    #[serde(default)]
+    pub format: RecordFormat,
+    /// Fields read by the `json`, `logfmt` and `delimited` formats.
+    #[serde(default)]
+    pub fields: Vec<FieldSpec>,
+    /// Column separator for the `delimited` format, `,` by default.
+    pub delimiter: Option<char>,
+    /// Leading lines of each file to ignore, e.g. a banner header.
+    #[serde(default)]
+    pub skip_lines: usize,
+    /// Trailing lines of each file to ignore, e.g. a summary footer.
+    #[serde(default)]
+    pub skip_footer: usize,
+    /// Encoding of the files, e.g. `utf-16le` or `latin1`; UTF-8 when unset.
+    pub encoding: Option<String>,
+    /// How timestamps without a year get one, e.g. `"mtime"` or `"2023"`.
+    pub year: Option<YearStrategy>,
+    /// Unit of timestamp columns, `s`, `ms`, `us` or `ns`; microseconds when unset.
+    pub timestamp_unit: Option<TimestampUnit>,
+    /// Unit of `duration(typed)` columns, `s`, `ms`, `us` or `ns`; milliseconds when
+    /// unset.
+    pub duration_unit: Option<TimestampUnit>,
+    /// Lines to drop as noise before matching, e.g. health checks; only used by the
+    /// `regex` format.
+    pub exclude_pattern: Option<String>,
+    /// Transform pipelines by field name, e.g. `{ level = ["lowercase"] }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub transforms: HashMap<String, Vec<String>>,
+    /// Values of fields whose group does not participate, e.g. `{ level = "INFO" }`;
+    /// only used by the `regex` format.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub defaults: HashMap<String, String>,
+    /// Value rules by field name, checked after transforms, e.g.
+    /// `{ status = ["range(100, 599)"] }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rules: HashMap<String, Vec<String>>,
+    /// What happens to records that break a rule.
+    #[serde(default)]
+    pub on_violation: OnViolation,
+    /// Fields redacted at scan time, e.g. `{ email = "hash", token = "drop" }`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub redact: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profiles {
+    #[serde(default)]
+    pub custom_macros: Vec<CustomMacro>,
+    pub profiles: Vec<Profile>,
+    /// Plugin libraries loaded by `lflog` at startup; relative paths are resolved
+    /// against the profiles file's directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<String>,
+}
+
+/// Serialization format of a profiles file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ProfileFormat {
+    /// Detect the format from a file extension, defaulting to TOML.
+    pub fn from_path(path: &str) -> Self {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match ext.as_deref() {
+            Some("json") => ProfileFormat::Json,
+            Some("yaml") | Some("yml") => ProfileFormat::Yaml,
+            _ => ProfileFormat::Toml,
+        }
+    }
+
+    /// Parse a format name such as `toml`, `json` or `yaml`.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name.to_lowercase().as_str() {
+            "toml" => Ok(ProfileFormat::Toml),
+            "json" => Ok(ProfileFormat::Json),
+            "yaml" | "yml" => Ok(ProfileFormat::Yaml),
+            other => Err(Error::Config(format!(
+                "unknown profile format '{}', expected toml, json or yaml",
+                other
+            ))),
+        }
+    }
+}
+
+impl Profiles {
+    /// Load profiles from a file, choosing the format from its extension.
+    pub fn from_file(path: &str) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut profiles = Self::from_str_with_format(&content, ProfileFormat::from_path(path))?;
+        let dir = std::path::Path::new(path)
+            .parent()
+            .unwrap_or(std::path::Path::new(""));
+        for plugin in profiles.plugins.iter_mut() {
+            *plugin = dir.join(&*plugin).to_string_lossy().into_owned();
+        }
+        Ok(profiles)
+    }
+
+    /// Parse profiles from a string in the given format.
+    pub fn from_str_with_format(content: &str, format: ProfileFormat) -> Result<Self> {
+        let mut profiles: Profiles = match format {
+            ProfileFormat::Toml => toml::from_str(content)?,
+            ProfileFormat::Json => serde_json::from_str(content)?,
+            ProfileFormat::Yaml => serde_yaml::from_str(content)?,
+        };
+
+        // Merge profile macros with custom macros
+        for profile in profiles.profiles.iter_mut() {
+            profile.custom_macros.extend(profiles.custom_macros.clone());
+        }
+
+        Ok(profiles)
+    }
+
+    /// Serialize profiles to a string in the given format.
+    ///
+    /// Global macros merged into each profile on load are written only once,
+    /// at the top level, so the output can be loaded back unchanged.
+    pub fn to_string_with_format(&self, format: ProfileFormat) -> Result<String> {
+        let mut exported = self.clone();
+        let global_names: Vec<&str> = self.custom_macros.iter().map(|m| m.name.as_str()).collect();
+        for profile in exported.profiles.iter_mut() {
+            let len = profile.custom_macros.len();
+            if len >= global_names.len()
+                && profile.custom_macros[len - global_names.len()..]
+                    .iter()
+                    .map(|m| m.name.as_str())
+                    .eq(global_names.iter().copied())
+            {
+                profile.custom_macros.truncate(len - global_names.len());
+            }
+        }
+
+        let out = match format {
+            ProfileFormat::Toml => toml::to_string_pretty(&exported)?,
+            ProfileFormat::Json => serde_json::to_string_pretty(&exported)?,
+            ProfileFormat::Yaml => serde_yaml::to_string(&exported)?,
+        };
+        Ok(out)
+    }
+
+    /// Write profiles to a file, choosing the format from its extension.
+    pub fn to_file(&self, path: &str) -> Result<()> {
+        let content = self.to_string_with_format(ProfileFormat::from_path(path))?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Add the global macros and profiles of `other` that are not already defined.
+    ///
+    /// Entries already present (by name) win, so sources merged first take precedence.
+    pub fn merge(&mut self, other: Profiles) {
+        for custom in other.custom_macros {
+            if self.get_macro(&custom.name).is_none() {
+                self.custom_macros.push(custom);
+            }
+        }
+        for profile in other.profiles {
+            if self.get_profile(&profile.name).is_none() {
+                self.profiles.push(profile);
+            }
+        }
+    }
+
+    pub fn get_macro(&self, name: &str) -> Option<&CustomMacro> {
+        self.custom_macros.iter().find(|m| m.name == name)
+    }
+
+    pub fn get_profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+}
+
+impl Profile {
+    pub fn get_macro(&self, name: &str) -> Option<&CustomMacro> {
+        self.custom_macros.iter().find(|m| m.name == name)
+    }
+
+    /// `pattern` followed by `patterns`, in the order they are tried.
+    pub fn regex_patterns(&self) -> Vec<String> {
+        std::iter::once(&self.pattern)
+            .filter(|p| !p.is_empty() || self.patterns.is_empty())
+            .chain(&self.patterns)
+            .cloned()
+            .collect()
+    }
+
+    /// Build the record extractor selected by this profile's `format`.
+    pub fn extractor(&self) -> Result<Arc<dyn RecordExtractor>> {
+        if self.format != RecordFormat::Regex && self.fields.is_empty() {
+            return Err(Error::Config(format!(
+                "Profile '{}' must list the fields to read",
+                self.name
+            )));
+        }
+        if self.format != RecordFormat::Regex && !self.defaults.is_empty() {
+            return Err(Error::Config(format!(
+                "Profile '{}': field defaults need the regex format",
+                self.name
+            )));
+        }
+        if self.format != RecordFormat::Regex && self.exclude_pattern.is_some() {
+            return Err(Error::Config(format!(
+                "Profile '{}': an exclude pattern needs the regex format",
+                self.name
+            )));
+        }
+        let fields = self.fields.clone();
+        let extractor: Arc<dyn RecordExtractor> = match self.format {
+            RecordFormat::Regex => {
+                let scanner = Scanner::with_patterns_and_engine(
+                    self.regex_patterns(),
+                    Some(&self.custom_macros),
+                    self.engine,
+                )?
+                .with_flags(&self.flags)?
+                .with_defaults(&self.defaults)?;
+                let scanner = match &self.exclude_pattern {
+                    Some(exclude) => {
+                        scanner.with_exclude_pattern(exclude, Some(&self.custom_macros))?
+                    }
+                    None => scanner,
+                };
+                match &self.prefilter {
+                    Some(literal) => Arc::new(scanner.with_prefilter(literal)),
+                    None => Arc::new(scanner),
+                }
+            }
+            RecordFormat::Json => Arc::new(JsonExtractor::new(fields)),
+            RecordFormat::Logfmt => Arc::new(LogfmtExtractor::new(fields)),
+            RecordFormat::Delimited => Arc::new(DelimitedExtractor::new(
+                self.delimiter.unwrap_or(','),
+                fields,
+            )),
+        };
+        let extractor: Arc<dyn RecordExtractor> = if self.transforms.is_empty() {
+            extractor
+        } else {
+            let transforms = parse_transforms(&self.transforms)
+                .and_then(|transforms| TransformExtractor::new(extractor, transforms))
+                .map_err(|e| Error::Config(format!("Profile '{}': {}", self.name, e)))?;
+            Arc::new(transforms)
+        };
+        let extractor: Arc<dyn RecordExtractor> = if self.rules.is_empty() {
+            extractor
+        } else {
+            let rules = parse_rules(&self.rules)
+                .and_then(|rules| RuleExtractor::new(extractor, rules, self.on_violation))
+                .map_err(|e| Error::Config(format!("Profile '{}': {}", self.name, e)))?;
+            Arc::new(rules)
+        };
+        if self.redact.is_empty() {
+            return Ok(extractor);
+        }
+        let redact = parse_redactions(&self.redact)
+            .and_then(|redactions| RedactExtractor::new(extractor, redactions))
+            .map_err(|e| Error::Config(format!("Profile '{}': {}", self.name, e)))?;
+        Ok(Arc::new(redact))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+[[custom_macros]]
+name = "ip"
+pattern = '\d{1,3}(?:\.\d{1,3}){3}'
+type_hint = "String"
+
+[[profiles]]
+name = "access"
+pattern = '{{client:ip}} {{status:number}}'
+
+[[profiles.custom_macros]]
+name = "code"
+pattern = '\d{3}'
+type_hint = "Int"
+"#;
+
+    #[test]
+    fn test_profiles_roundtrip_formats() {
+        let profiles = Profiles::from_str_with_format(SAMPLE, ProfileFormat::Toml).unwrap();
+        assert_eq!(profiles.profiles[0].custom_macros.len(), 2);
+
+        for format in [
+            ProfileFormat::Toml,
+            ProfileFormat::Json,
+            ProfileFormat::Yaml,
+        ] {
+            let text = profiles.to_string_with_format(format).unwrap();
+            let loaded = Profiles::from_str_with_format(&text, format).unwrap();
+            assert_eq!(loaded.custom_macros.len(), 1);
+            let profile = loaded.get_profile("access").unwrap();
+            assert_eq!(profile.pattern, "{{client:ip}} {{status:number}}");
+            // Global macros must not be duplicated on a second load
+            assert_eq!(profile.custom_macros.len(), 2);
+            assert_eq!(
+                profile.get_macro("code").unwrap().type_hint,
+                Some(FieldType::Int)
+            );
+        }
+    }
+
+    #[test]
+    fn test_profile_format_detection() {
+        assert_eq!(ProfileFormat::from_path("a/b.json"), ProfileFormat::Json);
+        assert_eq!(ProfileFormat::from_path("b.YML"), ProfileFormat::Yaml);
+        assert_eq!(ProfileFormat::from_path("config.toml"), ProfileFormat::Toml);
+        assert!(ProfileFormat::from_name("xml").is_err());
+    }
+
+    #[test]
+    fn test_profile_fallback_patterns() {
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "mixed"
+pattern = '{{ts:number}} {{msg:any}}'
+patterns = ['^-- {{note:any}}$']
+prefilter = '--'
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+        let profile = profiles.get_profile("mixed").unwrap();
+        assert_eq!(
+            profile.regex_patterns(),
+            vec!["{{ts:number}} {{msg:any}}", "^-- {{note:any}}$"]
+        );
+        let extractor = profile.extractor().unwrap();
+        assert_eq!(extractor.field_names(), ["ts", "msg", "note"]);
+        let mut out = Vec::new();
+        assert_eq!(extractor.extract_pattern("-- hello", &mut out), Some(1));
+        assert_eq!(out, vec!["", "", "hello"]);
+        // Lines without the prefilter literal are rejected before the patterns run
+        assert!(!extractor.is_match("1 hello"));
+    }
+
+    #[test]
+    fn test_profile_engine() {
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "fast"
+pattern = '^{{ts:number}} {{msg:any}}$'
+engine = "lazy_dfa"
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+        let profile = profiles.get_profile("fast").unwrap();
+        assert_eq!(profile.engine, Engine::LazyDfa);
+        let extractor = profile.extractor();
+        #[cfg(feature = "dfa")]
+        {
+            let mut out = Vec::new();
+            assert!(extractor.unwrap().extract("12 hello", &mut out));
+            assert_eq!(out, vec!["12", "hello"]);
+        }
+        #[cfg(not(feature = "dfa"))]
+        assert!(extractor.is_err());
+    }
+
+    #[test]
+    fn test_profile_flags() {
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "loose"
+pattern = '^warn: {{msg:any}}$'
+flags = ["i"]
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+        let profile = profiles.get_profile("loose").unwrap();
+        assert_eq!(profile.flags, vec!["i"]);
+        let mut out = Vec::new();
+        assert!(
+            profile
+                .extractor()
+                .unwrap()
+                .extract("WARN: low disk", &mut out)
+        );
+        assert_eq!(out, vec!["low disk"]);
+
+        let mut profile = profile.clone();
+        profile.flags = vec!["z".into()];
+        assert!(profile.extractor().is_err());
+    }
+
+    #[test]
+    fn test_profile_transforms() {
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "web"
+pattern = '^{{level:var_name}} {{path:any}}$'
+transforms = { level = ["lowercase"], path = ["url_decode", "replace('/api')"] }
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+        let profile = profiles.get_profile("web").unwrap();
+        let mut out = Vec::new();
+        assert!(
+            profile
+                .extractor()
+                .unwrap()
+                .extract("WARN /api/users%2F7", &mut out)
+        );
+        assert_eq!(out, vec!["warn", "/users/7"]);
+
+        let mut profile = profile.clone();
+        profile.transforms = HashMap::from([("status".into(), vec!["trim".into()])]);
+        let err = profile.extractor().unwrap_err();
+        assert!(
+            err.to_string().contains("unknown field 'status'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_profile_rules() {
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "web"
+pattern = '^{{level:var_name}} {{status:number}}$'
+transforms = { level = ["lowercase"] }
+rules = { level = ["one_of(info, warn)"], status = ["range(100, 599)"] }
+on_violation = "unmatched"
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+        let profile = profiles.get_profile("web").unwrap();
+        assert_eq!(profile.on_violation, OnViolation::Unmatched);
+        let extractor = profile.extractor().unwrap();
+        // Rules see transformed values
+        assert!(extractor.is_match("WARN 503"));
+        assert!(!extractor.is_match("WARN 5030"));
+        assert!(!extractor.is_match("TRACE 200"));
+
+        let mut profile = profile.clone();
+        profile.rules = HashMap::from([("status".into(), vec!["positive".into()])]);
+        let err = profile.extractor().unwrap_err();
+        assert!(
+            err.to_string().contains("Unknown rule 'positive'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_profile_defaults() {
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "app"
+pattern = '^{{ts:number}}(?: \[{{level:var_name}}\])? {{msg:any}}$'
+defaults = { level = "INFO" }
+
+[[profiles]]
+name = "tagged"
+pattern = '^{{ts:number}} {{tag:tag}}?: {{msg:any}}$'
+
+[[profiles.custom_macros]]
+name = "tag"
+pattern = '[a-z]+'
+default = "none"
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        let app = profiles.get_profile("app").unwrap().extractor().unwrap();
+        assert!(app.extract("1 [WARN] disk", &mut out));
+        assert_eq!(out, vec!["1", "WARN", "disk"]);
+        assert!(app.extract("2 started", &mut out));
+        assert_eq!(out, vec!["2", "INFO", "started"]);
+
+        let tagged = profiles.get_profile("tagged").unwrap().extractor().unwrap();
+        assert!(tagged.extract("3 : hello", &mut out));
+        assert_eq!(out, vec!["3", "none", "hello"]);
+
+        let mut profile = profiles.get_profile("app").unwrap().clone();
+        profile.defaults = HashMap::from([("nope".into(), "x".into())]);
+        assert!(profile.extractor().is_err());
+    }
+}
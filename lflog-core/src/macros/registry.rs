@@ -0,0 +1,159 @@
+//! Macros registered in code.
+//!
+//! A [`MacroRegistry`] collects macros for applications embedding lflog, either as a
+//! fixed regex like those of profiles or computed from their arguments like the
+//! builtins:
+//!
+//! ```
+//! use lflog_core::macros::{CustomMacro, MacroRegistry};
+//! use lflog_core::{Error, FieldType, Scanner};
+//!
+//! let registry = MacroRegistry::new()
+//!     .with_macro(CustomMacro::new("ticket", r"[A-Z]+-\d+"))
+//!     .with_macro(
+//!         CustomMacro::from_fn("digits", |args| match args {
+//!             [n] => Ok(format!(r"\d{{{}}}", n)),
+//!             _ => Err(Error::Macro("digits takes a count".into())),
+//!         })
+//!         .with_type_hint(FieldType::Int),
+//!     );
+//! let scanner = Scanner::with_custom_macros(
+//!     "^{{id:ticket}} {{code:digits(3)}}$".to_string(),
+//!     Some(registry.macros()),
+//! )
+//! .unwrap();
+//! assert_eq!(scanner.type_hints["code"], FieldType::Int);
+//! ```
+
+use crate::macros::parser::{CustomMacro, Profiles};
+
+/// Macros to expand before the builtins; a macro replaces any earlier one of the
+/// same name, so registering `ip` overrides the builtin `ip` too.
+#[derive(Clone, Default)]
+pub struct MacroRegistry {
+    macros: Vec<CustomMacro>,
+}
+
+impl MacroRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `custom`, replacing the macro of the same name.
+    pub fn register(&mut self, custom: CustomMacro) {
+        match self.macros.iter_mut().find(|m| m.name == custom.name) {
+            Some(existing) => *existing = custom,
+            None => self.macros.push(custom),
+        }
+    }
+
+    /// Add `custom`, like [`register`](Self::register).
+    pub fn with_macro(mut self, custom: CustomMacro) -> Self {
+        self.register(custom);
+        self
+    }
+
+    /// Add the global custom macros of `profiles`.
+    pub fn with_profiles(mut self, profiles: &Profiles) -> Self {
+        for custom in &profiles.custom_macros {
+            self.register(custom.clone());
+        }
+        self
+    }
+
+    /// Remove the macro `name`, returning it.
+    pub fn unregister(&mut self, name: &str) -> Option<CustomMacro> {
+        let index = self.macros.iter().position(|m| m.name == name)?;
+        Some(self.macros.remove(index))
+    }
+
+    /// The macro `name`.
+    pub fn get(&self, name: &str) -> Option<&CustomMacro> {
+        self.macros.iter().find(|m| m.name == name)
+    }
+
+    /// The macros in order of registration, for
+    /// [`Scanner::with_custom_macros`](crate::Scanner::with_custom_macros) and the
+    /// other functions taking custom macros.
+    pub fn macros(&self) -> &[CustomMacro] {
+        &self.macros
+    }
+}
+
+impl From<Vec<CustomMacro>> for MacroRegistry {
+    fn from(macros: Vec<CustomMacro>) -> Self {
+        macros
+            .into_iter()
+            .fold(Self::new(), |registry, custom| registry.with_macro(custom))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::scanner::Scanner;
+    use crate::types::FieldType;
+
+    fn repeat(args: &[String]) -> crate::Result<String> {
+        match args {
+            [item, n] => Ok(format!("(?:{}){{{}}}", regex::escape(item), n)),
+            _ => Err(Error::Macro("repeat takes an item and a count".into())),
+        }
+    }
+
+    #[test]
+    fn test_registry_macros() {
+        let mut registry = MacroRegistry::new()
+            .with_macro(CustomMacro::new("ip", "old"))
+            .with_macro(CustomMacro::from_fn("repeat", repeat))
+            .with_macro(
+                CustomMacro::new("ip", r"\d+(?:\.\d+){3}").with_type_hint(FieldType::String),
+            );
+        assert_eq!(registry.macros().len(), 2);
+        assert_eq!(registry.get("ip").unwrap().pattern, r"\d+(?:\.\d+){3}");
+
+        let scanner = Scanner::with_custom_macros(
+            "^{{client:ip}} {{dots:repeat(., 3)}}$".to_string(),
+            Some(registry.macros()),
+        )
+        .unwrap();
+        assert_eq!(
+            scanner.scan("10.0.0.1 ..."),
+            Some(vec!["10.0.0.1".into(), "...".into()])
+        );
+        assert!(scanner.scan("10.0.0.1 ..").is_none());
+
+        // Errors of the expander point at the macro
+        let err = Scanner::with_custom_macros("x {{repeat(a)}}".into(), Some(registry.macros()))
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("repeat takes an item and a count"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("column 3"), "{}", err);
+
+        assert!(registry.unregister("repeat").is_some());
+        assert!(registry.unregister("repeat").is_none());
+        assert!(
+            Scanner::with_custom_macros("{{repeat(a, 2)}}".into(), Some(registry.macros()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_registry_from_profiles() {
+        let profiles = Profiles::from_str_with_format(
+            "profiles = []\n[[custom_macros]]\nname = \"ticket\"\npattern = '[A-Z]+-\\d+'\n",
+            crate::ProfileFormat::Toml,
+        )
+        .unwrap();
+        let registry =
+            MacroRegistry::from(vec![CustomMacro::new("ticket", "x")]).with_profiles(&profiles);
+        assert_eq!(registry.macros().len(), 1);
+        assert_eq!(registry.get("ticket").unwrap().pattern, r"[A-Z]+-\d+");
+    }
+}
@@ -0,0 +1,255 @@
+//! PCRE2 matching for patterns the `regex` crate rejects.
+//!
+//! PCRE2 supports lookarounds, backreferences and atomic groups, at the cost of
+//! backtracking: a pathological pattern can take exponential time on a line. Patterns
+//! are compiled in UTF mode with Unicode properties, so `\d`, `\w` and friends match
+//! like they do in the `regex` crate, and JIT-compiled when PCRE2 supports it.
+//!
+//! `libpcre2-8` is loaded at runtime (`libpcre2-8.so.0`, then `libpcre2-8.so`), so
+//! the feature builds without PCRE2 installed and fails only when a scanner selects it.
+
+use std::ffi::{c_int, c_void};
+use std::sync::OnceLock;
+
+use crate::dl::Library;
+use crate::error::{Error, Result};
+
+/// Library names tried in order.
+const LIBRARIES: [&str; 2] = ["libpcre2-8.so.0", "libpcre2-8.so"];
+
+const PCRE2_UCP: u32 = 0x0002_0000;
+const PCRE2_UTF: u32 = 0x0008_0000;
+const PCRE2_NO_UTF_CHECK: u32 = 0x4000_0000;
+const PCRE2_ANCHORED: u32 = 0x8000_0000;
+const PCRE2_ENDANCHORED: u32 = 0x2000_0000;
+const PCRE2_JIT_COMPLETE: u32 = 1;
+const PCRE2_INFO_CAPTURECOUNT: u32 = 4;
+const PCRE2_INFO_NAMECOUNT: u32 = 17;
+const PCRE2_INFO_NAMEENTRYSIZE: u32 = 18;
+const PCRE2_INFO_NAMETABLE: u32 = 19;
+/// Offset of a group that did not participate in the match.
+const PCRE2_UNSET: usize = usize::MAX;
+
+/// The `libpcre2-8` functions used, resolved once.
+struct Api {
+    /// Kept loaded while the functions below are in use.
+    _library: Library,
+    compile: unsafe extern "C" fn(
+        pattern: *const u8,
+        length: usize,
+        options: u32,
+        error_code: *mut c_int,
+        error_offset: *mut usize,
+        context: *mut c_void,
+    ) -> *mut c_void,
+    get_error_message: unsafe extern "C" fn(code: c_int, buffer: *mut u8, length: usize) -> c_int,
+    code_free: unsafe extern "C" fn(code: *mut c_void),
+    jit_compile: unsafe extern "C" fn(code: *mut c_void, options: u32) -> c_int,
+    pattern_info: unsafe extern "C" fn(code: *const c_void, what: u32, out: *mut c_void) -> c_int,
+    match_data_create_from_pattern:
+        unsafe extern "C" fn(code: *const c_void, context: *mut c_void) -> *mut c_void,
+    match_data_free: unsafe extern "C" fn(data: *mut c_void),
+    matches: unsafe extern "C" fn(
+        code: *const c_void,
+        subject: *const u8,
+        length: usize,
+        start: usize,
+        options: u32,
+        data: *mut c_void,
+        context: *mut c_void,
+    ) -> c_int,
+    get_ovector_pointer: unsafe extern "C" fn(data: *mut c_void) -> *mut usize,
+    get_ovector_count: unsafe extern "C" fn(data: *mut c_void) -> u32,
+}
+
+/// Load `libpcre2-8`, or the reason it is unavailable.
+fn api() -> Result<&'static Api> {
+    static API: OnceLock<std::result::Result<Api, String>> = OnceLock::new();
+    API.get_or_init(load)
+        .as_ref()
+        .map_err(|e| Error::Config(format!("PCRE2 is unavailable: {}", e)))
+}
+
+fn load() -> std::result::Result<Api, String> {
+    let library = Library::open_first(&LIBRARIES).map_err(|e| e.to_string())?;
+    // Safety: the types are those of the C declarations, and the library is kept
+    // alongside the functions
+    fn symbol<F: Copy>(library: &Library, name: &str) -> std::result::Result<F, String> {
+        unsafe { library.symbol(name) }.map_err(|e| e.to_string())
+    }
+    Ok(Api {
+        compile: symbol(&library, "pcre2_compile_8")?,
+        get_error_message: symbol(&library, "pcre2_get_error_message_8")?,
+        code_free: symbol(&library, "pcre2_code_free_8")?,
+        jit_compile: symbol(&library, "pcre2_jit_compile_8")?,
+        pattern_info: symbol(&library, "pcre2_pattern_info_8")?,
+        match_data_create_from_pattern: symbol(&library, "pcre2_match_data_create_from_pattern_8")?,
+        match_data_free: symbol(&library, "pcre2_match_data_free_8")?,
+        matches: symbol(&library, "pcre2_match_8")?,
+        get_ovector_pointer: symbol(&library, "pcre2_get_ovector_pointer_8")?,
+        get_ovector_count: symbol(&library, "pcre2_get_ovector_count_8")?,
+        _library: library,
+    })
+}
+
+/// Whether `libpcre2-8` can be loaded.
+pub fn is_available() -> bool {
+    api().is_ok()
+}
+
+/// A compiled PCRE2 pattern.
+#[derive(Debug)]
+pub(crate) struct Code {
+    code: *mut c_void,
+    /// Name of each capture group, by index; group 0 is the whole match.
+    names: Vec<Option<String>>,
+}
+
+// Compiled (and JIT-compiled) patterns are read-only; matching only needs
+// per-thread match data
+unsafe impl Send for Code {}
+unsafe impl Sync for Code {}
+
+impl Code {
+    pub(crate) fn new(pattern: &str) -> Result<Self> {
+        let api = api()?;
+        let mut error_code = 0;
+        let mut error_offset = 0;
+        let code = unsafe {
+            (api.compile)(
+                pattern.as_ptr(),
+                pattern.len(),
+                PCRE2_UTF | PCRE2_UCP,
+                &mut error_code,
+                &mut error_offset,
+                std::ptr::null_mut(),
+            )
+        };
+        if code.is_null() {
+            let mut buffer = [0u8; 256];
+            let length =
+                unsafe { (api.get_error_message)(error_code, buffer.as_mut_ptr(), buffer.len()) };
+            let message = String::from_utf8_lossy(&buffer[..length.max(0) as usize]);
+            return Err(Error::Config(format!(
+                "Invalid PCRE2 pattern at offset {}: {}",
+                error_offset, message
+            )));
+        }
+        // Without JIT support matching falls back to the interpreter
+        unsafe { (api.jit_compile)(code, PCRE2_JIT_COMPLETE) };
+        let mut code = Self {
+            code,
+            names: Vec::new(),
+        };
+        code.names = code.read_names(api);
+        Ok(code)
+    }
+
+    /// Capture group names from the pattern's name table.
+    fn read_names(&self, api: &Api) -> Vec<Option<String>> {
+        let info =
+            |what: u32, out: *mut c_void| unsafe { (api.pattern_info)(self.code, what, out) };
+        let (mut groups, mut count, mut entry_size) = (0u32, 0u32, 0u32);
+        let mut table: *const u8 = std::ptr::null();
+        info(
+            PCRE2_INFO_CAPTURECOUNT,
+            &mut groups as *mut u32 as *mut c_void,
+        );
+        info(PCRE2_INFO_NAMECOUNT, &mut count as *mut u32 as *mut c_void);
+        info(
+            PCRE2_INFO_NAMEENTRYSIZE,
+            &mut entry_size as *mut u32 as *mut c_void,
+        );
+        info(
+            PCRE2_INFO_NAMETABLE,
+            &mut table as *mut *const u8 as *mut c_void,
+        );
+
+        let mut names = vec![None; groups as usize + 1];
+        for i in 0..count as usize {
+            // Each entry is a big-endian group number followed by the NUL-terminated name
+            let entry = unsafe {
+                std::slice::from_raw_parts(table.add(i * entry_size as usize), entry_size as usize)
+            };
+            let group = u16::from_be_bytes([entry[0], entry[1]]) as usize;
+            let name = &entry[2..];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            if let Some(slot) = names.get_mut(group) {
+                *slot = Some(String::from_utf8_lossy(name).into_owned());
+            }
+        }
+        names
+    }
+
+    /// Name of each capture group, by index.
+    pub(crate) fn capture_names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.names.iter().map(|name| name.as_deref())
+    }
+
+    /// Match data sized for this pattern's groups.
+    pub(crate) fn match_data(&self) -> MatchData {
+        let api = api().expect("a compiled pattern implies a loaded library");
+        let data = unsafe { (api.match_data_create_from_pattern)(self.code, std::ptr::null_mut()) };
+        assert!(!data.is_null(), "out of memory allocating PCRE2 match data");
+        MatchData { data }
+    }
+
+    /// Match `line`, filling `data`. Match errors, such as hitting the backtracking
+    /// limit, count as no match.
+    ///
+    /// An `anchored` match must span the whole line, so no other start is tried.
+    pub(crate) fn read(&self, line: &str, data: &mut MatchData, anchored: bool) -> bool {
+        let api = api().expect("a compiled pattern implies a loaded library");
+        let rc = unsafe {
+            (api.matches)(
+                self.code,
+                line.as_ptr(),
+                line.len(),
+                0,
+                match anchored {
+                    true => PCRE2_NO_UTF_CHECK | PCRE2_ANCHORED | PCRE2_ENDANCHORED,
+                    false => PCRE2_NO_UTF_CHECK,
+                },
+                data.data,
+                std::ptr::null_mut(),
+            )
+        };
+        rc >= 0
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe { (api.code_free)(self.code) };
+        }
+    }
+}
+
+/// Capture offsets of the last match of a [`Code`].
+#[derive(Debug)]
+pub(crate) struct MatchData {
+    data: *mut c_void,
+}
+
+impl MatchData {
+    /// Start and end of capture group `index`, if it participated in the match.
+    pub(crate) fn get(&self, index: usize) -> Option<(usize, usize)> {
+        let api = api().expect("match data implies a loaded library");
+        let count = unsafe { (api.get_ovector_count)(self.data) } as usize;
+        if index >= count {
+            return None;
+        }
+        let ovector = unsafe { (api.get_ovector_pointer)(self.data) };
+        let (start, end) = unsafe { (*ovector.add(2 * index), *ovector.add(2 * index + 1)) };
+        (start != PCRE2_UNSET).then_some((start, end))
+    }
+}
+
+impl Drop for MatchData {
+    fn drop(&mut self) {
+        if let Ok(api) = api() {
+            unsafe { (api.match_data_free)(self.data) };
+        }
+    }
+}
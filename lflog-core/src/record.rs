@@ -0,0 +1,269 @@
+//! Streaming records out of a reader with a [`Scanner`], without the query engine.
+
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use crate::error::Result;
+use crate::extractor::RecordExtractor;
+use crate::scanner::Scanner;
+use crate::stats::ScanCounts;
+use crate::types::{FieldType, TimestampUnit};
+
+/// A line matched by [`Scanner::scan_iter`].
+#[derive(Debug, Clone)]
+pub struct Record<'s> {
+    scanner: &'s Scanner,
+    line_number: usize,
+    pattern: usize,
+    values: Vec<String>,
+}
+
+impl<'s> Record<'s> {
+    /// 1-based number of the line in the reader.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Index of the scanner pattern that matched the line.
+    pub fn pattern(&self) -> usize {
+        self.pattern
+    }
+
+    /// Field names, in the scanner's order.
+    pub fn field_names(&self) -> &'s [String] {
+        &self.scanner.field_names
+    }
+
+    /// Field values, in the order of [`field_names`](Self::field_names).
+    ///
+    /// Fields the matching pattern lacks are empty.
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    /// Value of field `name`, if the scanner has it.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let index = self.field_names().iter().position(|field| field == name)?;
+        Some(&self.values[index])
+    }
+
+    /// Field name and value pairs, in the scanner's order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.field_names()
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter().map(String::as_str))
+    }
+
+    /// Field values keyed by name.
+    pub fn into_map(self) -> HashMap<String, String> {
+        self.field_names()
+            .iter()
+            .cloned()
+            .zip(self.values)
+            .collect()
+    }
+
+    /// Deserialize the record into a row struct, converting fields by type hint.
+    ///
+    /// `Int`, `Int64` and `Float` fields become numbers, `Duration` fields numbers of
+    /// milliseconds and `Json` fields their parsed value; empty values of these become
+    /// `null`, so they fit `Option` members.
+    /// `List` fields become arrays of strings, `Map` fields objects of strings and
+    /// `Binary` fields arrays of bytes, or `null` when they do not decode.
+    /// Other fields, including date-times, stay strings; so do `Decimal` fields, to
+    /// stay exact, except that empty ones become `null`.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        let hints = &self.scanner.type_hints;
+        let mut row = Map::new();
+        for (name, value) in self.iter() {
+            let value = match hints.get(name) {
+                Some(
+                    FieldType::Int
+                    | FieldType::Int64
+                    | FieldType::Float
+                    | FieldType::Decimal(..)
+                    | FieldType::Duration
+                    | FieldType::Json,
+                ) if value.is_empty() => Value::Null,
+                Some(FieldType::Int | FieldType::Int64) => match value.parse::<i64>() {
+                    Ok(number) => number.into(),
+                    Err(_) => value.into(),
+                },
+                Some(FieldType::Float) => match value.parse::<f64>() {
+                    Ok(number) => number.into(),
+                    Err(_) => value.into(),
+                },
+                Some(FieldType::Duration) => {
+                    match FieldType::parse_duration(value, TimestampUnit::Millis) {
+                        Some(millis) => millis.into(),
+                        None => value.into(),
+                    }
+                }
+                Some(FieldType::Json) => serde_json::from_str(value)?,
+                Some(FieldType::List(separator)) => {
+                    FieldType::split_list(value, separator).collect()
+                }
+                Some(FieldType::Binary(encoding)) => match encoding.decode(value) {
+                    Some(bytes) => bytes.into(),
+                    None => Value::Null,
+                },
+                Some(FieldType::Map) => FieldType::split_pairs(value)
+                    .map(|(key, value)| (key.to_string(), value.into_owned().into()))
+                    .collect::<Map<String, Value>>()
+                    .into(),
+                _ => value.into(),
+            };
+            row.insert(name.to_string(), value);
+        }
+        Ok(serde_json::from_value(Value::Object(row))?)
+    }
+}
+
+/// Iterator over the records of a reader, returned by [`Scanner::scan_iter`].
+///
+/// Lines no pattern matches are skipped and counted; invalid UTF-8 is replaced with
+/// U+FFFD. Yields an error, then stops, when reading fails.
+#[derive(Debug)]
+pub struct ScanIter<'s, R> {
+    scanner: &'s Scanner,
+    reader: R,
+    buffer: Vec<u8>,
+    line_number: usize,
+    unmatched: usize,
+    failed: bool,
+}
+
+impl<'s, R: BufRead> ScanIter<'s, R> {
+    pub(crate) fn new(scanner: &'s Scanner, reader: R) -> Self {
+        Self {
+            scanner,
+            reader,
+            buffer: Vec::new(),
+            line_number: 0,
+            unmatched: 0,
+            failed: false,
+        }
+    }
+
+    /// Number of lines read so far.
+    pub fn lines(&self) -> usize {
+        self.line_number
+    }
+
+    /// Number of lines read so far that no pattern matched, not counting excluded
+    /// lines.
+    pub fn unmatched(&self) -> usize {
+        self.unmatched
+    }
+}
+
+impl<'s, R: BufRead> Iterator for ScanIter<'s, R> {
+    type Item = Result<Record<'s>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.failed {
+            self.buffer.clear();
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e.into()));
+                }
+            }
+            self.line_number += 1;
+            let mut counts = ScanCounts {
+                bytes: self.buffer.len() as u64,
+                ..ScanCounts::default()
+            };
+            let line = self.buffer.strip_suffix(b"\n").unwrap_or(&self.buffer);
+            let line = String::from_utf8_lossy(line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            let mut values = Vec::new();
+            if let Some(pattern) = self.scanner.extract_pattern(line, &mut values) {
+                counts.matched = 1;
+                self.scanner.add_stats(counts);
+                return Some(Ok(Record {
+                    scanner: self.scanner,
+                    line_number: self.line_number,
+                    pattern,
+                    values: values.into_iter().map(Cow::into_owned).collect(),
+                }));
+            }
+            if self.scanner.is_excluded(line) {
+                counts.skipped = 1;
+            } else {
+                self.unmatched += 1;
+                counts.unmatched = !line.trim().is_empty() as u64;
+            }
+            self.scanner.add_stats(counts);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Request {
+        status: i32,
+        path: String,
+        bytes: Option<i64>,
+    }
+
+    #[test]
+    fn test_scan_iter() {
+        let scanner = Scanner::with_patterns(
+            vec![
+                "^{{status:number}} {{path:any}} {{bytes:number}}$".into(),
+                "^{{status:number}} {{path:any}}$".into(),
+            ],
+            None,
+        )
+        .unwrap();
+        let input = b"200 /index 512\r\nnoise\n404 /missing\n\xff\n";
+        let mut records = scanner.scan_iter(&input[..]);
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.line_number(), 1);
+        assert_eq!(record.pattern(), 0);
+        assert_eq!(record.get("bytes"), Some("512"));
+        assert_eq!(record.get("missing"), None);
+        assert_eq!(
+            record.deserialize::<Request>().unwrap(),
+            Request {
+                status: 200,
+                path: "/index".into(),
+                bytes: Some(512),
+            }
+        );
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.line_number(), 3);
+        assert_eq!(record.pattern(), 1);
+        assert_eq!(record.deserialize::<Request>().unwrap().bytes, None);
+        let map = record.into_map();
+        assert_eq!(map["path"], "/missing");
+        assert_eq!(map["bytes"], "");
+
+        assert!(records.next().is_none());
+        assert_eq!(records.lines(), 4);
+        assert_eq!(records.unmatched(), 2);
+        assert_eq!(
+            scanner.stats(),
+            ScanCounts {
+                matched: 2,
+                unmatched: 2,
+                skipped: 0,
+                bytes: input.len() as u64,
+            }
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Field redaction applied at scan time, so personal data never reaches a column.
+//!
+//! A profile names the fields to redact and how, e.g.
+//! `redact = { email = "hash", ip = "mask", token = "drop" }`; [`RedactExtractor`]
+//! rewrites every record its inner extractor produces.
+
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::extractor::RecordExtractor;
+use crate::macros::parser::parse_macro_invocation;
+use crate::stats::ScanStats;
+use crate::types::FieldType;
+
+/// Replacement of masked values.
+pub const MASK: &str = "***";
+
+/// How a field is redacted. Empty values are left empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redaction {
+    /// `mask`: replace the value with [`MASK`].
+    Mask,
+    /// `drop`: remove the field from the table.
+    Drop,
+    /// `hash` or `hash(salt)`: replace the value with the first 16 hex digits of the
+    /// SHA-256 of `salt` followed by the value. The same value always hashes the same,
+    /// so redacted columns can still be grouped and joined.
+    Hash(String),
+}
+
+impl std::str::FromStr for Redaction {
+    type Err = Error;
+
+    /// Parse a redaction like `mask` or `hash("pepper")`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invocation = parse_macro_invocation(s)?;
+        match (
+            invocation.field,
+            invocation.name.as_str(),
+            invocation.args.as_slice(),
+        ) {
+            (None, "mask", []) => Ok(Redaction::Mask),
+            (None, "drop", []) => Ok(Redaction::Drop),
+            (None, "hash", []) => Ok(Redaction::Hash(String::new())),
+            (None, "hash", [salt]) => Ok(Redaction::Hash(salt.clone())),
+            _ => Err(Error::Config(format!(
+                "Unknown redaction '{}', expected mask, drop, hash or hash(salt)",
+                s
+            ))),
+        }
+    }
+}
+
+impl Redaction {
+    /// Redact `value`; dropped fields are removed by [`RedactExtractor`] instead.
+    pub fn apply<'a>(&self, value: Cow<'a, str>) -> Cow<'a, str> {
+        if value.is_empty() {
+            return value;
+        }
+        match self {
+            Redaction::Mask => Cow::Borrowed(MASK),
+            Redaction::Drop => value,
+            Redaction::Hash(salt) => {
+                let digest = Sha256::new()
+                    .chain_update(salt)
+                    .chain_update(value.as_bytes())
+                    .finalize();
+                let mut hex = String::with_capacity(16);
+                for byte in &digest[..8] {
+                    let _ = write!(hex, "{:02x}", byte);
+                }
+                Cow::Owned(hex)
+            }
+        }
+    }
+}
+
+/// Parse the redactions of a profile, by field name.
+pub fn parse_redactions(redact: &HashMap<String, String>) -> Result<HashMap<String, Redaction>> {
+    redact
+        .iter()
+        .map(|(field, redaction)| Ok((field.clone(), redaction.parse()?)))
+        .collect()
+}
+
+/// Redacts fields of the records of another extractor.
+#[derive(Debug, Clone)]
+pub struct RedactExtractor {
+    inner: Arc<dyn RecordExtractor>,
+    /// The inner extractor's fields, without dropped ones.
+    field_names: Vec<String>,
+    /// The inner type hints, with redacted fields as strings.
+    type_hints: HashMap<String, FieldType>,
+    /// Redactions by index in the inner extractor's fields.
+    redactions: Vec<(usize, Redaction)>,
+    /// Whether any field is dropped.
+    drops: bool,
+}
+
+impl RedactExtractor {
+    /// Wrap `inner`, redacting the fields named in `redactions`.
+    ///
+    /// Fails if a redacted field is not one of `inner`'s fields.
+    pub fn new(
+        inner: Arc<dyn RecordExtractor>,
+        redactions: HashMap<String, Redaction>,
+    ) -> Result<Self> {
+        let mut type_hints = inner.type_hints().clone();
+        let mut redactions = redactions
+            .into_iter()
+            .map(|(field, redaction)| {
+                let index = inner
+                    .field_names()
+                    .iter()
+                    .position(|name| *name == field)
+                    .ok_or_else(|| {
+                        Error::Config(format!("Cannot redact unknown field '{}'", field))
+                    })?;
+                type_hints.insert(field, FieldType::String);
+                Ok((index, redaction))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        redactions.sort_by_key(|(index, _)| *index);
+        let field_names = inner
+            .field_names()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                !redactions
+                    .iter()
+                    .any(|(dropped, r)| dropped == index && *r == Redaction::Drop)
+            })
+            .map(|(_, name)| name.clone())
+            .collect();
+        let drops = redactions.iter().any(|(_, r)| *r == Redaction::Drop);
+        Ok(Self {
+            inner,
+            field_names,
+            type_hints,
+            redactions,
+            drops,
+        })
+    }
+
+    fn redact(&self, out: &mut Vec<Cow<'_, str>>) {
+        for (index, redaction) in &self.redactions {
+            if let Some(value) = out.get_mut(*index) {
+                *value = redaction.apply(std::mem::take(value));
+            }
+        }
+        if self.drops {
+            let mut index = 0;
+            out.retain(|_| {
+                let keep = !self
+                    .redactions
+                    .iter()
+                    .any(|(dropped, r)| *dropped == index && *r == Redaction::Drop);
+                index += 1;
+                keep
+            });
+        }
+    }
+}
+
+impl RecordExtractor for RedactExtractor {
+    fn field_names(&self) -> &[String] {
+        &self.field_names
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        &self.type_hints
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        self.extract_pattern(line, out).is_some()
+    }
+
+    fn extract_pattern<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        let pattern = self.inner.extract_pattern(line, out)?;
+        self.redact(out);
+        Some(pattern)
+    }
+
+    fn extract_bytes<'a>(&self, line: &'a [u8], out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        let pattern = self.inner.extract_bytes(line, out)?;
+        self.redact(out);
+        Some(pattern)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.inner.is_match(line)
+    }
+
+    fn is_excluded(&self, line: &str) -> bool {
+        self.inner.is_excluded(line)
+    }
+
+    fn scan_stats(&self) -> Option<&ScanStats> {
+        self.inner.scan_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn test_redactions() {
+        let apply = |redaction: &str, value| {
+            redaction
+                .parse::<Redaction>()
+                .unwrap()
+                .apply(Cow::Borrowed(value))
+                .into_owned()
+        };
+        assert_eq!(apply("mask", "alice@example.com"), MASK);
+        assert_eq!(apply("mask", ""), "");
+        // SHA-256 of "abc"
+        assert_eq!(apply("hash", "abc"), "ba7816bf8f01cfea");
+        assert_eq!(apply("hash(pepper)", "abc").len(), 16);
+        assert_ne!(apply("hash(pepper)", "abc"), apply("hash", "abc"));
+
+        assert!("erase".parse::<Redaction>().is_err());
+        assert!("mask(4)".parse::<Redaction>().is_err());
+    }
+
+    #[test]
+    fn test_redact_extractor() {
+        let scanner = Scanner::new(
+            "^{{user:var_name}} {{ip:any}} {{token:var_name}} {{took:number}}$".to_string(),
+        )
+        .unwrap();
+        let redactions = parse_redactions(&HashMap::from([
+            ("ip".to_string(), "mask".to_string()),
+            ("token".to_string(), "drop".to_string()),
+            ("took".to_string(), "hash".to_string()),
+        ]))
+        .unwrap();
+        let extractor = RedactExtractor::new(Arc::new(scanner.clone()), redactions).unwrap();
+        assert_eq!(extractor.field_names(), ["user", "ip", "took"]);
+        assert_eq!(extractor.type_hints()["took"], FieldType::String);
+
+        let mut out = Vec::new();
+        assert_eq!(
+            extractor.extract_bytes(b"bob 10.0.0.1 s3cr3t 42", &mut out),
+            Some(0)
+        );
+        assert_eq!(out, vec!["bob", MASK, "73475cb40a568e8d"]);
+
+        let unknown = HashMap::from([("nope".to_string(), Redaction::Mask)]);
+        assert!(RedactExtractor::new(Arc::new(scanner), unknown).is_err());
+    }
+}
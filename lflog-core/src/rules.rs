@@ -0,0 +1,286 @@
+//! Per-field value rules that catch parser drift.
+//!
+//! A profile lists rules per field, e.g. `rules = { status = ["range(100, 599)"] }`.
+//! [`RuleExtractor`] checks them on every record its inner extractor produces, and
+//! handles records that break one according to the profile's [`OnViolation`] policy.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::extractor::RecordExtractor;
+use crate::macros::parser::parse_macro_invocation;
+use crate::stats::ScanStats;
+use crate::types::FieldType;
+
+/// A constraint on the values of a field. Empty values, from fields that did not
+/// participate in the match, are never checked.
+#[derive(Debug, Clone)]
+pub enum Rule {
+    /// `range(min, max)`: a number from `min` to `max`, inclusive.
+    Range(f64, f64),
+    /// `one_of(a, b, ...)`: exactly one of the listed values.
+    OneOf(Vec<String>),
+    /// `matches(regex)`: the whole value matches `regex`.
+    Matches(Regex),
+}
+
+impl std::str::FromStr for Rule {
+    type Err = Error;
+
+    /// Parse a rule like `range(0, 65535)` or `one_of(GET, POST)`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invocation = parse_macro_invocation(s)?;
+        let args = invocation.args;
+        match (invocation.field, invocation.name.as_str(), args.as_slice()) {
+            (None, "range", [min, max]) => {
+                let bound = |arg: &str| {
+                    arg.parse::<f64>().map_err(|_| {
+                        Error::Config(format!("Rule '{}' has a non-numeric bound '{}'", s, arg))
+                    })
+                };
+                Ok(Rule::Range(bound(min)?, bound(max)?))
+            }
+            (None, "one_of", [_, ..]) => Ok(Rule::OneOf(args)),
+            (None, "matches", [regex]) => Regex::new(&format!("^(?:{})$", regex))
+                .map(Rule::Matches)
+                .map_err(|e| Error::Config(format!("Rule '{}' has an invalid regex: {}", s, e))),
+            _ => Err(Error::Config(format!(
+                "Unknown rule '{}', expected range(min, max), one_of(values...) or matches(regex)",
+                s
+            ))),
+        }
+    }
+}
+
+impl Rule {
+    /// Whether `value` satisfies the rule.
+    pub fn check(&self, value: &str) -> bool {
+        if value.is_empty() {
+            return true;
+        }
+        match self {
+            Rule::Range(min, max) => value
+                .parse::<f64>()
+                .is_ok_and(|number| (*min..=*max).contains(&number)),
+            Rule::OneOf(values) => values.iter().any(|allowed| allowed == value),
+            Rule::Matches(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// What happens to a record with a value that breaks a rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnViolation {
+    /// Clear the offending values: typed columns become NULL, strings empty.
+    #[default]
+    Null,
+    /// Drop the record like an excluded line: no row, not unmatched.
+    Drop,
+    /// Treat the line as unmatched, so it appears in `<table>__unmatched` and lowers
+    /// the match rate.
+    Unmatched,
+}
+
+impl std::str::FromStr for OnViolation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "null" => Ok(OnViolation::Null),
+            "drop" => Ok(OnViolation::Drop),
+            "unmatched" => Ok(OnViolation::Unmatched),
+            other => Err(Error::Config(format!(
+                "unknown violation policy '{}', expected null, drop or unmatched",
+                other
+            ))),
+        }
+    }
+}
+
+/// Parse the rules of a profile, by field name.
+pub fn parse_rules(rules: &HashMap<String, Vec<String>>) -> Result<HashMap<String, Vec<Rule>>> {
+    rules
+        .iter()
+        .map(|(field, rules)| {
+            let rules = rules
+                .iter()
+                .map(|rule| rule.parse())
+                .collect::<Result<Vec<_>>>()?;
+            Ok((field.clone(), rules))
+        })
+        .collect()
+}
+
+/// Checks per-field rules on the records of another extractor.
+#[derive(Debug, Clone)]
+pub struct RuleExtractor {
+    inner: Arc<dyn RecordExtractor>,
+    /// Rules by index in the inner extractor's fields.
+    rules: Vec<(usize, Vec<Rule>)>,
+    on_violation: OnViolation,
+}
+
+impl RuleExtractor {
+    /// Wrap `inner`, checking the fields named in `rules`.
+    ///
+    /// Fails if a checked field is not one of `inner`'s fields.
+    pub fn new(
+        inner: Arc<dyn RecordExtractor>,
+        rules: HashMap<String, Vec<Rule>>,
+        on_violation: OnViolation,
+    ) -> Result<Self> {
+        let mut rules = rules
+            .into_iter()
+            .filter(|(_, rules)| !rules.is_empty())
+            .map(|(field, rules)| {
+                let index = inner
+                    .field_names()
+                    .iter()
+                    .position(|name| *name == field)
+                    .ok_or_else(|| {
+                        Error::Config(format!("Cannot check rules on unknown field '{}'", field))
+                    })?;
+                Ok((index, rules))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rules.sort_by_key(|(index, _)| *index);
+        Ok(Self {
+            inner,
+            rules,
+            on_violation,
+        })
+    }
+
+    /// Apply the policy to an extracted record; `false` when it is rejected.
+    fn enforce(&self, out: &mut [Cow<'_, str>]) -> bool {
+        let mut valid = true;
+        for (index, rules) in &self.rules {
+            let Some(value) = out.get_mut(*index) else {
+                continue;
+            };
+            if rules.iter().all(|rule| rule.check(value)) {
+                continue;
+            }
+            match self.on_violation {
+                OnViolation::Null => *value = Cow::Borrowed(""),
+                OnViolation::Drop | OnViolation::Unmatched => valid = false,
+            }
+        }
+        valid
+    }
+}
+
+impl RecordExtractor for RuleExtractor {
+    fn field_names(&self) -> &[String] {
+        self.inner.field_names()
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        self.inner.type_hints()
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        self.extract_pattern(line, out).is_some()
+    }
+
+    fn extract_pattern<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        let pattern = self.inner.extract_pattern(line, out)?;
+        self.enforce(out).then_some(pattern)
+    }
+
+    fn extract_bytes<'a>(&self, line: &'a [u8], out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        let pattern = self.inner.extract_bytes(line, out)?;
+        self.enforce(out).then_some(pattern)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        if self.on_violation == OnViolation::Null {
+            return self.inner.is_match(line);
+        }
+        self.extract(line, &mut Vec::new())
+    }
+
+    fn is_excluded(&self, line: &str) -> bool {
+        if self.inner.is_excluded(line) {
+            return true;
+        }
+        // A dropped record matched, but is not counted as unmatched
+        let mut out = Vec::new();
+        self.on_violation == OnViolation::Drop
+            && self.inner.extract(line, &mut out)
+            && !self.enforce(&mut out)
+    }
+
+    fn scan_stats(&self) -> Option<&ScanStats> {
+        self.inner.scan_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn test_rules() {
+        let check = |rule: &str, value| rule.parse::<Rule>().unwrap().check(value);
+        assert!(check("range(100, 599)", "404"));
+        assert!(check("range(0, 1.5)", "1.5"));
+        assert!(!check("range(100, 599)", "99"));
+        assert!(!check("range(100, 599)", "abc"));
+        assert!(check("one_of(GET, POST)", "POST"));
+        assert!(!check("one_of(GET, POST)", "get"));
+        assert!(check(r"matches(/\w+)", "/index"));
+        assert!(!check(r"matches(/\w+)", "x/index"));
+        // Empty values did not participate and are not checked
+        assert!(check("one_of(GET)", ""));
+
+        assert!("range(1)".parse::<Rule>().is_err());
+        assert!("range(a, b)".parse::<Rule>().is_err());
+        assert!("one_of()".parse::<Rule>().is_err());
+        assert!("matches(\"(\")".parse::<Rule>().is_err());
+        assert!("positive".parse::<Rule>().is_err());
+        assert!("unmatched".parse::<OnViolation>().is_ok());
+        assert!("ignore".parse::<OnViolation>().is_err());
+    }
+
+    #[test]
+    fn test_rule_extractor() {
+        let scanner = Scanner::new("^{{method:var_name}} {{status:number}}$".to_string()).unwrap();
+        let rules = parse_rules(&HashMap::from([
+            ("method".to_string(), vec!["one_of(GET, POST)".to_string()]),
+            ("status".to_string(), vec!["range(100, 599)".to_string()]),
+        ]))
+        .unwrap();
+        let extractor = |on_violation| {
+            RuleExtractor::new(Arc::new(scanner.clone()), rules.clone(), on_violation).unwrap()
+        };
+        let mut out = Vec::new();
+
+        let nulled = extractor(OnViolation::Null);
+        assert_eq!(nulled.extract_pattern("GET 200", &mut out), Some(0));
+        assert_eq!(out, vec!["GET", "200"]);
+        assert_eq!(nulled.extract_bytes(b"GTE 999", &mut out), Some(0));
+        assert_eq!(out, vec!["", ""]);
+
+        let dropped = extractor(OnViolation::Drop);
+        assert_eq!(dropped.extract_pattern("GET 999", &mut out), None);
+        assert!(dropped.is_excluded("GET 999"));
+        assert!(!dropped.is_excluded("GET 200"));
+        assert!(!dropped.is_excluded("no match"));
+
+        let unmatched = extractor(OnViolation::Unmatched);
+        assert_eq!(unmatched.extract_bytes(b"GET 999", &mut out), None);
+        assert!(!unmatched.is_match("GET 999"));
+        assert!(!unmatched.is_excluded("GET 999"));
+        assert!(unmatched.is_match("POST 201"));
+
+        let unknown = HashMap::from([("nope".to_string(), vec![Rule::OneOf(vec![])])]);
+        assert!(RuleExtractor::new(Arc::new(scanner), unknown, OnViolation::Null).is_err());
+    }
+}
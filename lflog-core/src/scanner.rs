@@ -0,0 +1,2279 @@
+//! Log line scanner using compiled regex patterns.
+
+use memchr::memmem::Finder;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{Error, Result};
+use crate::extractor::RecordExtractor;
+use crate::macros::expand;
+use crate::macros::parser::CustomMacro;
+use crate::record::ScanIter;
+use crate::stats::{ScanCounts, ScanStats};
+use crate::transform::Transform;
+use crate::types::FieldType;
+use crate::validate::Validation;
+
+/// Number of patterns whose capture locations each thread keeps for reuse.
+const LOCATION_CACHE_SIZE: usize = 8;
+
+static NEXT_PATTERN_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Capture locations reused across lines, keyed by pattern id and whether they
+    /// are for matching bytes.
+    static LOCATIONS: RefCell<Vec<((usize, bool), Locations)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Regex engine a [`Scanner`] matches lines with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    /// The `regex` crate, picking its internal strategy per search.
+    #[default]
+    Regex,
+    /// The `regex-automata` meta engine limited to backtracking-free strategies, so
+    /// searches run on a lazy DFA with a literal prefilter and captures come from a
+    /// one-pass DFA or the PikeVM. Often faster for simple anchored patterns.
+    ///
+    /// Requires the `dfa` feature.
+    LazyDfa,
+    /// A Hyperscan (or Vectorscan) database of all patterns picks the ones that may
+    /// match each line in a single pass; the `regex` crate then extracts the captures
+    /// of the first that does. Pays off with many patterns over large files.
+    ///
+    /// Requires the `hyperscan` feature and `libhs` at runtime.
+    Hyperscan,
+    /// PCRE2, for patterns using lookarounds or backreferences, which the `regex`
+    /// crate rejects. Backtracks, so a careless pattern can be slow on some lines.
+    ///
+    /// Requires the `pcre2` feature and `libpcre2-8` at runtime.
+    Pcre2,
+}
+
+impl Engine {
+    /// The engine's profile name, e.g. `lazy_dfa`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Regex => "regex",
+            Engine::LazyDfa => "lazy_dfa",
+            Engine::Hyperscan => "hyperscan",
+            Engine::Pcre2 => "pcre2",
+        }
+    }
+}
+
+impl std::str::FromStr for Engine {
+    type Err = Error;
+
+    /// Parse an engine by its profile name, e.g. `lazy_dfa`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "regex" => Ok(Engine::Regex),
+            "lazy_dfa" => Ok(Engine::LazyDfa),
+            "hyperscan" => Ok(Engine::Hyperscan),
+            "pcre2" => Ok(Engine::Pcre2),
+            other => Err(Error::Config(format!(
+                "unknown regex engine '{}', expected regex, lazy_dfa, hyperscan or pcre2",
+                other
+            ))),
+        }
+    }
+}
+
+/// Scans log lines using compiled regex patterns with named capture groups.
+///
+/// A scanner holds one or more patterns, tried in order; the first that matches a
+/// line extracts it. Their fields are merged into one schema, and fields a pattern
+/// lacks are empty in the lines it matches.
+///
+/// A trailing `\r` is not part of the line, so patterns anchored with `$` match the
+/// lines of Windows-produced (CRLF) logs too.
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    patterns: Vec<Pattern>,
+    /// Substring every matching line contains, checked before running the regexes.
+    prefilter: Option<Finder<'static>>,
+    /// Lines to drop as noise: the expanded pattern, then a string and a bytes regex
+    /// compiled with the scanner's flags.
+    exclude: Option<(String, Regex, regex::bytes::Regex)>,
+    /// Regex flags applied to every pattern, as given to [`Scanner::with_flags`].
+    flags: Vec<String>,
+    /// Value of each field, by index in `field_names`, when its group is absent.
+    field_defaults: Vec<Option<String>>,
+    /// `field_defaults` by index of `indices_map`.
+    slot_defaults: Vec<Option<String>>,
+    /// Transform of each field, by index in `field_names`, from macros that normalize
+    /// what they capture.
+    field_transforms: Vec<Option<Transform>>,
+    /// Database selecting candidate patterns, with [`Engine::Hyperscan`].
+    #[cfg(feature = "hyperscan")]
+    hyperscan: Option<Arc<crate::hyperscan::Database>>,
+    /// Ordered list of field names extracted from the patterns.
+    pub field_names: Vec<String>,
+    /// Map of field names to their corresponding capture group indices.
+    ///
+    /// Indices are those of the first pattern; fields only found in later patterns
+    /// are numbered after its capture groups.
+    pub indices_map: HashMap<String, usize>,
+    /// Type hints for fields, used for schema generation.
+    pub type_hints: HashMap<String, FieldType>,
+    /// Counters of the scans using this scanner, shared by its clones.
+    stats: Arc<ScanStats>,
+}
+
+/// One compiled pattern of a [`Scanner`].
+#[derive(Debug, Clone)]
+struct Pattern {
+    /// Expanded regex, recompiled when the engine changes.
+    source: String,
+    matcher: Matcher,
+    /// The regex, with the scanner's flags, starts with `^` and ends with `$`, so the
+    /// engines that take a hint search only from the start of the line.
+    anchored: bool,
+    /// Capture group index of each field in the scanner's `field_names`.
+    field_indices: Vec<usize>,
+    /// Capture group index for each index of the scanner's `indices_map`.
+    slots: Vec<usize>,
+    /// Identifies this pattern's regex (shared by clones) in the location cache.
+    id: usize,
+}
+
+impl Scanner {
+    /// Create a new Scanner from a pattern string using only builtin macros.
+    ///
+    /// The pattern can contain macros like `{{field:datetime("%Y-%m-%d")}}` or
+    /// standard regex named capture groups like `(?P<name>...)`.
+    pub fn new(pattern: String) -> Result<Self> {
+        Self::with_custom_macros(pattern, None)
+    }
+
+    /// Create a new Scanner with custom macros.
+    ///
+    /// Custom macros are checked before builtin macros during expansion.
+    pub fn with_custom_macros(
+        pattern: String,
+        custom_macros: Option<&[CustomMacro]>,
+    ) -> Result<Self> {
+        Self::with_patterns(vec![pattern], custom_macros)
+    }
+
+    /// Create a Scanner trying several patterns in order, first match wins.
+    ///
+    /// The schema holds the fields of every pattern in order of first appearance. A
+    /// field shared by several patterns must have the same type in each.
+    pub fn with_patterns(
+        patterns: Vec<String>,
+        custom_macros: Option<&[CustomMacro]>,
+    ) -> Result<Self> {
+        Self::with_patterns_and_engine(patterns, custom_macros, Engine::Regex)
+    }
+
+    /// Like [`with_patterns`](Self::with_patterns), compiling the patterns for `engine`.
+    ///
+    /// Patterns only [`Engine::Pcre2`] accepts, such as those with lookarounds, must
+    /// be compiled this way rather than switched to it with
+    /// [`with_engine`](Self::with_engine).
+    pub fn with_patterns_and_engine(
+        patterns: Vec<String>,
+        custom_macros: Option<&[CustomMacro]>,
+        engine: Engine,
+    ) -> Result<Self> {
+        if patterns.is_empty() {
+            return Err(Error::Config("A scanner needs at least one pattern".into()));
+        }
+        let mut field_names: Vec<String> = Vec::new();
+        let mut type_hints: HashMap<String, FieldType> = HashMap::new();
+        let mut macro_defaults: HashMap<String, String> = HashMap::new();
+        let mut macro_transforms: HashMap<String, Transform> = HashMap::new();
+        let mut compiled = Vec::with_capacity(patterns.len());
+        for pattern in &patterns {
+            let expansion = expand(pattern, custom_macros)?;
+            let (expanded, mut names, hints) =
+                (expansion.regex, expansion.fields, expansion.type_hints);
+            for (name, value) in expansion.defaults {
+                macro_defaults.entry(name).or_insert(value);
+            }
+            for (name, transform) in expansion.transforms {
+                macro_transforms.entry(name).or_insert(transform);
+            }
+            tracing::trace!(%pattern, %expanded, "expanded pattern macros");
+            let (matcher, groups) = Matcher::compile(&expanded, engine)?;
+
+            // If no macros were found, extract field names from regex named capture groups
+            if names.is_empty() {
+                names = groups.iter().flatten().cloned().collect();
+            }
+            for name in names {
+                if !field_names.contains(&name) {
+                    field_names.push(name);
+                }
+            }
+            for (name, hint) in hints {
+                match type_hints.get(&name) {
+                    Some(existing) if *existing != hint => {
+                        return Err(Error::Config(format!(
+                            "Field '{}' has different types in the scanner's patterns",
+                            name
+                        )));
+                    }
+                    _ => {
+                        type_hints.insert(name, hint);
+                    }
+                }
+            }
+            compiled.push((expanded, matcher, groups));
+        }
+
+        let captures = |groups: &[Option<String>]| {
+            groups
+                .iter()
+                .enumerate()
+                .filter_map(|(i, name)| name.clone().map(|name| (name, i)))
+                .collect::<HashMap<String, usize>>()
+        };
+        // Fields missing from the first pattern are numbered after its capture groups
+        let mut indices_map = captures(&compiled[0].2);
+        let mut next = compiled[0].2.len();
+        for name in &field_names {
+            if !indices_map.contains_key(name) {
+                indices_map.insert(name.clone(), next);
+                next += 1;
+            }
+        }
+
+        let patterns: Vec<Pattern> = compiled
+            .into_iter()
+            .map(|(source, matcher, groups)| {
+                let own = captures(&groups);
+                let field_indices = field_names
+                    .iter()
+                    .map(|name| own.get(name).copied().unwrap_or(usize::MAX))
+                    .collect();
+                let mut slots = vec![usize::MAX; next];
+                for (name, &slot) in &indices_map {
+                    if let Some(&index) = own.get(name) {
+                        slots[slot] = index;
+                    }
+                }
+                Pattern {
+                    anchored: is_fully_anchored(&source),
+                    source,
+                    matcher,
+                    field_indices,
+                    slots,
+                    id: NEXT_PATTERN_ID.fetch_add(1, Ordering::Relaxed),
+                }
+            })
+            .collect();
+        tracing::debug!(fields = ?field_names, ?engine, "compiled scanner");
+
+        let scanner = Self {
+            #[cfg(feature = "hyperscan")]
+            hyperscan: hyperscan_database(&patterns, "", engine)?,
+            patterns,
+            prefilter: None,
+            exclude: None,
+            flags: Vec::new(),
+            field_defaults: vec![None; field_names.len()],
+            slot_defaults: vec![None; next],
+            field_transforms: field_names
+                .iter()
+                .map(|name| macro_transforms.remove(name))
+                .collect(),
+            indices_map,
+            field_names,
+            type_hints,
+            stats: Arc::default(),
+        };
+        scanner.with_defaults(&macro_defaults)
+    }
+
+    /// Give fields a value for lines where their group does not participate, e.g. an
+    /// optional group or a field a fallback pattern lacks.
+    ///
+    /// Replaces defaults declared by custom macros. Fails if a field is unknown.
+    pub fn with_defaults(mut self, defaults: &HashMap<String, String>) -> Result<Self> {
+        for (name, value) in defaults {
+            let index = self
+                .field_names
+                .iter()
+                .position(|field| field == name)
+                .ok_or_else(|| {
+                    Error::Config(format!("Cannot set a default for unknown field '{}'", name))
+                })?;
+            self.field_defaults[index] = Some(value.clone());
+            if let Some(&slot) = self.indices_map.get(name) {
+                self.slot_defaults[slot] = Some(value.clone());
+            }
+        }
+        Ok(self)
+    }
+
+    /// Combine with `other` into one scanner over both log sources, trying this
+    /// scanner's patterns first.
+    ///
+    /// The schema is the union of both: fields come in order of first appearance, and
+    /// fields a line's pattern lacks are empty (NULL in typed columns) unless they
+    /// have a default. The prefilter is kept only if both scanners have the same one.
+    /// Fails if a shared field has different types, or the scanners use different
+    /// engines, flags or exclude patterns.
+    pub fn union(mut self, other: Scanner) -> Result<Self> {
+        let engine = self.engine();
+        if other.engine() != engine {
+            return Err(Error::Config(
+                "Cannot union scanners using different engines".into(),
+            ));
+        }
+        if other.flags != self.flags {
+            return Err(Error::Config(
+                "Cannot union scanners with different flags".into(),
+            ));
+        }
+        if other.exclude_pattern() != self.exclude_pattern() {
+            return Err(Error::Config(
+                "Cannot union scanners with different exclude patterns".into(),
+            ));
+        }
+        for (name, hint) in &other.type_hints {
+            match self.type_hints.get(name) {
+                Some(existing) if existing != hint => {
+                    return Err(Error::Config(format!(
+                        "Field '{}' has different types in the scanner's patterns",
+                        name
+                    )));
+                }
+                _ => {
+                    self.type_hints.insert(name.clone(), hint.clone());
+                }
+            }
+        }
+        if other.prefilter() != self.prefilter() {
+            self.prefilter = None;
+        }
+
+        // Fields new to this scanner take the slots after its own
+        let own_fields = self.field_names.len();
+        for (index, name) in other.field_names.iter().enumerate() {
+            match self.field_names.iter().position(|field| field == name) {
+                Some(field) => {
+                    if self.field_defaults[field].is_none() {
+                        self.field_defaults[field] = other.field_defaults[index].clone();
+                    }
+                    if self.field_transforms[field].is_none() {
+                        self.field_transforms[field] = other.field_transforms[index].clone();
+                    }
+                }
+                None => {
+                    self.field_names.push(name.clone());
+                    self.field_defaults
+                        .push(other.field_defaults[index].clone());
+                    self.field_transforms
+                        .push(other.field_transforms[index].clone());
+                }
+            }
+        }
+        for name in &self.field_names[own_fields..] {
+            self.indices_map
+                .insert(name.clone(), self.slot_defaults.len());
+            self.slot_defaults.push(None);
+        }
+        for (index, name) in self.field_names.iter().enumerate() {
+            if let Some(&slot) = self.indices_map.get(name) {
+                self.slot_defaults[slot] = self.field_defaults[index].clone();
+            }
+        }
+
+        let slots = self.slot_defaults.len();
+        for pattern in &mut self.patterns {
+            pattern
+                .field_indices
+                .resize(self.field_names.len(), usize::MAX);
+            pattern.slots.resize(slots, usize::MAX);
+        }
+        for mut pattern in other.patterns {
+            let own: HashMap<&String, usize> = other
+                .field_names
+                .iter()
+                .zip(&pattern.field_indices)
+                .map(|(name, &index)| (name, index))
+                .collect();
+            pattern.field_indices = self
+                .field_names
+                .iter()
+                .map(|name| own.get(name).copied().unwrap_or(usize::MAX))
+                .collect();
+            let mut slots = vec![usize::MAX; slots];
+            for (name, &slot) in &self.indices_map {
+                if let Some(&index) = own.get(name) {
+                    slots[slot] = index;
+                }
+            }
+            pattern.slots = slots;
+            self.patterns.push(pattern);
+        }
+        #[cfg(feature = "hyperscan")]
+        {
+            let prefix = flag_prefix(&self.flags, engine)?;
+            self.hyperscan = hyperscan_database(&self.patterns, &prefix, engine)?;
+        }
+        Ok(self)
+    }
+
+    /// The default value of field `name`, if it has one.
+    pub fn default_value(&self, name: &str) -> Option<&str> {
+        let index = self.field_names.iter().position(|field| field == name)?;
+        self.field_defaults[index].as_deref()
+    }
+
+    /// Match lines with `engine`, recompiling the patterns.
+    ///
+    /// Fails for [`Engine::LazyDfa`], [`Engine::Hyperscan`] and [`Engine::Pcre2`]
+    /// unless the crate is built with the matching feature, and for patterns the new
+    /// engine does not accept.
+    pub fn with_engine(mut self, engine: Engine) -> Result<Self> {
+        self.recompile(engine)?;
+        Ok(self)
+    }
+
+    /// Apply regex flags to every pattern, recompiling them.
+    ///
+    /// Flags are `i` (case-insensitive), `m` (multi-line), `s` (`.` matches `\n`),
+    /// `x` (ignore whitespace), `U` (swap greedy and lazy quantifiers) and `u`
+    /// (Unicode, on by default); a leading `-` clears a flag, e.g. `-u`. Unknown flags,
+    /// flags both set and cleared, and flags the engine does not support are errors.
+    pub fn with_flags<S: AsRef<str>>(mut self, flags: &[S]) -> Result<Self> {
+        let flags: Vec<String> = flags.iter().map(|f| f.as_ref().to_string()).collect();
+        if flags == self.flags {
+            return Ok(self);
+        }
+        self.flags = flags;
+        let engine = self.engine();
+        self.recompile(engine)?;
+        if let Some((pattern, ..)) = self.exclude.take() {
+            self.exclude = Some(self.compile_exclude(pattern)?);
+        }
+        Ok(self)
+    }
+
+    /// The regex flags set with [`with_flags`](Self::with_flags).
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// Compile every pattern with the current flags for `engine`.
+    fn recompile(&mut self, engine: Engine) -> Result<()> {
+        let prefix = flag_prefix(&self.flags, engine)?;
+        for pattern in &mut self.patterns {
+            let source = format!("{}{}", prefix, pattern.source);
+            pattern.matcher = Matcher::compile(&source, engine)
+                .map_err(|e| match prefix.is_empty() {
+                    true => e,
+                    false => Error::Config(format!("With flags {}: {}", prefix, e)),
+                })?
+                .0;
+            pattern.anchored = is_fully_anchored(&source);
+            // Cached locations belong to the previous engine
+            pattern.id = NEXT_PATTERN_ID.fetch_add(1, Ordering::Relaxed);
+        }
+        #[cfg(feature = "hyperscan")]
+        {
+            self.hyperscan = hyperscan_database(&self.patterns, &prefix, engine)?;
+        }
+        Ok(())
+    }
+
+    /// The engine lines are matched with.
+    pub fn engine(&self) -> Engine {
+        #[cfg(feature = "hyperscan")]
+        if self.hyperscan.is_some() {
+            return Engine::Hyperscan;
+        }
+        match self.patterns[0].matcher {
+            Matcher::Regex(..) => Engine::Regex,
+            #[cfg(feature = "dfa")]
+            Matcher::LazyDfa(_) => Engine::LazyDfa,
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(_) => Engine::Pcre2,
+        }
+    }
+
+    /// Require `literal` in every line before trying the patterns.
+    ///
+    /// Lines without it are rejected with a memmem search instead of the regexes,
+    /// which skips most lines of a file at memory bandwidth when the literal is rare.
+    /// The literal must occur in every line the patterns can match, or such lines are
+    /// lost. An empty literal disables the prefilter.
+    pub fn with_prefilter(mut self, literal: impl AsRef<str>) -> Self {
+        let literal = literal.as_ref();
+        self.prefilter = (!literal.is_empty()).then(|| Finder::new(literal).into_owned());
+        self
+    }
+
+    /// The required substring set with [`with_prefilter`](Self::with_prefilter).
+    pub fn prefilter(&self) -> Option<&str> {
+        self.prefilter
+            .as_ref()
+            .map(|finder| std::str::from_utf8(finder.needle()).unwrap())
+    }
+
+    /// Whether `line` passes the prefilter, if any.
+    fn passes_prefilter(&self, line: &[u8]) -> bool {
+        self.prefilter
+            .as_ref()
+            .is_none_or(|finder| finder.find(line).is_some())
+    }
+
+    /// Drop lines matching `pattern`, e.g. health checks or debug spam.
+    ///
+    /// Excluded lines are never records, and are not unmatched lines either: they do
+    /// not count towards the match rate or appear in `__unmatched` tables. The
+    /// pattern may use macros, but its fields are ignored; it is searched anywhere
+    /// in the line unless anchored. It is matched with the `regex` crate and the
+    /// scanner's [flags](Self::with_flags), whichever is set first.
+    pub fn with_exclude_pattern(
+        mut self,
+        pattern: &str,
+        custom_macros: Option<&[CustomMacro]>,
+    ) -> Result<Self> {
+        let expanded = expand(pattern, custom_macros)?.regex;
+        self.exclude = Some(self.compile_exclude(expanded)?);
+        Ok(self)
+    }
+
+    /// The exclude pattern set with [`with_exclude_pattern`](Self::with_exclude_pattern),
+    /// with macros expanded.
+    pub fn exclude_pattern(&self) -> Option<&str> {
+        self.exclude.as_ref().map(|(pattern, ..)| pattern.as_str())
+    }
+
+    /// Compile the expanded exclude `pattern` with the current flags.
+    fn compile_exclude(&self, pattern: String) -> Result<(String, Regex, regex::bytes::Regex)> {
+        let source = format!("{}{}", flag_prefix(&self.flags, Engine::Regex)?, pattern);
+        let regex = Regex::new(&source)?;
+        let bytes = regex::bytes::Regex::new(&source)?;
+        Ok((pattern, regex, bytes))
+    }
+
+    /// Run `f` on this thread's reusable capture locations for `pattern`.
+    ///
+    /// Avoids allocating a fresh `Captures` for every scanned line.
+    fn with_locations<R>(pattern: &Pattern, f: impl FnOnce(&mut Locations) -> R) -> R {
+        Self::with_cached_locations(pattern, false, f)
+    }
+
+    /// Run `f` on this thread's reusable locations for `pattern`, for matching bytes
+    /// if `bytes` is set.
+    fn with_cached_locations<R>(
+        pattern: &Pattern,
+        bytes: bool,
+        f: impl FnOnce(&mut Locations) -> R,
+    ) -> R {
+        LOCATIONS.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            let key = (pattern.id, bytes);
+            let index = match cache.iter().position(|(k, _)| *k == key) {
+                Some(index) => index,
+                None => {
+                    if cache.len() >= LOCATION_CACHE_SIZE {
+                        cache.remove(0);
+                    }
+                    let locations = match bytes {
+                        true => pattern.bytes_locations(),
+                        false => pattern.locations(),
+                    };
+                    cache.push((key, locations));
+                    cache.len() - 1
+                }
+            };
+            f(&mut cache[index].1)
+        })
+    }
+
+    /// Match `line` against the patterns in order and run `f` on the first match.
+    ///
+    /// `f` gets the index of the matching pattern, the pattern and its capture locations.
+    /// A trailing `\r` is left out of the search, so the locations index `line` as is.
+    fn find_match<R>(
+        &self,
+        line: &str,
+        f: impl FnOnce(usize, &Pattern, &Locations) -> R,
+    ) -> Option<R> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut f = Some(f);
+        let mut result = None;
+        self.first_match(line, |i, pattern| {
+            Self::with_locations(pattern, |locations| {
+                if !pattern.read(line, locations) {
+                    return false;
+                }
+                result = f.take().map(|f| f(i, pattern, locations));
+                true
+            })
+        });
+        result
+    }
+
+    /// Index of the first pattern `read` accepts for `line`, trying only the
+    /// candidates of the prefilter, exclude pattern and Hyperscan database.
+    fn first_match(
+        &self,
+        line: &str,
+        mut read: impl FnMut(usize, &Pattern) -> bool,
+    ) -> Option<usize> {
+        if !self.passes_prefilter(line.as_bytes()) || self.is_excluded(line) {
+            return None;
+        }
+        #[cfg(feature = "hyperscan")]
+        if let Some(database) = &self.hyperscan {
+            return database.with_candidates(line, |candidates| {
+                self.patterns
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| candidates[*i])
+                    .find_map(|(i, pattern)| read(i, pattern).then_some(i))
+            });
+        }
+        self.patterns
+            .iter()
+            .enumerate()
+            .find_map(|(i, pattern)| read(i, pattern).then_some(i))
+    }
+
+    /// Match raw `line` against the patterns in order and run `f` on the first match.
+    ///
+    /// The `regex` and `lazy_dfa` engines search the bytes directly. PCRE2 and
+    /// Hyperscan need valid UTF-8, so with them a line is validated first and invalid
+    /// lines do not match.
+    fn find_match_bytes<R>(
+        &self,
+        line: &[u8],
+        f: impl FnOnce(usize, &Pattern, &Locations) -> R,
+    ) -> Option<R> {
+        if !self.native_bytes() {
+            return self.find_match(std::str::from_utf8(line).ok()?, f);
+        }
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let mut f = Some(f);
+        let mut result = None;
+        self.first_match_bytes(line, |i, pattern| {
+            Self::with_cached_locations(pattern, true, |locations| {
+                if !pattern.read_bytes(line, locations) {
+                    return false;
+                }
+                result = f.take().map(|f| f(i, pattern, locations));
+                true
+            })
+        });
+        result
+    }
+
+    /// [`first_match`](Self::first_match) for raw `line`, with an engine that
+    /// searches bytes.
+    fn first_match_bytes(
+        &self,
+        line: &[u8],
+        mut read: impl FnMut(usize, &Pattern) -> bool,
+    ) -> Option<usize> {
+        if !self.passes_prefilter(line)
+            || self
+                .exclude
+                .as_ref()
+                .is_some_and(|(_, _, exclude)| exclude.is_match(line))
+        {
+            return None;
+        }
+        self.patterns
+            .iter()
+            .enumerate()
+            .find_map(|(i, pattern)| read(i, pattern).then_some(i))
+    }
+
+    /// Capture locations to reuse across calls to [`match_into`](Self::match_into).
+    pub fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations {
+            patterns: self
+                .patterns
+                .iter()
+                .map(|pattern| PatternLocations {
+                    id: pattern.id,
+                    field_indices: pattern.field_indices.clone(),
+                    text: None,
+                    bytes: None,
+                })
+                .collect(),
+            matched: None,
+        }
+    }
+
+    /// Match `line` like [`scan`](Self::scan), recording the spans of its fields in
+    /// `locations` instead of copying them out.
+    ///
+    /// Nothing is allocated once `locations` has been used with each pattern, so
+    /// high-throughput callers can match every line with one [`CaptureLocations`]
+    /// and slice the fields they need from the line. Defaults and macro transforms
+    /// are not applied: a field whose group is absent has no span.
+    ///
+    /// # Panics
+    ///
+    /// If `locations` were created by another scanner.
+    pub fn match_into(&self, line: &str, locations: &mut CaptureLocations) -> bool {
+        locations.matched = None;
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let pattern = self.first_match(line, |i, pattern| {
+            pattern.read(line, locations.text(i, pattern))
+        });
+        locations.matched = pattern.map(|pattern| (pattern, false));
+        pattern.is_some()
+    }
+
+    /// [`match_into`](Self::match_into) for a raw line, like
+    /// [`scan_direct_bytes`](Self::scan_direct_bytes); spans index `line`.
+    ///
+    /// # Panics
+    ///
+    /// If `locations` were created by another scanner.
+    pub fn match_bytes_into(&self, line: &[u8], locations: &mut CaptureLocations) -> bool {
+        if !self.native_bytes() {
+            return std::str::from_utf8(line).is_ok_and(|line| self.match_into(line, locations));
+        }
+        locations.matched = None;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let pattern = self.first_match_bytes(line, |i, pattern| {
+            pattern.read_bytes(line, locations.bytes(i, pattern))
+        });
+        locations.matched = pattern.map(|pattern| (pattern, true));
+        pattern.is_some()
+    }
+
+    /// Whether the patterns can search bytes without validating them as UTF-8.
+    fn native_bytes(&self) -> bool {
+        matches!(self.engine(), Engine::Regex | Engine::LazyDfa)
+    }
+
+    /// Number of patterns tried for each line.
+    pub fn pattern_count(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Prepare capture indices for a list of fields.
+    ///
+    /// This resolves the mapping from field names to regex capture group indices once,
+    /// allowing for faster lookups during scanning.
+    ///
+    /// Returns an error if a field name is not found in either the regex capture groups
+    /// or the additional columns.
+    pub fn prepare_indices(
+        &self,
+        field_names: &[&str],
+        additional_columns: &[&str],
+    ) -> Result<Vec<usize>> {
+        let capture_count = self.patterns[0].slots.len();
+        let additional_indices_map = additional_columns
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (*name, capture_count + i))
+            .collect::<HashMap<&str, usize>>();
+
+        let mut indices = Vec::with_capacity(field_names.len() + additional_columns.len());
+        for name in field_names {
+            if let Some(index) = self.indices_map.get(*name) {
+                indices.push(*index);
+            } else if let Some(index) = additional_indices_map.get(*name) {
+                indices.push(*index);
+            } else {
+                return Err(Error::other(format!("Field name not found: {}", name)));
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Scan a log line using pre-calculated indices and populate a buffer with slices.
+    ///
+    /// This method avoids String allocations by returning slices of the input line (`&str`).
+    /// It appends results to the provided `out` buffer, which should be reused across calls
+    /// to minimize allocation overhead.
+    ///
+    /// Fields whose group is absent get their [default](Self::with_defaults), if any.
+    /// Values are slices of the line, so macros that normalize what they capture, such
+    /// as `loglevel(normalize)`, leave them as they are.
+    ///
+    /// Returns `true` if the line matches the pattern, `false` otherwise.
+    pub fn scan_direct<'a>(
+        &'a self,
+        line: &'a str,
+        field_indices: &[usize],
+        out: &mut Vec<&'a str>,
+    ) -> bool {
+        self.find_match(line, |_, pattern, locations| {
+            out.clear();
+            for &index in field_indices {
+                out.push(
+                    pattern
+                        .slots
+                        .get(index)
+                        .and_then(|&capture| locations.get(capture))
+                        .map_or_else(
+                            || self.slot_default(index),
+                            |(start, end)| &line[start..end],
+                        ),
+                );
+            }
+        })
+        .is_some()
+    }
+
+    /// Default of the field at `index` of `indices_map`, or the empty string.
+    fn slot_default(&self, index: usize) -> &str {
+        self.slot_defaults
+            .get(index)
+            .and_then(Option::as_deref)
+            .unwrap_or("")
+    }
+
+    /// Default of the field at `index` of `field_names`, or the empty string.
+    fn field_default(&self, index: usize) -> &str {
+        self.field_defaults[index].as_deref().unwrap_or("")
+    }
+
+    /// `value` of the field at `index` of `field_names`, after its macro's transform.
+    fn field_value<'a>(&self, index: usize, value: Cow<'a, str>) -> Cow<'a, str> {
+        match &self.field_transforms[index] {
+            Some(transform) => transform.apply(value),
+            None => value,
+        }
+    }
+
+    /// [`field_default`](Self::field_default) as a value of any lifetime.
+    fn field_default_cow<'a>(&self, index: usize) -> Cow<'a, str> {
+        match &self.field_defaults[index] {
+            Some(value) => Cow::Owned(value.clone()),
+            None => Cow::Borrowed(""),
+        }
+    }
+
+    /// Scan raw bytes like [`scan_direct`](Self::scan_direct), without decoding the line.
+    ///
+    /// With the `regex` and `lazy_dfa` engines the line is never validated as UTF-8;
+    /// decode only the captured slices that are kept, e.g. with
+    /// [`String::from_utf8_lossy`]. Invalid bytes do not match Unicode classes such as
+    /// `.` or `\w`, so a line with them in a field may not match where its lossily
+    /// decoded form would.
+    pub fn scan_direct_bytes<'a>(
+        &'a self,
+        line: &'a [u8],
+        field_indices: &[usize],
+        out: &mut Vec<&'a [u8]>,
+    ) -> bool {
+        self.find_match_bytes(line, |_, pattern, locations| {
+            out.clear();
+            for &index in field_indices {
+                out.push(
+                    pattern
+                        .slots
+                        .get(index)
+                        .and_then(|&capture| locations.get(capture))
+                        .map_or_else(
+                            || self.slot_default(index).as_bytes(),
+                            |(start, end)| &line[start..end],
+                        ),
+                );
+            }
+        })
+        .is_some()
+    }
+
+    /// Check whether a log line matches any pattern without extracting fields.
+    pub fn is_match(&self, line: &str) -> bool {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if !self.passes_prefilter(line.as_bytes()) || self.is_excluded(line) {
+            return false;
+        }
+        #[cfg(feature = "hyperscan")]
+        if let Some(database) = &self.hyperscan {
+            return database.with_candidates(line, |candidates| {
+                self.patterns
+                    .iter()
+                    .zip(candidates)
+                    .any(|(pattern, candidate)| *candidate && pattern.is_match(line))
+            });
+        }
+        self.patterns.iter().any(|pattern| pattern.is_match(line))
+    }
+
+    /// Scan a log line and return captured field values in order.
+    ///
+    /// Returns `None` if the line doesn't match the pattern.
+    pub fn scan(&self, line: &str) -> Option<Vec<String>> {
+        self.find_match(line, |_, pattern, locations| {
+            pattern
+                .field_indices
+                .iter()
+                .enumerate()
+                .map(|(field, &index)| match locations.get(index) {
+                    Some((start, end)) => self
+                        .field_value(field, Cow::Borrowed(&line[start..end]))
+                        .into_owned(),
+                    None => self.field_default(field).to_owned(),
+                })
+                .collect()
+        })
+    }
+
+    /// Lines and bytes counted by the scans using this scanner, or one of its clones,
+    /// so far: table scans and [`scan_iter`](Self::scan_iter).
+    pub fn stats(&self) -> ScanCounts {
+        self.stats.counts()
+    }
+
+    /// Return [`stats`](Self::stats) and start counting again from zero, e.g. once
+    /// per monitoring interval.
+    pub fn reset_stats(&self) -> ScanCounts {
+        self.stats.reset()
+    }
+
+    pub(crate) fn add_stats(&self, counts: ScanCounts) {
+        self.stats.add(counts);
+    }
+
+    /// Stream the records of `reader`, one line at a time.
+    ///
+    /// Each matched line yields a [`Record`](crate::Record) whose fields can be read
+    /// by name or deserialized into a row struct; unmatched lines are skipped.
+    pub fn scan_iter<R: BufRead>(&self, reader: R) -> ScanIter<'_, R> {
+        ScanIter::new(self, reader)
+    }
+
+    /// Infer types for fields without a type hint, e.g. raw `(?P<status>\d+)` groups,
+    /// from the values they capture in sample `lines`.
+    ///
+    /// A field becomes `Int`, `Int64`, `Float` or a timestamp when all its sampled
+    /// values parse as one (see [`FieldType::infer`]); otherwise it stays a string.
+    /// Lines no pattern matches are ignored.
+    pub fn with_inferred_types<I, S>(mut self, lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let untyped: Vec<usize> = (0..self.field_names.len())
+            .filter(|&i| !self.type_hints.contains_key(&self.field_names[i]))
+            .collect();
+        if untyped.is_empty() {
+            return self;
+        }
+        let mut samples: Vec<Vec<String>> = vec![Vec::new(); untyped.len()];
+        for line in lines {
+            if let Some(values) = self.scan(line.as_ref()) {
+                for (sample, &field) in samples.iter_mut().zip(&untyped) {
+                    sample.push(values[field].clone());
+                }
+            }
+        }
+        for (sample, field) in samples.iter().zip(untyped) {
+            if let Some(hint) = FieldType::infer(sample.iter().map(String::as_str)) {
+                self.type_hints
+                    .insert(self.field_names[field].clone(), hint);
+            }
+        }
+        self
+    }
+
+    /// Check sample lines against the patterns, reporting what each line captures.
+    ///
+    /// Lines no pattern matches get the furthest point any pattern reached in them,
+    /// which usually points at the part of the pattern that needs fixing.
+    pub fn validate<I, S>(&self, lines: I) -> Validation
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Validation::new(self, lines)
+    }
+
+    /// Regex source of each pattern with the scanner's flags, in `regex` syntax.
+    pub(crate) fn pattern_sources(&self) -> impl Iterator<Item = String> + '_ {
+        let prefix = flag_prefix(&self.flags, Engine::Regex).unwrap_or_default();
+        self.patterns
+            .iter()
+            .map(move |pattern| format!("{}{}", prefix, pattern.source))
+    }
+
+    /// Scan a log line and return captured values for specific fields only.
+    ///
+    /// Unlike [`scan`], this method returns only the values for the specified
+    /// `field_names` in the order they are provided. This is useful for query
+    /// projections where only a subset of fields are needed.
+    ///
+    /// Returns `None` if the line doesn't match the pattern.
+    pub fn scan_with(&self, line: &str, field_names: &[&str]) -> Option<Vec<String>> {
+        self.find_match(line, |_, pattern, locations| {
+            field_names
+                .iter()
+                .map(|name| {
+                    let slot = self.indices_map.get(*name).copied();
+                    match slot.and_then(|slot| locations.get(pattern.slots[slot])) {
+                        Some((start, end)) => {
+                            let value = Cow::Borrowed(&line[start..end]);
+                            match self.field_names.iter().position(|field| field == name) {
+                                Some(field) => self.field_value(field, value).into_owned(),
+                                None => value.into_owned(),
+                            }
+                        }
+                        None => slot.map_or("", |slot| self.slot_default(slot)).to_owned(),
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+impl RecordExtractor for Scanner {
+    fn field_names(&self) -> &[String] {
+        &self.field_names
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        &self.type_hints
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        self.extract_pattern(line, out).is_some()
+    }
+
+    fn extract_pattern<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        self.find_match(line, |i, pattern, locations| {
+            out.clear();
+            out.extend(
+                pattern
+                    .field_indices
+                    .iter()
+                    .enumerate()
+                    .map(|(field, &index)| match locations.get(index) {
+                        Some((start, end)) => {
+                            self.field_value(field, Cow::Borrowed(&line[start..end]))
+                        }
+                        None => self.field_default_cow(field),
+                    }),
+            );
+            i
+        })
+    }
+
+    fn extract_bytes<'a>(&self, line: &'a [u8], out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        self.find_match_bytes(line, |i, pattern, locations| {
+            out.clear();
+            out.extend(
+                pattern
+                    .field_indices
+                    .iter()
+                    .enumerate()
+                    .map(|(field, &index)| match locations.get(index) {
+                        Some((start, end)) => {
+                            self.field_value(field, String::from_utf8_lossy(&line[start..end]))
+                        }
+                        None => self.field_default_cow(field),
+                    }),
+            );
+            i
+        })
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        Scanner::is_match(self, line)
+    }
+
+    fn is_excluded(&self, line: &str) -> bool {
+        self.exclude
+            .as_ref()
+            .is_some_and(|(_, exclude, _)| exclude.is_match(line))
+    }
+
+    fn scan_stats(&self) -> Option<&ScanStats> {
+        Some(&self.stats)
+    }
+}
+
+impl Pattern {
+    /// Search input for the lazy DFA, anchored at the start of `line` when the
+    /// pattern is.
+    #[cfg(feature = "dfa")]
+    fn input<'h, H: ?Sized + AsRef<[u8]>>(&self, line: &'h H) -> regex_automata::Input<'h> {
+        let anchored = match self.anchored {
+            true => regex_automata::Anchored::Yes,
+            false => regex_automata::Anchored::No,
+        };
+        regex_automata::Input::new(line).anchored(anchored)
+    }
+
+    /// Fresh capture locations for this pattern's engine.
+    fn locations(&self) -> Locations {
+        match &self.matcher {
+            Matcher::Regex(regex, _) => Locations::Regex(regex.capture_locations()),
+            #[cfg(feature = "dfa")]
+            Matcher::LazyDfa(dfa) => Locations::Dfa(dfa.create_captures()),
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(code) => Locations::Pcre2(code.match_data()),
+        }
+    }
+
+    /// Match `line`, filling `locations` (created by [`Pattern::locations`]).
+    fn read(&self, line: &str, locations: &mut Locations) -> bool {
+        match (&self.matcher, locations) {
+            (Matcher::Regex(regex, _), Locations::Regex(locations)) => {
+                regex.captures_read(locations, line).is_some()
+            }
+            #[cfg(feature = "dfa")]
+            (Matcher::LazyDfa(dfa), Locations::Dfa(captures)) => {
+                dfa.search_captures(&self.input(line), captures);
+                captures.is_match()
+            }
+            #[cfg(feature = "pcre2")]
+            (Matcher::Pcre2(code), Locations::Pcre2(data)) => code.read(line, data, self.anchored),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("locations created for another engine"),
+        }
+    }
+
+    /// Fresh capture locations for [`Pattern::read_bytes`].
+    fn bytes_locations(&self) -> Locations {
+        match &self.matcher {
+            Matcher::Regex(regex, bytes) => {
+                Locations::Bytes(bytes_regex(regex, bytes).capture_locations())
+            }
+            #[allow(unreachable_patterns)]
+            _ => self.locations(),
+        }
+    }
+
+    /// Match raw `line`, filling `locations` (created by [`Pattern::bytes_locations`]).
+    ///
+    /// Only the `regex` and `lazy_dfa` engines search bytes.
+    fn read_bytes(&self, line: &[u8], locations: &mut Locations) -> bool {
+        match (&self.matcher, locations) {
+            (Matcher::Regex(regex, bytes), Locations::Bytes(locations)) => {
+                bytes_regex(regex, bytes)
+                    .captures_read(locations, line)
+                    .is_some()
+            }
+            #[cfg(feature = "dfa")]
+            (Matcher::LazyDfa(dfa), Locations::Dfa(captures)) => {
+                dfa.search_captures(&self.input(line), captures);
+                captures.is_match()
+            }
+            _ => unreachable!("bytes are only searched by the regex and lazy_dfa engines"),
+        }
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        match &self.matcher {
+            Matcher::Regex(regex, _) => regex.is_match(line),
+            #[cfg(feature = "dfa")]
+            Matcher::LazyDfa(dfa) => dfa.is_match(self.input(line)),
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(code) => Scanner::with_locations(self, |locations| match locations {
+                Locations::Pcre2(data) => code.read(line, data, self.anchored),
+                _ => unreachable!("locations created for another engine"),
+            }),
+        }
+    }
+}
+
+/// `regex` over bytes, compiling it on first use.
+fn bytes_regex<'a>(
+    regex: &Regex,
+    bytes: &'a OnceLock<regex::bytes::Regex>,
+) -> &'a regex::bytes::Regex {
+    bytes.get_or_init(|| {
+        regex::bytes::Regex::new(regex.as_str()).expect("a valid regex is valid over bytes")
+    })
+}
+
+/// A pattern compiled for one [`Engine`].
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// The regex, and the same regex over bytes, compiled on first use by
+    /// [`Scanner::scan_direct_bytes`].
+    Regex(Regex, OnceLock<regex::bytes::Regex>),
+    #[cfg(feature = "dfa")]
+    LazyDfa(regex_automata::meta::Regex),
+    #[cfg(feature = "pcre2")]
+    Pcre2(Arc<crate::pcre2::Code>),
+}
+
+impl Matcher {
+    /// Compile `source` for `engine`, with the name of each capture group by index.
+    ///
+    /// [`Engine::Hyperscan`] still extracts captures with the `regex` crate.
+    fn compile(source: &str, engine: Engine) -> Result<(Self, Vec<Option<String>>)> {
+        let regex = |source: &str| -> Result<(Self, Vec<Option<String>>)> {
+            let regex = Regex::new(source)?;
+            let groups = regex
+                .capture_names()
+                .map(|name| name.map(str::to_string))
+                .collect();
+            Ok((Matcher::Regex(regex, OnceLock::new()), groups))
+        };
+        match engine {
+            Engine::Regex => regex(source),
+            #[cfg(feature = "hyperscan")]
+            Engine::Hyperscan => regex(source),
+            #[cfg(feature = "dfa")]
+            Engine::LazyDfa => {
+                use regex_automata::meta;
+                let config = meta::Config::new()
+                    .backtrack(false)
+                    .onepass(true)
+                    .hybrid(true);
+                let dfa = meta::Regex::builder()
+                    .configure(config)
+                    .build(source)
+                    .map_err(|e| Error::Config(format!("Cannot build lazy DFA: {}", e)))?;
+                let groups = dfa
+                    .group_info()
+                    .pattern_names(regex_automata::PatternID::ZERO)
+                    .map(|name| name.map(str::to_string))
+                    .collect();
+                Ok((Matcher::LazyDfa(dfa), groups))
+            }
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2 => {
+                let code = crate::pcre2::Code::new(source)?;
+                let groups = code
+                    .capture_names()
+                    .map(|name| name.map(str::to_string))
+                    .collect();
+                Ok((Matcher::Pcre2(Arc::new(code)), groups))
+            }
+            #[allow(unreachable_patterns)]
+            engine => {
+                let feature = match engine {
+                    Engine::LazyDfa => "dfa",
+                    engine => engine.name(),
+                };
+                Err(Error::Config(format!(
+                    "The {} engine requires the `{}` feature",
+                    engine.name(),
+                    feature
+                )))
+            }
+        }
+    }
+}
+
+/// The Hyperscan database selecting among `patterns`, if `engine` asks for one.
+#[cfg(feature = "hyperscan")]
+fn hyperscan_database(
+    patterns: &[Pattern],
+    prefix: &str,
+    engine: Engine,
+) -> Result<Option<Arc<crate::hyperscan::Database>>> {
+    if engine != Engine::Hyperscan {
+        return Ok(None);
+    }
+    let sources: Vec<String> = patterns
+        .iter()
+        .map(|p| format!("{}{}", prefix, p.source))
+        .collect();
+    let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+    Ok(Some(Arc::new(crate::hyperscan::Database::compile(
+        &sources,
+    )?)))
+}
+
+/// Whether `source` can only match a whole line: it starts with `^` and ends with
+/// `$` (in single-line mode). Patterns outside `regex` syntax are not anchored.
+fn is_fully_anchored(source: &str) -> bool {
+    use regex_syntax::hir::Look;
+    regex_automata::util::syntax::parse(source).is_ok_and(|hir| {
+        let properties = hir.properties();
+        properties.look_set_prefix().contains(Look::Start)
+            && properties.look_set_suffix().contains(Look::End)
+    })
+}
+
+/// Regex flags known to [`Scanner::with_flags`].
+const FLAGS: &str = "imsxUu";
+
+/// Inline flag group, e.g. `(?is-u)`, setting `flags` for `engine`.
+fn flag_prefix(flags: &[String], engine: Engine) -> Result<String> {
+    let (mut set, mut cleared) = (String::new(), String::new());
+    for flag in flags {
+        let (clear, name) = match flag.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, flag.as_str()),
+        };
+        let mut chars = name.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return Err(unknown_flag(flag));
+        };
+        if !FLAGS.contains(c) {
+            return Err(unknown_flag(flag));
+        }
+        if (clear && set.contains(c)) || (!clear && cleared.contains(c)) {
+            return Err(Error::Config(format!(
+                "Regex flag '{}' is both set and cleared",
+                c
+            )));
+        }
+        let supported = match engine {
+            Engine::Regex | Engine::LazyDfa => true,
+            // PCRE2 has no inline Unicode switch; it is always in UTF mode
+            Engine::Pcre2 => c != 'u',
+            Engine::Hyperscan => "imsx".contains(c),
+        };
+        if !supported {
+            return Err(Error::Config(format!(
+                "Regex flag '{}' is not supported by the {} engine",
+                flag,
+                engine.name()
+            )));
+        }
+        let target = if clear { &mut cleared } else { &mut set };
+        if !target.contains(c) {
+            target.push(c);
+        }
+    }
+    Ok(match (set.is_empty(), cleared.is_empty()) {
+        (true, true) => String::new(),
+        (false, true) => format!("(?{})", set),
+        _ => format!("(?{}-{})", set, cleared),
+    })
+}
+
+fn unknown_flag(flag: &str) -> Error {
+    Error::Config(format!(
+        "Unknown regex flag '{}', expected i, m, s, x, U or u (prefix - to clear)",
+        flag
+    ))
+}
+
+/// Field spans of the last line matched by [`Scanner::match_into`], reused across
+/// lines.
+///
+/// Created by [`Scanner::capture_locations`]; only valid with that scanner.
+#[derive(Debug)]
+pub struct CaptureLocations {
+    patterns: Vec<PatternLocations>,
+    /// Index of the pattern that matched, and whether it matched bytes.
+    matched: Option<(usize, bool)>,
+}
+
+/// The locations of one pattern, created on first use.
+#[derive(Debug)]
+struct PatternLocations {
+    id: usize,
+    field_indices: Vec<usize>,
+    text: Option<Locations>,
+    bytes: Option<Locations>,
+}
+
+impl CaptureLocations {
+    /// Index of the pattern that matched the last line, or `None` if it did not match.
+    pub fn pattern(&self) -> Option<usize> {
+        self.matched.map(|(pattern, _)| pattern)
+    }
+
+    /// Start and end of field `field`, by index in the scanner's `field_names`, in the
+    /// last matched line; `None` if the line did not match or the field's group is
+    /// absent.
+    pub fn span(&self, field: usize) -> Option<(usize, usize)> {
+        let (pattern, bytes) = self.matched?;
+        let pattern = &self.patterns[pattern];
+        let locations = match bytes {
+            true => pattern.bytes.as_ref(),
+            false => pattern.text.as_ref(),
+        }?;
+        locations.get(*pattern.field_indices.get(field)?)
+    }
+
+    /// Spans of every field, as [`span`](Self::span) gives them.
+    pub fn spans(&self) -> impl Iterator<Item = Option<(usize, usize)>> + '_ {
+        let fields = self
+            .matched
+            .map_or(0, |(pattern, _)| self.patterns[pattern].field_indices.len());
+        (0..fields).map(|field| self.span(field))
+    }
+
+    fn entry(&mut self, index: usize, pattern: &Pattern) -> &mut PatternLocations {
+        self.patterns
+            .get_mut(index)
+            .filter(|entry| entry.id == pattern.id)
+            .expect("capture locations used with another scanner")
+    }
+
+    fn text(&mut self, index: usize, pattern: &Pattern) -> &mut Locations {
+        self.entry(index, pattern)
+            .text
+            .get_or_insert_with(|| pattern.locations())
+    }
+
+    fn bytes(&mut self, index: usize, pattern: &Pattern) -> &mut Locations {
+        self.entry(index, pattern)
+            .bytes
+            .get_or_insert_with(|| pattern.bytes_locations())
+    }
+}
+
+/// Capture group positions of the last match, for any engine.
+#[derive(Debug)]
+enum Locations {
+    Regex(regex::CaptureLocations),
+    Bytes(regex::bytes::CaptureLocations),
+    #[cfg(feature = "dfa")]
+    Dfa(regex_automata::util::captures::Captures),
+    #[cfg(feature = "pcre2")]
+    Pcre2(crate::pcre2::MatchData),
+}
+
+impl Locations {
+    /// Start and end of capture group `index`, if it participated in the match.
+    fn get(&self, index: usize) -> Option<(usize, usize)> {
+        match self {
+            Locations::Regex(locations) => locations.get(index),
+            Locations::Bytes(locations) => locations.get(index),
+            #[cfg(feature = "dfa")]
+            Locations::Dfa(captures) => {
+                captures.get_group(index).map(|span| (span.start, span.end))
+            }
+            #[cfg(feature = "pcre2")]
+            Locations::Pcre2(data) => data.get(index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_integration() {
+        let pattern = r#"^\[{{time:datetime("%a %b %d %H:%M:%S %Y")}}\] \[{{level:var_name}}\] {{message:any}}$"#;
+        let scanner = Scanner::new(pattern.to_string()).unwrap();
+        let line = "[Sun Dec 04 04:47:44 2005] [notice] workerEnv.init() ok /etc/httpd/conf/workers2.properties";
+        let fields = scanner.scan(line).unwrap();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[0], "Sun Dec 04 04:47:44 2005");
+        assert_eq!(fields[1], "notice");
+    }
+
+    #[test]
+    fn test_scanner_no_named_groups_panic() {
+        // Pattern with no named groups
+        let pattern = r"^\d+$";
+        let scanner = Scanner::new(pattern.to_string()).unwrap();
+
+        // Field names should be empty since there are no named groups
+        assert!(scanner.field_names.is_empty());
+
+        // This panicked with the old logic: 0 + 0 - 1 (underflow)
+        // New logic: captures_len() + 0
+        // NOTE: We must request "__FILE__" in the field list for it to be indexed
+        let indices = scanner
+            .prepare_indices(&["__FILE__"], &["__FILE__"])
+            .unwrap();
+
+        assert_eq!(indices.len(), 1);
+        // captures_len is 1 (group 0 is the whole match)
+        assert!(indices[0] >= 1);
+    }
+
+    #[test]
+    fn test_scanner_mixed_groups_indices() {
+        // Pattern: Unnamed group (\d+) then named group (?P<name>\w+)
+        // Captures: 0 (all), 1 (digits), 2 (name) -> captures_len = 3
+        let pattern = r"^(\d+) (?P<name>\w+)$";
+        let scanner = Scanner::new(pattern.to_string()).unwrap();
+
+        assert_eq!(scanner.field_names, vec!["name"]);
+
+        // We want 'name' and '__FILE__'
+        // NOTE: We must request both in the field list
+        let indices = scanner
+            .prepare_indices(&["name", "__FILE__"], &["__FILE__"])
+            .unwrap();
+
+        assert_eq!(indices.len(), 2);
+
+        // Index for 'name' should be 2
+        assert_eq!(indices[0], 2);
+
+        // Index for '__FILE__' should be >= 3 (captures_len)
+        // Old logic: 0 + 1 (field_names.len) - 1 = 0 (Collision with group 0!)
+        assert!(indices[1] >= 3);
+
+        // Verify scan_direct behavior
+        let line = "123 test";
+        let mut values = Vec::new();
+        let matched = scanner.scan_direct(line, &indices, &mut values);
+
+        assert!(matched);
+        assert_eq!(values[0], "test"); // name
+        assert_eq!(values[1], ""); // __FILE__ (should be empty/None from regex)
+    }
+
+    #[test]
+    fn test_scanners_share_thread_location_cache() {
+        let digits = Scanner::new(r"^(?P<a>\d+)-(?P<b>\d+)$".to_string()).unwrap();
+        let words = Scanner::new(r"^(?P<w>[a-z]+)$".to_string()).unwrap();
+        let cloned = digits.clone();
+        let mut out = Vec::new();
+        for _ in 0..3 {
+            assert!(digits.extract("12-34", &mut out));
+            assert_eq!(out, vec!["12", "34"]);
+            assert!(words.extract("abc", &mut out));
+            assert_eq!(out, vec!["abc"]);
+            assert!(cloned.extract("5-6", &mut out));
+            assert_eq!(out, vec!["5", "6"]);
+        }
+
+        // More scanners than cache slots evict the oldest without mixing them up
+        let scanners: Vec<Scanner> = (0..LOCATION_CACHE_SIZE + 2)
+            .map(|i| Scanner::new(format!(r"^(?P<x>{})$", i)).unwrap())
+            .collect();
+        for (i, scanner) in scanners.iter().enumerate().rev() {
+            let line = i.to_string();
+            let mut out = Vec::new();
+            assert!(scanner.extract(&line, &mut out));
+            assert_eq!(out, vec![line.as_str()]);
+        }
+        assert!(digits.extract("7-8", &mut out));
+        assert_eq!(out, vec!["7", "8"]);
+    }
+
+    #[test]
+    fn test_scanner_multiple_patterns() {
+        let scanner = Scanner::with_patterns(
+            vec![
+                r"^{{ts:number}} ERROR {{msg:any}}$".to_string(),
+                r"^{{ts:number}} {{level:var_name}} {{msg:any}}$".to_string(),
+                r"^(?P<msg>-- .*)$".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(scanner.pattern_count(), 3);
+        assert_eq!(scanner.field_names, vec!["ts", "msg", "level"]);
+        assert!(scanner.type_hints.contains_key("ts"));
+
+        let mut out = Vec::new();
+        // The first matching pattern wins, even though the second also matches
+        assert_eq!(
+            scanner.extract_pattern("1 ERROR disk full", &mut out),
+            Some(0)
+        );
+        assert_eq!(out, vec!["1", "disk full", ""]);
+        assert_eq!(scanner.extract_pattern("2 INFO started", &mut out), Some(1));
+        assert_eq!(out, vec!["2", "started", "INFO"]);
+        assert_eq!(scanner.extract_pattern("-- marker", &mut out), Some(2));
+        assert_eq!(out, vec!["", "-- marker", ""]);
+        assert_eq!(scanner.extract_pattern("garbage", &mut out), None);
+        assert!(scanner.is_match("-- x") && !scanner.is_match("garbage"));
+
+        assert_eq!(
+            scanner.scan("2 INFO started").unwrap(),
+            vec!["2", "started", "INFO"]
+        );
+        let indices = scanner.prepare_indices(&["level", "ts"], &[]).unwrap();
+        let mut direct = Vec::new();
+        assert!(scanner.scan_direct("2 INFO started", &indices, &mut direct));
+        assert_eq!(direct, vec!["INFO", "2"]);
+        assert!(scanner.scan_direct("1 ERROR disk full", &indices, &mut direct));
+        assert_eq!(direct, vec!["", "1"]);
+    }
+
+    #[test]
+    fn test_match_into() {
+        let scanner = Scanner::with_patterns(
+            vec![
+                r"^{{ts:number}} {{level:var_name}} {{msg:any}}$".to_string(),
+                r"^-- {{msg:any}}$".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+        let mut locations = scanner.capture_locations();
+        let line = "12 INFO started\r";
+        assert!(scanner.match_into(line, &mut locations));
+        assert_eq!(locations.pattern(), Some(0));
+        let (start, end) = locations.span(2).unwrap();
+        assert_eq!(&line[start..end], "started");
+        assert_eq!(
+            locations.spans().collect::<Vec<_>>(),
+            [Some((0, 2)), Some((3, 7)), Some((8, 15))]
+        );
+
+        assert!(scanner.match_bytes_into(b"-- marker", &mut locations));
+        assert_eq!(locations.pattern(), Some(1));
+        assert_eq!(locations.span(0), None);
+        assert_eq!(locations.span(2), Some((3, 9)));
+        assert_eq!(locations.span(3), None);
+
+        assert!(!scanner.match_into("garbage", &mut locations));
+        assert_eq!(locations.pattern(), None);
+        assert_eq!(locations.span(1), None);
+        assert_eq!(locations.spans().count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "another scanner")]
+    fn test_match_into_other_scanner() {
+        let scanner = Scanner::new("^{{msg:any}}$".to_string()).unwrap();
+        let other = Scanner::new("^{{msg:any}}$".to_string()).unwrap();
+        scanner.match_into("x", &mut other.capture_locations());
+    }
+
+    #[test]
+    fn test_loglevel_macro() {
+        let scanner = Scanner::with_patterns(
+            vec![
+                r"^\[{{level:loglevel(normalize)}}\] {{msg:any}}$".to_string(),
+                r"^{{raw:loglevel}}: {{msg:any}}$".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(scanner.type_hints["level"], FieldType::Enum);
+        let mut out = Vec::new();
+        assert!(scanner.extract("[warning] disk at 91%", &mut out));
+        assert_eq!(out, vec!["WARN", "disk at 91%", ""]);
+        assert!(scanner.extract_bytes(b"[Err] timeout", &mut out).is_some());
+        assert_eq!(out[0], "ERROR");
+        assert_eq!(scanner.scan("[crit] down").unwrap()[0], "CRITICAL");
+        assert_eq!(
+            scanner.scan_with("[info] up", &["level"]).unwrap(),
+            ["INFO"]
+        );
+        // Without `normalize` the level is kept as written
+        assert_eq!(scanner.scan("Warning: low").unwrap()[2], "Warning");
+        assert!(!scanner.is_match("[verbose] x"));
+        assert!(Scanner::new("{{level:loglevel(upper)}}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_quoted_macro() {
+        let scanner = Scanner::new(
+            r#"^{{status:number}} {{referer:quoted}} {{agent:quoted}} {{took:number}}$"#
+                .to_string(),
+        )
+        .unwrap();
+        let line = r#"200 "-" "Mozilla/5.0 (X11) \"beta\"" 12"#;
+        assert_eq!(
+            scanner.scan(line).unwrap(),
+            ["200", "-", r#"Mozilla/5.0 (X11) "beta""#, "12"]
+        );
+        assert_eq!(
+            scanner.scan(r#"404 'a b' "" 3"#).unwrap(),
+            ["404", "a b", "", "3"]
+        );
+        assert!(!scanner.is_match(r#"200 "unterminated 12"#));
+    }
+
+    #[test]
+    fn test_bracketed_macro() {
+        let scanner = Scanner::new(
+            "^{{thread:bracketed}} {{logger:bracketed(<>)}} {{ctx:bracketed({})}} {{msg:any}}$"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            scanner.scan("[pool-1 [3]] <c.e.Api> {} started").unwrap(),
+            ["pool-1 [3]", "c.e.Api", "", "started"]
+        );
+        assert_eq!(
+            scanner.scan("[main] <App> {user=1} up").unwrap(),
+            ["main", "App", "user=1", "up"]
+        );
+        assert!(!scanner.is_match("main <App> {} up"));
+        let parens = Scanner::new("^{{pid:bracketed(())}}: {{msg:any}}$".to_string()).unwrap();
+        assert_eq!(parens.scan("(42): ok").unwrap(), ["42", "ok"]);
+        assert!(Scanner::new("{{x:bracketed(||)}}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_hex_macro() {
+        let scanner =
+            Scanner::new("^at {{addr:hex}} flags={{flags:hex(int)}}$".to_string()).unwrap();
+        assert_eq!(scanner.type_hints["addr"], FieldType::String);
+        assert_eq!(scanner.type_hints["flags"], FieldType::Int64);
+        assert_eq!(
+            scanner.scan("at 0xffffffff81000000 flags=0x1F").unwrap(),
+            ["0xffffffff81000000", "31"]
+        );
+        assert_eq!(
+            scanner.scan("at dead flags=beef").unwrap(),
+            ["dead", "48879"]
+        );
+        assert!(!scanner.is_match("at 0xzz flags=1"));
+        assert!(Scanner::new("{{x:hex(i32)}}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_pid_macro() {
+        let scanner =
+            Scanner::new("^{{proc:var_name}}{{pid:pid}}: tid {{tid:tid}}$".to_string()).unwrap();
+        assert_eq!(scanner.type_hints["pid"], FieldType::Int);
+        assert_eq!(scanner.type_hints["tid"], FieldType::Int);
+        assert_eq!(
+            scanner.scan("sshd[1234]: tid 17").unwrap(),
+            ["sshd", "1234", "17"]
+        );
+        assert_eq!(
+            scanner.scan("cron[8]: tid [9]").unwrap(),
+            ["cron", "8", "9"]
+        );
+        assert!(!scanner.is_match("sshd[12a]: tid 1"));
+    }
+
+    #[test]
+    fn test_size_macro() {
+        let scanner = Scanner::new("^sent {{bytes:size}} in".to_string()).unwrap();
+        assert_eq!(scanner.type_hints["bytes"], FieldType::Int64);
+        let bytes = |line: &str| scanner.scan(line).map(|values| values[0].clone());
+        assert_eq!(bytes("sent 532 in").as_deref(), Some("532"));
+        assert_eq!(bytes("sent 1.5GB in").as_deref(), Some("1500000000"));
+        assert_eq!(bytes("sent 128KiB in").as_deref(), Some("131072"));
+        assert_eq!(bytes("sent 4M in").as_deref(), Some("4194304"));
+        assert_eq!(bytes("sent lots in"), None);
+    }
+
+    #[test]
+    fn test_duration_macro() {
+        let scanner = Scanner::new("^took {{latency:duration}} ok$".to_string()).unwrap();
+        assert_eq!(scanner.type_hints["latency"], FieldType::Float);
+        let latency = |line: &str| scanner.scan(line).map(|values| values[0].clone());
+        assert_eq!(latency("took 250ms ok").as_deref(), Some("250"));
+        assert_eq!(latency("took 1.2s ok").as_deref(), Some("1200"));
+        assert_eq!(latency("took 3m12s ok").as_deref(), Some("192000"));
+        assert_eq!(latency("took 40µs ok").as_deref(), Some("0.04"));
+        assert_eq!(latency("took 250 ok"), None);
+        assert_eq!(latency("took 5 minutes ok"), None);
+
+        let scanner = Scanner::new("^took {{latency:duration(typed)}} ok$".to_string()).unwrap();
+        assert_eq!(scanner.type_hints["latency"], FieldType::Duration);
+        assert!(Scanner::new("{{latency:duration(s)}}".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_scanner_union() {
+        let nginx = Scanner::new(r"^{{ip:any}} {{status:number}} /{{path:any}}$".to_string())
+            .unwrap()
+            .with_prefilter("/");
+        let app =
+            Scanner::new(r"^\[{{level:var_name}}\] {{status:number}} {{msg:any}}$".to_string())
+                .unwrap()
+                .with_defaults(&HashMap::from([("level".to_string(), "INFO".to_string())]))
+                .unwrap();
+        let scanner = nginx.clone().union(app).unwrap();
+        assert_eq!(scanner.pattern_count(), 2);
+        assert_eq!(
+            scanner.field_names,
+            ["ip", "status", "path", "level", "msg"]
+        );
+        assert_eq!(scanner.type_hints["status"], FieldType::Int);
+        assert_eq!(scanner.prefilter(), None);
+        assert_eq!(scanner.default_value("level"), Some("INFO"));
+
+        let mut out = Vec::new();
+        assert_eq!(
+            scanner.extract_pattern("10.0.0.1 200 /index", &mut out),
+            Some(0)
+        );
+        assert_eq!(out, vec!["10.0.0.1", "200", "index", "INFO", ""]);
+        assert_eq!(
+            scanner.extract_pattern("[WARN] 503 slow", &mut out),
+            Some(1)
+        );
+        assert_eq!(out, vec!["", "503", "", "WARN", "slow"]);
+        assert_eq!(
+            scanner.scan("[WARN] 503 slow").unwrap(),
+            vec!["", "503", "", "WARN", "slow"]
+        );
+        let indices = scanner
+            .prepare_indices(&["msg", "status", "ip"], &[])
+            .unwrap();
+        let mut direct = Vec::new();
+        assert!(scanner.scan_direct("[WARN] 503 slow", &indices, &mut direct));
+        assert_eq!(direct, vec!["slow", "503", ""]);
+        assert!(scanner.scan_direct("10.0.0.1 200 /index", &indices, &mut direct));
+        assert_eq!(direct, vec!["", "200", "10.0.0.1"]);
+
+        let text = Scanner::new("^{{status:any}}$".to_string()).unwrap();
+        assert!(nginx.clone().union(text).is_err());
+        let ignore_case = Scanner::new("^{{msg:any}}$".to_string())
+            .unwrap()
+            .with_flags(&["i"])
+            .unwrap();
+        assert!(nginx.union(ignore_case).is_err());
+    }
+
+    #[test]
+    fn test_scanner_patterns_must_agree_on_types() {
+        let err = Scanner::with_patterns(
+            vec![
+                r"^{{v:number}}$".to_string(),
+                r"^{{v:float}} s$".to_string(),
+            ],
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("'v'"), "{}", err);
+        assert!(Scanner::with_patterns(Vec::new(), None).is_err());
+    }
+
+    #[test]
+    fn test_scanner_prefilter() {
+        let scanner = Scanner::new(r"^(?P<id>\d+) (?P<msg>.*)$".to_string())
+            .unwrap()
+            .with_prefilter("jk2_init()");
+        assert_eq!(scanner.prefilter(), Some("jk2_init()"));
+
+        let hit = "1 jk2_init() Found child";
+        // Matches the regex but lacks the literal, so the prefilter rejects it
+        let miss = "2 workerEnv.init() ok";
+        assert!(scanner.is_match(hit) && !scanner.is_match(miss));
+        assert_eq!(
+            scanner.scan(hit).unwrap(),
+            vec!["1", "jk2_init() Found child"]
+        );
+        assert!(scanner.scan(miss).is_none());
+
+        let indices = scanner.prepare_indices(&["id"], &[]).unwrap();
+        let mut direct = Vec::new();
+        assert!(scanner.scan_direct(hit, &indices, &mut direct));
+        assert_eq!(direct, vec!["1"]);
+        assert!(!scanner.scan_direct(miss, &indices, &mut direct));
+        let mut out = Vec::new();
+        assert!(!scanner.extract(miss, &mut out));
+
+        assert_eq!(scanner.with_prefilter("").prefilter(), None);
+    }
+
+    #[cfg(feature = "dfa")]
+    #[test]
+    fn test_scanner_lazy_dfa_engine() {
+        let patterns = vec![
+            r"^{{ts:number}} {{level:var_name}} {{msg:any}}$".to_string(),
+            r"^(?P<msg>-- .*)$".to_string(),
+        ];
+        let regex = Scanner::with_patterns(patterns.clone(), None).unwrap();
+        let dfa = Scanner::with_patterns(patterns, None)
+            .unwrap()
+            .with_engine(Engine::LazyDfa)
+            .unwrap();
+        assert_eq!(regex.engine(), Engine::Regex);
+        assert_eq!(dfa.engine(), Engine::LazyDfa);
+
+        let indices = dfa.prepare_indices(&["msg", "level"], &[]).unwrap();
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        let (mut direct_a, mut direct_b) = (Vec::new(), Vec::new());
+        for line in ["2 INFO started", "-- marker", "garbage", ""] {
+            assert_eq!(dfa.is_match(line), regex.is_match(line));
+            assert_eq!(
+                dfa.extract_pattern(line, &mut a),
+                regex.extract_pattern(line, &mut b)
+            );
+            assert_eq!(a, b);
+            assert_eq!(
+                dfa.scan_direct(line, &indices, &mut direct_a),
+                regex.scan_direct(line, &indices, &mut direct_b)
+            );
+            assert_eq!(direct_a, direct_b);
+        }
+
+        // Switching back drops the DFA, along with any locations cached for it
+        let back = dfa.with_engine(Engine::Regex).unwrap();
+        assert_eq!(back.engine(), Engine::Regex);
+        assert!(back.extract("-- again", &mut a));
+        assert_eq!(a, vec!["", "", "-- again"]);
+    }
+
+    #[cfg(feature = "hyperscan")]
+    #[test]
+    fn test_scanner_hyperscan_engine() {
+        if !crate::hyperscan::is_available() {
+            eprintln!("libhs not found, skipping Hyperscan test");
+            return;
+        }
+        let patterns = vec![
+            r"^{{ts:number}} ERROR {{msg:any}}$".to_string(),
+            r"^{{ts:number}} {{level:var_name}} {{msg:any}}$".to_string(),
+            r"^(?P<msg>-- .*)$".to_string(),
+        ];
+        let regex = Scanner::with_patterns(patterns.clone(), None).unwrap();
+        let hyperscan = Scanner::with_patterns(patterns, None)
+            .unwrap()
+            .with_engine(Engine::Hyperscan)
+            .unwrap();
+        assert_eq!(hyperscan.engine(), Engine::Hyperscan);
+
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        for line in [
+            "1 ERROR disk full",
+            "2 INFO started",
+            "-- marker",
+            "garbage",
+            "é",
+        ] {
+            assert_eq!(hyperscan.is_match(line), regex.is_match(line));
+            assert_eq!(
+                hyperscan.extract_pattern(line, &mut a),
+                regex.extract_pattern(line, &mut b)
+            );
+            assert_eq!(a, b);
+        }
+        assert_eq!(
+            hyperscan.with_engine(Engine::Regex).unwrap().engine(),
+            Engine::Regex
+        );
+    }
+
+    #[cfg(feature = "pcre2")]
+    #[test]
+    fn test_scanner_pcre2_engine() {
+        if !crate::pcre2::is_available() {
+            eprintln!("libpcre2-8 not found, skipping PCRE2 test");
+            return;
+        }
+        // A backreference, a negative lookahead and a lookbehind, which `regex` rejects
+        let patterns = vec![
+            r"^{{id:number}} {{word:string}} (?P=word)(?! ignored){{rest:any}}$".to_string(),
+            r"^-- (?P<rest>.*)(?<!\.)$".to_string(),
+        ];
+        assert!(Scanner::with_patterns(patterns.clone(), None).is_err());
+        let scanner = Scanner::with_patterns_and_engine(patterns, None, Engine::Pcre2).unwrap();
+        assert_eq!(scanner.engine(), Engine::Pcre2);
+        assert_eq!(scanner.field_names, vec!["id", "word", "rest"]);
+        assert!(scanner.type_hints.contains_key("id"));
+
+        let mut out = Vec::new();
+        assert_eq!(
+            scanner.extract_pattern("1 echo echo loud", &mut out),
+            Some(0)
+        );
+        assert_eq!(out, vec!["1", "echo", " loud"]);
+        assert_eq!(scanner.extract_pattern("2 été été!", &mut out), Some(0));
+        assert_eq!(out, vec!["2", "été", "!"]);
+        assert_eq!(scanner.extract_pattern("-- note", &mut out), Some(1));
+        assert_eq!(out, vec!["", "", "note"]);
+        for line in ["3 echo ping", "4 echo echo ignored", "-- note."] {
+            assert!(!scanner.is_match(line), "{}", line);
+            assert_eq!(scanner.extract_pattern(line, &mut out), None);
+        }
+        assert!(scanner.is_match("1 echo echo loud"));
+        assert_eq!(
+            scanner.scan_with("-- note", &["rest", "id"]).unwrap(),
+            vec!["note", ""]
+        );
+        let indices = scanner.prepare_indices(&["rest", "word"], &[]).unwrap();
+        let mut direct = Vec::new();
+        assert!(scanner.scan_direct("1 echo echo loud", &indices, &mut direct));
+        assert_eq!(direct, vec![" loud", "echo"]);
+
+        assert!(scanner.with_engine(Engine::Regex).is_err());
+        assert!(Scanner::with_patterns_and_engine(vec!["(".into()], None, Engine::Pcre2).is_err());
+    }
+
+    #[cfg(not(feature = "pcre2"))]
+    #[test]
+    fn test_scanner_pcre2_requires_feature() {
+        let err = Scanner::with_patterns_and_engine(vec![r"^(?=a)".into()], None, Engine::Pcre2)
+            .unwrap_err();
+        assert!(err.to_string().contains("`pcre2` feature"), "{}", err);
+    }
+
+    #[cfg(not(feature = "hyperscan"))]
+    #[test]
+    fn test_scanner_hyperscan_requires_feature() {
+        let scanner = Scanner::new(r"^(?P<id>\d+)$".to_string()).unwrap();
+        assert!(scanner.with_engine(Engine::Hyperscan).is_err());
+    }
+
+    #[cfg(not(feature = "dfa"))]
+    #[test]
+    fn test_scanner_lazy_dfa_requires_feature() {
+        let scanner = Scanner::new(r"^(?P<id>\d+)$".to_string()).unwrap();
+        assert!(scanner.with_engine(Engine::LazyDfa).is_err());
+    }
+
+    #[test]
+    fn test_scanner_flags() {
+        let scanner = Scanner::new(r"^(?P<level>error) (?P<msg>.+)$".to_string()).unwrap();
+        assert!(scanner.scan("ERROR disk\nfull").is_none());
+
+        let scanner = scanner.with_flags(&["i", "s"]).unwrap();
+        assert_eq!(scanner.flags(), ["i", "s"]);
+        assert_eq!(
+            scanner.scan("ERROR disk\nfull").unwrap(),
+            vec!["ERROR", "disk\nfull"]
+        );
+
+        // Clearing a flag set by default: without Unicode, \w is ASCII only
+        let scanner = Scanner::new(r"^(?P<word>\w+)$".to_string()).unwrap();
+        assert!(scanner.scan("été").is_some());
+        assert!(scanner.with_flags(&["-u"]).unwrap().scan("été").is_none());
+
+        let scanner = Scanner::new(r"^(?P<id>\d+)$".to_string()).unwrap();
+        let err = scanner.clone().with_flags(&["q"]).unwrap_err();
+        assert!(
+            err.to_string().contains("Unknown regex flag 'q'"),
+            "{}",
+            err
+        );
+        assert!(scanner.clone().with_flags(&["is"]).is_err());
+        let err = scanner.with_flags(&["i", "-i"]).unwrap_err();
+        assert!(err.to_string().contains("both set and cleared"), "{}", err);
+    }
+
+    #[test]
+    fn test_flag_prefix() {
+        let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        assert_eq!(flag_prefix(&[], Engine::Regex).unwrap(), "");
+        assert_eq!(
+            flag_prefix(&flags(&["i", "x", "i"]), Engine::Regex).unwrap(),
+            "(?ix)"
+        );
+        assert_eq!(
+            flag_prefix(&flags(&["-u", "U"]), Engine::LazyDfa).unwrap(),
+            "(?U-u)"
+        );
+        assert!(flag_prefix(&flags(&["-u"]), Engine::Pcre2).is_err());
+        assert!(flag_prefix(&flags(&["m"]), Engine::Pcre2).is_ok());
+        let err = flag_prefix(&flags(&["U"]), Engine::Hyperscan).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("not supported by the hyperscan engine"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_scanner_scan_direct_bytes() {
+        let scanner = Scanner::with_patterns(
+            vec![r"(?P<id>\d+) (?P<msg>\w+)$".into(), r"^(?P<id>\d+)$".into()],
+            None,
+        )
+        .unwrap();
+        let indices = scanner.prepare_indices(&["msg", "id"], &[]).unwrap();
+        let mut out = Vec::new();
+        // Invalid bytes outside the match do not stop it
+        assert!(scanner.scan_direct_bytes(b"\xff\xfe 7 caf\xc3\xa9", &indices, &mut out));
+        assert_eq!(out, vec!["café".as_bytes(), b"7"]);
+        assert!(scanner.scan_direct_bytes(b"8", &indices, &mut out));
+        assert_eq!(out, vec![&b""[..], b"8"]);
+        // but do not match Unicode classes
+        assert!(!scanner.scan_direct_bytes(b"9 caf\xe9", &indices, &mut out));
+
+        let mut values = Vec::new();
+        assert_eq!(scanner.extract_bytes(b"\xff 7 ok", &mut values), Some(0));
+        assert_eq!(values, vec!["7", "ok"]);
+
+        // A prefilter applies to the raw bytes too
+        let scanner = scanner.with_prefilter("ok");
+        assert!(!scanner.scan_direct_bytes(b"8", &indices, &mut Vec::new()));
+    }
+
+    #[test]
+    fn test_scanner_defaults() {
+        let scanner = Scanner::with_patterns(
+            vec![
+                r"^(?P<id>\d+)(?: (?P<level>[A-Z]+))? (?P<msg>\w+)$".into(),
+                r"^(?P<msg>\w+)$".into(),
+            ],
+            None,
+        )
+        .unwrap()
+        .with_defaults(&HashMap::from([
+            ("level".to_string(), "INFO".to_string()),
+            ("id".to_string(), "0".to_string()),
+        ]))
+        .unwrap();
+        assert_eq!(scanner.default_value("level"), Some("INFO"));
+        assert_eq!(scanner.default_value("msg"), None);
+
+        assert_eq!(
+            scanner.scan("1 WARN disk").unwrap(),
+            vec!["1", "WARN", "disk"]
+        );
+        assert_eq!(scanner.scan("1 disk").unwrap(), vec!["1", "INFO", "disk"]);
+        assert_eq!(scanner.scan("disk").unwrap(), vec!["0", "INFO", "disk"]);
+        assert_eq!(
+            scanner.scan_with("disk", &["msg", "level"]).unwrap(),
+            vec!["disk", "INFO"]
+        );
+
+        let indices = scanner.prepare_indices(&["level", "id"], &[]).unwrap();
+        let mut direct = Vec::new();
+        assert!(scanner.scan_direct("disk", &indices, &mut direct));
+        assert_eq!(direct, vec!["INFO", "0"]);
+        let mut bytes = Vec::new();
+        assert!(scanner.scan_direct_bytes(b"2 disk", &indices, &mut bytes));
+        assert_eq!(bytes, vec![&b"INFO"[..], b"2"]);
+
+        let mut values = Vec::new();
+        assert_eq!(scanner.extract_pattern("disk", &mut values), Some(1));
+        assert_eq!(values, vec!["0", "INFO", "disk"]);
+
+        let unknown = HashMap::from([("nope".to_string(), String::new())]);
+        assert!(scanner.with_defaults(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_scanner_inferred_types() {
+        let scanner = Scanner::new(
+            r"^(?P<time>\S+ \S+) (?P<status>\d+) (?P<bytes>\d+) (?P<took>\S+) (?P<host>\S+)$"
+                .to_string(),
+        )
+        .unwrap()
+        .with_inferred_types([
+            "2024-01-02 03:04:05 200 4294967296 0.5 web1",
+            "not a record",
+            "2024-01-02 03:04:06.250 404 12 1e3 10.0.0.1",
+        ]);
+        let hints = &scanner.type_hints;
+        assert_eq!(
+            hints["time"],
+            FieldType::DateTime(crate::types::DateTime::new(Some(vec![
+                "%Y-%m-%d %H:%M:%S%.f".to_string()
+            ])))
+        );
+        assert_eq!(hints["status"], FieldType::Int);
+        assert_eq!(hints["bytes"], FieldType::Int64);
+        assert_eq!(hints["took"], FieldType::Float);
+        assert!(!hints.contains_key("host"));
+
+        // Macro fields keep their type
+        let scanner = Scanner::new("^{{id:any}}$".to_string())
+            .unwrap()
+            .with_inferred_types(["1", "2"]);
+        assert_eq!(scanner.type_hints["id"], FieldType::String);
+
+        assert_eq!(FieldType::infer(["", "inf"]), None);
+        assert_eq!(FieldType::infer(["", ""]), None);
+        assert_eq!(FieldType::infer(["-3", ""]), Some(FieldType::Int));
+    }
+
+    #[test]
+    fn test_scanner_crlf() {
+        let scanner = Scanner::new(r"^(?P<id>\d+) (?P<msg>.*)$".to_string()).unwrap();
+        assert!(scanner.is_match("1 up\r"));
+        assert_eq!(
+            scanner.scan("1 up\r"),
+            Some(vec!["1".to_string(), "up".to_string()])
+        );
+        let mut out = Vec::new();
+        assert!(scanner.scan_direct_bytes(
+            b"2 down\r",
+            &scanner.prepare_indices(&["msg"], &[]).unwrap(),
+            &mut out
+        ));
+        assert_eq!(out, vec![b"down".as_slice()]);
+        // Only one trailing `\r` is dropped
+        assert_eq!(
+            scanner.scan("3 x\r\r"),
+            Some(vec!["3".to_string(), "x\r".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_scanner_exclude_pattern() {
+        let scanner = Scanner::new(r"^(?P<method>\w+) (?P<path>\S+)$".to_string())
+            .unwrap()
+            .with_exclude_pattern("{{path:enum(/health,/ready)}}$", None)
+            .unwrap();
+        assert_eq!(
+            scanner.exclude_pattern(),
+            Some(r"(?P<path>(?:/health|/ready))$")
+        );
+        assert!(scanner.is_match("GET /users"));
+        assert!(!scanner.is_match("GET /health"));
+        assert!(scanner.is_excluded("GET /ready"));
+        assert!(!scanner.is_excluded("GET /users"));
+        assert_eq!(scanner.scan("GET /ready"), None);
+        let indices = scanner.prepare_indices(&["path"], &[]).unwrap();
+        let mut out = Vec::new();
+        assert!(!scanner.scan_direct_bytes(b"HEAD /health", &indices, &mut out));
+        assert!(scanner.scan_direct_bytes(b"HEAD /", &indices, &mut out));
+
+        let validation = scanner.validate(["GET /", "GET /health", "nope"]);
+        assert!(validation.lines[1].excluded);
+        assert_eq!(validation.lines[1].to_string(), "excluded");
+        assert_eq!(validation.unmatched().count(), 1);
+        assert_eq!(validation.match_rate(), 0.5);
+
+        assert!(
+            Scanner::new("x".to_string())
+                .unwrap()
+                .with_exclude_pattern("(", None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_scanner_exclude_pattern_flags() {
+        let scanner = Scanner::new(r"^(?P<method>\w+) (?P<path>\S+)$".to_string()).unwrap();
+        // Flags set before or after the exclude pattern both apply to it
+        let before = scanner
+            .clone()
+            .with_flags(&["i"])
+            .unwrap()
+            .with_exclude_pattern("/health$", None)
+            .unwrap();
+        let after = scanner
+            .with_exclude_pattern("/health$", None)
+            .unwrap()
+            .with_flags(&["i"])
+            .unwrap();
+        for scanner in [&before, &after] {
+            assert_eq!(scanner.exclude_pattern(), Some("/health$"));
+            assert!(scanner.is_excluded("GET /HEALTH"));
+            assert!(!scanner.is_match("get /Health"));
+            let indices = scanner.prepare_indices(&["path"], &[]).unwrap();
+            let mut out = Vec::new();
+            assert!(!scanner.scan_direct_bytes(b"GET /Health", &indices, &mut out));
+            assert!(scanner.is_match("GET /users"));
+        }
+        let cleared = after.with_flags::<&str>(&[]).unwrap();
+        assert!(!cleared.is_excluded("GET /HEALTH"));
+        assert!(cleared.is_excluded("GET /health"));
+    }
+
+    #[test]
+    fn test_fully_anchored_patterns() {
+        assert!(is_fully_anchored(r"^(?P<a>\d+) (?P<b>.+?)$"));
+        assert!(is_fully_anchored(r"(?i)^a$"));
+        assert!(!is_fully_anchored(r"^a"));
+        assert!(!is_fully_anchored(r"a$"));
+        assert!(!is_fully_anchored(r"^a|b$"));
+        assert!(!is_fully_anchored(r"(?m)^a$"));
+        assert!(!is_fully_anchored(r"(?<=a)b"));
+
+        let scanner = Scanner::new("^{{id:number}} {{msg:any}}$".to_string()).unwrap();
+        assert!(scanner.patterns[0].anchored);
+        // Multi-line `^` and `$` also match at line breaks, so anchoring is rechecked
+        let scanner = scanner.with_flags(&["m"]).unwrap();
+        assert!(!scanner.patterns[0].anchored);
+        assert_eq!(
+            scanner.scan("7 up"),
+            Some(vec!["7".to_string(), "up".to_string()])
+        );
+        #[cfg(feature = "dfa")]
+        {
+            let scanner = Scanner::new("^{{id:number}} {{msg:any}}$".to_string())
+                .unwrap()
+                .with_engine(Engine::LazyDfa)
+                .unwrap();
+            assert!(scanner.patterns[0].anchored);
+            assert!(scanner.is_match("7 up"));
+            assert!(!scanner.is_match("x 7 up"));
+            assert_eq!(scanner.scan("7 up").unwrap()[1], "up");
+        }
+    }
+}
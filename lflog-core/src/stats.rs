@@ -0,0 +1,89 @@
+//! Match-rate counters accumulated while scanning.
+//!
+//! A [`Scanner`] keeps a [`ScanStats`] shared by its clones. Table scans and
+//! [`Scanner::scan_iter`] add to it, so a program embedding lflog can read
+//! [`Scanner::stats`] after a query and alert when a deployment changes a log format
+//! and the match rate drops.
+//!
+//! [`Scanner`]: crate::scanner::Scanner
+//! [`Scanner::scan_iter`]: crate::scanner::Scanner::scan_iter
+//! [`Scanner::stats`]: crate::scanner::Scanner::stats
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Line and byte counts of one or more scans.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScanCounts {
+    /// Lines that became records.
+    pub matched: u64,
+    /// Non-empty lines no pattern matched.
+    pub unmatched: u64,
+    /// Non-empty lines dropped without counting towards the match rate, such as
+    /// excluded noise or over-long lines.
+    pub skipped: u64,
+    /// Bytes read.
+    pub bytes: u64,
+}
+
+impl ScanCounts {
+    /// Fraction of matched lines among matched and unmatched ones, or `None` before
+    /// any were scanned.
+    pub fn match_rate(&self) -> Option<f64> {
+        let total = self.matched + self.unmatched;
+        (total > 0).then(|| self.matched as f64 / total as f64)
+    }
+}
+
+impl std::ops::Sub for ScanCounts {
+    type Output = Self;
+
+    /// Counts since an earlier snapshot.
+    fn sub(self, earlier: Self) -> Self {
+        Self {
+            matched: self.matched.saturating_sub(earlier.matched),
+            unmatched: self.unmatched.saturating_sub(earlier.unmatched),
+            skipped: self.skipped.saturating_sub(earlier.skipped),
+            bytes: self.bytes.saturating_sub(earlier.bytes),
+        }
+    }
+}
+
+/// Counters added to by concurrent scans.
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    matched: AtomicU64,
+    unmatched: AtomicU64,
+    skipped: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl ScanStats {
+    /// Add the counts of a scan.
+    pub fn add(&self, counts: ScanCounts) {
+        self.matched.fetch_add(counts.matched, Ordering::Relaxed);
+        self.unmatched
+            .fetch_add(counts.unmatched, Ordering::Relaxed);
+        self.skipped.fetch_add(counts.skipped, Ordering::Relaxed);
+        self.bytes.fetch_add(counts.bytes, Ordering::Relaxed);
+    }
+
+    /// The counts so far.
+    pub fn counts(&self) -> ScanCounts {
+        ScanCounts {
+            matched: self.matched.load(Ordering::Relaxed),
+            unmatched: self.unmatched.load(Ordering::Relaxed),
+            skipped: self.skipped.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Return the counts so far and start again from zero.
+    pub fn reset(&self) -> ScanCounts {
+        ScanCounts {
+            matched: self.matched.swap(0, Ordering::Relaxed),
+            unmatched: self.unmatched.swap(0, Ordering::Relaxed),
+            skipped: self.skipped.swap(0, Ordering::Relaxed),
+            bytes: self.bytes.swap(0, Ordering::Relaxed),
+        }
+    }
+}
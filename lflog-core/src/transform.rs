@@ -0,0 +1,466 @@
+//! Field transforms applied to extracted values before they become columns.
+//!
+//! A profile lists transforms per field, e.g. `transforms = { level = ["lowercase"] }`;
+//! [`TransformExtractor`] runs them on every record its inner extractor produces, so
+//! values are normalized once at ingest instead of in every query.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::extractor::RecordExtractor;
+use crate::macros::parser::parse_macro_invocation;
+use crate::stats::ScanStats;
+use crate::types::FieldType;
+
+/// One step of a field's transform pipeline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transform {
+    /// `lowercase`: lowercase the value.
+    Lowercase,
+    /// `trim`: remove leading and trailing whitespace.
+    Trim,
+    /// `strip_quotes`: remove one pair of matching `"` or `'` around the value.
+    StripQuotes,
+    /// `url_decode`: decode `%XX` escapes; invalid UTF-8 in the result is replaced.
+    UrlDecode,
+    /// `replace(from, to)`: replace every `from` with `to`, which defaults to nothing.
+    Replace(String, String),
+    /// `unquote`: remove one pair of matching `"` or `'` around the value and undo
+    /// backslash escapes inside it, so `"say \"hi\""` becomes `say "hi"`.
+    Unquote,
+    /// `unwrap(open, close)`: remove `open` from the start and `close` from the end
+    /// of the value when it has both, e.g. the brackets of `[main]`.
+    Unwrap(String, String),
+    /// `from_hex`: write a hex number, with or without `0x`, in decimal. Values over
+    /// 64 bits are left as they are; those over `i64::MAX` wrap around to negative
+    /// numbers, keeping their bits, as a C cast would.
+    FromHex,
+    /// `size`: write a byte size like `1.5GB` or `128KiB` as a number of bytes. `KB`
+    /// and the like are powers of 1000, `KiB` and bare `K` powers of 1024; units are
+    /// case-insensitive. Other values are left as they are.
+    Size,
+    /// `duration`: write a duration like `250ms`, `1.2s` or `3m12s` as a number of
+    /// milliseconds. Units are `ns`, `us` (or `µs`), `ms`, `s`, `m`, `h` and `d`.
+    /// Other values are left as they are.
+    Duration,
+    /// `loglevel`: spell a log level the canonical way, e.g. `warning` as `WARN`;
+    /// other values are left as they are.
+    LogLevel,
+}
+
+/// Spellings of log levels, lowercase, with their canonical form.
+pub const LOG_LEVELS: &[(&str, &str)] = &[
+    ("trace", "TRACE"),
+    ("debug", "DEBUG"),
+    ("info", "INFO"),
+    ("notice", "NOTICE"),
+    ("warning", "WARN"),
+    ("warn", "WARN"),
+    ("error", "ERROR"),
+    ("err", "ERROR"),
+    ("fatal", "FATAL"),
+    ("critical", "CRITICAL"),
+    ("crit", "CRITICAL"),
+];
+
+impl std::str::FromStr for Transform {
+    type Err = Error;
+
+    /// Parse a transform like `trim` or `replace("-", "_")`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invocation = parse_macro_invocation(s)?;
+        let mut args = invocation.args.into_iter();
+        let transform = match (invocation.field, invocation.name.as_str()) {
+            (None, "lowercase") => Transform::Lowercase,
+            (None, "trim") => Transform::Trim,
+            (None, "strip_quotes") => Transform::StripQuotes,
+            (None, "url_decode") => Transform::UrlDecode,
+            (None, "unquote") => Transform::Unquote,
+            (None, "loglevel") => Transform::LogLevel,
+            (None, "from_hex") => Transform::FromHex,
+            (None, "size") => Transform::Size,
+            (None, "duration") => Transform::Duration,
+            (None, "unwrap") => match (args.next(), args.next()) {
+                (Some(open), Some(close)) if !open.is_empty() && !close.is_empty() => {
+                    Transform::Unwrap(open, close)
+                }
+                _ => {
+                    return Err(Error::Config(format!(
+                        "Transform '{}' needs an opening and a closing delimiter",
+                        s
+                    )));
+                }
+            },
+            (None, "replace") => {
+                let from = args.next().filter(|from| !from.is_empty()).ok_or_else(|| {
+                    Error::Config(format!("Transform '{}' needs a string to replace", s))
+                })?;
+                Transform::Replace(from, args.next().unwrap_or_default())
+            }
+            _ => {
+                return Err(Error::Config(format!(
+                    "Unknown transform '{}', expected lowercase, trim, strip_quotes, unquote, unwrap(open, close), url_decode, loglevel, from_hex, size, duration or replace(from, to)",
+                    s
+                )));
+            }
+        };
+        if args.next().is_some() {
+            return Err(Error::Config(format!(
+                "Too many arguments in transform '{}'",
+                s
+            )));
+        }
+        Ok(transform)
+    }
+}
+
+impl Transform {
+    /// Apply the transform, borrowing from `value` when nothing changes.
+    pub fn apply<'a>(&self, value: Cow<'a, str>) -> Cow<'a, str> {
+        match self {
+            Transform::Lowercase if value.chars().any(char::is_uppercase) => {
+                Cow::Owned(value.to_lowercase())
+            }
+            Transform::Trim => map_slice(value, str::trim),
+            Transform::StripQuotes => map_slice(value, |value| {
+                ['"', '\'']
+                    .into_iter()
+                    .find_map(|quote| value.strip_prefix(quote)?.strip_suffix(quote))
+                    .unwrap_or(value)
+            }),
+            Transform::Unquote => {
+                let len = value.len();
+                let value = Transform::StripQuotes.apply(value);
+                match value.len() < len && value.contains('\\') {
+                    true => Cow::Owned(unescape(&value)),
+                    false => value,
+                }
+            }
+            Transform::Unwrap(open, close) => map_slice(value, |value| {
+                value
+                    .strip_prefix(open.as_str())
+                    .and_then(|inner| inner.strip_suffix(close.as_str()))
+                    .unwrap_or(value)
+            }),
+            Transform::FromHex => {
+                let digits = value
+                    .strip_prefix("0x")
+                    .or_else(|| value.strip_prefix("0X"))
+                    .unwrap_or(&value);
+                match u64::from_str_radix(digits, 16) {
+                    Ok(number) => Cow::Owned((number as i64).to_string()),
+                    Err(_) => value,
+                }
+            }
+            Transform::Size => match parse_size(&value) {
+                Some(bytes) if bytes.to_string() != *value => Cow::Owned(bytes.to_string()),
+                _ => value,
+            },
+            Transform::Duration => match parse_duration_ms(&value) {
+                Some(ms) => Cow::Owned(ms.to_string()),
+                None => value,
+            },
+            Transform::UrlDecode if value.contains('%') => Cow::Owned(url_decode(&value)),
+            Transform::Replace(from, to) if value.contains(from.as_str()) => {
+                Cow::Owned(value.replace(from.as_str(), to))
+            }
+            Transform::LogLevel => LOG_LEVELS
+                .iter()
+                .find(|(spelling, _)| value.eq_ignore_ascii_case(spelling))
+                .map_or(value, |(_, canonical)| Cow::Borrowed(canonical)),
+            _ => value,
+        }
+    }
+}
+
+/// Narrow `value` to a subslice, staying borrowed if it was.
+fn map_slice<'a>(value: Cow<'a, str>, f: impl for<'s> Fn(&'s str) -> &'s str) -> Cow<'a, str> {
+    match value {
+        Cow::Borrowed(value) => Cow::Borrowed(f(value)),
+        Cow::Owned(value) => Cow::Owned(f(&value).to_string()),
+    }
+}
+
+/// Bytes in a size like `532`, `1.5GB` or `128KiB`, rounded to the nearest byte.
+fn parse_size(value: &str) -> Option<i64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let unit = unit.to_ascii_lowercase();
+    let (prefix, base) = if let Some(prefix) = unit.strip_suffix("ib") {
+        (prefix, 1024f64)
+    } else if let Some(prefix) = unit.strip_suffix('b') {
+        (prefix, 1000f64)
+    } else {
+        // `K`, `M` and the like without a `B` are binary, as in `ls -h`
+        (unit.strip_suffix('i').unwrap_or(&unit), 1024f64)
+    };
+    let exponent = match prefix {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        "e" => 6,
+        _ => return None,
+    };
+    let bytes = (number * base.powi(exponent)).round();
+    (bytes <= i64::MAX as f64).then_some(bytes as i64)
+}
+
+/// Milliseconds in a duration made of one or more `<number><unit>` parts.
+pub(crate) fn parse_duration_ms(value: &str) -> Option<f64> {
+    let mut rest = value;
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let split = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let number: f64 = rest[..split].parse().ok()?;
+        rest = &rest[split..];
+        let units = [
+            ("ns", 1e-6),
+            ("us", 1e-3),
+            ("µs", 1e-3),
+            ("ms", 1.0),
+            ("s", 1e3),
+            ("m", 60e3),
+            ("h", 3600e3),
+            ("d", 86400e3),
+        ];
+        let (unit, scale) = units.iter().find(|(unit, _)| rest.starts_with(unit))?;
+        total += number * scale;
+        rest = &rest[unit.len()..];
+    }
+    (!value.is_empty()).then_some(total)
+}
+
+/// Replace each backslash escape with the character it escapes.
+pub(crate) fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode `%XX` escapes, leaving malformed ones as they are.
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse the transform pipelines of a profile, by field name.
+pub fn parse_transforms(
+    transforms: &HashMap<String, Vec<String>>,
+) -> Result<HashMap<String, Vec<Transform>>> {
+    transforms
+        .iter()
+        .map(|(field, steps)| {
+            let steps = steps
+                .iter()
+                .map(|step| step.parse())
+                .collect::<Result<Vec<_>>>()?;
+            Ok((field.clone(), steps))
+        })
+        .collect()
+}
+
+/// Runs per-field transform pipelines on the records of another extractor.
+#[derive(Debug, Clone)]
+pub struct TransformExtractor {
+    inner: Arc<dyn RecordExtractor>,
+    /// Pipelines by index in the inner extractor's fields.
+    pipelines: Vec<(usize, Vec<Transform>)>,
+}
+
+impl TransformExtractor {
+    /// Wrap `inner`, transforming the fields named in `transforms`.
+    ///
+    /// Fails if a transformed field is not one of `inner`'s fields.
+    pub fn new(
+        inner: Arc<dyn RecordExtractor>,
+        transforms: HashMap<String, Vec<Transform>>,
+    ) -> Result<Self> {
+        let mut pipelines = transforms
+            .into_iter()
+            .filter(|(_, steps)| !steps.is_empty())
+            .map(|(field, steps)| {
+                let index = inner
+                    .field_names()
+                    .iter()
+                    .position(|name| *name == field)
+                    .ok_or_else(|| {
+                        Error::Config(format!("Cannot transform unknown field '{}'", field))
+                    })?;
+                Ok((index, steps))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        pipelines.sort_by_key(|(index, _)| *index);
+        Ok(Self { inner, pipelines })
+    }
+
+    fn transform(&self, out: &mut [Cow<'_, str>]) {
+        for (index, steps) in &self.pipelines {
+            if let Some(value) = out.get_mut(*index) {
+                let mut current = std::mem::take(value);
+                for step in steps {
+                    current = step.apply(current);
+                }
+                *value = current;
+            }
+        }
+    }
+}
+
+impl RecordExtractor for TransformExtractor {
+    fn field_names(&self) -> &[String] {
+        self.inner.field_names()
+    }
+
+    fn type_hints(&self) -> &HashMap<String, FieldType> {
+        self.inner.type_hints()
+    }
+
+    fn extract<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> bool {
+        self.extract_pattern(line, out).is_some()
+    }
+
+    fn extract_pattern<'a>(&self, line: &'a str, out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        let pattern = self.inner.extract_pattern(line, out)?;
+        self.transform(out);
+        Some(pattern)
+    }
+
+    fn extract_bytes<'a>(&self, line: &'a [u8], out: &mut Vec<Cow<'a, str>>) -> Option<usize> {
+        let pattern = self.inner.extract_bytes(line, out)?;
+        self.transform(out);
+        Some(pattern)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.inner.is_match(line)
+    }
+
+    fn is_excluded(&self, line: &str) -> bool {
+        self.inner.is_excluded(line)
+    }
+
+    fn scan_stats(&self) -> Option<&ScanStats> {
+        self.inner.scan_stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn apply(transform: &str, value: &str) -> String {
+        transform
+            .parse::<Transform>()
+            .unwrap()
+            .apply(Cow::Borrowed(value))
+            .into_owned()
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(apply("lowercase", "WARN"), "warn");
+        assert_eq!(apply("loglevel", "Warning"), "WARN");
+        assert_eq!(apply("unquote", r#""say \"hi\" \\o/""#), r#"say "hi" \o/"#);
+        assert_eq!(apply("unquote", r"'it\'s'"), "it's");
+        assert_eq!(apply("unquote", r"a\b"), r"a\b");
+        assert_eq!(apply("unwrap(<, >)", "<main>"), "main");
+        assert_eq!(apply("unwrap(<, >)", "<main"), "<main");
+        assert_eq!(apply("from_hex", "0x1F"), "31");
+        assert_eq!(apply("from_hex", "ff"), "255");
+        assert_eq!(apply("from_hex", "0xffffffffffffffff"), "-1");
+        assert_eq!(apply("from_hex", "0xg"), "0xg");
+        assert_eq!(apply("size", "532"), "532");
+        assert_eq!(apply("size", "1.5GB"), "1500000000");
+        assert_eq!(apply("size", "128KiB"), "131072");
+        assert_eq!(apply("size", "2k"), "2048");
+        assert_eq!(apply("size", "10b"), "10");
+        assert_eq!(apply("size", "3XB"), "3XB");
+        assert_eq!(apply("duration", "250ms"), "250");
+        assert_eq!(apply("duration", "1.2s"), "1200");
+        assert_eq!(apply("duration", "3m12s"), "192000");
+        assert_eq!(apply("duration", "1h0m0.5s"), "3600500");
+        assert_eq!(apply("duration", "750us"), "0.75");
+        assert_eq!(apply("duration", "12"), "12");
+        assert_eq!(apply("duration", "5 s"), "5 s");
+        assert_eq!(apply("loglevel", "err"), "ERROR");
+        assert_eq!(apply("loglevel", "verbose"), "verbose");
+        assert_eq!(apply("trim", "  x \t"), "x");
+        assert_eq!(apply("strip_quotes", "\"GET /\""), "GET /");
+        assert_eq!(apply("strip_quotes", "'a'"), "a");
+        assert_eq!(apply("strip_quotes", "\"a'"), "\"a'");
+        assert_eq!(apply("url_decode", "/a%20b%2Fc%zz%"), "/a b/c%zz%");
+        assert_eq!(apply("url_decode", "caf%C3%A9"), "café");
+        assert_eq!(apply(r#"replace("-", "_")"#, "a-b-c"), "a_b_c");
+        assert_eq!(apply("replace(ms)", "120ms"), "120");
+
+        // Unchanged values stay borrowed
+        let trim: Transform = "trim".parse().unwrap();
+        assert!(matches!(
+            trim.apply(Cow::Borrowed(" x")),
+            Cow::Borrowed("x")
+        ));
+
+        assert!("upper".parse::<Transform>().is_err());
+        assert!("replace()".parse::<Transform>().is_err());
+        assert!("replace(a, b, c)".parse::<Transform>().is_err());
+        assert!("x:trim".parse::<Transform>().is_err());
+    }
+
+    #[test]
+    fn test_transform_extractor() {
+        let scanner = Scanner::new(r#"^{{level:var_name}} "{{path:any}}"$"#.to_string()).unwrap();
+        let transforms = parse_transforms(&HashMap::from([
+            ("level".to_string(), vec!["lowercase".to_string()]),
+            (
+                "path".to_string(),
+                vec!["url_decode".to_string(), "trim".to_string()],
+            ),
+        ]))
+        .unwrap();
+        let extractor = TransformExtractor::new(Arc::new(scanner.clone()), transforms).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(
+            extractor.extract_pattern(r#"ERROR "%20/a%20b""#, &mut out),
+            Some(0)
+        );
+        assert_eq!(out, vec!["error", "/a b"]);
+        assert_eq!(extractor.extract_bytes(br#"Info "/x""#, &mut out), Some(0));
+        assert_eq!(out, vec!["info", "/x"]);
+
+        let unknown = HashMap::from([("nope".to_string(), vec![Transform::Trim])]);
+        assert!(TransformExtractor::new(Arc::new(scanner), unknown).is_err());
+    }
+}
@@ -0,0 +1,888 @@
+use chrono::format::{Fixed, Item, Numeric, Parsed, StrftimeItems, parse_and_remainder};
+use chrono::{Datelike, FixedOffset, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::error::{Error, Result};
+use crate::locale::{self, Locale};
+use crate::transform::{parse_duration_ms, unescape};
+
+/// Represents the type of a field extracted from log patterns.
+/// Used for type hints that determine Arrow column types.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FieldType {
+    String,
+    Int,
+    /// 64-bit integer, for values that overflow `Int` (e.g. byte counts).
+    Int64,
+    Float,
+    /// Exact decimal number of up to `precision` digits, `scale` of them after the
+    /// point, e.g. an amount of money: `Decimal(precision, scale)`.
+    Decimal(u8, u8),
+    DateTime(DateTime),
+    Enum,
+    /// One of a fixed set of values, read into a dictionary column whose dictionary
+    /// is exactly that set, in order; other values are NULL.
+    Dictionary(Vec<String>),
+    Json,
+    /// Items joined by the separator, read into a list of strings.
+    List(String),
+    /// Whitespace-separated `key=value` pairs, read into a map of strings. Values may
+    /// be double-quoted, with backslash escapes.
+    Map,
+    /// Length of time, as a number of milliseconds or a duration like `3m12s`, read
+    /// into milliseconds unless the table's `duration_unit` says otherwise.
+    Duration,
+    /// Bytes written in the encoding, e.g. a checksum in hex, decoded into a binary
+    /// column.
+    Binary(BinaryEncoding),
+}
+
+/// How the bytes of a `Binary` field are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryEncoding {
+    /// Two hex digits a byte, in either case, after an optional `0x`.
+    Hex,
+    /// Base64 with `+` and `/`, padded or not.
+    Base64,
+    /// Base64 with the URL-safe `-` and `_`, padded or not.
+    Base64Url,
+}
+
+impl BinaryEncoding {
+    /// The bytes `value` encodes, or `None` when it is empty or not valid in the
+    /// encoding.
+    pub fn decode(&self, value: &str) -> Option<Vec<u8>> {
+        match self {
+            BinaryEncoding::Hex => {
+                let digits = value
+                    .strip_prefix("0x")
+                    .or_else(|| value.strip_prefix("0X"))
+                    .unwrap_or(value);
+                if digits.is_empty() || !digits.len().is_multiple_of(2) {
+                    return None;
+                }
+                digits
+                    .as_bytes()
+                    .chunks(2)
+                    .map(|pair| Some((hex_digit(pair[0])? << 4) | hex_digit(pair[1])?))
+                    .collect()
+            }
+            BinaryEncoding::Base64 => decode_base64(value, b'+', b'/'),
+            BinaryEncoding::Base64Url => decode_base64(value, b'-', b'_'),
+        }
+    }
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Decode base64 whose last two symbols are `symbol_62` and `symbol_63`.
+fn decode_base64(value: &str, symbol_62: u8, symbol_63: u8) -> Option<Vec<u8>> {
+    let symbols = value.trim_end_matches('=').as_bytes();
+    // A lone symbol in the last group holds fewer than 8 bits
+    if symbols.is_empty() || value.len() - symbols.len() > 2 || symbols.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(symbols.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for &symbol in symbols {
+        let sextet = match symbol {
+            b'A'..=b'Z' => symbol - b'A',
+            b'a'..=b'z' => symbol - b'a' + 26,
+            b'0'..=b'9' => symbol - b'0' + 52,
+            _ if symbol == symbol_62 => 62,
+            _ if symbol == symbol_63 => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | u32::from(sextet);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Most values of a `Dictionary` field, as many as 8-bit keys address.
+pub const MAX_DICTIONARY_VALUES: usize = 128;
+
+/// Most digits of a `Decimal` field, as many as Arrow's `Decimal128` holds.
+pub const MAX_DECIMAL_PRECISION: u8 = 38;
+
+/// Timestamp formats tried, in order, when inferring a field's type from values.
+pub const INFERRED_DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%d/%b/%Y:%H:%M:%S",
+    "%Y/%m/%d %H:%M:%S%.f",
+];
+
+impl FieldType {
+    /// Name of the variant, e.g. `Decimal` for `Decimal(18, 4)`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldType::String => "String",
+            FieldType::Int => "Int",
+            FieldType::Int64 => "Int64",
+            FieldType::Float => "Float",
+            FieldType::Decimal(..) => "Decimal",
+            FieldType::DateTime(_) => "DateTime",
+            FieldType::Enum => "Enum",
+            FieldType::Dictionary(_) => "Dictionary",
+            FieldType::Json => "Json",
+            FieldType::List(_) => "List",
+            FieldType::Map => "Map",
+            FieldType::Duration => "Duration",
+            FieldType::Binary(_) => "Binary",
+        }
+    }
+
+    /// A `Decimal` of `precision` digits, `scale` of them after the point, or `None`
+    /// unless `precision` is from 1 to [`MAX_DECIMAL_PRECISION`] and `scale` at most
+    /// `precision`.
+    pub fn decimal(precision: u8, scale: u8) -> Option<Self> {
+        ((1..=MAX_DECIMAL_PRECISION).contains(&precision) && scale <= precision)
+            .then_some(FieldType::Decimal(precision, scale))
+    }
+
+    /// A `Duration` value in whole `unit`s, rounded: either a number of milliseconds,
+    /// as the `duration` macro writes them, or a duration like `250us` or `1h30m`.
+    pub fn parse_duration(value: &str, unit: TimestampUnit) -> Option<i64> {
+        let millis = match value.parse::<f64>() {
+            Ok(millis) if millis.is_finite() => millis,
+            _ => parse_duration_ms(value)?,
+        };
+        let per_milli = match unit {
+            TimestampUnit::Seconds => 1e-3,
+            TimestampUnit::Millis => 1.0,
+            TimestampUnit::Micros => 1e3,
+            TimestampUnit::Nanos => 1e6,
+        };
+        let duration = (millis * per_milli).round();
+        (duration.abs() <= i64::MAX as f64).then_some(duration as i64)
+    }
+
+    /// A decimal value like `-12.5` as an integer of `scale` digits after the point,
+    /// e.g. `-1250` for scale 2. Extra digits are rounded half away from zero.
+    ///
+    /// Returns `None` for values that are not plain decimal numbers or have more than
+    /// `precision` digits. Out-of-range precisions and scales are clamped, like the
+    /// column types of `Decimal` fields.
+    pub fn parse_decimal(value: &str, precision: u8, scale: u8) -> Option<i128> {
+        let precision = precision.clamp(1, MAX_DECIMAL_PRECISION);
+        let scale = scale.min(precision);
+        let (negative, digits) = match value.as_bytes().first()? {
+            b'-' => (true, &value[1..]),
+            b'+' => (false, &value[1..]),
+            _ => (false, value),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if (whole.is_empty() && fraction.is_empty())
+            || !whole
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let scale = scale as usize;
+        let padded = fraction.bytes().chain(std::iter::repeat(b'0')).take(scale);
+        let mut number: i128 = 0;
+        for digit in whole.bytes().chain(padded) {
+            number = number.checked_mul(10)?.checked_add((digit - b'0').into())?;
+        }
+        if fraction
+            .as_bytes()
+            .get(scale)
+            .is_some_and(|digit| *digit >= b'5')
+        {
+            number = number.checked_add(1)?;
+        }
+        if number >= 10i128.pow(precision.into()) {
+            return None;
+        }
+        Some(if negative { -number } else { number })
+    }
+
+    /// Items of a `List` value, without the blanks around them: none for an empty
+    /// value.
+    pub fn split_list<'a>(value: &'a str, separator: &'a str) -> impl Iterator<Item = &'a str> {
+        (!value.is_empty())
+            .then(|| value.split(separator).map(str::trim))
+            .into_iter()
+            .flatten()
+    }
+
+    /// Pairs of a `Map` value, with quoted values unquoted. Reading stops at the first
+    /// word that is not a pair.
+    pub fn split_pairs(value: &str) -> impl Iterator<Item = (&str, Cow<'_, str>)> {
+        let mut rest = value;
+        std::iter::from_fn(move || {
+            rest = rest.trim_start();
+            let (key, after) = rest.split_once('=')?;
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return None;
+            }
+            let (value, end) = match after.strip_prefix('"') {
+                Some(quoted) => {
+                    let mut escaped = false;
+                    let close = quoted.find(|c| match (escaped, c) {
+                        (true, _) => {
+                            escaped = false;
+                            false
+                        }
+                        (false, '\\') => {
+                            escaped = true;
+                            false
+                        }
+                        (false, c) => c == '"',
+                    })?;
+                    let inner = &quoted[..close];
+                    let value = match inner.contains('\\') {
+                        true => Cow::Owned(unescape(inner)),
+                        false => Cow::Borrowed(inner),
+                    };
+                    (value, close + 2)
+                }
+                None => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (Cow::Borrowed(&after[..end]), end)
+                }
+            };
+            rest = &after[end..];
+            Some((key, value))
+        })
+    }
+
+    /// The narrowest type every non-empty value parses as: `Int`, `Int64`, `Float`
+    /// or a `DateTime` in one of [`INFERRED_DATETIME_FORMATS`].
+    ///
+    /// Returns `None` when the values are plain strings or all empty.
+    pub fn infer<'a>(values: impl IntoIterator<Item = &'a str>) -> Option<FieldType> {
+        let values: Vec<&str> = values.into_iter().filter(|v| !v.is_empty()).collect();
+        if values.is_empty() {
+            return None;
+        }
+        if values.iter().all(|v| v.parse::<i32>().is_ok()) {
+            return Some(FieldType::Int);
+        }
+        if values.iter().all(|v| v.parse::<i64>().is_ok()) {
+            return Some(FieldType::Int64);
+        }
+        // `f64` also parses `inf` and `NaN`, which are not numbers in a log
+        let is_float = |v: &&str| {
+            v.bytes().any(|b| b.is_ascii_digit())
+                && v.bytes()
+                    .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b))
+                && v.parse::<f64>().is_ok()
+        };
+        if values.iter().all(is_float) {
+            return Some(FieldType::Float);
+        }
+        INFERRED_DATETIME_FORMATS.iter().find_map(|format| {
+            let datetime = DateTime::new(Some(vec![format.to_string()]));
+            values
+                .iter()
+                .all(|v| datetime.parse(v).is_some())
+                .then_some(FieldType::DateTime(datetime))
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateTime {
+    pub formats: Option<Vec<String>>,
+    /// Locale of month and weekday names, e.g. `de`; English when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    /// Unit of the column, overriding that of the table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<TimestampUnit>,
+    /// Time zone of the column, e.g. `UTC`, `+02:00` or `Europe/Berlin`: values are
+    /// read as local times there, unless they have an offset, and stored in UTC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+impl DateTime {
+    pub fn new(formats: Option<Vec<String>>) -> Self {
+        DateTime {
+            formats,
+            locale: None,
+            unit: None,
+            timezone: None,
+        }
+    }
+
+    /// Read month and weekday names in `locale`, e.g. `de` for `Mär` and `Dez`.
+    pub fn with_locale(mut self, locale: Option<String>) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Store the field in `unit` rather than the unit of its table.
+    pub fn with_unit(mut self, unit: Option<TimestampUnit>) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Read local times in `timezone`, which [`Zone::parse`] must accept, and make the
+    /// column zoned.
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Parse `value` into microseconds since the epoch. Timestamps without a year get
+    /// one by [`YearStrategy::Rollover`] from the current time.
+    pub fn parse(&self, value: &str) -> Option<i64> {
+        self.parse_with_year(value, YearStrategy::Rollover, None)
+    }
+
+    /// Like [`parse`](Self::parse), choosing the year of timestamps without one by
+    /// `strategy` from `reference`, in microseconds since the epoch, such as the
+    /// modification time of the file they come from. Without a reference, the
+    /// current time is used.
+    pub fn parse_with_year(
+        &self,
+        value: &str,
+        strategy: YearStrategy,
+        reference: Option<i64>,
+    ) -> Option<i64> {
+        let datetime = self.parse_datetime(value, strategy, reference)?;
+        Some(datetime.and_utc().timestamp_micros())
+    }
+
+    /// Like [`parse_with_year`](Self::parse_with_year), returning the date and time
+    /// itself, e.g. for [`TimestampUnit::timestamp`].
+    pub fn parse_datetime(
+        &self,
+        value: &str,
+        strategy: YearStrategy,
+        reference: Option<i64>,
+    ) -> Option<NaiveDateTime> {
+        let formats = self.formats.as_ref()?;
+        let locale = self.locale.as_deref().and_then(locale::find);
+        let zone = match &self.timezone {
+            Some(timezone) => Some(Zone::parse(timezone)?),
+            None => None,
+        };
+        formats.iter().find_map(|format| {
+            let mut parsed = Parsed::new();
+            parse_into(&mut parsed, value, format, locale)?;
+            let datetime = match parsed.to_naive_datetime_with_offset(0) {
+                Ok(datetime) => datetime,
+                Err(_)
+                    if parsed.year().is_some()
+                        || parsed.year_mod_100().is_some()
+                        || parsed.isoyear().is_some() =>
+                {
+                    return None;
+                }
+                Err(_) => strategy.resolve(&parsed, reference)?,
+            };
+            match (&zone, parsed.offset()) {
+                (None, _) => Some(datetime),
+                (Some(_), Some(offset)) => {
+                    datetime.checked_sub_signed(chrono::TimeDelta::seconds(offset.into()))
+                }
+                (Some(zone), None) => zone.to_utc(datetime),
+            }
+        })
+    }
+}
+
+/// Time zone of a `DateTime` column.
+#[derive(Debug, Clone, Copy)]
+pub enum Zone {
+    /// A fixed offset from UTC, like `+02:00`.
+    Fixed(FixedOffset),
+    /// A zone of the IANA database, like `Europe/Berlin` or `UTC`, with its daylight
+    /// saving time.
+    Named(chrono_tz::Tz),
+}
+
+impl Zone {
+    /// Parse `UTC`, an offset like `+02:00` or `-0530`, or an IANA name.
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Ok(offset) = s.parse::<FixedOffset>() {
+            return Some(Zone::Fixed(offset));
+        }
+        s.parse().ok().map(Zone::Named)
+    }
+
+    /// The UTC time of local time `datetime` in this zone; the earlier one when clocks
+    /// go back, and `None` when they skip it.
+    pub fn to_utc(&self, datetime: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Zone::Fixed(offset) => offset.from_local_datetime(&datetime).earliest(),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&datetime)
+                .earliest()
+                .map(|d| d.fixed_offset()),
+        }
+        .map(|datetime| datetime.naive_utc())
+    }
+}
+
+/// Parse `value` by `format` into `parsed` like chrono, except that `%f` reads its
+/// digits as a fraction of a second, so `%S.%f` and `%S,%f` read `56,789` as 789
+/// milliseconds rather than nanoseconds, and that month and weekday names are read in
+/// `locale` when given.
+fn parse_into(
+    parsed: &mut Parsed,
+    value: &str,
+    format: &str,
+    locale: Option<&Locale>,
+) -> Option<()> {
+    let mut rest = value;
+    for item in StrftimeItems::new(format) {
+        match (item, locale) {
+            (Item::Numeric(Numeric::Nanosecond, _), _) => {
+                let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+                if digits == 0 {
+                    return None;
+                }
+                parsed
+                    .set_nanosecond(fraction_nanos(&rest[..digits]))
+                    .ok()?;
+                rest = &rest[digits..];
+            }
+            (Item::Fixed(Fixed::ShortMonthName | Fixed::LongMonthName), Some(locale)) => {
+                let (month, len) = locale.month(rest)?;
+                parsed.set_month(month.into()).ok()?;
+                rest = &rest[len..];
+            }
+            (Item::Fixed(Fixed::ShortWeekdayName | Fixed::LongWeekdayName), Some(locale)) => {
+                let (weekday, len) = locale.weekday(rest)?;
+                parsed.set_weekday(weekday).ok()?;
+                rest = &rest[len..];
+            }
+            (item, _) => rest = parse_and_remainder(parsed, rest, std::iter::once(item)).ok()?,
+        }
+    }
+    rest.is_empty().then_some(())
+}
+
+/// Nanoseconds of the fraction of a second written `digits`; digits past the ninth
+/// are dropped.
+fn fraction_nanos(digits: &str) -> i64 {
+    digits
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(9)
+        .fold(0, |nanos, digit| nanos * 10 + i64::from(digit - b'0'))
+}
+
+/// Unit of the timestamps `DateTime` fields are read into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimestampUnit {
+    /// `s`: whole seconds.
+    #[serde(rename = "s")]
+    Seconds,
+    /// `ms`: milliseconds.
+    #[serde(rename = "ms")]
+    Millis,
+    /// `us`: microseconds.
+    #[default]
+    #[serde(rename = "us")]
+    Micros,
+    /// `ns`: nanoseconds, for dates from 1677 to 2262.
+    #[serde(rename = "ns")]
+    Nanos,
+}
+
+impl TimestampUnit {
+    /// `datetime` as this many units since the epoch, rounded down; `None` when it
+    /// does not fit.
+    pub fn timestamp(&self, datetime: NaiveDateTime) -> Option<i64> {
+        let datetime = datetime.and_utc();
+        match self {
+            TimestampUnit::Seconds => Some(datetime.timestamp()),
+            TimestampUnit::Millis => Some(datetime.timestamp_millis()),
+            TimestampUnit::Micros => Some(datetime.timestamp_micros()),
+            TimestampUnit::Nanos => datetime.timestamp_nanos_opt(),
+        }
+    }
+}
+
+impl std::str::FromStr for TimestampUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "s" => Ok(TimestampUnit::Seconds),
+            "ms" => Ok(TimestampUnit::Millis),
+            "us" => Ok(TimestampUnit::Micros),
+            "ns" => Ok(TimestampUnit::Nanos),
+            other => Err(Error::Config(format!(
+                "unknown timestamp unit '{}', expected s, ms, us or ns",
+                other
+            ))),
+        }
+    }
+}
+
+/// How timestamps whose format has no year, like syslog's `%b %e %H:%M:%S`, get one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum YearStrategy {
+    /// `rollover`: the year of the reference time, or the year before for timestamps
+    /// that would fall more than a day after it, so a file spanning New Year reads
+    /// December as last year.
+    #[default]
+    Rollover,
+    /// `mtime`: the year of the reference time.
+    Mtime,
+    /// A fixed year, e.g. `2023`.
+    Fixed(i32),
+}
+
+impl YearStrategy {
+    /// Date and time of `parsed`, which has no year, in the chosen year.
+    fn resolve(&self, parsed: &Parsed, reference: Option<i64>) -> Option<NaiveDateTime> {
+        let reference = reference.unwrap_or_else(|| chrono::Utc::now().timestamp_micros());
+        let reference_year = chrono::DateTime::from_timestamp_micros(reference)?.year();
+        let in_year = |year: i32| {
+            let mut parsed = parsed.clone();
+            parsed.set_year(year.into()).ok()?;
+            parsed.to_naive_datetime_with_offset(0).ok()
+        };
+        match self {
+            YearStrategy::Fixed(year) => in_year(*year),
+            YearStrategy::Mtime => in_year(reference_year),
+            YearStrategy::Rollover => match in_year(reference_year) {
+                Some(datetime)
+                    if datetime.and_utc().timestamp_micros()
+                        <= reference + ROLLOVER_SLACK_MICROS =>
+                {
+                    Some(datetime)
+                }
+                _ => in_year(reference_year - 1),
+            },
+        }
+    }
+}
+
+/// How far after the reference time a timestamp may fall before
+/// [`YearStrategy::Rollover`] moves it to the year before, allowing for clock skew.
+const ROLLOVER_SLACK_MICROS: i64 = 24 * 60 * 60 * 1_000_000;
+
+impl std::str::FromStr for YearStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "rollover" => Ok(YearStrategy::Rollover),
+            "mtime" => Ok(YearStrategy::Mtime),
+            year => year.parse().map(YearStrategy::Fixed).map_err(|_| {
+                Error::Config(format!(
+                    "unknown year strategy '{}', expected rollover, mtime or a year",
+                    s
+                ))
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for YearStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            YearStrategy::Rollover => write!(f, "rollover"),
+            YearStrategy::Mtime => write!(f, "mtime"),
+            YearStrategy::Fixed(year) => write!(f, "{}", year),
+        }
+    }
+}
+
+impl TryFrom<String> for YearStrategy {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<YearStrategy> for String {
+    fn from(strategy: YearStrategy) -> Self {
+        strategy.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_year_strategies() {
+        let syslog = DateTime::new(Some(vec!["%b %e %H:%M:%S".to_string()]));
+        let micros = |s: &str| {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+                .unwrap()
+                .and_utc()
+                .timestamp_micros()
+        };
+        let mtime = Some(micros("2024-01-02 08:00:00"));
+        let parse = |value, strategy| syslog.parse_with_year(value, strategy, mtime);
+
+        assert_eq!(
+            parse("Jan  1 00:00:01", YearStrategy::Rollover),
+            Some(micros("2024-01-01 00:00:01"))
+        );
+        // Written before New Year, so last year
+        assert_eq!(
+            parse("Dec 31 23:59:59", YearStrategy::Rollover),
+            Some(micros("2023-12-31 23:59:59"))
+        );
+        // Within a day of the modification time, for clock skew
+        assert_eq!(
+            parse("Jan  3 00:00:00", YearStrategy::Rollover),
+            Some(micros("2024-01-03 00:00:00"))
+        );
+        assert_eq!(
+            parse("Dec 31 23:59:59", YearStrategy::Mtime),
+            Some(micros("2024-12-31 23:59:59"))
+        );
+        assert_eq!(
+            parse("Feb 29 12:00:00", YearStrategy::Fixed(2020)),
+            Some(micros("2020-02-29 12:00:00"))
+        );
+        assert_eq!(parse("Feb 29 12:00:00", YearStrategy::Fixed(2021)), None);
+
+        // Formats with a year ignore the strategy
+        let iso = DateTime::new(Some(vec!["%Y-%m-%d %H:%M:%S".to_string()]));
+        assert_eq!(
+            iso.parse_with_year("2019-05-03 12:00:00", YearStrategy::Fixed(2020), mtime),
+            Some(micros("2019-05-03 12:00:00"))
+        );
+
+        assert_eq!(
+            "mtime".parse::<YearStrategy>().unwrap(),
+            YearStrategy::Mtime
+        );
+        assert_eq!(
+            "2023".parse::<YearStrategy>().unwrap(),
+            YearStrategy::Fixed(2023)
+        );
+        assert!("last".parse::<YearStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_fractional_seconds() {
+        let micros = |format: &str, value| {
+            DateTime::new(Some(vec![format.to_string()]))
+                .parse(value)
+                .map(|micros| micros.rem_euclid(1_000_000))
+        };
+        assert_eq!(
+            micros("%Y-%m-%dT%H:%M:%S%.f", "2023-05-03T12:34:56.789"),
+            Some(789_000)
+        );
+        assert_eq!(
+            micros("%Y-%m-%dT%H:%M:%S%.f", "2023-05-03T12:34:56"),
+            Some(0)
+        );
+        assert_eq!(
+            micros("%Y-%m-%d %H:%M:%S,%f", "2023-05-03 12:34:56,789"),
+            Some(789_000)
+        );
+        assert_eq!(
+            micros("%Y-%m-%d %H:%M:%S.%f", "2023-05-03 12:34:56.000789"),
+            Some(789)
+        );
+        // Nanoseconds are kept to the microsecond
+        assert_eq!(
+            micros("%Y-%m-%d %H:%M:%S%.9f", "2023-05-03 12:34:56.123456789"),
+            Some(123_456)
+        );
+        assert_eq!(
+            micros("%Y-%m-%d %H:%M:%S%.3f", "2023-05-03 12:34:56.1234"),
+            None
+        );
+
+        // What chrono writes reads back the same
+        let written = chrono::NaiveDate::from_ymd_opt(2023, 5, 3)
+            .unwrap()
+            .and_hms_micro_opt(12, 34, 56, 789_012)
+            .unwrap();
+        for format in [
+            "%Y-%m-%dT%H:%M:%S%.f",
+            "%Y-%m-%d %H:%M:%S%.6f",
+            "%F %T.%f",
+            "%F %T,%6f",
+        ] {
+            let value = written.format(format).to_string();
+            assert_eq!(
+                DateTime::new(Some(vec![format.to_string()])).parse(&value),
+                Some(written.and_utc().timestamp_micros()),
+                "{}: {}",
+                format,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_timestamp_units() {
+        let datetime = chrono::NaiveDateTime::parse_from_str(
+            "2024-01-02 03:04:05.678901234",
+            "%Y-%m-%d %H:%M:%S%.f",
+        )
+        .unwrap();
+        assert_eq!(
+            TimestampUnit::Seconds.timestamp(datetime),
+            Some(1_704_164_645)
+        );
+        assert_eq!(
+            TimestampUnit::Millis.timestamp(datetime),
+            Some(1_704_164_645_678)
+        );
+        assert_eq!(
+            TimestampUnit::Micros.timestamp(datetime),
+            Some(1_704_164_645_678_901)
+        );
+        assert_eq!(
+            TimestampUnit::Nanos.timestamp(datetime),
+            Some(1_704_164_645_678_901_234)
+        );
+        // Out of the range of nanoseconds
+        let ancient = chrono::NaiveDate::from_ymd_opt(1500, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap();
+        assert_eq!(TimestampUnit::Nanos.timestamp(ancient), None);
+
+        assert_eq!(
+            "ms".parse::<TimestampUnit>().unwrap(),
+            TimestampUnit::Millis
+        );
+        assert!("millis".parse::<TimestampUnit>().is_err());
+    }
+
+    #[test]
+    fn test_parse_decimal() {
+        let parse = |value| FieldType::parse_decimal(value, 6, 2);
+        assert_eq!(parse("1234.5"), Some(123_450));
+        assert_eq!(parse("-0.01"), Some(-1));
+        assert_eq!(parse("+.5"), Some(50));
+        assert_eq!(parse("7."), Some(700));
+        // Rounded half away from zero
+        assert_eq!(parse("0.125"), Some(13));
+        assert_eq!(parse("-0.125"), Some(-13));
+        assert_eq!(parse("0.1249"), Some(12));
+        // More than 6 digits
+        assert_eq!(parse("10000"), None);
+        assert_eq!(parse("9999.995"), None);
+        for invalid in ["", "-", ".", "1e3", "1.2.3", "12a", " 1"] {
+            assert_eq!(parse(invalid), None, "{}", invalid);
+        }
+        assert_eq!(
+            FieldType::parse_decimal("99999999999999999999999999999999999999", 38, 0),
+            Some(10i128.pow(38) - 1)
+        );
+        assert_eq!(FieldType::parse_decimal(&"9".repeat(60), 38, 0), None);
+
+        assert_eq!(FieldType::decimal(18, 4), Some(FieldType::Decimal(18, 4)));
+        assert_eq!(FieldType::decimal(0, 0), None);
+        assert_eq!(FieldType::decimal(39, 0), None);
+        assert_eq!(FieldType::decimal(4, 5), None);
+    }
+
+    #[test]
+    fn test_binary_decoding() {
+        assert_eq!(
+            BinaryEncoding::Hex.decode("0xDEADbeef"),
+            Some(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(BinaryEncoding::Hex.decode("00ff"), Some(vec![0, 255]));
+        for invalid in ["", "0x", "abc", "zz"] {
+            assert_eq!(BinaryEncoding::Hex.decode(invalid), None, "{}", invalid);
+        }
+
+        let base64 = |value| BinaryEncoding::Base64.decode(value);
+        assert_eq!(base64("aGVsbG8="), Some(b"hello".to_vec()));
+        assert_eq!(base64("aGVsbG8"), Some(b"hello".to_vec()));
+        assert_eq!(base64("YQ=="), Some(b"a".to_vec()));
+        assert_eq!(base64("+/+/"), Some(vec![0xfb, 0xff, 0xbf]));
+        for invalid in ["", "=", "Y", "YQ===", "-_-_", "aGV sbG8"] {
+            assert_eq!(base64(invalid), None, "{}", invalid);
+        }
+        assert_eq!(
+            BinaryEncoding::Base64Url.decode("-_-_"),
+            Some(vec![0xfb, 0xff, 0xbf])
+        );
+        assert_eq!(BinaryEncoding::Base64Url.decode("+/+/"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let millis = |value| FieldType::parse_duration(value, TimestampUnit::Millis);
+        assert_eq!(millis("1500"), Some(1500));
+        assert_eq!(millis("-2.5"), Some(-3));
+        assert_eq!(millis("3m12s"), Some(192_000));
+        assert_eq!(millis("1499us"), Some(1));
+        assert_eq!(millis("1d"), Some(86_400_000));
+        for invalid in ["", "inf", "NaN", "3 s", "1w"] {
+            assert_eq!(millis(invalid), None, "{}", invalid);
+        }
+        assert_eq!(
+            FieldType::parse_duration("1499us", TimestampUnit::Micros),
+            Some(1499)
+        );
+        assert_eq!(
+            FieldType::parse_duration("1.5", TimestampUnit::Nanos),
+            Some(1_500_000)
+        );
+        assert_eq!(
+            FieldType::parse_duration("3m12s", TimestampUnit::Seconds),
+            Some(192)
+        );
+    }
+
+    #[test]
+    fn test_timezones() {
+        let micros = |s: &str| {
+            chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+                .unwrap()
+                .and_utc()
+                .timestamp_micros()
+        };
+        let zoned = |format: &str, timezone: &str| {
+            DateTime::new(Some(vec![format.to_string()])).with_timezone(Some(timezone.into()))
+        };
+
+        let berlin = zoned("%Y-%m-%d %H:%M:%S", "Europe/Berlin");
+        // Winter and summer time
+        assert_eq!(
+            berlin.parse("2024-01-15 12:00:00"),
+            Some(micros("2024-01-15 11:00:00"))
+        );
+        assert_eq!(
+            berlin.parse("2024-07-15 12:00:00"),
+            Some(micros("2024-07-15 10:00:00"))
+        );
+        // Skipped when clocks go forward
+        assert_eq!(berlin.parse("2024-03-31 02:30:00"), None);
+
+        let fixed = zoned("%Y-%m-%d %H:%M:%S", "-05:30");
+        assert_eq!(
+            fixed.parse("2024-01-15 12:00:00"),
+            Some(micros("2024-01-15 17:30:00"))
+        );
+        // An offset in the value wins over the zone
+        let offset = zoned("%Y-%m-%d %H:%M:%S %z", "UTC");
+        assert_eq!(
+            offset.parse("2024-01-15 12:00:00 +0200"),
+            Some(micros("2024-01-15 10:00:00"))
+        );
+
+        assert!(Zone::parse("UTC").is_some());
+        assert!(Zone::parse("+02:00").is_some());
+        assert!(Zone::parse("Mars/Olympus").is_none());
+        assert_eq!(zoned("%Y", "Mars/Olympus").parse("2024"), None);
+    }
+}
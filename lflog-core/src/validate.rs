@@ -0,0 +1,289 @@
+//! Checking sample lines against a [`Scanner`] while developing a pattern.
+//!
+//! [`Scanner::validate`] reports, for every sample line, the pattern that matched and
+//! the values it captured, or for lines nothing matched, how far the closest pattern
+//! got before the line stopped fitting it.
+
+use regex_automata::hybrid::dfa::{Cache, DFA};
+use regex_automata::{Anchored, Input};
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::extractor::RecordExtractor;
+use crate::scanner::Scanner;
+
+/// Result of [`Scanner::validate`], one report per sample line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validation {
+    pub lines: Vec<LineReport>,
+}
+
+/// How one sample line fared against the scanner's patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineReport {
+    /// The sample line.
+    pub line: String,
+    /// Index of the pattern that matched the line, if any.
+    pub pattern: Option<usize>,
+    /// Field names and captured values, in the scanner's order; empty for misses.
+    pub fields: Vec<(String, String)>,
+    /// For lines no pattern matched, the pattern that got furthest into the line.
+    pub near_miss: Option<NearMiss>,
+    /// The line matches the scanner's exclude pattern, so it is dropped as noise.
+    pub excluded: bool,
+}
+
+/// Where a line stops fitting a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NearMiss {
+    /// Index of the pattern.
+    pub pattern: usize,
+    /// Byte offset of the first part of the line the pattern cannot match, read from
+    /// the start of the line. Equals the line length when the line ends too early.
+    pub offset: usize,
+}
+
+impl Validation {
+    pub(crate) fn new<I, S>(scanner: &Scanner, lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut walkers: Vec<Option<Walker>> = scanner
+            .pattern_sources()
+            .map(|source| Walker::new(&source))
+            .collect();
+        let lines = lines
+            .into_iter()
+            .map(|line| {
+                let line = line.as_ref();
+                let mut values = Vec::new();
+                match scanner.extract_pattern(line, &mut values) {
+                    Some(pattern) => LineReport {
+                        line: line.to_string(),
+                        pattern: Some(pattern),
+                        fields: scanner
+                            .field_names
+                            .iter()
+                            .cloned()
+                            .zip(values.into_iter().map(Cow::into_owned))
+                            .collect(),
+                        near_miss: None,
+                        excluded: false,
+                    },
+                    None if scanner.is_excluded(line) => LineReport {
+                        line: line.to_string(),
+                        pattern: None,
+                        fields: Vec::new(),
+                        near_miss: None,
+                        excluded: true,
+                    },
+                    None => LineReport {
+                        line: line.to_string(),
+                        pattern: None,
+                        fields: Vec::new(),
+                        near_miss: near_miss(
+                            &mut walkers,
+                            line.strip_suffix('\r').unwrap_or(line).as_bytes(),
+                        ),
+                        excluded: false,
+                    },
+                }
+            })
+            .collect();
+        Self { lines }
+    }
+
+    /// Reports of the lines some pattern matched.
+    pub fn matched(&self) -> impl Iterator<Item = &LineReport> {
+        self.lines.iter().filter(|line| line.pattern.is_some())
+    }
+
+    /// Reports of the lines no pattern matched, leaving out excluded lines.
+    pub fn unmatched(&self) -> impl Iterator<Item = &LineReport> {
+        self.lines
+            .iter()
+            .filter(|line| line.pattern.is_none() && !line.excluded)
+    }
+
+    /// Fraction of the lines that matched, not counting excluded lines, or 0 without
+    /// lines.
+    pub fn match_rate(&self) -> f64 {
+        let matched = self.matched().count();
+        let total = matched + self.unmatched().count();
+        if total == 0 {
+            return 0.0;
+        }
+        matched as f64 / total as f64
+    }
+}
+
+impl LineReport {
+    /// Value captured for field `name`, if the line matched.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl fmt::Display for LineReport {
+    /// One line: the captured fields, or where the closest pattern diverged.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.pattern, self.near_miss) {
+            _ if self.excluded => write!(f, "excluded"),
+            (Some(pattern), _) => {
+                write!(f, "pattern {} matched", pattern)?;
+                for (name, value) in &self.fields {
+                    write!(f, " {}={:?}", name, value)?;
+                }
+                Ok(())
+            }
+            (None, Some(miss)) => {
+                let offset = floor_char_boundary(&self.line, miss.offset);
+                write!(
+                    f,
+                    "no match, pattern {} diverges at byte {}: {:?} then {:?}",
+                    miss.pattern,
+                    miss.offset,
+                    &self.line[..offset],
+                    &self.line[offset..]
+                )
+            }
+            (None, None) => write!(f, "no match"),
+        }
+    }
+}
+
+fn floor_char_boundary(line: &str, offset: usize) -> usize {
+    (0..=offset.min(line.len()))
+        .rev()
+        .find(|&i| line.is_char_boundary(i))
+        .unwrap_or(0)
+}
+
+/// The pattern that reads furthest into `line`; the first on ties.
+fn near_miss(walkers: &mut [Option<Walker>], line: &[u8]) -> Option<NearMiss> {
+    let mut best: Option<NearMiss> = None;
+    for (pattern, walker) in walkers.iter_mut().enumerate() {
+        let Some(offset) = walker.as_mut().and_then(|walker| walker.divergence(line)) else {
+            continue;
+        };
+        if best.is_none_or(|best| offset > best.offset) {
+            best = Some(NearMiss { pattern, offset });
+        }
+    }
+    best
+}
+
+/// A lazy DFA of one pattern, stepped a byte at a time to find where a line
+/// leaves it.
+struct Walker {
+    dfa: DFA,
+    cache: Cache,
+}
+
+impl Walker {
+    /// Build the DFA, or `None` for patterns outside what it supports, such as
+    /// PCRE2-only syntax.
+    fn new(source: &str) -> Option<Self> {
+        let dfa = DFA::builder()
+            .configure(DFA::config().unicode_word_boundary(true))
+            .build(source)
+            .ok()?;
+        let cache = dfa.create_cache();
+        Some(Self { dfa, cache })
+    }
+
+    /// Offset of the first byte of `line` after which no match starting at the
+    /// beginning of the line is possible, or the line length if none is.
+    ///
+    /// `None` when the DFA gives up, e.g. on a Unicode word boundary next to
+    /// non-ASCII text.
+    fn divergence(&mut self, line: &[u8]) -> Option<usize> {
+        let input = Input::new(line).anchored(Anchored::Yes);
+        let mut state = self.dfa.start_state_forward(&mut self.cache, &input).ok()?;
+        for (offset, &byte) in line.iter().enumerate() {
+            state = self.dfa.next_state(&mut self.cache, state, byte).ok()?;
+            if state.is_dead() {
+                return Some(offset);
+            }
+            if state.is_quit() {
+                return None;
+            }
+        }
+        Some(line.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let scanner = Scanner::with_patterns(
+            vec![
+                r"^\[{{level:var_name}}\] {{msg:any}}$".into(),
+                "^{{status:number}} {{path:any}}$".into(),
+            ],
+            None,
+        )
+        .unwrap();
+        let validation = scanner.validate(["[INFO] up", "200 /index", "[WARN x", "20x /"]);
+        assert_eq!(validation.matched().count(), 2);
+        assert_eq!(validation.match_rate(), 0.5);
+
+        let info = &validation.lines[0];
+        assert_eq!(info.pattern, Some(0));
+        assert_eq!(info.get("msg"), Some("up"));
+        assert_eq!(info.get("status"), Some(""));
+        assert_eq!(
+            info.to_string(),
+            r#"pattern 0 matched level="INFO" msg="up" status="" path="""#
+        );
+
+        let misses: Vec<&LineReport> = validation.unmatched().collect();
+        // `[WARN` reads as a level until the space where `]` was expected
+        assert_eq!(
+            misses[0].near_miss,
+            Some(NearMiss {
+                pattern: 0,
+                offset: 5
+            })
+        );
+        assert!(misses[0].fields.is_empty());
+        assert_eq!(
+            misses[0].to_string(),
+            r#"no match, pattern 0 diverges at byte 5: "[WARN" then " x""#
+        );
+        assert_eq!(
+            misses[1].near_miss,
+            Some(NearMiss {
+                pattern: 1,
+                offset: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_line_ends_early() {
+        let scanner = Scanner::new("^{{a:number}}-{{b:number}}$".to_string()).unwrap();
+        let validation = scanner.validate(vec!["12-".to_string()]);
+        assert_eq!(
+            validation.lines[0].near_miss,
+            Some(NearMiss {
+                pattern: 0,
+                offset: 3
+            })
+        );
+        assert_eq!(
+            Scanner::new("x".to_string())
+                .unwrap()
+                .validate([] as [&str; 0])
+                .match_rate(),
+            0.0
+        );
+    }
+}
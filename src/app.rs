@@ -3,42 +3,142 @@
 //! Provides high-level API for loading configuration and querying log files with SQL.
 
 use crate::error::{Error, Result};
-use datafusion::prelude::{DataFrame, SessionContext};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::TableProvider;
+use datafusion::catalog::view::ViewTable;
+use datafusion::datasource::provider_as_source;
+use datafusion::execution::runtime_env::RuntimeEnvBuilder;
+use datafusion::functions_aggregate::min_max::min_udaf;
+use datafusion::logical_expr::expr::{WindowFunction, WindowFunctionDefinition};
+use datafusion::logical_expr::{ExprFunctionExt, LogicalPlanBuilder, ScalarUDF};
+use datafusion::prelude::{DataFrame, Expr, SessionConfig, SessionContext, ident};
+use encoding_rs::Encoding;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Cursor};
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::datafusion::LogTableProvider;
-use crate::macros::parser::Profiles;
-use crate::scanner::Scanner;
+use crate::builtin;
+use crate::cancel::CancellationToken;
+use crate::datafusion::{
+    LogTableProvider, LongLines, STRICT_MATCH_RATE, check_min_match_rate, register_udfs,
+    resolve_file_list,
+};
+use crate::extractor::RecordExtractor;
+use crate::macros::parser::{Profile, Profiles};
+use crate::plugin::Plugin;
+use crate::redact::{RedactExtractor, parse_redactions};
+use crate::scanner::{Engine, Scanner};
+use crate::stats::ScanCounts;
+use crate::types::{TimestampUnit, YearStrategy};
+
+/// Default number of matching lines sampled by [`QueryOptions::with_infer_types`].
+pub const DEFAULT_INFER_SAMPLE_LINES: usize = 1000;
 
 /// Query options for registering a log file.
 #[derive(Debug, Clone)]
 pub struct QueryOptions {
-    /// Path to the log file to query.
-    pub log_file: String,
+    /// Paths or glob patterns of the log files to query, unioned into one table.
+    pub log_files: Vec<String>,
     /// Profile name from config (optional).
     pub profile_name: Option<String>,
     /// Override pattern (optional). If provided, overrides the profile's pattern.
     pub pattern_override: Option<String>,
+    /// Patterns tried in order for lines `pattern_override` does not match.
+    pub fallback_patterns: Vec<String>,
+    /// Substring required in every line matched by the override patterns (optional).
+    pub prefilter: Option<String>,
+    /// Regex engine for the override patterns (default: `regex`).
+    pub engine: Engine,
+    /// Regex flags applied to the override patterns, e.g. `i` (default: none).
+    pub flags: Vec<String>,
+    /// Lines to drop as noise with the override patterns (default: None).
+    pub exclude_pattern: Option<String>,
+    /// Infer types of untyped override pattern fields from this many matching lines
+    /// (default: None).
+    pub infer_types: Option<usize>,
+    /// Fields redacted at scan time by name, e.g. `email` to `hash`, on top of the
+    /// profile's (default: none).
+    pub redact: HashMap<String, String>,
     /// Table name for SQL queries (default: "log").
     pub table_name: String,
     /// Add file path to the schema (default: false).
     pub add_file_path: bool,
     /// Add raw log line to the schema (default: false).
     pub add_raw: bool,
+    /// Add file modification time to the schema (default: false).
+    pub add_file_mtime: bool,
+    /// Add file size in bytes to the schema (default: false).
+    pub add_file_size: bool,
+    /// Add the index of the matching pattern to the schema (default: false).
+    pub add_pattern: bool,
+    /// Declare rows ordered by the first datetime field (default: false).
+    pub time_ordered: bool,
+    /// Merge files by the first datetime field, each file being ordered (default: false).
+    pub merge_by_time: bool,
+    /// Also register `<table>__unmatched` with the rejected lines (default: false).
+    pub register_unmatched: bool,
+    /// Fail queries when a file's match rate is below this fraction (default: None).
+    pub min_match_rate: Option<f64>,
+    /// Leading lines of each file to ignore (default: the profile's `skip_lines`, or 0).
+    pub skip_lines: Option<usize>,
+    /// Trailing lines of each file to ignore (default: the profile's `skip_footer`, or 0).
+    pub skip_footer: Option<usize>,
+    /// Encoding label of the files, e.g. `utf-16le` (default: the profile's, or UTF-8).
+    pub encoding: Option<String>,
+    /// How timestamps without a year get one (default: the profile's, or rollover).
+    pub year: Option<YearStrategy>,
+    /// Unit of timestamp columns (default: the profile's, or microseconds).
+    pub timestamp_unit: Option<TimestampUnit>,
+    /// Unit of typed duration columns (default: the profile's, or milliseconds).
+    pub duration_unit: Option<TimestampUnit>,
+    /// Lines longer than this many bytes are skipped or truncated (default: None).
+    pub max_line_length: Option<usize>,
+    /// What to do with lines over `max_line_length` (default: skip).
+    pub long_lines: LongLines,
+    /// Skip blocks using per-file token bloom indexes (default: false).
+    pub index: bool,
     /// Number of threads
     pub num_threads: Option<usize>,
+    /// Keep each event only from the first file it appears in (default: false).
+    pub dedup: bool,
 }
 
 impl Default for QueryOptions {
     fn default() -> Self {
         Self {
-            log_file: String::new(),
+            log_files: Vec::new(),
             profile_name: None,
             pattern_override: None,
+            fallback_patterns: Vec::new(),
+            prefilter: None,
+            engine: Engine::default(),
+            flags: Vec::new(),
+            exclude_pattern: None,
+            infer_types: None,
+            redact: HashMap::new(),
             table_name: "log".to_string(),
             add_file_path: false,
             add_raw: false,
+            add_file_mtime: false,
+            add_file_size: false,
+            add_pattern: false,
+            time_ordered: false,
+            merge_by_time: false,
+            register_unmatched: false,
+            min_match_rate: None,
+            skip_lines: None,
+            skip_footer: None,
+            encoding: None,
+            year: None,
+            timestamp_unit: None,
+            duration_unit: None,
+            max_line_length: None,
+            long_lines: LongLines::Skip,
+            index: false,
             num_threads: None,
+            dedup: false,
         }
     }
 }
@@ -47,11 +147,27 @@ impl QueryOptions {
     /// Create new QueryOptions with a log file path.
     pub fn new(log_file: impl Into<String>) -> Self {
         Self {
-            log_file: log_file.into(),
+            log_files: vec![log_file.into()],
             ..Default::default()
         }
     }
 
+    /// Replace the log file paths or glob patterns.
+    pub fn with_log_files<I, S>(mut self, log_files: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.log_files = log_files.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add another log file path or glob pattern to the table.
+    pub fn add_log_file(mut self, log_file: impl Into<String>) -> Self {
+        self.log_files.push(log_file.into());
+        self
+    }
+
     /// Set the profile name.
     pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
         self.profile_name = Some(profile.into());
@@ -61,6 +177,50 @@ impl QueryOptions {
     /// Set the pattern override.
     pub fn with_pattern(mut self, pattern: impl Into<String>) -> Self {
         self.pattern_override = Some(pattern.into());
+        self.fallback_patterns.clear();
+        self
+    }
+
+    /// Set several override patterns, tried in order for each line.
+    ///
+    /// The first pattern that matches a line extracts it; the table has the fields of
+    /// all patterns.
+    pub fn with_patterns<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut patterns = patterns.into_iter().map(Into::into);
+        self.pattern_override = patterns.next();
+        self.fallback_patterns = patterns.collect();
+        self
+    }
+
+    /// Set a substring every line must contain before the override patterns run.
+    ///
+    /// Profiles set their own with `prefilter`.
+    pub fn with_prefilter(mut self, prefilter: Option<String>) -> Self {
+        self.prefilter = prefilter;
+        self
+    }
+
+    /// Set the regex engine the override patterns are compiled for.
+    ///
+    /// Profiles set their own with `engine`.
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Set the regex flags applied to the override patterns, e.g. `i`.
+    ///
+    /// Profiles set their own with `flags`.
+    pub fn with_flags<I, S>(mut self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.flags = flags.into_iter().map(Into::into).collect();
         self
     }
 
@@ -82,101 +242,436 @@ impl QueryOptions {
         self
     }
 
+    /// Set whether to add file modification time column.
+    pub fn with_add_file_mtime(mut self, add_file_mtime: bool) -> Self {
+        self.add_file_mtime = add_file_mtime;
+        self
+    }
+
+    /// Set whether to add file size column.
+    pub fn with_add_file_size(mut self, add_file_size: bool) -> Self {
+        self.add_file_size = add_file_size;
+        self
+    }
+
+    /// Set whether to add a `__PATTERN__` column with the index of the matching pattern.
+    pub fn with_add_pattern(mut self, add_pattern: bool) -> Self {
+        self.add_pattern = add_pattern;
+        self
+    }
+
+    /// Set whether the log is ordered by its first datetime field.
+    ///
+    /// Rotated files are always read oldest first; this additionally lets
+    /// DataFusion skip sorting on that field.
+    pub fn with_time_ordered(mut self, time_ordered: bool) -> Self {
+        self.time_ordered = time_ordered;
+        self
+    }
+
+    /// Set whether to merge files by their first datetime field.
+    ///
+    /// Each file must be ordered by that field; `ORDER BY` on it then merges the
+    /// files instead of sorting all rows.
+    pub fn with_merge_by_time(mut self, merge_by_time: bool) -> Self {
+        self.merge_by_time = merge_by_time;
+        self
+    }
+
+    /// Set whether to also register a `<table>__unmatched` table.
+    ///
+    /// It has `__FILE__`, `__LINE__` and `__RAW__` columns holding the non-empty
+    /// lines the pattern rejected, which helps when developing a pattern.
+    pub fn with_unmatched(mut self, register_unmatched: bool) -> Self {
+        self.register_unmatched = register_unmatched;
+        self
+    }
+
+    /// Set the minimum fraction (0.0-1.0) of non-empty lines that must match per file.
+    pub fn with_min_match_rate(mut self, min_match_rate: Option<f64>) -> Self {
+        self.min_match_rate = min_match_rate;
+        self
+    }
+
+    /// Drop lines matching `exclude_pattern` before matching the override patterns;
+    /// see [`Scanner::with_exclude_pattern`].
+    pub fn with_exclude_pattern(mut self, exclude_pattern: Option<String>) -> Self {
+        self.exclude_pattern = exclude_pattern;
+        self
+    }
+
+    /// Infer column types for override pattern fields without a type hint, such as
+    /// raw `(?P<status>\d+)` groups, from the first `sample` matching lines.
+    pub fn with_infer_types(mut self, sample: Option<usize>) -> Self {
+        self.infer_types = sample;
+        self
+    }
+
+    /// Redact the fields named in `redact` with `mask`, `drop`, `hash` or
+    /// `hash(salt)`, after any redaction the profile applies.
+    pub fn with_redact(mut self, redact: HashMap<String, String>) -> Self {
+        self.redact = redact;
+        self
+    }
+
+    /// Ignore the first `skip_lines` lines of each file, overriding the profile.
+    pub fn with_skip_lines(mut self, skip_lines: Option<usize>) -> Self {
+        self.skip_lines = skip_lines;
+        self
+    }
+
+    /// Ignore the last `skip_footer` lines of each file, overriding the profile.
+    pub fn with_skip_footer(mut self, skip_footer: Option<usize>) -> Self {
+        self.skip_footer = skip_footer;
+        self
+    }
+
+    /// Transcode the files from the encoding labelled `encoding`, e.g. `utf-16le`,
+    /// `latin1` or `windows-1252`, overriding the profile.
+    pub fn with_encoding(mut self, encoding: Option<String>) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Choose how timestamps whose format has no year get one, overriding the profile.
+    pub fn with_year(mut self, year: Option<YearStrategy>) -> Self {
+        self.year = year;
+        self
+    }
+
+    /// Store timestamp columns in `unit`, overriding the profile.
+    pub fn with_timestamp_unit(mut self, unit: Option<TimestampUnit>) -> Self {
+        self.timestamp_unit = unit;
+        self
+    }
+
+    /// Store typed duration columns in `unit`, overriding the profile.
+    pub fn with_duration_unit(mut self, unit: Option<TimestampUnit>) -> Self {
+        self.duration_unit = unit;
+        self
+    }
+
+    /// Guard against pathological lines longer than `max_line_length` bytes, which are
+    /// skipped or truncated according to `long_lines`.
+    pub fn with_max_line_length(
+        mut self,
+        max_line_length: Option<usize>,
+        long_lines: LongLines,
+    ) -> Self {
+        self.max_line_length = max_line_length;
+        self.long_lines = long_lines;
+        self
+    }
+
+    /// Enable strict mode, requiring a 95% match rate per file.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.min_match_rate = strict.then_some(STRICT_MATCH_RATE);
+        self
+    }
+
+    /// Use per-file token bloom indexes to skip blocks for `__RAW__` searches.
+    ///
+    /// The index is stored next to each file as `<file>.lflogidx` and rebuilt when the file changes.
+    pub fn with_index(mut self, index: bool) -> Self {
+        self.index = index;
+        self
+    }
+
     /// Set the number of threads to use for processing.
+    ///
+    /// Builds a dedicated pool for this table; leave unset to use the [`LfLog`] pool.
     pub fn with_num_threads(mut self, num_threads: Option<u32>) -> Self {
         self.num_threads = num_threads.map(|n| n as usize);
         self
     }
+
+    /// Set whether to drop events already seen in another file.
+    ///
+    /// Useful when logs were shipped twice or rotated segments overlap. An event is a
+    /// row with the same values in every non-metadata column (e.g. timestamp and
+    /// message); only its rows from the first file by path are kept.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
 }
 
 /// Main application struct for querying log files.
 pub struct LfLog {
     ctx: SessionContext,
     profiles: Option<Profiles>,
+    /// Pool shared by tables registered without their own thread count.
+    thread_pool: Option<Arc<ThreadPool>>,
+    /// SQL functions added by plugins.
+    functions: Vec<ScalarUDF>,
 }
 
 impl LfLog {
-    /// Initialize from a TOML config file.
+    /// Initialize from a TOML config file, loading the plugins it lists.
     pub fn from_config(config_path: &str) -> Result<Self> {
         let profiles = Profiles::from_file(config_path)?;
-        Ok(Self::from_profiles(profiles))
+        let plugins = profiles.plugins.clone();
+        let mut lflog = Self::from_profiles(profiles);
+        for plugin in &plugins {
+            lflog = lflog.with_plugin(plugin)?;
+        }
+        Ok(lflog)
     }
 
     /// Initialize from a Profiles struct.
     pub fn from_profiles(profiles: Profiles) -> Self {
         Self {
-            ctx: SessionContext::new(),
+            ctx: Self::session(),
             profiles: Some(profiles),
+            thread_pool: None,
+            functions: Vec::new(),
         }
     }
 
     /// Initialize with no profiles (for inline pattern usage only).
     pub fn new() -> Self {
         Self {
-            ctx: SessionContext::new(),
+            ctx: Self::session(),
             profiles: None,
+            thread_pool: None,
+            functions: Vec::new(),
+        }
+    }
+
+    /// Limit the memory used by queries to about `bytes`.
+    ///
+    /// Scans fail with a resources exhausted error instead of exceeding the limit, and
+    /// operators such as sorts spill to disk. Replaces the session, so call it before
+    /// registering tables.
+    pub fn with_memory_limit(mut self, bytes: usize) -> Result<Self> {
+        let runtime = RuntimeEnvBuilder::new()
+            .with_memory_limit(bytes, 1.0)
+            .build_arc()?;
+        self.ctx = SessionContext::new_with_config_rt(SessionConfig::new(), runtime);
+        register_udfs(&self.ctx);
+        for function in &self.functions {
+            self.ctx.register_udf(function.clone());
+        }
+        Ok(self)
+    }
+
+    /// Load the plugin library at `path`, adding its macros, profiles and SQL functions.
+    ///
+    /// Profiles already loaded win over the plugin's, and its global macros become
+    /// available to every loaded profile not defining a macro of the same name.
+    pub fn with_plugin(mut self, path: &str) -> Result<Self> {
+        let plugin = Plugin::load(path)?;
+        for function in &plugin.functions {
+            self.ctx.register_udf(function.clone());
+        }
+        self.functions.extend(plugin.functions);
+
+        let profiles = self.profiles.get_or_insert_with(|| Profiles {
+            custom_macros: Vec::new(),
+            profiles: Vec::new(),
+            plugins: Vec::new(),
+        });
+        for profile in profiles.profiles.iter_mut() {
+            for custom in &plugin.profiles.custom_macros {
+                if !profile.custom_macros.iter().any(|m| m.name == custom.name) {
+                    profile.custom_macros.push(custom.clone());
+                }
+            }
         }
+        profiles.merge(plugin.profiles);
+        Ok(self)
+    }
+
+    /// Parse the files of every table in a dedicated pool of `num_threads` threads.
+    ///
+    /// Tables registered with [`QueryOptions::with_num_threads`] still get their own pool.
+    pub fn with_num_threads(self, num_threads: usize) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|e| Error::Config(format!("Cannot build thread pool: {}", e)))?;
+        Ok(self.with_thread_pool(Arc::new(pool)))
+    }
+
+    /// Parse the files of every table in `thread_pool`.
+    pub fn with_thread_pool(mut self, thread_pool: Arc<ThreadPool>) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+
+    /// Session with lflog's functions registered.
+    fn session() -> SessionContext {
+        let ctx = SessionContext::new();
+        register_udfs(&ctx);
+        ctx
+    }
+
+    /// Look up a profile by name in the loaded config, falling back to builtin profiles.
+    pub fn find_profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get_profile(name))
+            .or_else(|| builtin::profiles().get_profile(name))
     }
 
     /// Register a log file for querying.
     ///
-    /// The pattern is determined in the following order:
+    /// The extractor is determined in the following order:
     /// 1. `pattern_override` if provided
-    /// 2. Profile's pattern if `profile_name` is provided
+    /// 2. Profile's format (its pattern for regex profiles) if `profile_name` is
+    ///    provided (config first, then builtin)
     /// 3. Error if neither is provided
     pub fn register(&self, options: QueryOptions) -> Result<()> {
-        // Determine the pattern to use
-        let (pattern, custom_macros) =
-            if let Some(ref override_pattern) = options.pattern_override {
-                // Use override pattern with profile's macros if available
-                let macros = if let (Some(profiles), Some(profile_name)) =
-                    (&self.profiles, &options.profile_name)
-                {
-                    profiles
-                        .get_profile(profile_name)
-                        .map(|p| p.custom_macros.clone())
-                } else {
-                    self.profiles
-                        .as_ref()
-                        .map(|profiles| profiles.custom_macros.clone())
-                };
-                (override_pattern.clone(), macros)
-            } else if let Some(ref profile_name) = options.profile_name {
-                // Use profile's pattern
-                let profiles = self.profiles.as_ref().ok_or_else(|| {
-                    Error::Config("No profiles loaded, cannot use --profile".into())
-                })?;
-                let profile = profiles.get_profile(profile_name).ok_or_else(|| {
-                    Error::Config(format!("Profile '{}' not found", profile_name))
-                })?;
-                (profile.pattern.clone(), Some(profile.custom_macros.clone()))
-            } else {
-                return Err(Error::Config(
-                    "Either --profile or --pattern must be provided".into(),
-                ));
-            };
-
-        // Create scanner with the pattern and custom macros
-        let scanner = if let Some(macros) = custom_macros {
-            Scanner::with_custom_macros(pattern, Some(&macros))?
+        check_min_match_rate(options.min_match_rate)?;
+        let profile = options
+            .profile_name
+            .as_deref()
+            .and_then(|name| self.find_profile(name));
+        let skip_lines = options
+            .skip_lines
+            .unwrap_or_else(|| profile.map_or(0, |p| p.skip_lines));
+        let skip_footer = options
+            .skip_footer
+            .unwrap_or_else(|| profile.map_or(0, |p| p.skip_footer));
+        let encoding = options
+            .encoding
+            .as_deref()
+            .or_else(|| profile.and_then(|p| p.encoding.as_deref()))
+            .map(|label| {
+                Encoding::for_label(label.as_bytes())
+                    .ok_or_else(|| Error::Config(format!("Unknown encoding '{}'", label)))
+            })
+            .transpose()?;
+        let extractor = self.extractor(&options, encoding, skip_lines)?;
+        let extractor: Arc<dyn RecordExtractor> = if options.redact.is_empty() {
+            extractor
         } else {
-            Scanner::new(pattern)?
+            Arc::new(RedactExtractor::new(
+                extractor,
+                parse_redactions(&options.redact)?,
+            )?)
         };
+        let year = options
+            .year
+            .or_else(|| profile.and_then(|p| p.year))
+            .unwrap_or_default();
+        let timestamp_unit = options
+            .timestamp_unit
+            .or_else(|| profile.and_then(|p| p.timestamp_unit))
+            .unwrap_or_default();
+        let duration_unit = options
+            .duration_unit
+            .or_else(|| profile.and_then(|p| p.duration_unit))
+            .unwrap_or(TimestampUnit::Millis);
 
         // Create table provider and register it
-        let table = LogTableProvider::new(
-            scanner,
-            options.log_file,
-            options.add_file_path,
-            options.add_raw,
-            options.num_threads,
-        );
-        self.ctx
-            .register_table(&options.table_name, Arc::new(table))?;
+        let builder = LogTableProvider::builder_with_extractor(extractor);
+        let builder = match encoding {
+            Some(encoding) => builder.with_encoding(encoding),
+            None => builder,
+        };
+        let table = builder
+            .with_max_line_length(options.max_line_length, options.long_lines)
+            .with_file_paths(options.log_files)
+            .with_add_file_path(options.add_file_path || options.dedup)
+            .with_add_raw(options.add_raw)
+            .with_add_file_mtime(options.add_file_mtime)
+            .with_add_file_size(options.add_file_size)
+            .with_add_pattern(options.add_pattern)
+            .with_time_ordered(options.time_ordered)
+            .with_merge_by_time(options.merge_by_time)
+            .with_min_match_rate(options.min_match_rate)
+            .with_index(options.index)
+            .with_skip_lines(skip_lines)
+            .with_skip_footer(skip_footer)
+            .with_year(year)
+            .with_timestamp_unit(timestamp_unit)
+            .with_duration_unit(duration_unit)
+            .with_num_threads(options.num_threads)
+            .with_thread_pool(match options.num_threads {
+                Some(_) => None,
+                None => self.thread_pool.clone(),
+            })
+            .build();
+        if options.register_unmatched {
+            self.ctx.register_table(
+                format!("{}__unmatched", options.table_name),
+                Arc::new(table.unmatched_table()),
+            )?;
+        }
+        let table: Arc<dyn TableProvider> = Arc::new(table);
+        let table = match options.dedup {
+            true => Arc::new(dedup_view(table, options.add_file_path)?),
+            false => table,
+        };
+        self.ctx.register_table(&options.table_name, table)?;
 
         Ok(())
     }
 
+    /// Record extractor for `options`: the override pattern or the profile's format.
+    ///
+    /// Types are inferred from the files read as the table reads them: transcoded
+    /// from `encoding` and without their first `skip_lines` lines.
+    fn extractor(
+        &self,
+        options: &QueryOptions,
+        encoding: Option<&'static Encoding>,
+        skip_lines: usize,
+    ) -> Result<Arc<dyn RecordExtractor>> {
+        let (pattern, custom_macros) = if let Some(ref override_pattern) = options.pattern_override
+        {
+            // Use override pattern with profile's macros if available
+            let macros = if let Some(profile_name) = &options.profile_name {
+                self.find_profile(profile_name)
+                    .map(|p| p.custom_macros.clone())
+            } else {
+                self.profiles
+                    .as_ref()
+                    .map(|profiles| profiles.custom_macros.clone())
+            };
+            (override_pattern.clone(), macros)
+        } else if let Some(ref profile_name) = options.profile_name {
+            let profile = self
+                .find_profile(profile_name)
+                .ok_or_else(|| Error::Config(format!("Profile '{}' not found", profile_name)))?;
+            return Ok(profile.extractor()?);
+        } else {
+            return Err(Error::Config(
+                "Either --profile or --pattern must be provided".into(),
+            ));
+        };
+
+        // Create scanner with the patterns and custom macros
+        let patterns = std::iter::once(pattern)
+            .chain(options.fallback_patterns.iter().cloned())
+            .collect();
+        let scanner =
+            Scanner::with_patterns_and_engine(patterns, custom_macros.as_deref(), options.engine)?
+                .with_flags(&options.flags)?;
+        let scanner = match &options.prefilter {
+            Some(literal) => scanner.with_prefilter(literal),
+            None => scanner,
+        };
+        let scanner = match &options.exclude_pattern {
+            Some(exclude) => scanner.with_exclude_pattern(exclude, custom_macros.as_deref())?,
+            None => scanner,
+        };
+        Ok(Arc::new(match options.infer_types {
+            Some(sample) => {
+                let lines = sample_matching_lines(
+                    &scanner,
+                    &options.log_files,
+                    sample,
+                    encoding,
+                    skip_lines,
+                )?;
+                scanner.with_inferred_types(lines)
+            }
+            None => scanner,
+        }))
+    }
+
     /// Execute a SQL query and return results as a DataFrame.
     pub async fn query(&self, sql: &str) -> Result<DataFrame> {
         let df = self.ctx.sql(sql).await?;
@@ -190,6 +685,144 @@ impl LfLog {
         Ok(())
     }
 
+    /// Plan a SQL query whose scans stop when `token` is cancelled.
+    ///
+    /// The token applies when the returned DataFrame is executed, e.g. by `collect()`.
+    pub async fn query_with_token(
+        &self,
+        sql: &str,
+        token: &CancellationToken,
+    ) -> Result<DataFrame> {
+        // The state shares this context's catalog, so registered tables stay visible
+        let mut state = self.ctx.state();
+        state.config_mut().set_extension(Arc::new(token.clone()));
+        let df = SessionContext::new_with_state(state).sql(sql).await?;
+        Ok(df)
+    }
+
+    /// Execute a SQL query, failing with [`Error::Timeout`] if it runs longer than `timeout`.
+    pub async fn query_with_timeout(
+        &self,
+        sql: &str,
+        timeout: Duration,
+    ) -> Result<Vec<RecordBatch>> {
+        let token = CancellationToken::new().with_timeout(timeout);
+        let df = self.query_with_token(sql, &token).await?;
+        match tokio::time::timeout(timeout, df.collect()).await {
+            Ok(batches) => batches.map_err(Error::from_datafusion),
+            Err(_) => {
+                token.cancel();
+                Err(Error::Timeout(timeout))
+            }
+        }
+    }
+
+    /// Execute a SQL query and print results to stdout, stopping when `token` is cancelled.
+    pub async fn query_and_show_with_token(
+        &self,
+        sql: &str,
+        token: &CancellationToken,
+    ) -> Result<()> {
+        let df = self.query_with_token(sql, token).await?;
+        df.show().await.map_err(Error::from_datafusion)
+    }
+
+    /// Top `limit` values of `column` in `table` with their counts and percentages.
+    ///
+    /// The result has the column itself, `count` and `percent` (of all rows, NULLs
+    /// included), ordered by descending count.
+    pub async fn frequencies(&self, table: &str, column: &str, limit: usize) -> Result<DataFrame> {
+        self.frequencies_with_token(table, column, limit, &CancellationToken::new())
+            .await
+    }
+
+    /// [`LfLog::frequencies`] whose scan stops when `token` is cancelled.
+    pub async fn frequencies_with_token(
+        &self,
+        table: &str,
+        column: &str,
+        limit: usize,
+        token: &CancellationToken,
+    ) -> Result<DataFrame> {
+        let provider = self.ctx.table_provider(quote_ident(table)).await?;
+        if provider.schema().field_with_name(column).is_err() {
+            return Err(Error::Config(format!(
+                "Table '{}' has no column '{}'",
+                table, column
+            )));
+        }
+        // A single-column GROUP BY keeps dictionary-encoded columns grouped by key
+        let sql = format!(
+            "SELECT {column}, COUNT(*) AS count, \
+             ROUND(100.0 * COUNT(*) / SUM(COUNT(*)) OVER (), 2) AS percent \
+             FROM {table} GROUP BY {column} ORDER BY count DESC, {column} LIMIT {limit}",
+            column = quote_ident(column),
+            table = quote_ident(table),
+            limit = limit,
+        );
+        self.query_with_token(&sql, token).await
+    }
+
+    /// Events of `table` that appear in more than one file.
+    ///
+    /// Events are grouped by `columns`, or by every non-metadata column when empty, e.g.
+    /// a timestamp and message. The result has those columns, `files` (the number of
+    /// files holding the event), `copies` (its total rows) and `file_list`, ordered by
+    /// descending `copies`. `table` must have been registered with a `__FILE__` column.
+    pub async fn duplicates(&self, table: &str, columns: &[&str]) -> Result<DataFrame> {
+        let provider = self.ctx.table_provider(quote_ident(table)).await?;
+        let schema = provider.schema();
+        if schema.field_with_name("__FILE__").is_err() {
+            return Err(Error::Config(format!(
+                "Table '{}' needs a __FILE__ column to find duplicates across files",
+                table
+            )));
+        }
+        let keys: Vec<String> = if columns.is_empty() {
+            schema
+                .fields()
+                .iter()
+                .map(|f| f.name().as_str())
+                .filter(|name| !METADATA_COLUMNS.contains(name))
+                .map(quote_ident)
+                .collect()
+        } else {
+            columns
+                .iter()
+                .map(|&name| match schema.field_with_name(name) {
+                    Ok(_) => Ok(quote_ident(name)),
+                    Err(_) => Err(Error::Config(format!(
+                        "Table '{}' has no column '{}'",
+                        table, name
+                    ))),
+                })
+                .collect::<Result<_>>()?
+        };
+        let keys = keys.join(", ");
+        let sql = format!(
+            "SELECT {keys}, COUNT(DISTINCT \"__FILE__\") AS files, COUNT(*) AS copies, \
+             array_agg(DISTINCT \"__FILE__\" ORDER BY \"__FILE__\") AS file_list \
+             FROM {table} GROUP BY {keys} HAVING COUNT(DISTINCT \"__FILE__\") > 1 \
+             ORDER BY copies DESC",
+            keys = keys,
+            table = quote_ident(table),
+        );
+        self.query(&sql).await
+    }
+
+    /// Lines and bytes counted by the scans of `table` so far, to alert when its match
+    /// rate drops after a log format change.
+    ///
+    /// `None` if `table` is not a log table or its extractor keeps no counts, as only
+    /// regex profiles and patterns do.
+    pub async fn scan_stats(&self, table: &str) -> Result<Option<ScanCounts>> {
+        let provider = self.ctx.table_provider(quote_ident(table)).await?;
+        Ok(provider
+            .as_any()
+            .downcast_ref::<LogTableProvider>()
+            .and_then(LogTableProvider::scan_stats))
+    }
+
     /// Get the underlying SessionContext for advanced usage.
     pub fn context(&self) -> &SessionContext {
         &self.ctx
@@ -202,6 +835,90 @@ impl Default for LfLog {
     }
 }
 
+/// Metadata columns, which describe where a row was read rather than the event.
+const METADATA_COLUMNS: [&str; 5] = [
+    "__FILE__",
+    "__RAW__",
+    "__FILE_MTIME__",
+    "__FILE_SIZE__",
+    "__PATTERN__",
+];
+
+/// View over `table` keeping each event only from the first file (by path) it appears in.
+///
+/// Events are rows with equal values in every non-metadata column. Repeats within that
+/// file are kept. `table` must have a `__FILE__` column, which the view drops unless
+/// `keep_file_path` is set.
+fn dedup_view(table: Arc<dyn TableProvider>, keep_file_path: bool) -> Result<ViewTable> {
+    let schema = table.schema();
+    let keys: Vec<Expr> = schema
+        .fields()
+        .iter()
+        .filter(|f| !METADATA_COLUMNS.contains(&f.name().as_str()))
+        .map(|f| ident(f.name()))
+        .collect();
+    let first_file = Expr::from(WindowFunction::new(
+        WindowFunctionDefinition::AggregateUDF(min_udaf()),
+        vec![ident("__FILE__")],
+    ))
+    .partition_by(keys)
+    .build()?
+    .alias("__first_file__");
+    let columns: Vec<Expr> = schema
+        .fields()
+        .iter()
+        .filter(|f| keep_file_path || f.name() != "__FILE__")
+        .map(|f| ident(f.name()))
+        .collect();
+    let plan = LogicalPlanBuilder::scan("events", provider_as_source(table), None)?
+        .window(vec![first_file])?
+        .filter(ident("__FILE__").eq(ident("__first_file__")))?
+        .project(columns)?
+        .build()?;
+    Ok(ViewTable::new(plan, None))
+}
+
+/// The first `sample` lines of `log_files` that `scanner` matches, in file order.
+///
+/// Files are transcoded from `encoding`, if any, and their first `skip_lines` lines
+/// are ignored, as when the table reads them.
+fn sample_matching_lines(
+    scanner: &Scanner,
+    log_files: &[String],
+    sample: usize,
+    encoding: Option<&'static Encoding>,
+    skip_lines: usize,
+) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    for file in resolve_file_list(log_files)? {
+        let reader: Box<dyn BufRead> = match encoding {
+            Some(encoding) => {
+                let data = std::fs::read(&file)?;
+                let (text, _, _) = encoding.decode(&data);
+                Box::new(Cursor::new(text.into_owned().into_bytes()))
+            }
+            None => Box::new(BufReader::new(std::fs::File::open(&file)?)),
+        };
+        for line in reader.split(b'\n').skip(skip_lines) {
+            if lines.len() >= sample {
+                return Ok(lines);
+            }
+            let line = line?;
+            let line = String::from_utf8_lossy(&line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if scanner.is_match(line) {
+                lines.push(line.to_string());
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// Quote an identifier for SQL, keeping its case.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,4 +938,636 @@ mod tests {
         let batches = df.collect().await.unwrap();
         assert!(!batches.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_lflog_builtin_profile() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(
+            temp_file,
+            "2024-01-15 10:23:45.123 UTC [12345] LOG:  duration: 12.5 ms  statement: SELECT 1"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let lflog = LfLog::new();
+        let options =
+            QueryOptions::new(temp_file.path().to_string_lossy()).with_profile("postgres_duration");
+        lflog.register(options).unwrap();
+
+        let df = lflog
+            .query("SELECT duration_ms, query FROM log")
+            .await
+            .unwrap();
+        let batches = df.collect().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lflog_record_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("app.json"),
+            "{\"level\":\"error\",\"status\":500}\nnot json\n{\"level\":\"info\",\"status\":200}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("app.logfmt"),
+            "level=warn status=429 msg=\"slow down\"\n",
+        )
+        .unwrap();
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "json"
+format = "json"
+fields = [{ name = "level" }, { name = "status", type_hint = "Int" }]
+
+[[profiles]]
+name = "logfmt"
+format = "logfmt"
+fields = [{ name = "level" }, { name = "msg" }]
+"#,
+            crate::ProfileFormat::Toml,
+        )
+        .unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        let lflog = LfLog::from_profiles(profiles);
+        lflog
+            .register(QueryOptions::new(path("app.json")).with_profile("json"))
+            .unwrap();
+        lflog
+            .register(
+                QueryOptions::new(path("app.logfmt"))
+                    .with_profile("logfmt")
+                    .with_table_name("lf"),
+            )
+            .unwrap();
+
+        let batches = lflog
+            .query("SELECT SUM(status) FROM log WHERE level = 'error' OR level = 'info'")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let sum = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<datafusion::arrow::array::Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(sum, 700);
+
+        let batches = lflog
+            .query("SELECT msg FROM lf")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let msg = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<datafusion::arrow::array::StringArray>()
+            .unwrap()
+            .value(0)
+            .to_string();
+        assert_eq!(msg, "slow down");
+    }
+
+    #[tokio::test]
+    async fn test_lflog_shared_thread_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let lines: String = (0..1000).map(|i| format!("{} msg\n", i)).collect();
+        std::fs::write(dir.path().join("a.log"), &lines).unwrap();
+        let path = dir.path().join("a.log").to_string_lossy().to_string();
+
+        let lflog = LfLog::new().with_num_threads(2).unwrap();
+        assert_eq!(lflog.thread_pool.as_ref().unwrap().current_num_threads(), 2);
+        for (table, num_threads) in [("shared", None), ("own", Some(3))] {
+            lflog
+                .register(
+                    QueryOptions::new(&path)
+                        .with_pattern(r"^{{id:number}} {{msg:any}}$")
+                        .with_table_name(table)
+                        .with_num_threads(num_threads),
+                )
+                .unwrap();
+        }
+
+        let batches = lflog
+            .query("SELECT COUNT(*) FROM shared UNION ALL SELECT COUNT(*) FROM own")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let counts: Vec<i64> = batches
+            .iter()
+            .map(|b| {
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<datafusion::arrow::array::Int64Array>()
+                    .unwrap()
+                    .value(0)
+            })
+            .collect();
+        assert_eq!(counts, vec![1000, 1000]);
+    }
+
+    #[tokio::test]
+    async fn test_lflog_multiple_log_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("archive")).unwrap();
+        std::fs::write(dir.path().join("archive/access.log"), "1 old\n2 old\n").unwrap();
+        std::fs::write(dir.path().join("access.log"), "3 new\n").unwrap();
+        std::fs::write(dir.path().join("extra.txt"), "4 extra\n").unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        let lflog = LfLog::new();
+        let options = QueryOptions::new(path("access.log*"))
+            .add_log_file(path("archive/*.log"))
+            .add_log_file(path("extra.txt"))
+            .with_pattern(r"^{{id:number}} {{msg:any}}$");
+        lflog.register(options).unwrap();
+
+        let batches = lflog
+            .query("SELECT COUNT(*) FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<datafusion::arrow::array::Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(count, 4);
+    }
+
+    #[tokio::test]
+    async fn test_lflog_frequencies() {
+        use datafusion::arrow::array::{Float64Array, Int64Array, StringArray};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("levels.log");
+        std::fs::write(&path, "1 error\n2 info\n3 info\n4 warn\n5 info\n6 error\n").unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^{{id:number}} {{Level:var_name}}$"),
+            )
+            .unwrap();
+
+        let batches = lflog
+            .frequencies("log", "Level", 2)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch =
+            datafusion::arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        let column = |i: usize| batch.column(i).as_any();
+        let values = column(0).downcast_ref::<StringArray>().unwrap();
+        let counts = column(1).downcast_ref::<Int64Array>().unwrap();
+        let percents = column(2).downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!((values.value(0), counts.value(0)), ("info", 3));
+        assert_eq!((values.value(1), counts.value(1)), ("error", 2));
+        assert_eq!(percents.value(0), 50.0);
+        assert_eq!(percents.value(1), 33.33);
+
+        assert!(lflog.frequencies("log", "level", 2).await.is_err());
+    }
+
+    #[test]
+    fn test_lflog_rejects_match_rate_outside_fraction() {
+        let lflog = LfLog::new();
+        let options = |rate| {
+            QueryOptions::new("app.log")
+                .with_pattern(r"^{{id:number}}$")
+                .with_min_match_rate(Some(rate))
+        };
+        let err = lflog.register(options(95.0)).unwrap_err();
+        assert!(matches!(err, Error::Config(_)), "{}", err);
+        assert!(lflog.register(options(-0.1)).is_err());
+        assert!(lflog.register(options(0.95)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_lflog_duplicates_and_dedup() {
+        use datafusion::arrow::array::{Int64Array, StringArray};
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("a.log"),
+            "1 start\n2 tick\n2 tick\n3 stop\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("b.log"), "2 tick\n3 stop\n4 exit\n").unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let options = |table: &str| {
+            QueryOptions::new(path("a.log"))
+                .add_log_file(path("b.log"))
+                .with_pattern(r"^{{ts:number}} {{msg:var_name}}$")
+                .with_table_name(table)
+        };
+
+        let lflog = LfLog::new();
+        lflog
+            .register(options("plain").with_add_file_path(true))
+            .unwrap();
+        lflog.register(options("dedup").with_dedup(true)).unwrap();
+
+        let batches = lflog
+            .duplicates("plain", &[])
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch =
+            datafusion::arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        let msgs = batch
+            .column_by_name("msg")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let copies = batch
+            .column_by_name("copies")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!((msgs.value(0), copies.value(0)), ("tick", 3));
+        assert_eq!((msgs.value(1), copies.value(1)), ("stop", 2));
+
+        // Repeats within the first file survive; copies from the second file do not
+        let batches = lflog
+            .query("SELECT msg FROM dedup ORDER BY ts, msg")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].num_columns(), 1);
+        let msgs: Vec<String> = batches
+            .iter()
+            .flat_map(|b| {
+                let col = b.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+                col.iter()
+                    .map(|v| v.unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(msgs, vec!["start", "tick", "tick", "stop", "exit"]);
+
+        assert!(lflog.duplicates("dedup", &[]).await.is_err());
+        assert!(lflog.duplicates("plain", &["nope"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lflog_multiple_patterns() {
+        use datafusion::arrow::array::{Int64Array, StringArray};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mixed.log");
+        std::fs::write(&path, "1 INFO started\n-- checkpoint\n2 WARN slow\nnoise\n").unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_patterns([
+                        r"^{{ts:number}} {{level:var_name}} {{msg:any}}$",
+                        r"^-- {{msg:any}}$",
+                    ])
+                    .with_add_pattern(true),
+            )
+            .unwrap();
+
+        let batches = lflog
+            .query(r#"SELECT msg, level, "__PATTERN__" FROM log"#)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch =
+            datafusion::arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        let msgs = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let levels = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let patterns = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        let rows: Vec<(&str, &str, i64)> = (0..batch.num_rows())
+            .map(|i| (msgs.value(i), levels.value(i), patterns.value(i)))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("started", "INFO", 0),
+                ("checkpoint", "", 1),
+                ("slow", "WARN", 0)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lflog_prefilter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("apache.log");
+        std::fs::write(
+            &path,
+            "1 jk2_init() Found child\n2 workerEnv.init() ok\n3 jk2_init() Found child\n",
+        )
+        .unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^{{id:number}} {{msg:any}}$")
+                    .with_prefilter(Some("jk2_init()".to_string())),
+            )
+            .unwrap();
+        let batches = lflog
+            .query("SELECT COUNT(*) FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let count = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<datafusion::arrow::array::Int64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(count, 2);
+    }
+
+    fn register_large_log(lflog: &LfLog, dir: &tempfile::TempDir) {
+        use std::io::Write;
+
+        let path = dir.path().join("big.log");
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+        for i in 0..200_000 {
+            writeln!(file, "{} INFO message number {}", i, i).unwrap();
+        }
+        file.flush().unwrap();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^{{id:number}} {{level:var_name}} {{msg:any}}$")
+                    .with_num_threads(Some(2)),
+            )
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_lflog_query_cancellation() {
+        let dir = tempfile::tempdir().unwrap();
+        let lflog = LfLog::new();
+        register_large_log(&lflog, &dir);
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let df = lflog
+            .query_with_token("SELECT COUNT(*) FROM log", &token)
+            .await
+            .unwrap();
+        let err = df
+            .collect()
+            .await
+            .map_err(Error::from_datafusion)
+            .unwrap_err();
+        assert!(matches!(err, Error::Cancelled), "{}", err);
+
+        let err = lflog
+            .query_with_timeout("SELECT COUNT(*) FROM log", Duration::ZERO)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Timeout(_)), "{}", err);
+
+        let batches = lflog
+            .query_with_timeout("SELECT COUNT(*) FROM log", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(batches[0].num_rows(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lflog_memory_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let lflog = LfLog::new().with_memory_limit(64 * 1024).unwrap();
+        register_large_log(&lflog, &dir);
+
+        let err = lflog
+            .query("SELECT * FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Resources exhausted"), "{}", err);
+
+        // Peak memory is reported in the scan's metrics
+        let lflog = LfLog::new();
+        register_large_log(&lflog, &dir);
+        let batches = lflog
+            .query("EXPLAIN ANALYZE SELECT COUNT(*) FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let plan = datafusion::arrow::util::pretty::pretty_format_batches(&batches)
+            .unwrap()
+            .to_string();
+        assert!(plan.contains("LogTableExec"), "{}", plan);
+        assert!(plan.contains("peak_mem_used="), "{}", plan);
+    }
+
+    #[tokio::test]
+    async fn test_lflog_infer_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "200 0.5\nheader\n503 1.25\n").unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^(?P<status>\d+) (?P<took>\S+)$")
+                    .with_infer_types(Some(DEFAULT_INFER_SAMPLE_LINES)),
+            )
+            .unwrap();
+        let batches = lflog
+            .query("SELECT SUM(status), SUM(took) FROM log WHERE status >= 500")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert_eq!(
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<datafusion::arrow::array::Int64Array>()
+                .unwrap()
+                .value(0),
+            503
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<datafusion::arrow::array::Float64Array>()
+                .unwrap()
+                .value(0),
+            1.25
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lflog_infer_types_reads_like_the_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("service.log");
+        // A banner that would make `took` a string, then UTF-16 records
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend(
+            "000 n/a\r\n200 0.5\r\n503 1.25\r\n"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        std::fs::write(&path, bytes).unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^(?P<status>\d+) (?P<took>\S+)$")
+                    .with_encoding(Some("utf-16le".into()))
+                    .with_skip_lines(Some(1))
+                    .with_infer_types(Some(DEFAULT_INFER_SAMPLE_LINES)),
+            )
+            .unwrap();
+        let batches = lflog
+            .query("SELECT SUM(status), SUM(took) FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        assert_eq!(
+            batch
+                .column(0)
+                .as_any()
+                .downcast_ref::<datafusion::arrow::array::Int64Array>()
+                .unwrap()
+                .value(0),
+            703
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_any()
+                .downcast_ref::<datafusion::arrow::array::Float64Array>()
+                .unwrap()
+                .value(0),
+            1.75
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lflog_redact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("signup.log");
+        std::fs::write(&path, "a@x.io 10.0.0.1 t0k\nb@y.io 10.0.0.2 t1k\n").unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^(?P<email>\S+) (?P<ip>\S+) (?P<token>\S+)$")
+                    .with_redact(HashMap::from([
+                        ("email".to_string(), "hash".to_string()),
+                        ("ip".to_string(), "mask".to_string()),
+                        ("token".to_string(), "drop".to_string()),
+                    ])),
+            )
+            .unwrap();
+        let batches = lflog
+            .query("SELECT * FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&batches)
+            .unwrap()
+            .to_string();
+        assert!(!formatted.contains("token"), "{}", formatted);
+        assert!(!formatted.contains('@'), "{}", formatted);
+        assert!(!formatted.contains("10.0"), "{}", formatted);
+        assert!(formatted.contains("| ***"), "{}", formatted);
+    }
+
+    #[tokio::test]
+    async fn test_lflog_scan_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let text = "200 /a\nGET /health\n\nboom\n404 /b\n";
+        std::fs::write(&path, text).unwrap();
+        let lflog = LfLog::new();
+        lflog
+            .register(
+                QueryOptions::new(path.to_string_lossy())
+                    .with_pattern(r"^(?P<status>\d+) (?P<path>\S+)$")
+                    .with_exclude_pattern(Some("/health".to_string())),
+            )
+            .unwrap();
+        assert_eq!(
+            lflog.scan_stats("log").await.unwrap(),
+            Some(ScanCounts::default())
+        );
+        for _ in 0..2 {
+            lflog
+                .query("SELECT COUNT(*) FROM log")
+                .await
+                .unwrap()
+                .collect()
+                .await
+                .unwrap();
+        }
+        let stats = lflog.scan_stats("log").await.unwrap().unwrap();
+        assert_eq!(
+            stats,
+            ScanCounts {
+                matched: 4,
+                unmatched: 2,
+                skipped: 2,
+                bytes: 2 * text.len() as u64,
+            }
+        );
+        assert_eq!(stats.match_rate(), Some(4.0 / 6.0));
+    }
 }
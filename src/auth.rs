@@ -0,0 +1,227 @@
+//! Canned security summaries over auth logs.
+//!
+//! Registers the sysadmin builtin profiles against an auth log (e.g.
+//! `/var/log/auth.log` or `/var/log/secure`) and runs a fixed set of reports, so
+//! responders get failed logins, su/sudo activity and brute-force bursts without
+//! writing SQL.
+
+use datafusion::arrow::record_batch::RecordBatch;
+
+use crate::app::{LfLog, QueryOptions};
+use crate::error::Result;
+
+/// Tables registered by [`register`] as `(table, builtin profile)` pairs.
+pub const TABLES: &[(&str, &str)] = &[
+    ("sshd_auth", "sshd_auth"),
+    ("sshd_invalid_user", "sshd_invalid_user"),
+    ("su", "su"),
+    ("sudo", "sudo"),
+];
+
+/// Default number of failed attempts from one IP within 60 seconds that counts as a
+/// burst.
+pub const DEFAULT_BURST_THRESHOLD: u32 = 5;
+
+/// A named report and its SQL.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub title: &'static str,
+    pub sql: String,
+}
+
+/// Register one table per auth profile in [`TABLES`] over `log_files` (paths or globs).
+pub fn register(lflog: &LfLog, log_files: &[String], num_threads: Option<u32>) -> Result<()> {
+    for (table, profile) in TABLES {
+        lflog.register(
+            QueryOptions::default()
+                .with_log_files(log_files)
+                .with_profile(*profile)
+                .with_table_name(*table)
+                .with_num_threads(num_threads),
+        )?;
+    }
+    Ok(())
+}
+
+/// The canned reports, `limit` rows each.
+///
+/// Bursts count each IP's failed attempts in a window sliding over the 60 seconds up
+/// to every attempt, so bursts straddling a minute boundary are found too. Syslog
+/// timestamps carry no year, so they are parsed in the leap year 2000; a burst
+/// spanning New Year is split in two.
+pub fn reports(limit: usize, burst_threshold: u32) -> Vec<Report> {
+    vec![
+        Report {
+            title: "Failed logins by IP",
+            sql: format!(
+                "SELECT ip, COUNT(*) AS attempts, COUNT(DISTINCT user) AS users, \
+                 MIN(timestamp) AS first_seen, MAX(timestamp) AS last_seen \
+                 FROM sshd_auth WHERE result = 'Failed' \
+                 GROUP BY ip ORDER BY attempts DESC, ip LIMIT {limit}"
+            ),
+        },
+        Report {
+            title: "Failed logins by user",
+            sql: format!(
+                "SELECT user, invalid_user = 'invalid user' AS invalid, COUNT(*) AS attempts, \
+                 COUNT(DISTINCT ip) AS ips \
+                 FROM sshd_auth WHERE result = 'Failed' \
+                 GROUP BY user, invalid ORDER BY attempts DESC, user LIMIT {limit}"
+            ),
+        },
+        Report {
+            title: "Invalid users probed",
+            sql: format!(
+                "SELECT user, COUNT(*) AS attempts, COUNT(DISTINCT ip) AS ips \
+                 FROM sshd_invalid_user \
+                 GROUP BY user ORDER BY attempts DESC, user LIMIT {limit}"
+            ),
+        },
+        Report {
+            title: "Successful logins",
+            sql: format!(
+                "SELECT user, ip, method, COUNT(*) AS logins, MAX(timestamp) AS last_seen \
+                 FROM sshd_auth WHERE result = 'Accepted' \
+                 GROUP BY user, ip, method ORDER BY logins DESC, user, ip LIMIT {limit}"
+            ),
+        },
+        Report {
+            title: "Brute-force bursts",
+            sql: format!(
+                "SELECT ip, MAX(in_window) AS peak_per_minute, COUNT(*) AS attempts, \
+                 COUNT(DISTINCT user) AS users, \
+                 first_value(timestamp ORDER BY ts) AS first_seen, \
+                 last_value(timestamp ORDER BY ts) AS last_seen \
+                 FROM (SELECT ip, user, timestamp, ts, COUNT(*) OVER (PARTITION BY ip ORDER BY ts \
+                 RANGE BETWEEN INTERVAL '59 seconds' PRECEDING AND CURRENT ROW) AS in_window \
+                 FROM (SELECT ip, user, timestamp, \
+                 to_timestamp(concat('2000 ', timestamp), '%Y %b %e %H:%M:%S') AS ts \
+                 FROM sshd_auth WHERE result = 'Failed')) \
+                 GROUP BY ip HAVING MAX(in_window) >= {burst_threshold} \
+                 ORDER BY peak_per_minute DESC, ip LIMIT {limit}"
+            ),
+        },
+        Report {
+            title: "su events",
+            sql: format!(
+                "SELECT user, target_user, failed = 'FAILED SU' AS failed, COUNT(*) AS events, \
+                 MAX(timestamp) AS last_seen \
+                 FROM su GROUP BY user, target_user, failed \
+                 ORDER BY failed DESC, events DESC, user LIMIT {limit}"
+            ),
+        },
+        Report {
+            title: "sudo events",
+            sql: format!(
+                "SELECT user, target_user, failure, command, COUNT(*) AS events, \
+                 MAX(timestamp) AS last_seen \
+                 FROM sudo GROUP BY user, target_user, failure, command \
+                 ORDER BY failure IS NULL OR failure = '', events DESC, user LIMIT {limit}"
+            ),
+        },
+    ]
+}
+
+/// Run every report, returning its title and results.
+pub async fn run(
+    lflog: &LfLog,
+    limit: usize,
+    burst_threshold: u32,
+) -> Result<Vec<(&'static str, Vec<RecordBatch>)>> {
+    let mut results = Vec::new();
+    for report in reports(limit, burst_threshold) {
+        let batches = lflog.query(&report.sql).await?.collect().await?;
+        results.push((report.title, batches));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_auth_reports() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        // A burst straddling a minute boundary: 10:23:40 to 10:24:05
+        for i in 0..6 {
+            writeln!(
+                temp_file,
+                "Jan 15 10:2{}:{:02} web01 sshd[1234]: Failed password for invalid user admin{} from 203.0.113.7 port 5221{} ssh2",
+                3 + (40 + i * 5) / 60,
+                (40 + i * 5) % 60,
+                i % 2,
+                i
+            )
+            .unwrap();
+        }
+        // As many attempts, but a minute apart
+        for i in 0..5 {
+            writeln!(
+                temp_file,
+                "Jan  5 09:0{}:00 web01 sshd[1230]: Failed password for root from 198.51.100.9 port 4000{} ssh2",
+                i, i
+            )
+            .unwrap();
+        }
+        writeln!(temp_file, "Jan 15 10:25:01 web01 sshd[1240]: Failed password for root from 198.51.100.2 port 40000 ssh2").unwrap();
+        writeln!(
+            temp_file,
+            "Jan 15 10:25:09 web01 sshd[1241]: Invalid user oracle from 198.51.100.2 port 40001"
+        )
+        .unwrap();
+        writeln!(temp_file, "Jan 15 10:26:00 web01 sshd[1250]: Accepted publickey for deploy from 192.0.2.10 port 50000 ssh2").unwrap();
+        writeln!(
+            temp_file,
+            "Jan 15 10:27:00 web01 su[4321]: FAILED SU (to root) alice on pts/0"
+        )
+        .unwrap();
+        writeln!(temp_file, "Jan 15 10:28:00 web01 sudo:    alice : TTY=pts/0 ; PWD=/home/alice ; USER=root ; COMMAND=/usr/bin/id").unwrap();
+        writeln!(
+            temp_file,
+            "Jan 15 10:29:00 web01 CRON[5678]: (root) CMD (run-parts /etc/cron.hourly)"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let lflog = LfLog::new();
+        register(
+            &lflog,
+            &[temp_file.path().to_string_lossy().to_string()],
+            Some(2),
+        )
+        .unwrap();
+        let results = run(&lflog, 10, DEFAULT_BURST_THRESHOLD).await.unwrap();
+        assert_eq!(results.len(), reports(10, DEFAULT_BURST_THRESHOLD).len());
+
+        let report = |title: &str| {
+            let (_, batches) = results.iter().find(|(t, _)| *t == title).unwrap();
+            pretty_format_batches(batches).unwrap().to_string()
+        };
+
+        let by_ip = report("Failed logins by IP");
+        assert!(
+            by_ip.contains("| 203.0.113.7  | 6        | 2     |"),
+            "{}",
+            by_ip
+        );
+        assert!(by_ip.contains("198.51.100.2"));
+
+        let bursts = report("Brute-force bursts");
+        assert!(
+            bursts.contains("| 203.0.113.7 | 6               | 6        | 2     | Jan 15 10:23:40 | Jan 15 10:24:05 |"),
+            "{}",
+            bursts
+        );
+        assert!(!bursts.contains("198.51.100.2"), "{}", bursts);
+        assert!(!bursts.contains("198.51.100.9"), "{}", bursts);
+
+        assert!(report("Invalid users probed").contains("oracle"));
+        assert!(report("Successful logins").contains("deploy"));
+        assert!(report("su events").contains("alice"));
+        assert!(report("sudo events").contains("/usr/bin/id"));
+    }
+}
@@ -1,28 +1,43 @@
 //! lflog CLI - Query log files with SQL.
 //!
-//! Usage: lflog [OPTIONS] <LOG_FILE>
+//! Usage: lflog [OPTIONS] <LOG_FILE>...
+//!        lflog profiles fetch <URL|NAME>
+//!        lflog auth <LOG_FILE>...
 //!
 //! Config file resolution order:
 //! 1. --config <path> if provided
 //! 2. LFLOG_CONFIG environment variable
 //! 3. ~/.config/lflog/config.toml (default)
+//!
+//! Profile packs installed in ~/.config/lflog/profiles.d are merged in after the config.
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use datafusion::arrow::util::pretty::pretty_format_batches;
 use lflog::error::{Error, Result};
 use std::io::{Write, stdout};
 use std::path::PathBuf;
+use std::time::Duration;
 
-use lflog::{LfLog, QueryOptions};
+use lflog::datafusion::LongLines;
+use lflog::macros::parser::Profile;
+use lflog::scanner::Engine;
+use lflog::{CancellationToken, LfLog, Profiles, QueryOptions, TimestampUnit, YearStrategy};
+use lflog::{auth, chart, coverage, export, macros, registry};
 
 /// Query log files with SQL using regex patterns.
 #[derive(Parser)]
 #[command(name = "lflog")]
 #[command(version, about = "Query log files with SQL")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Log file to query.
-    log_file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Log files or glob patterns to query, unioned into one table.
+    #[arg(required = true)]
+    log_files: Vec<String>,
 
-    /// Path to config file (TOML).
+    /// Path to config file (TOML, JSON or YAML).
     /// Default: ~/.config/lflog/config.toml or LFLOG_CONFIG env var.
     #[arg(short, long)]
     config: Option<String>,
@@ -31,9 +46,33 @@ struct Cli {
     #[arg(short, long)]
     profile: Option<String>,
 
-    /// Override pattern (or use without profile).
+    /// Override pattern (or use without profile); repeat to try several in order.
+    #[arg(long)]
+    pattern: Vec<String>,
+
+    /// Regex engine for --pattern: regex, lazy_dfa, hyperscan or pcre2 (default: regex).
+    #[arg(long, requires = "pattern")]
+    engine: Option<Engine>,
+
+    /// Regex flag for --pattern and --exclude: i, m, s, x, U or u, prefixed with - to clear; repeatable.
+    #[arg(long, requires = "pattern", allow_hyphen_values = true)]
+    flag: Vec<String>,
+
+    /// Drop lines matching this pattern (e.g. health checks) before --pattern is tried.
+    #[arg(long, requires = "pattern")]
+    exclude: Option<String>,
+
+    /// Infer Int/Float/Timestamp columns for untyped --pattern groups from N matching lines.
+    #[arg(long, requires = "pattern", num_args = 0..=1, default_missing_value = "1000")]
+    infer_types: Option<usize>,
+
+    /// Plugin library adding macros, profiles and SQL functions; repeatable.
     #[arg(long)]
-    pattern: Option<String>,
+    plugin: Vec<String>,
+
+    /// Substring every line matched by --pattern contains, checked before the regex.
+    #[arg(long, requires = "pattern")]
+    prefilter: Option<String>,
 
     /// Custom table name (default: "log").
     #[arg(short, long, default_value = "log")]
@@ -51,9 +90,262 @@ struct Cli {
     #[arg(short = 'r', long, default_value = "false")]
     add_raw: bool,
 
+    /// Whether to add file modification time column (default: false).
+    #[arg(long, default_value = "false")]
+    add_file_mtime: bool,
+
+    /// Whether to add file size column (default: false).
+    #[arg(long, default_value = "false")]
+    add_file_size: bool,
+
+    /// Whether to add a column with the index of the matching pattern (default: false).
+    #[arg(long, default_value = "false")]
+    add_pattern: bool,
+
+    /// Whether the log is ordered by its first datetime field (default: false).
+    #[arg(long, default_value = "false")]
+    time_ordered: bool,
+
+    /// Merge files by their first datetime field, each file being ordered (default: false).
+    #[arg(long, default_value = "false")]
+    merge_by_time: bool,
+
+    /// Also register `<table>__unmatched` with lines the pattern rejects (default: false).
+    #[arg(short = 'u', long, default_value = "false")]
+    unmatched: bool,
+
+    /// Fail if fewer than 95% of non-empty lines in a file match (default: false).
+    #[arg(long, default_value = "false")]
+    strict: bool,
+
+    /// Fail if a file's match rate is below this fraction, e.g. 0.99 (overrides --strict).
+    #[arg(long, value_parser = parse_match_rate)]
+    min_match_rate: Option<f64>,
+
+    /// Ignore this many leading lines of each file, e.g. a banner (default: the profile's).
+    #[arg(long)]
+    skip_lines: Option<usize>,
+
+    /// Ignore this many trailing lines of each file, e.g. a summary (default: the profile's).
+    #[arg(long)]
+    skip_footer: Option<usize>,
+
+    /// Encoding of the log files, e.g. utf-16le or latin1 (default: the profile's, or utf-8).
+    #[arg(long)]
+    encoding: Option<String>,
+
+    /// Year of timestamps without one: rollover, mtime or a year (default: the profile's,
+    /// or rollover).
+    #[arg(long)]
+    year: Option<YearStrategy>,
+
+    /// Unit of timestamp columns: s, ms, us or ns (default: the profile's, or us).
+    #[arg(long)]
+    timestamp_unit: Option<TimestampUnit>,
+
+    /// Unit of duration(typed) columns: s, ms, us or ns (default: the profile's, or ms).
+    #[arg(long)]
+    duration_unit: Option<TimestampUnit>,
+
+    /// Lines longer than this many bytes are skipped or truncated, see --long-lines.
+    #[arg(long)]
+    max_line_length: Option<usize>,
+
+    /// What to do with lines over --max-line-length: skip or truncate (default: skip).
+    #[arg(long, requires = "max_line_length")]
+    long_lines: Option<LongLines>,
+
+    /// Redact a field at scan time as FIELD=mask, FIELD=drop or FIELD=hash; repeatable.
+    #[arg(long, value_parser = parse_redaction)]
+    redact: Vec<(String, String)>,
+
+    /// Skip blocks for `__RAW__` searches using a token index saved as `<file>.lflogidx` (default: false).
+    #[arg(long, default_value = "false")]
+    index: bool,
+
+    /// Keep each event only from the first file it appears in (default: false).
+    #[arg(long, default_value = "false")]
+    dedup: bool,
+
+    /// Memory limit for queries, e.g. 512M or 2G; sorts spill to disk beyond it.
+    #[arg(long, value_parser = parse_size)]
+    memory_limit: Option<usize>,
+
+    /// Abort statements running longer than this many seconds.
+    #[arg(long)]
+    timeout: Option<f64>,
+
     /// Number of threads to use for processing (default: 8).
     #[arg(short, long, default_value = "8")]
     num_threads: Option<u32>,
+
+    /// Write query results to this CSV file instead of printing them.
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// Downsample results to at most this many time buckets per series.
+    #[arg(long)]
+    max_points: Option<usize>,
+
+    /// Timestamp column to downsample by (default: the first timestamp column).
+    #[arg(long, requires = "max_points")]
+    time_column: Option<String>,
+}
+
+/// Where and how statement results are written.
+#[derive(Clone)]
+struct Output {
+    path: Option<String>,
+    max_points: Option<usize>,
+    time_column: Option<String>,
+    /// Draw the two-column result as a terminal chart instead of a table.
+    chart: bool,
+}
+
+/// A REPL or `--query` statement.
+enum Statement<'a> {
+    Sql(&'a str),
+    /// `.freq <table> <column> [limit]`
+    Freq {
+        table: &'a str,
+        column: &'a str,
+        limit: usize,
+    },
+}
+
+/// Rows shown by `.freq` without an explicit limit.
+const DEFAULT_FREQ_LIMIT: usize = 20;
+
+impl<'a> Statement<'a> {
+    /// Parse the arguments of `.freq`.
+    fn freq(args: &'a str) -> Result<Self> {
+        let usage = || Error::Config("Usage: .freq <table> <column> [limit]".into());
+        let mut args = args.split_whitespace();
+        let (Some(table), Some(column)) = (args.next(), args.next()) else {
+            return Err(usage());
+        };
+        let limit = match args.next() {
+            Some(limit) => limit.parse().map_err(|_| usage())?,
+            None => DEFAULT_FREQ_LIMIT,
+        };
+        if args.next().is_some() {
+            return Err(usage());
+        }
+        Ok(Statement::Freq {
+            table,
+            column,
+            limit,
+        })
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage profile packs.
+    Profiles {
+        #[command(subcommand)]
+        action: ProfilesCommand,
+    },
+    /// Print security summaries of an auth log (e.g. /var/log/auth.log).
+    Auth {
+        /// Auth log files or glob patterns to analyze.
+        #[arg(required = true)]
+        log_files: Vec<String>,
+
+        /// Rows to show per report (default: 20).
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+
+        /// Failed attempts from one IP within 60 seconds reported as a burst (default: 5).
+        #[arg(long, default_value_t = auth::DEFAULT_BURST_THRESHOLD)]
+        burst_threshold: u32,
+
+        /// Number of threads to use for processing (default: 8).
+        #[arg(short, long, default_value = "8")]
+        num_threads: Option<u32>,
+    },
+    /// Report which profiles match what fraction of each file's lines.
+    Coverage {
+        /// Log files or glob patterns to check.
+        #[arg(required = true)]
+        log_files: Vec<String>,
+
+        /// Config file with the profiles to check (default: as for queries).
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// Also try the builtin profiles (always used when no config is found).
+        #[arg(long, default_value = "false")]
+        builtin: bool,
+
+        /// Lines read from the start of each file (default: 10000).
+        #[arg(long, default_value_t = coverage::DEFAULT_SAMPLE_LINES)]
+        sample_lines: usize,
+
+        /// Fraction of lines a profile must match to cover a file (default: 0.9).
+        #[arg(long, default_value_t = coverage::DEFAULT_MIN_RATE)]
+        min_rate: f64,
+    },
+    /// List the pattern macros: the custom macros of the config file, then builtins.
+    Macros {
+        /// Config file with custom macros (default: as for queries).
+        #[arg(short, long)]
+        config: Option<String>,
+
+        /// List the custom macros of this profile rather than the global ones.
+        #[arg(short, long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfilesCommand {
+    /// Download a profile pack into the local config directory.
+    Fetch {
+        /// Registry pack name (e.g. "nginx") or URL of a profile file.
+        source: String,
+
+        /// Expected SHA-256 checksum; required for URLs.
+        /// Default for registry packs: the checksum in the registry index.
+        #[arg(long)]
+        sha256: Option<String>,
+
+        /// Directory to install into (default: ~/.config/lflog/profiles.d).
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+}
+
+/// Parse a byte size with an optional K, M or G suffix (powers of 1024).
+fn parse_size(s: &str) -> std::result::Result<usize, String> {
+    let s = s.trim();
+    let (number, multiplier) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    number
+        .trim()
+        .parse::<usize>()
+        .map(|n| n * multiplier)
+        .map_err(|_| format!("invalid size '{}', expected e.g. 512M", s))
+}
+
+/// Parse a fraction from 0.0 to 1.0 for --min-match-rate.
+fn parse_match_rate(s: &str) -> std::result::Result<f64, String> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| format!("invalid rate '{}', expected 0.0-1.0, e.g. 0.95", s))
+}
+
+/// Parse a `FIELD=REDACTION` pair for --redact.
+fn parse_redaction(s: &str) -> std::result::Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(field, redaction)| (field.trim().to_string(), redaction.trim().to_string()))
+        .ok_or_else(|| format!("invalid redaction '{}', expected e.g. email=hash", s))
 }
 
 /// Resolve config file path from CLI, env var, or default.
@@ -79,8 +371,219 @@ fn resolve_config_path(cli_config: Option<String>) -> Option<PathBuf> {
     None
 }
 
+/// Load the config file (if any) merged with installed profile packs.
+fn load_profiles(config_path: Option<&PathBuf>) -> Result<Option<Profiles>> {
+    let mut profiles = match config_path {
+        Some(path) => Some(Profiles::from_file(&path.to_string_lossy())?),
+        None => None,
+    };
+
+    if let Some(dir) = registry::profiles_dir()
+        && let Some(installed) = registry::load_installed(&dir)?
+    {
+        match profiles.as_mut() {
+            Some(p) => p.merge(installed),
+            None => profiles = Some(installed),
+        }
+    }
+
+    Ok(profiles)
+}
+
+/// Run a `profiles` subcommand.
+fn run_profiles(action: ProfilesCommand) -> Result<()> {
+    match action {
+        ProfilesCommand::Fetch {
+            source,
+            sha256,
+            dir,
+        } => {
+            let dir = dir.or_else(registry::profiles_dir).ok_or_else(|| {
+                Error::Config("Cannot determine config directory, use --dir".into())
+            })?;
+            let path = registry::fetch_pack(&source, sha256.as_deref(), &dir)?;
+            println!("Installed {} to {}", source, path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Run the `auth` subcommand, printing each report.
+async fn run_auth(
+    log_files: &[String],
+    limit: usize,
+    burst_threshold: u32,
+    num_threads: Option<u32>,
+) -> Result<()> {
+    let lflog = LfLog::new();
+    auth::register(&lflog, log_files, num_threads)?;
+    for (title, batches) in auth::run(&lflog, limit, burst_threshold).await? {
+        println!("== {} ==", title);
+        if batches.iter().all(|b| b.num_rows() == 0) {
+            println!("(none)");
+        } else {
+            println!("{}", pretty_format_batches(&batches)?);
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Run the `coverage` subcommand, printing per-file matches and uncovered files.
+fn run_coverage(
+    profiles: Option<Profiles>,
+    log_files: &[String],
+    builtin: bool,
+    sample_lines: usize,
+    min_rate: f64,
+) -> Result<()> {
+    let mut candidates: Vec<&Profile> = Vec::new();
+    if let Some(profiles) = &profiles {
+        candidates.extend(&profiles.profiles);
+    }
+    if builtin || candidates.is_empty() {
+        candidates.extend(&lflog::builtin::profiles().profiles);
+    }
+    let report = coverage::coverage(log_files, candidates, sample_lines)?;
+    println!(
+        "{}",
+        pretty_format_batches(&[coverage::to_batch(&report)?])?
+    );
+
+    let uncovered: Vec<&coverage::FileCoverage> = report
+        .iter()
+        .filter(|file| !file.is_covered(min_rate))
+        .collect();
+    if uncovered.is_empty() {
+        println!(
+            "All {} files covered (>= {:.0}% of lines)",
+            report.len(),
+            100.0 * min_rate
+        );
+    } else {
+        println!(
+            "{} of {} files not covered by any profile (< {:.0}% of lines):",
+            uncovered.len(),
+            report.len(),
+            100.0 * min_rate
+        );
+        for file in uncovered {
+            match file.best() {
+                Some((profile, rate)) => {
+                    println!("  {} (best: {} {:.1}%)", file.file, profile, 100.0 * rate)
+                }
+                None => println!("  {} (no profile matches)", file.file),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the macros patterns of `profile`, or else of no profile, can use.
+fn run_macros(profiles: Option<Profiles>, profile: Option<&str>) -> Result<()> {
+    let customs = match (&profiles, profile) {
+        (Some(profiles), Some(name)) => {
+            let profile = profiles
+                .get_profile(name)
+                .ok_or_else(|| Error::Config(format!("Profile '{}' not found", name)))?;
+            profile.custom_macros.as_slice()
+        }
+        (None, Some(name)) => {
+            return Err(Error::Config(format!(
+                "Profile '{}' not found: no config file",
+                name
+            )));
+        }
+        (Some(profiles), None) => profiles.custom_macros.as_slice(),
+        (None, None) => &[],
+    };
+    let rows: Vec<[String; 4]> = macros::available_macros(Some(customs))
+        .into_iter()
+        .map(|info| {
+            let mut names = info.signature();
+            for alias in &info.aliases {
+                names.push_str(", ");
+                names.push_str(alias);
+            }
+            let source = match info.source {
+                macros::MacroSource::Builtin => "builtin",
+                macros::MacroSource::Custom => "custom",
+            };
+            [
+                names,
+                info.type_hint.map_or("-", |hint| hint.name()).to_string(),
+                source.to_string(),
+                info.description.unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let width = |column: usize| rows.iter().map(|row| row[column].len()).max().unwrap_or(0);
+    let (names, types, sources) = (width(0), width(1), width(2));
+    for [name, type_name, source, description] in rows {
+        let line = format!(
+            "{:names$}  {:types$}  {:sources$}  {}",
+            name, type_name, source, description
+        );
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+/// Run one statement and print or export its results; Ctrl-C or `timeout` cancels it.
+async fn run_statement(
+    lflog: &LfLog,
+    statement: Statement<'_>,
+    timeout: Option<Duration>,
+    output: &Output,
+) -> Result<()> {
+    let token = match timeout {
+        Some(timeout) => CancellationToken::new().with_timeout(timeout),
+        None => CancellationToken::new(),
+    };
+    let watcher = tokio::spawn({
+        let token = token.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                token.cancel();
+            }
+        }
+    });
+    let result = async {
+        let df = match statement {
+            Statement::Sql(sql) => lflog.query_with_token(sql, &token).await?,
+            Statement::Freq {
+                table,
+                column,
+                limit,
+            } => {
+                lflog
+                    .frequencies_with_token(table, column, limit, &token)
+                    .await?
+            }
+        };
+        let df = match output.max_points {
+            Some(max_points) => {
+                export::downsample(df, output.time_column.as_deref(), max_points).await?
+            }
+            None => df,
+        };
+        match &output.path {
+            Some(path) => export::write_csv(df, path).await,
+            None if output.chart => {
+                let batches = df.collect().await.map_err(Error::from_datafusion)?;
+                print!("{}", chart::render(&batches, chart::terminal_width())?);
+                Ok(())
+            }
+            None => df.show().await.map_err(Error::from_datafusion),
+        }
+    }
+    .await;
+    watcher.abort();
+    result
+}
+
 /// Run interactive REPL mode.
-async fn run_repl(lflog: &LfLog) -> Result<()> {
+async fn run_repl(lflog: &LfLog, timeout: Option<Duration>, output: &Output) -> Result<()> {
     let mut rl = rustyline::DefaultEditor::new()?;
 
     println!("lflog interactive mode. Type SQL queries, '.exit' to quit.");
@@ -97,15 +600,32 @@ async fn run_repl(lflog: &LfLog) -> Result<()> {
                 if line == ".exit" || line == ".quit" || line == "exit" || line == "quit" {
                     break;
                 }
-                if line.starts_with('.') {
+
+                let (statement, output) = if let Some(sql) = line.strip_prefix(".chart ") {
+                    let output = Output {
+                        chart: true,
+                        ..output.clone()
+                    };
+                    (Ok(Statement::Sql(sql.trim())), output)
+                } else if let Some(args) = line.strip_prefix(".freq ") {
+                    (Statement::freq(args), output.clone())
+                } else if line.starts_with('.') {
                     println!("Unknown command: {}", line);
-                    println!("Commands: .exit, .quit");
+                    println!(
+                        "Commands: .chart <SQL>, .freq <table> <column> [limit], .exit, .quit"
+                    );
                     continue;
-                }
+                } else {
+                    (Ok(Statement::Sql(line)), output.clone())
+                };
 
                 let _ = rl.add_history_entry(line);
 
-                match lflog.query_and_show(line).await {
+                let result = match statement {
+                    Ok(statement) => run_statement(lflog, statement, timeout, &output).await,
+                    Err(e) => Err(e),
+                };
+                match result {
                     Ok(()) => {}
                     Err(e) => {
                         eprintln!("Error: {}", e);
@@ -139,29 +659,74 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Profiles { action }) => return run_profiles(action),
+        Some(Command::Auth {
+            log_files,
+            limit,
+            burst_threshold,
+            num_threads,
+        }) => return run_auth(&log_files, limit, burst_threshold, num_threads).await,
+        Some(Command::Coverage {
+            log_files,
+            config,
+            builtin,
+            sample_lines,
+            min_rate,
+        }) => {
+            let profiles = load_profiles(resolve_config_path(config).as_ref())?;
+            return run_coverage(profiles, &log_files, builtin, sample_lines, min_rate);
+        }
+        Some(Command::Macros { config, profile }) => {
+            let profiles = load_profiles(resolve_config_path(config).as_ref())?;
+            return run_macros(profiles, profile.as_deref());
+        }
+        None => {}
+    }
+    if cli.log_files.is_empty() {
+        return Err(Error::Config("A log file is required".into()));
+    }
+
     // Resolve config file path
     let config_path = resolve_config_path(cli.config);
 
     // Initialize LfLog with or without config
-    let lflog = if let Some(ref path) = config_path {
-        LfLog::from_config(path.to_str().unwrap())?
+    let profiles = load_profiles(config_path.as_ref())?;
+    let plugins: Vec<String> = profiles
+        .iter()
+        .flat_map(|p| p.plugins.iter())
+        .chain(&cli.plugin)
+        .cloned()
+        .collect();
+    let lflog = if let Some(profiles) = profiles {
+        LfLog::from_profiles(profiles)
     } else {
-        // No config file - must use inline pattern
-        if cli.pattern.is_none() {
+        // No config file - must use inline pattern or a builtin profile
+        if cli.pattern.is_empty() && cli.profile.is_none() {
             return Err(Error::Config(
                 "No config file found. Either:\n\
                  - Create ~/.config/lflog/config.toml\n\
                  - Set LFLOG_CONFIG environment variable\n\
                  - Use --config <path>\n\
-                 - Use --pattern <regex> without a config file"
+                 - Use --pattern <regex> without a config file\n\
+                 - Use --profile <name> with a builtin profile"
                     .into(),
             ));
         }
         LfLog::new()
     };
+    let mut lflog = match cli.memory_limit {
+        Some(bytes) => lflog.with_memory_limit(bytes)?,
+        None => lflog,
+    };
+    for plugin in &plugins {
+        lflog = lflog.with_plugin(plugin)?;
+    }
 
     // Build query options
-    let options = QueryOptions::new(&cli.log_file).with_table_name(&cli.table);
+    let options = QueryOptions::default()
+        .with_log_files(cli.log_files)
+        .with_table_name(&cli.table);
 
     let options = if let Some(profile) = cli.profile {
         options.with_profile(profile)
@@ -169,25 +734,65 @@ async fn main() -> Result<()> {
         options
     };
 
-    let options = if let Some(pattern) = cli.pattern {
-        options.with_pattern(pattern)
+    let options = if cli.pattern.is_empty() {
+        options
     } else {
         options
+            .with_patterns(cli.pattern)
+            .with_prefilter(cli.prefilter)
+            .with_engine(cli.engine.unwrap_or_default())
+            .with_flags(cli.flag)
+            .with_exclude_pattern(cli.exclude)
+            .with_infer_types(cli.infer_types)
     };
 
     let options = options
         .with_add_file_path(cli.add_file_path)
         .with_add_raw(cli.add_raw)
+        .with_add_file_mtime(cli.add_file_mtime)
+        .with_add_file_size(cli.add_file_size)
+        .with_add_pattern(cli.add_pattern)
+        .with_time_ordered(cli.time_ordered)
+        .with_merge_by_time(cli.merge_by_time)
+        .with_unmatched(cli.unmatched)
+        .with_strict(cli.strict)
+        .with_index(cli.index)
+        .with_skip_lines(cli.skip_lines)
+        .with_skip_footer(cli.skip_footer)
+        .with_encoding(cli.encoding)
+        .with_year(cli.year)
+        .with_timestamp_unit(cli.timestamp_unit)
+        .with_duration_unit(cli.duration_unit)
+        .with_max_line_length(cli.max_line_length, cli.long_lines.unwrap_or_default())
+        .with_redact(cli.redact.into_iter().collect())
+        .with_dedup(cli.dedup)
         .with_num_threads(cli.num_threads);
 
+    let options = if cli.min_match_rate.is_some() {
+        options.with_min_match_rate(cli.min_match_rate)
+    } else {
+        options
+    };
+
     // Register the log file
     lflog.register(options)?;
 
     // Execute query or start REPL
+    let timeout = cli
+        .timeout
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|e| Error::Config(format!("Invalid --timeout: {}", e)))?;
+    let output = Output {
+        path: cli.output,
+        max_points: cli.max_points,
+        time_column: cli.time_column,
+        chart: false,
+    };
     if let Some(sql) = cli.query {
-        lflog.query_and_show(&sql).await?;
+        run_statement(&lflog, Statement::Sql(&sql), timeout, &output).await?;
     } else {
-        run_repl(&lflog).await?;
+        run_repl(&lflog, timeout, &output).await?;
     }
 
     Ok(())
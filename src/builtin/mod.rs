@@ -0,0 +1,334 @@
+//! Builtin profiles shipped with lflog.
+//!
+//! Profiles are grouped into packs stored as TOML files next to this module and
+//! embedded at compile time. They are used when a profile name is not found in the
+//! user's config.
+
+use std::sync::OnceLock;
+
+use crate::macros::parser::{ProfileFormat, Profiles};
+
+/// Builtin profile packs as `(name, toml)` pairs.
+const PACKS: &[(&str, &str)] = &[
+    ("postgres", include_str!("postgres.toml")),
+    ("mysql", include_str!("mysql.toml")),
+    ("kafka", include_str!("kafka.toml")),
+    ("proxy", include_str!("proxy.toml")),
+    ("sysadmin", include_str!("sysadmin.toml")),
+];
+
+/// Names of the builtin profile packs.
+pub fn pack_names() -> impl Iterator<Item = &'static str> {
+    PACKS.iter().map(|(name, _)| *name)
+}
+
+/// All builtin profiles, parsed once and merged across packs.
+pub fn profiles() -> &'static Profiles {
+    static PROFILES: OnceLock<Profiles> = OnceLock::new();
+    PROFILES.get_or_init(|| {
+        let mut merged = Profiles {
+            custom_macros: Vec::new(),
+            profiles: Vec::new(),
+            plugins: Vec::new(),
+        };
+        for (name, content) in PACKS {
+            let pack = Profiles::from_str_with_format(content, ProfileFormat::Toml)
+                .unwrap_or_else(|e| panic!("invalid builtin profile pack '{}': {}", name, e));
+            merged.profiles.extend(pack.profiles);
+        }
+        merged
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    fn scan(profile: &str, line: &str) -> Vec<(String, String)> {
+        let profile = profiles()
+            .get_profile(profile)
+            .unwrap_or_else(|| panic!("missing builtin profile {}", profile));
+        let scanner =
+            Scanner::with_patterns(profile.regex_patterns(), Some(&profile.custom_macros)).unwrap();
+        let values = scanner
+            .scan(line)
+            .unwrap_or_else(|| panic!("profile {} did not match: {}", profile.name, line));
+        scanner.field_names.iter().cloned().zip(values).collect()
+    }
+
+    fn field<'a>(fields: &'a [(String, String)], name: &str) -> &'a str {
+        &fields.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    #[test]
+    fn test_builtin_profiles_compile() {
+        for profile in &profiles().profiles {
+            Scanner::with_patterns(profile.regex_patterns(), Some(&profile.custom_macros))
+                .unwrap_or_else(|e| panic!("profile {} failed to compile: {}", profile.name, e));
+        }
+    }
+
+    #[test]
+    fn test_postgres_profiles() {
+        let f = scan(
+            "postgres",
+            "2024-01-15 10:23:45.123 UTC [12345] LOG:  checkpoint starting: time",
+        );
+        assert_eq!(field(&f, "pid"), "12345");
+        assert_eq!(field(&f, "level"), "LOG");
+        assert_eq!(field(&f, "message"), "checkpoint starting: time");
+
+        let f = scan(
+            "postgres_user_db",
+            "2024-01-15 10:23:45.123 UTC [12345] app@orders ERROR:  relation \"foo\" does not exist",
+        );
+        assert_eq!(field(&f, "user"), "app");
+        assert_eq!(field(&f, "database"), "orders");
+
+        let f = scan(
+            "postgres_duration",
+            "2024-01-15 10:23:45.123 UTC [12345] LOG:  duration: 1532.201 ms  execute <unnamed>: SELECT 1",
+        );
+        assert_eq!(field(&f, "duration_ms"), "1532.201");
+        assert_eq!(field(&f, "kind"), "execute");
+        assert_eq!(field(&f, "query"), "SELECT 1");
+
+        let f = scan(
+            "postgres_autovacuum",
+            "2024-01-15 10:23:45.123 UTC [12345] LOG:  automatic vacuum of table \"orders.public.items\": index scans: 1",
+        );
+        assert_eq!(field(&f, "operation"), "vacuum");
+        assert_eq!(field(&f, "table"), "orders.public.items");
+    }
+
+    #[test]
+    fn test_mysql_profiles() {
+        let f = scan(
+            "mysql_error",
+            "2024-01-15T10:23:45.123456Z 0 [Warning] [MY-010068] [Server] CA certificate ca.pem is self signed.",
+        );
+        assert_eq!(field(&f, "level"), "Warning");
+        assert_eq!(field(&f, "code"), "MY-010068");
+    }
+
+    #[test]
+    fn test_kafka_profiles() {
+        let f = scan(
+            "kafka_server",
+            "[2024-01-15 10:23:45,123] INFO [KafkaServer id=1] started (kafka.server.KafkaServer)",
+        );
+        assert_eq!(field(&f, "level"), "INFO");
+        assert_eq!(field(&f, "context"), "KafkaServer id=1");
+        assert_eq!(field(&f, "message"), "started");
+        assert_eq!(field(&f, "logger"), "kafka.server.KafkaServer");
+
+        let f = scan(
+            "kafka_server",
+            "[2024-01-15 10:23:45,123] INFO Kafka version: 3.6.1 (org.apache.kafka.common.utils.AppInfoParser)",
+        );
+        assert_eq!(field(&f, "context"), "");
+        assert_eq!(field(&f, "message"), "Kafka version: 3.6.1");
+
+        let f = scan(
+            "kafka_thread",
+            "[2024-01-15 10:23:45,123] WARN [ReplicaFetcherThread-0-2] Error in response for fetch request (kafka.server.ReplicaFetcherThread)",
+        );
+        assert_eq!(field(&f, "thread"), "ReplicaFetcherThread-0-2");
+
+        let f = scan(
+            "kafka_controller",
+            "[2024-01-15 10:23:45,123] INFO [Controller id=1 epoch=3] Processing automatic preferred replica leader election (kafka.controller.KafkaController)",
+        );
+        assert_eq!(field(&f, "controller_id"), "1");
+        assert_eq!(field(&f, "epoch"), "3");
+
+        let f = scan(
+            "zookeeper_server",
+            "2024-01-15 10:23:45,123 [myid:1] - INFO  [QuorumPeer[myid=1](plain=0.0.0.0:2181):QuorumPeer@1234] - LOOKING",
+        );
+        assert_eq!(field(&f, "myid"), "1");
+        assert_eq!(
+            field(&f, "thread"),
+            "QuorumPeer[myid=1](plain=0.0.0.0:2181)"
+        );
+        assert_eq!(field(&f, "component"), "QuorumPeer");
+        assert_eq!(field(&f, "line"), "1234");
+
+        let f = scan(
+            "zookeeper_server",
+            "2015-07-29 17:41:44,747 - INFO  [QuorumPeer[myid=1]/0:0:0:0:0:0:0:0:2181:FastLeaderElection@774] - Notification time out: 3200",
+        );
+        assert_eq!(field(&f, "myid"), "");
+        assert_eq!(field(&f, "component"), "FastLeaderElection");
+        assert_eq!(field(&f, "message"), "Notification time out: 3200");
+    }
+
+    #[test]
+    fn test_proxy_profiles() {
+        let f = scan(
+            "haproxy_http",
+            "Feb  6 12:14:14 localhost haproxy[14389]: 10.0.1.2:33317 [06/Feb/2009:12:14:14.655] http-in static/srv1 10/0/30/69/109 200 2750 - - ---- 1/1/1/1/0 0/0 {1wt.eu} {} \"GET /index.html HTTP/1.1\"",
+        );
+        assert_eq!(field(&f, "pid"), "14389");
+        assert_eq!(field(&f, "client_ip"), "10.0.1.2");
+        assert_eq!(field(&f, "backend"), "static");
+        assert_eq!(field(&f, "server"), "srv1");
+        assert_eq!(field(&f, "tr_server"), "69");
+        assert_eq!(field(&f, "ta"), "109");
+        assert_eq!(field(&f, "status"), "200");
+        assert_eq!(field(&f, "req_headers"), "1wt.eu");
+        assert_eq!(field(&f, "request"), "GET /index.html HTTP/1.1");
+
+        let f = scan(
+            "haproxy_http",
+            "10.0.1.2:33317 [06/Feb/2009:12:14:14.655] http-in~ static/<NOSRV> -1/-1/-1/-1/8 503 212 - - SC-- 0/0/0/0/0 0/0 \"GET / HTTP/1.1\"",
+        );
+        assert_eq!(field(&f, "pid"), "");
+        assert_eq!(field(&f, "tr"), "-1");
+        assert_eq!(field(&f, "termination_state"), "SC--");
+
+        let f = scan(
+            "envoy",
+            "[2016-04-15T20:17:00.310Z] \"POST /api/v1/locations HTTP/2\" 204 - 154 0 226 100 \"10.0.35.28\" \"nsq2http\" \"cc21d9b0-cf5c-432b-8c7e-98aeb7988cd2\" \"locations\" \"tcp://10.0.2.1:80\"",
+        );
+        assert_eq!(field(&f, "method"), "POST");
+        assert_eq!(field(&f, "status"), "204");
+        assert_eq!(field(&f, "response_flags"), "-");
+        assert_eq!(field(&f, "duration_ms"), "226");
+        assert_eq!(field(&f, "upstream_host"), "tcp://10.0.2.1:80");
+    }
+
+    #[test]
+    fn test_sysadmin_profiles() {
+        let f = scan(
+            "sshd_auth",
+            "Jan 15 10:23:45 web01 sshd[1234]: Failed password for invalid user admin from 203.0.113.7 port 52211 ssh2",
+        );
+        assert_eq!(field(&f, "result"), "Failed");
+        assert_eq!(field(&f, "invalid_user"), "invalid user");
+        assert_eq!(field(&f, "user"), "admin");
+        assert_eq!(field(&f, "ip"), "203.0.113.7");
+        assert_eq!(field(&f, "port"), "52211");
+
+        let f = scan(
+            "sshd_auth",
+            "Jan  5 10:23:45 web01.example.com sshd[1234]: Accepted publickey for deploy from 2001:db8::1 port 40022 ssh2: ED25519 SHA256:abc",
+        );
+        assert_eq!(field(&f, "timestamp"), "Jan  5 10:23:45");
+        assert_eq!(field(&f, "host"), "web01.example.com");
+        assert_eq!(field(&f, "invalid_user"), "");
+        assert_eq!(field(&f, "ip"), "2001:db8::1");
+
+        // Addresses are checked by the builtin `ip` and `ipv6` macros, and no pack
+        // macro shadows a builtin
+        let profile = profiles().get_profile("sshd_auth").unwrap();
+        assert!(profile.custom_macros.iter().any(|m| m.name == "syslog_ts"));
+        let shadowed: Vec<_> = crate::macros::available_macros(Some(&profile.custom_macros))
+            .into_iter()
+            .filter(|m| m.source == crate::macros::MacroSource::Custom)
+            .filter(|m| ["ip", "token", "hostname"].contains(&m.name.as_str()))
+            .collect();
+        assert!(shadowed.is_empty(), "{:?}", shadowed);
+        let scanner =
+            Scanner::with_patterns(profile.regex_patterns(), Some(&profile.custom_macros)).unwrap();
+        assert!(!scanner.is_match(
+            "Jan 15 10:23:45 web01 sshd[1234]: Failed password for root from 999.1.1.1 port 52211 ssh2"
+        ));
+
+        let f = scan(
+            "sshd_invalid_user",
+            "Jan 15 10:23:44 web01 sshd[1234]: Invalid user admin from 203.0.113.7 port 52211",
+        );
+        assert_eq!(field(&f, "user"), "admin");
+
+        let f = scan(
+            "su",
+            "Jan 15 10:23:45 web01 su[4321]: FAILED SU (to root) alice on pts/0",
+        );
+        assert_eq!(field(&f, "failed"), "FAILED SU");
+        assert_eq!(field(&f, "target_user"), "root");
+        assert_eq!(field(&f, "user"), "alice");
+
+        let f = scan("su", "Jan 15 10:24:02 web01 su: (to postgres) bob on pts/1");
+        assert_eq!(field(&f, "failed"), "");
+        assert_eq!(field(&f, "tty"), "pts/1");
+
+        let f = scan(
+            "sudo",
+            "Jan 15 10:23:45 web01 sudo:    alice : 3 incorrect password attempts ; TTY=pts/0 ; PWD=/home/alice ; USER=root ; COMMAND=/usr/bin/apt update",
+        );
+        assert_eq!(field(&f, "user"), "alice");
+        assert_eq!(field(&f, "failure"), "3 incorrect password attempts");
+        assert_eq!(field(&f, "target_user"), "root");
+        assert_eq!(field(&f, "command"), "/usr/bin/apt update");
+
+        let f = scan(
+            "sudo",
+            "Jan 15 10:23:45 web01 sudo[999]:      bob : TTY=pts/1 ; PWD=/ ; USER=root ; ENV=A=1 ; COMMAND=/bin/ls",
+        );
+        assert_eq!(field(&f, "pid"), "999");
+        assert_eq!(field(&f, "failure"), "");
+        assert_eq!(field(&f, "command"), "/bin/ls");
+
+        let f = scan(
+            "cron",
+            "Jan 15 10:17:01 web01 CRON[5678]: (root) CMD (   cd / && run-parts --report /etc/cron.hourly)",
+        );
+        assert_eq!(field(&f, "user"), "root");
+        assert_eq!(field(&f, "action"), "CMD");
+
+        let f = scan(
+            "fail2ban",
+            "2024-01-15 10:23:45,123 fail2ban.actions        [1234]: NOTICE  [sshd] Ban 203.0.113.7",
+        );
+        assert_eq!(field(&f, "jail"), "sshd");
+        assert_eq!(field(&f, "action"), "Ban");
+        assert_eq!(field(&f, "ip"), "203.0.113.7");
+
+        let f = scan(
+            "fail2ban",
+            "2024-01-15 10:23:45,123 fail2ban.actions        [1234]: NOTICE  [sshd] Restore Ban 203.0.113.8",
+        );
+        assert_eq!(field(&f, "action"), "Restore Ban");
+
+        let f = scan(
+            "fail2ban",
+            "2024-01-15 10:23:45,123 fail2ban.actions        [1234]: NOTICE  [sshd] Unban 2001:db8::7",
+        );
+        assert_eq!(field(&f, "ip"), "2001:db8::7");
+
+        let f = scan(
+            "smartd",
+            "Jan 15 10:23:45 nas01 smartd[812]: Device: /dev/sda [SAT], 8 Currently unreadable (pending) sectors",
+        );
+        assert_eq!(field(&f, "device"), "/dev/sda");
+        assert_eq!(field(&f, "device_type"), "SAT");
+
+        let f = scan(
+            "smartd_attribute",
+            "Jan 15 10:23:45 nas01 smartd[812]: Device: /dev/sda [SAT], SMART Usage Attribute: 194 Temperature_Celsius changed from 64 to 63",
+        );
+        assert_eq!(field(&f, "attribute"), "Temperature_Celsius");
+        assert_eq!(field(&f, "new_value"), "63");
+
+        let f = scan(
+            "systemd",
+            "Jan 15 10:23:45 web01 systemd[1]: Started nginx.service - A high performance web server.",
+        );
+        assert_eq!(field(&f, "pid"), "1");
+    }
+
+    #[test]
+    fn test_builtin_datetime_hints_parse() {
+        let profile = profiles().get_profile("postgres").unwrap();
+        let scanner =
+            Scanner::with_patterns(profile.regex_patterns(), Some(&profile.custom_macros)).unwrap();
+        match scanner.type_hints.get("timestamp") {
+            Some(crate::types::FieldType::DateTime(dt)) => {
+                assert!(dt.parse("2024-01-15 10:23:45.123").is_some());
+            }
+            other => panic!("unexpected hint: {:?}", other),
+        }
+    }
+}
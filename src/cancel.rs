@@ -0,0 +1,79 @@
+//! Cooperative cancellation of running queries.
+//!
+//! A [`CancellationToken`] attached to a query is checked by the log scan workers
+//! every few thousand lines, so a cancelled or timed out statement stops scanning
+//! instead of reading the rest of its files.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// Lines parsed between cancellation checks.
+pub const CHECK_INTERVAL: usize = 4096;
+
+/// Shared flag to abort a query, with an optional deadline.
+///
+/// Clones share the same flag; cancelling any clone cancels them all.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl CancellationToken {
+    /// Create a token that is cancelled only by [`CancellationToken::cancel`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also cancel the query once `timeout` has elapsed from now.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some((Instant::now() + timeout, timeout));
+        self
+    }
+
+    /// Request cancellation.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token was cancelled or its deadline has passed.
+    pub fn is_cancelled(&self) -> bool {
+        self.check().is_err()
+    }
+
+    /// Return [`Error::Cancelled`] or [`Error::Timeout`] if the query should stop.
+    pub fn check(&self) -> Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+        match self.deadline {
+            Some((deadline, timeout)) if Instant::now() >= deadline => Err(Error::Timeout(timeout)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+        clone.cancel();
+        assert!(matches!(token.check(), Err(Error::Cancelled)));
+
+        let token = CancellationToken::new().with_timeout(Duration::ZERO);
+        assert!(matches!(token.check(), Err(Error::Timeout(_))));
+        assert!(
+            !CancellationToken::new()
+                .with_timeout(Duration::from_secs(60))
+                .is_cancelled()
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Terminal charts of two-column query results.
+//!
+//! The first column labels each row and the second holds its value. Results keyed
+//! by time or a number are drawn as a braille line chart; results keyed by
+//! categories are drawn as horizontal bars.
+
+use datafusion::arrow::array::{Array, ArrayRef, Float64Array};
+use datafusion::arrow::compute::{cast, concat};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::array_value_to_string;
+
+use crate::error::{Error, Result};
+
+/// Rows of a bar chart drawn before the rest are summarized.
+const MAX_BARS: usize = 50;
+/// Widest label column of a bar chart, in characters.
+const MAX_LABEL_WIDTH: usize = 24;
+/// Height of a line chart, in characters (4 dots each).
+const LINE_HEIGHT: usize = 12;
+/// Eighth blocks used for the fractional end of a bar.
+const PARTIAL_BLOCKS: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+/// Render a two-column result as a chart `width` characters wide.
+pub fn render(batches: &[RecordBatch], width: usize) -> Result<String> {
+    let Some(first) = batches.first() else {
+        return Ok("(no rows)\n".to_string());
+    };
+    if first.num_columns() != 2 {
+        return Err(Error::Config(format!(
+            "A chart needs two columns (label, value), got {}",
+            first.num_columns()
+        )));
+    }
+    let column = |i: usize| -> Result<ArrayRef> {
+        let arrays: Vec<&dyn Array> = batches.iter().map(|b| b.column(i).as_ref()).collect();
+        Ok(concat(&arrays)?)
+    };
+    let labels = column(0)?;
+    let values = column(1)?;
+    if !values.data_type().is_numeric() {
+        return Err(Error::Config(
+            "The second chart column must be numeric".into(),
+        ));
+    }
+    let values = cast(&values, &DataType::Float64)?;
+    let values = values.as_any().downcast_ref::<Float64Array>().unwrap();
+    if labels.is_empty() {
+        return Ok("(no rows)\n".to_string());
+    }
+
+    let label_type = labels.data_type();
+    if label_type.is_temporal() || label_type.is_numeric() {
+        line_chart(&labels, values, width)
+    } else {
+        bar_chart(&labels, values, width)
+    }
+}
+
+/// Horizontal bars, one row per label.
+fn bar_chart(labels: &ArrayRef, values: &Float64Array, width: usize) -> Result<String> {
+    let rows = labels.len().min(MAX_BARS);
+    let labels: Vec<String> = (0..rows)
+        .map(|i| array_value_to_string(labels, i).map(|s| truncate(&s, MAX_LABEL_WIDTH)))
+        .collect::<std::result::Result<_, _>>()?;
+    let texts: Vec<String> = (0..rows)
+        .map(|i| match values.is_null(i) {
+            true => "NULL".to_string(),
+            false => format_value(values.value(i)),
+        })
+        .collect();
+    let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let text_width = texts.iter().map(|t| t.len()).max().unwrap_or(0);
+    let bar_width = width.saturating_sub(label_width + text_width + 4).max(10);
+    let max = (0..rows)
+        .filter(|&i| !values.is_null(i))
+        .map(|i| values.value(i))
+        .fold(0.0_f64, f64::max);
+
+    let mut out = String::new();
+    for i in 0..rows {
+        let value = if values.is_null(i) {
+            0.0
+        } else {
+            values.value(i).max(0.0)
+        };
+        let eighths = if max > 0.0 {
+            (value / max * (bar_width * 8) as f64).round() as usize
+        } else {
+            0
+        };
+        let mut bar = "█".repeat(eighths / 8);
+        if eighths % 8 > 0 {
+            bar.push(PARTIAL_BLOCKS[eighths % 8]);
+        }
+        out.push_str(&format!(
+            "{:>label_width$} │{:<bar_width$} {}\n",
+            labels[i],
+            bar,
+            texts[i],
+            label_width = label_width,
+            bar_width = bar_width
+        ));
+    }
+    if labels.len() < values.len() {
+        out.push_str(&format!("... {} more rows\n", values.len() - labels.len()));
+    }
+    Ok(out)
+}
+
+/// Braille line chart of values against time or a numeric key.
+fn line_chart(labels: &ArrayRef, values: &Float64Array, width: usize) -> Result<String> {
+    // Position rows on the x axis by their key; timestamps become their epoch value
+    let keys = match labels.data_type() {
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            cast(labels, &DataType::Float64)?
+        }
+        _ => cast(&cast(labels, &DataType::Int64)?, &DataType::Float64)?,
+    };
+    let keys = keys.as_any().downcast_ref::<Float64Array>().unwrap();
+    let points: Vec<(f64, f64)> = (0..values.len())
+        .filter(|&i| !keys.is_null(i) && !values.is_null(i))
+        .map(|i| (keys.value(i), values.value(i)))
+        .collect();
+    if points.is_empty() {
+        return Ok("(no values)\n".to_string());
+    }
+
+    let (x_min, x_max) = bounds(points.iter().map(|p| p.0));
+    let (y_min, y_max) = bounds(points.iter().map(|p| p.1));
+    let y_labels = [format_value(y_max), format_value(y_min)];
+    let axis_width = y_labels.iter().map(|l| l.len()).max().unwrap();
+    let columns = width.saturating_sub(axis_width + 2).max(10);
+    let (dots_x, dots_y) = (columns * 2, LINE_HEIGHT * 4);
+
+    let scale = |v: f64, min: f64, max: f64, dots: usize| -> usize {
+        if max > min {
+            ((v - min) / (max - min) * (dots - 1) as f64).round() as usize
+        } else {
+            dots / 2
+        }
+    };
+    let mut cells = vec![vec![0u8; columns]; LINE_HEIGHT];
+    let mut set = |x: usize, y: usize| {
+        // y counts up from the bottom; braille dot rows count down from the top
+        let row = dots_y - 1 - y;
+        const BITS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+        cells[row / 4][x / 2] |= BITS[row % 4][x % 2];
+    };
+    let mut previous: Option<(usize, usize)> = None;
+    for &(x, y) in &points {
+        let point = (
+            scale(x, x_min, x_max, dots_x),
+            scale(y, y_min, y_max, dots_y),
+        );
+        // Connect consecutive points so sparse series read as a line
+        if let Some(prev) = previous {
+            let steps = prev
+                .0
+                .abs_diff(point.0)
+                .max(prev.1.abs_diff(point.1))
+                .max(1);
+            for step in 0..=steps {
+                let t = step as f64 / steps as f64;
+                let lerp = |a: usize, b: usize| (a as f64 + (b as f64 - a as f64) * t).round();
+                set(
+                    lerp(prev.0, point.0) as usize,
+                    lerp(prev.1, point.1) as usize,
+                );
+            }
+        } else {
+            set(point.0, point.1);
+        }
+        previous = Some(point);
+    }
+
+    let mut out = String::new();
+    for (i, row) in cells.iter().enumerate() {
+        let axis = match i {
+            0 => y_labels[0].as_str(),
+            i if i == LINE_HEIGHT - 1 => y_labels[1].as_str(),
+            _ => "",
+        };
+        let line: String = row
+            .iter()
+            .map(|&bits| char::from_u32(0x2800 + bits as u32).unwrap())
+            .collect();
+        out.push_str(&format!("{:>axis_width$} ┤{}\n", axis, line));
+    }
+    let first = array_value_to_string(labels, 0)?;
+    let last = array_value_to_string(labels, labels.len() - 1)?;
+    let gap = columns.saturating_sub(first.chars().count() + last.chars().count());
+    out.push_str(&format!(
+        "{:axis_width$}  {}{}{}\n",
+        "",
+        first,
+        " ".repeat(gap.max(1)),
+        last
+    ));
+    Ok(out)
+}
+
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    })
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Terminal width from `COLUMNS`, or 80 columns.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray, TimestampSecondArray};
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(labels: ArrayRef, values: ArrayRef) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("label", labels.data_type().clone(), true),
+            Field::new("value", values.data_type().clone(), true),
+        ]);
+        RecordBatch::try_new(Arc::new(schema), vec![labels, values]).unwrap()
+    }
+
+    #[test]
+    fn test_bar_chart() {
+        let batch = batch(
+            Arc::new(StringArray::from(vec!["error", "warn", "info"])),
+            Arc::new(Int64Array::from(vec![Some(10), Some(5), None])),
+        );
+        let chart = render(&[batch], 40).unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("error │"), "{}", chart);
+        assert!(lines[0].ends_with(" 10"));
+        assert!(lines[2].ends_with(" NULL"));
+        let bar = |line: &str| line.chars().filter(|&c| c == '█').count();
+        assert_eq!(bar(lines[0]) / 2, bar(lines[1]));
+        assert_eq!(bar(lines[2]), 0);
+    }
+
+    #[test]
+    fn test_line_chart() {
+        let batch = batch(
+            Arc::new(TimestampSecondArray::from(vec![0, 60, 120, 180])),
+            Arc::new(Int64Array::from(vec![1, 4, 2, 8])),
+        );
+        let chart = render(&[batch], 40).unwrap();
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!(lines.len(), LINE_HEIGHT + 1);
+        assert!(lines[0].starts_with("8 ┤"), "{}", chart);
+        assert!(lines[LINE_HEIGHT - 1].starts_with("1 ┤"), "{}", chart);
+        assert!(
+            lines[LINE_HEIGHT].contains("1970-01-01T00:00:00")
+                && lines[LINE_HEIGHT].contains("1970-01-01T00:03:00")
+        );
+        // The last point sits in the top-right cell, the first in the bottom-left
+        assert_ne!(lines[0].chars().last(), Some('\u{2800}'));
+        assert_ne!(lines[LINE_HEIGHT - 1].chars().nth(4), Some('\u{2800}'));
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let one = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, true)])),
+            vec![Arc::new(Int64Array::from(vec![1]))],
+        )
+        .unwrap();
+        assert!(render(&[one], 40).is_err());
+        let text = batch(
+            Arc::new(StringArray::from(vec!["a"])),
+            Arc::new(StringArray::from(vec!["x"])),
+        );
+        assert!(render(&[text], 40).is_err());
+        assert_eq!(render(&[], 40).unwrap(), "(no rows)\n");
+    }
+}
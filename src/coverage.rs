@@ -0,0 +1,218 @@
+//! Profile coverage of log files.
+//!
+//! Matches a sample of every file's lines against a set of profiles and reports which
+//! profile matches what fraction of each file, so gaps in a profile library show up
+//! as services add new log shapes.
+
+use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use rayon::prelude::*;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use crate::datafusion::resolve_file_list;
+use crate::error::Result;
+use crate::extractor::RecordExtractor;
+use crate::macros::parser::Profile;
+
+/// Default number of lines read from the start of each file.
+pub const DEFAULT_SAMPLE_LINES: usize = 10_000;
+
+/// Default fraction of lines the best profile must match for a file to be covered.
+pub const DEFAULT_MIN_RATE: f64 = 0.9;
+
+/// How well the profiles match one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileCoverage {
+    pub file: String,
+    /// Non-empty lines sampled.
+    pub lines: usize,
+    /// Profiles matching at least one line, with their matched line counts, best first.
+    pub matches: Vec<(String, usize)>,
+}
+
+impl FileCoverage {
+    /// The profile matching the most lines and the fraction it matches.
+    pub fn best(&self) -> Option<(&str, f64)> {
+        self.matches
+            .first()
+            .map(|(profile, matched)| (profile.as_str(), self.rate(*matched)))
+    }
+
+    /// Whether the best profile matches at least `min_rate` of the sampled lines.
+    pub fn is_covered(&self, min_rate: f64) -> bool {
+        self.best().is_some_and(|(_, rate)| rate >= min_rate)
+    }
+
+    fn rate(&self, matched: usize) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            matched as f64 / self.lines as f64
+        }
+    }
+}
+
+/// Match the first `sample_lines` lines of every file in `log_files` (paths or globs)
+/// against each profile.
+///
+/// Profiles whose extractor cannot be built are skipped with a warning. Files are
+/// returned in the order they resolve to.
+pub fn coverage<'a>(
+    log_files: &[String],
+    profiles: impl IntoIterator<Item = &'a Profile>,
+    sample_lines: usize,
+) -> Result<Vec<FileCoverage>> {
+    let files = resolve_file_list(log_files)?;
+    let extractors: Vec<(&str, Arc<dyn RecordExtractor>)> = profiles
+        .into_iter()
+        .filter_map(|profile| match profile.extractor() {
+            Ok(extractor) => Some((profile.name.as_str(), extractor)),
+            Err(e) => {
+                log::warn!("Skipping profile '{}': {}", profile.name, e);
+                None
+            }
+        })
+        .collect();
+
+    files
+        .par_iter()
+        .map(|file| {
+            let reader = BufReader::new(std::fs::File::open(file)?);
+            let mut lines = 0;
+            let mut counts = vec![0; extractors.len()];
+            for line in reader.split(b'\n').take(sample_lines) {
+                let line = line?;
+                let line = String::from_utf8_lossy(&line);
+                let line = line.strip_suffix('\r').unwrap_or(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                lines += 1;
+                for (count, (_, extractor)) in counts.iter_mut().zip(&extractors) {
+                    *count += extractor.is_match(line) as usize;
+                }
+            }
+            let mut matches: Vec<(String, usize)> = extractors
+                .iter()
+                .zip(counts)
+                .filter(|(_, count)| *count > 0)
+                .map(|((name, _), count)| (name.to_string(), count))
+                .collect();
+            matches.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            Ok(FileCoverage {
+                file: file.clone(),
+                lines,
+                matches,
+            })
+        })
+        .collect()
+}
+
+/// One row per file and matching profile: `file`, `lines`, `profile`, `matched` and
+/// `percent`. Files no profile matches get a single row with a NULL profile.
+pub fn to_batch(coverage: &[FileCoverage]) -> Result<RecordBatch> {
+    let mut files = Vec::new();
+    let mut lines = Vec::new();
+    let mut profiles = Vec::new();
+    let mut matched = Vec::new();
+    let mut percents = Vec::new();
+    for file in coverage {
+        let rows: Vec<(Option<&str>, usize)> = if file.matches.is_empty() {
+            vec![(None, 0)]
+        } else {
+            file.matches
+                .iter()
+                .map(|(profile, count)| (Some(profile.as_str()), *count))
+                .collect()
+        };
+        for (profile, count) in rows {
+            files.push(file.file.as_str());
+            lines.push(file.lines as i64);
+            profiles.push(profile);
+            matched.push(count as i64);
+            percents.push((file.rate(count) * 10_000.0).round() / 100.0);
+        }
+    }
+    let schema = Schema::new(vec![
+        Field::new("file", DataType::Utf8, false),
+        Field::new("lines", DataType::Int64, false),
+        Field::new("profile", DataType::Utf8, true),
+        Field::new("matched", DataType::Int64, false),
+        Field::new("percent", DataType::Float64, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from(files)),
+        Arc::new(Int64Array::from(lines)),
+        Arc::new(StringArray::from(profiles)),
+        Arc::new(Int64Array::from(matched)),
+        Arc::new(Float64Array::from(percents)),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::macros::parser::{ProfileFormat, Profiles};
+
+    #[test]
+    fn test_coverage() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("api.log"),
+            "1 GET /a\n2 GET /b\n\n3 POST /c\n-- restart\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("worker.log"), "job=1 ok\njob=2 ok\n").unwrap();
+        let profiles = Profiles::from_str_with_format(
+            r#"
+[[profiles]]
+name = "api"
+pattern = '^{{id:number}} {{method:var_name}} {{path:any}}$'
+
+[[profiles]]
+name = "get"
+pattern = '^{{id:number}} GET '
+
+[[profiles]]
+name = "broken"
+pattern = '{{x:no_such_macro}}'
+"#,
+            ProfileFormat::Toml,
+        )
+        .unwrap();
+
+        let pattern = dir.path().join("*.log").to_string_lossy().to_string();
+        let report = coverage(std::slice::from_ref(&pattern), &profiles.profiles, 100).unwrap();
+        assert_eq!(report.len(), 2);
+        let api = report.iter().find(|c| c.file.ends_with("api.log")).unwrap();
+        assert_eq!(api.lines, 4);
+        assert_eq!(
+            api.matches,
+            vec![("api".to_string(), 3), ("get".to_string(), 2)]
+        );
+        assert_eq!(api.best(), Some(("api", 0.75)));
+        assert!(api.is_covered(0.75) && !api.is_covered(0.9));
+        let worker = report
+            .iter()
+            .find(|c| c.file.ends_with("worker.log"))
+            .unwrap();
+        assert_eq!(worker.best(), None);
+        assert!(!worker.is_covered(0.0));
+
+        // Sampling stops after the first lines
+        let sampled = coverage(&[pattern], &profiles.profiles, 2).unwrap();
+        assert!(sampled.iter().all(|c| c.lines <= 2));
+
+        let batch = to_batch(&report).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let percents = batch
+            .column(4)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert!(percents.iter().any(|p| p == Some(75.0)));
+    }
+}
@@ -1,41 +1,281 @@
 //! Arrow array builder for log fields.
 
 use datafusion::arrow::array::{
-    ArrayBuilder, ArrayRef, Float64Builder, Int32Builder, StringBuilder,
-    TimestampMicrosecondBuilder,
+    ArrayBuilder, ArrayRef, BinaryBuilder, Decimal128Builder, DictionaryArray,
+    DurationMicrosecondBuilder, DurationMillisecondBuilder, DurationNanosecondBuilder,
+    DurationSecondBuilder, Float64Builder, Int8Array, Int8Builder, Int32Builder, Int64Builder,
+    ListBuilder, MapBuilder, StringArray, StringBuilder, TimestampMicrosecondBuilder,
+    TimestampMillisecondBuilder, TimestampNanosecondBuilder, TimestampSecondBuilder,
 };
+use datafusion::arrow::datatypes::{DataType, Field, Fields, Int8Type, TimeUnit};
+use std::any::Any;
+use std::sync::Arc;
 
-use crate::types::FieldType;
+use crate::types::{
+    FieldType, MAX_DECIMAL_PRECISION, MAX_DICTIONARY_VALUES, TimestampUnit, YearStrategy,
+};
+
+/// Arrow type of `Decimal` fields, as built by [`FieldsBuilder`].
+///
+/// Out-of-range precisions and scales, which `decimal(...)` rejects but a profile's
+/// type hint may hold, are clamped.
+pub fn decimal_data_type(precision: u8, scale: u8) -> DataType {
+    let precision = precision.clamp(1, MAX_DECIMAL_PRECISION);
+    DataType::Decimal128(precision, scale.min(precision) as i8)
+}
+
+fn time_unit(unit: TimestampUnit) -> TimeUnit {
+    match unit {
+        TimestampUnit::Seconds => TimeUnit::Second,
+        TimestampUnit::Millis => TimeUnit::Millisecond,
+        TimestampUnit::Micros => TimeUnit::Microsecond,
+        TimestampUnit::Nanos => TimeUnit::Nanosecond,
+    }
+}
+
+/// Arrow type of `DateTime` fields stored in `unit`, in `timezone` if they have one,
+/// as built by [`FieldsBuilder`].
+pub fn timestamp_data_type(unit: TimestampUnit, timezone: Option<&str>) -> DataType {
+    DataType::Timestamp(time_unit(unit), timezone.map(Into::into))
+}
+
+/// Arrow type of `Duration` fields stored in `unit`, as built by [`FieldsBuilder`].
+pub fn duration_data_type(unit: TimestampUnit) -> DataType {
+    DataType::Duration(time_unit(unit))
+}
+
+/// Builder of the Arrow timestamps of `unit` in `timezone`.
+fn timestamp_builder(unit: TimestampUnit, timezone: Option<&str>) -> Box<dyn ArrayBuilder> {
+    match unit {
+        TimestampUnit::Seconds => {
+            Box::new(TimestampSecondBuilder::new().with_timezone_opt(timezone))
+        }
+        TimestampUnit::Millis => {
+            Box::new(TimestampMillisecondBuilder::new().with_timezone_opt(timezone))
+        }
+        TimestampUnit::Micros => {
+            Box::new(TimestampMicrosecondBuilder::new().with_timezone_opt(timezone))
+        }
+        TimestampUnit::Nanos => {
+            Box::new(TimestampNanosecondBuilder::new().with_timezone_opt(timezone))
+        }
+    }
+}
+
+/// Append `timestamp` to a builder made by [`timestamp_builder`] for `unit`.
+fn append_timestamp(builder: &mut dyn ArrayBuilder, unit: TimestampUnit, timestamp: Option<i64>) {
+    let builder = builder.as_any_mut();
+    match unit {
+        TimestampUnit::Seconds => builder
+            .downcast_mut::<TimestampSecondBuilder>()
+            .unwrap()
+            .append_option(timestamp),
+        TimestampUnit::Millis => builder
+            .downcast_mut::<TimestampMillisecondBuilder>()
+            .unwrap()
+            .append_option(timestamp),
+        TimestampUnit::Micros => builder
+            .downcast_mut::<TimestampMicrosecondBuilder>()
+            .unwrap()
+            .append_option(timestamp),
+        TimestampUnit::Nanos => builder
+            .downcast_mut::<TimestampNanosecondBuilder>()
+            .unwrap()
+            .append_option(timestamp),
+    }
+}
+
+/// Arrow type of `Map` fields, as built by [`FieldsBuilder`].
+pub fn map_data_type() -> DataType {
+    let entries = Fields::from(vec![
+        Field::new("keys", DataType::Utf8, false),
+        Field::new("values", DataType::Utf8, true),
+    ]);
+    DataType::Map(
+        Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+        false,
+    )
+}
+
+/// Builder of the Arrow durations of `unit`.
+fn duration_builder(unit: TimestampUnit) -> Box<dyn ArrayBuilder> {
+    match unit {
+        TimestampUnit::Seconds => Box::new(DurationSecondBuilder::new()),
+        TimestampUnit::Millis => Box::new(DurationMillisecondBuilder::new()),
+        TimestampUnit::Micros => Box::new(DurationMicrosecondBuilder::new()),
+        TimestampUnit::Nanos => Box::new(DurationNanosecondBuilder::new()),
+    }
+}
+
+/// Append `duration` to a builder made by [`duration_builder`] for `unit`.
+fn append_duration(builder: &mut dyn ArrayBuilder, unit: TimestampUnit, duration: Option<i64>) {
+    let builder = builder.as_any_mut();
+    match unit {
+        TimestampUnit::Seconds => builder
+            .downcast_mut::<DurationSecondBuilder>()
+            .unwrap()
+            .append_option(duration),
+        TimestampUnit::Millis => builder
+            .downcast_mut::<DurationMillisecondBuilder>()
+            .unwrap()
+            .append_option(duration),
+        TimestampUnit::Micros => builder
+            .downcast_mut::<DurationMicrosecondBuilder>()
+            .unwrap()
+            .append_option(duration),
+        TimestampUnit::Nanos => builder
+            .downcast_mut::<DurationNanosecondBuilder>()
+            .unwrap()
+            .append_option(duration),
+    }
+}
+
+/// Arrow type of `Dictionary` fields, as built by [`FieldsBuilder`].
+pub fn dictionary_data_type() -> DataType {
+    DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8))
+}
+
+/// Builds a dictionary array over a fixed set of values, so every batch has the same
+/// dictionary however few of them it uses.
+struct FixedDictionaryBuilder {
+    values: Vec<String>,
+    dictionary: ArrayRef,
+    keys: Int8Builder,
+}
+
+impl FixedDictionaryBuilder {
+    fn new(values: &[String]) -> Self {
+        // Keys are 8 bits; the `enum` macro caps the values at MAX_DICTIONARY_VALUES
+        let values = &values[..values.len().min(MAX_DICTIONARY_VALUES)];
+        Self {
+            values: values.to_vec(),
+            dictionary: Arc::new(StringArray::from(values.to_vec())),
+            keys: Int8Builder::new(),
+        }
+    }
+
+    /// Append `value`, or null when it is not one of the values.
+    fn append(&mut self, value: &str) {
+        let key = self.values.iter().position(|v| v == value);
+        self.keys.append_option(key.map(|key| key as i8));
+    }
+
+    fn array(&self, keys: Int8Array) -> ArrayRef {
+        Arc::new(DictionaryArray::<Int8Type>::try_new(keys, self.dictionary.clone()).unwrap())
+    }
+}
+
+impl ArrayBuilder for FixedDictionaryBuilder {
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        let keys = self.keys.finish();
+        self.array(keys)
+    }
+
+    fn finish_cloned(&self) -> ArrayRef {
+        self.array(self.keys.finish_cloned())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_box_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
 
 /// Builds Arrow arrays from parsed log field values.
 pub struct FieldsBuilder {
     builders: Vec<Box<dyn ArrayBuilder>>,
+    /// How timestamps without a year get one, and from what time in microseconds.
+    year: (YearStrategy, Option<i64>),
+    /// Unit of `DateTime` fields that do not choose their own.
+    timestamp_unit: TimestampUnit,
+    /// Unit of `Duration` fields.
+    duration_unit: TimestampUnit,
 }
 
 impl FieldsBuilder {
     /// Create a new FieldsBuilder with the appropriate builder for each field type.
     pub fn new(fields: &[&FieldType]) -> Self {
+        Self::with_timestamp_unit(fields, TimestampUnit::default())
+    }
+
+    /// Like [`FieldsBuilder::new`], storing `DateTime` fields without a unit of their
+    /// own in `timestamp_unit`.
+    pub fn with_timestamp_unit(fields: &[&FieldType], timestamp_unit: TimestampUnit) -> Self {
+        Self::with_units(fields, timestamp_unit, TimestampUnit::Millis)
+    }
+
+    /// Like [`FieldsBuilder::with_timestamp_unit`], storing `Duration` fields in
+    /// `duration_unit` rather than milliseconds.
+    pub fn with_units(
+        fields: &[&FieldType],
+        timestamp_unit: TimestampUnit,
+        duration_unit: TimestampUnit,
+    ) -> Self {
         let builders = fields
             .iter()
             .map(|field| match field {
                 FieldType::String => Box::new(StringBuilder::new()) as Box<dyn ArrayBuilder>,
                 FieldType::Int => Box::new(Int32Builder::new()) as Box<dyn ArrayBuilder>,
+                FieldType::Int64 => Box::new(Int64Builder::new()) as Box<dyn ArrayBuilder>,
                 FieldType::Float => Box::new(Float64Builder::new()) as Box<dyn ArrayBuilder>,
-                FieldType::DateTime(_) => {
-                    Box::new(TimestampMicrosecondBuilder::new()) as Box<dyn ArrayBuilder>
+                FieldType::Decimal(precision, scale) => Box::new(
+                    Decimal128Builder::new().with_data_type(decimal_data_type(*precision, *scale)),
+                ) as Box<dyn ArrayBuilder>,
+                FieldType::DateTime(d) => {
+                    timestamp_builder(d.unit.unwrap_or(timestamp_unit), d.timezone.as_deref())
                 }
                 FieldType::Enum => Box::new(StringBuilder::new()) as Box<dyn ArrayBuilder>,
+                FieldType::Dictionary(values) => {
+                    Box::new(FixedDictionaryBuilder::new(values)) as Box<dyn ArrayBuilder>
+                }
                 FieldType::Json => Box::new(StringBuilder::new()) as Box<dyn ArrayBuilder>,
+                FieldType::List(_) => {
+                    Box::new(ListBuilder::new(StringBuilder::new())) as Box<dyn ArrayBuilder>
+                }
+                FieldType::Duration => duration_builder(duration_unit),
+                FieldType::Binary(_) => Box::new(BinaryBuilder::new()) as Box<dyn ArrayBuilder>,
+                FieldType::Map => Box::new(MapBuilder::new(
+                    None,
+                    StringBuilder::new(),
+                    StringBuilder::new(),
+                )) as Box<dyn ArrayBuilder>,
             })
             .collect();
-        Self { builders }
+        Self {
+            builders,
+            year: (YearStrategy::default(), None),
+            timestamp_unit,
+            duration_unit,
+        }
+    }
+
+    /// Give timestamps without a year one by `strategy`, from `reference` in
+    /// microseconds since the epoch, e.g. the modification time of their file.
+    pub fn with_year(mut self, strategy: YearStrategy, reference: Option<i64>) -> Self {
+        self.year = (strategy, reference);
+        self
     }
 
     /// Push a row of values into the builders.
     ///
     /// Accepts string slices (`&str`) to avoid intermediate allocations.
-    /// For Int and Float types, parsing errors result in null values.
-    pub fn push(&mut self, field_types: &[&FieldType], values: &[&str]) {
+    /// For Int, Float, Decimal and Duration types, parsing errors result in null values.
+    pub fn push<'v>(
+        &mut self,
+        field_types: &[&FieldType],
+        values: impl IntoIterator<Item = &'v str>,
+    ) {
         for ((builder, field_type), value) in self.builders.iter_mut().zip(field_types).zip(values)
         {
             match field_type {
@@ -47,14 +287,11 @@ impl FieldsBuilder {
                         .append_value(value);
                 }
                 FieldType::DateTime(d) => {
-                    let datetime_builder = builder
-                        .as_any_mut()
-                        .downcast_mut::<TimestampMicrosecondBuilder>()
-                        .unwrap();
-                    match d.parse(value) {
-                        Some(i) => datetime_builder.append_value(i),
-                        None => datetime_builder.append_null(),
-                    }
+                    let unit = d.unit.unwrap_or(self.timestamp_unit);
+                    let timestamp = d
+                        .parse_datetime(value, self.year.0, self.year.1)
+                        .and_then(|datetime| unit.timestamp(datetime));
+                    append_timestamp(builder.as_mut(), unit, timestamp);
                 }
                 FieldType::Int => {
                     let int_builder = builder.as_any_mut().downcast_mut::<Int32Builder>().unwrap();
@@ -63,6 +300,13 @@ impl FieldsBuilder {
                         Err(_) => int_builder.append_null(),
                     }
                 }
+                FieldType::Int64 => {
+                    let int_builder = builder.as_any_mut().downcast_mut::<Int64Builder>().unwrap();
+                    match value.parse::<i64>() {
+                        Ok(i) => int_builder.append_value(i),
+                        Err(_) => int_builder.append_null(),
+                    }
+                }
                 FieldType::Float => {
                     let float_builder = builder
                         .as_any_mut()
@@ -73,6 +317,52 @@ impl FieldsBuilder {
                         Err(_) => float_builder.append_null(),
                     }
                 }
+                FieldType::Decimal(precision, scale) => {
+                    builder
+                        .as_any_mut()
+                        .downcast_mut::<Decimal128Builder>()
+                        .unwrap()
+                        .append_option(FieldType::parse_decimal(value, *precision, *scale));
+                }
+                FieldType::Dictionary(_) => {
+                    builder
+                        .as_any_mut()
+                        .downcast_mut::<FixedDictionaryBuilder>()
+                        .unwrap()
+                        .append(value);
+                }
+                FieldType::Duration => {
+                    let duration = FieldType::parse_duration(value, self.duration_unit);
+                    append_duration(builder.as_mut(), self.duration_unit, duration);
+                }
+                FieldType::Binary(encoding) => {
+                    builder
+                        .as_any_mut()
+                        .downcast_mut::<BinaryBuilder>()
+                        .unwrap()
+                        .append_option(encoding.decode(value));
+                }
+                FieldType::List(separator) => {
+                    let list_builder = builder
+                        .as_any_mut()
+                        .downcast_mut::<ListBuilder<StringBuilder>>()
+                        .unwrap();
+                    for item in FieldType::split_list(value, separator) {
+                        list_builder.values().append_value(item);
+                    }
+                    list_builder.append(true);
+                }
+                FieldType::Map => {
+                    let map_builder = builder
+                        .as_any_mut()
+                        .downcast_mut::<MapBuilder<StringBuilder, StringBuilder>>()
+                        .unwrap();
+                    for (key, value) in FieldType::split_pairs(value) {
+                        map_builder.keys().append_value(key);
+                        map_builder.values().append_value(value);
+                    }
+                    map_builder.append(true).expect("as many keys as values");
+                }
             }
         }
     }
@@ -82,3 +372,194 @@ impl FieldsBuilder {
         self.builders.iter_mut().map(|b| b.finish()).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::RecordExtractor;
+    use crate::scanner::Scanner;
+    use datafusion::arrow::array::{Array, Float64Array, Int32Array, StringArray};
+
+    /// Kind of a randomly generated field.
+    #[derive(Clone, Copy)]
+    enum Kind {
+        Number,
+        Float,
+        Word,
+        Any,
+    }
+
+    impl Kind {
+        fn random(rng: &mut fastrand::Rng) -> Self {
+            [Kind::Number, Kind::Float, Kind::Word, Kind::Any][rng.usize(..4)]
+        }
+
+        fn macro_name(self) -> &'static str {
+            match self {
+                Kind::Number => "number",
+                Kind::Float => "float",
+                Kind::Word => "var_name",
+                Kind::Any => "any",
+            }
+        }
+
+        fn regex(self) -> &'static str {
+            match self {
+                Kind::Number => r"\d+",
+                Kind::Float => r"-?\d+\.\d+",
+                Kind::Word => r"[A-Za-z_]\w*",
+                Kind::Any => r".+?",
+            }
+        }
+
+        fn value(self, rng: &mut fastrand::Rng) -> String {
+            let digits = |rng: &mut fastrand::Rng, max: usize| {
+                (0..rng.usize(1..=max))
+                    .map(|_| rng.digit(10))
+                    .collect::<String>()
+            };
+            match self {
+                // Up to 12 digits, so some overflow Int32 and must come out as null
+                Kind::Number => digits(rng, 12),
+                Kind::Float => format!(
+                    "{}{}.{}",
+                    if rng.bool() { "-" } else { "" },
+                    digits(rng, 6),
+                    digits(rng, 4)
+                ),
+                Kind::Word => {
+                    let mut word = rng.alphabetic().to_string();
+                    word.extend((0..rng.usize(..8)).map(|_| rng.alphanumeric()));
+                    word
+                }
+                Kind::Any => (0..rng.usize(1..12))
+                    .map(|_| ['x', 'é', '-', '.', '7', '日'][rng.usize(..6)])
+                    .collect(),
+            }
+        }
+    }
+
+    /// Random pattern, a matching line and the expected value of every field.
+    ///
+    /// Fields are written as macros or as raw named groups (never mixed, since raw
+    /// groups are ignored when macros are present), separated by literals. Optional
+    /// groups that do not participate must come out as empty strings.
+    fn random_case(rng: &mut fastrand::Rng) -> (String, String, Vec<String>) {
+        let use_macros = rng.bool();
+        let mut pattern = String::from("^");
+        let mut line = String::new();
+        let mut expected = Vec::new();
+        for i in 0..rng.usize(1..7) {
+            let kind = Kind::random(rng);
+            let name = format!("f{}", i);
+            let separator = [" ", " | ", "=", "] ["][rng.usize(..4)];
+            if i > 0 {
+                pattern.push_str(&regex::escape(separator));
+                line.push_str(separator);
+            }
+            let optional = !use_macros && rng.u8(..4) == 0;
+            let group = if use_macros {
+                format!("{{{{{}:{}}}}}", name, kind.macro_name())
+            } else {
+                format!("(?P<{}>{})", name, kind.regex())
+            };
+            if optional {
+                pattern.push_str(&format!("(?:<{}>)?", group));
+            } else {
+                pattern.push_str(&group);
+            }
+            let value = kind.value(rng);
+            if optional && rng.bool() {
+                expected.push(String::new());
+            } else {
+                if optional {
+                    line.push_str(&format!("<{}>", value));
+                } else {
+                    line.push_str(&value);
+                }
+                expected.push(value);
+            }
+        }
+        pattern.push('$');
+        (pattern, line, expected)
+    }
+
+    #[test]
+    fn test_scan_paths_agree_on_random_patterns() {
+        let mut rng = fastrand::Rng::with_seed(0x1f1a9);
+        for _ in 0..500 {
+            let (pattern, line, expected) = random_case(&mut rng);
+            let context = format!("pattern {:?}, line {:?}", pattern, line);
+            let scanner = Scanner::new(pattern.clone()).expect(&context);
+            let names: Vec<&str> = scanner.field_names.iter().map(String::as_str).collect();
+            assert_eq!(names.len(), expected.len(), "{}", context);
+
+            let scanned = scanner.scan(&line).expect(&context);
+            assert_eq!(scanned, expected, "{}", context);
+            assert_eq!(
+                scanner.scan_with(&line, &names).expect(&context),
+                scanned,
+                "{}",
+                context
+            );
+
+            let indices = scanner.prepare_indices(&names, &[]).unwrap();
+            let mut direct = Vec::new();
+            assert!(
+                scanner.scan_direct(&line, &indices, &mut direct),
+                "{}",
+                context
+            );
+            assert_eq!(direct, scanned, "{}", context);
+
+            let mut extracted = Vec::new();
+            assert!(scanner.extract(&line, &mut extracted), "{}", context);
+            assert_eq!(extracted, scanned, "{}", context);
+            assert!(scanner.is_match(&line), "{}", context);
+
+            // Every value must survive conversion to Arrow with the field's type
+            let default_type = FieldType::String;
+            let types: Vec<&FieldType> = names
+                .iter()
+                .map(|name| scanner.type_hints.get(*name).unwrap_or(&default_type))
+                .collect();
+            let mut builder = FieldsBuilder::new(&types);
+            builder.push(&types, direct.iter().copied());
+            let columns = builder.finish();
+            for ((column, field_type), value) in columns.iter().zip(&types).zip(&scanned) {
+                assert_eq!(column.len(), 1, "{}", context);
+                match field_type {
+                    FieldType::Int => {
+                        let array = column.as_any().downcast_ref::<Int32Array>().unwrap();
+                        let actual = (!array.is_null(0)).then(|| array.value(0));
+                        assert_eq!(actual, value.parse::<i32>().ok(), "{}", context);
+                    }
+                    FieldType::Float => {
+                        let array = column.as_any().downcast_ref::<Float64Array>().unwrap();
+                        assert_eq!(array.value(0), value.parse::<f64>().unwrap(), "{}", context);
+                    }
+                    _ => {
+                        let array = column.as_any().downcast_ref::<StringArray>().unwrap();
+                        assert_eq!(array.value(0), value, "{}", context);
+                    }
+                }
+            }
+
+            // All paths must agree on whether a mangled line matches
+            let mut mangled: Vec<char> = line.chars().collect();
+            if !mangled.is_empty() {
+                mangled.remove(rng.usize(..mangled.len()));
+            }
+            let mangled: String = mangled.into_iter().collect();
+            let matched = scanner.scan(&mangled).is_some();
+            assert_eq!(
+                scanner.scan_direct(&mangled, &indices, &mut direct),
+                matched,
+                "{}",
+                context
+            );
+            assert_eq!(scanner.extract(&mangled, &mut extracted), matched);
+            assert_eq!(scanner.is_match(&mangled), matched, "{}", context);
+        }
+    }
+}
@@ -1,39 +1,67 @@
 //! LogTableExec execution plan implementation.
 
 use crate::error::{Error as LfError, Result as LfResult};
+use datafusion::arrow::compute::SortOptions;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
 use datafusion::execution::SendableRecordBatchStream;
-use datafusion::physical_expr::{EquivalenceProperties, Partitioning};
+use datafusion::execution::memory_pool::{MemoryConsumer, MemoryReservation};
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::{EquivalenceProperties, Partitioning, PhysicalSortExpr};
 use datafusion::physical_plan::execution_plan::{Boundedness, EmissionType};
 use datafusion::physical_plan::memory::MemoryStream;
+use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricBuilder, MetricsSet};
 use datafusion::physical_plan::{DisplayAs, ExecutionPlan, PlanProperties};
 use datafusion_common::Result;
-use glob::glob;
+use encoding_rs::Encoding;
+use flate2::read::MultiGzDecoder;
 use memmap2::Mmap;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use std::fs::File;
-use std::sync::Arc;
-
-use crate::Scanner;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use std::borrow::Cow;
+use std::fs::{File, Metadata};
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::cancel::{CHECK_INTERVAL, CancellationToken};
 use crate::datafusion::builder::FieldsBuilder;
-use crate::datafusion::provider::LogTableProvider;
-use crate::types::FieldType;
+use crate::datafusion::files::is_gzip;
+use crate::datafusion::index::BlockIndex;
+use crate::datafusion::provider::{LogTableProvider, LongLines};
+use crate::datafusion::search::RawFilter;
+use crate::extractor::RecordExtractor;
+use crate::stats::ScanCounts;
+use crate::types::{DateTime, FieldType, TimestampUnit, YearStrategy};
 
 /// Physical execution plan for reading log files.
 #[derive(Debug)]
 pub struct LogTableExec {
     provider: LogTableProvider,
+    files: Vec<String>,
+    /// Tokens every matching line contains, used to skip blocks via the file index.
+    tokens: Vec<String>,
+    /// Searches each raw line must pass before it is parsed.
+    raw_filters: Vec<RawFilter>,
     projected_schema: SchemaRef,
     plan_properties: PlanProperties,
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl LogTableExec {
-    /// Create a new LogTableExec with optional column projections.
+    /// Create a new LogTableExec over `files` with optional column projections.
+    ///
+    /// Files are read in the given order. When the provider is `time_ordered`, the
+    /// output is declared sorted by its first datetime field if that field is projected.
+    ///
+    /// When the provider is `merge_by_time`, each file becomes its own partition declared
+    /// sorted by that field, letting DataFusion k-way merge the files instead of sorting.
     pub fn new(
         projections: Option<&Vec<usize>>,
         schema: SchemaRef,
         provider: LogTableProvider,
+        files: Vec<String>,
     ) -> Self {
         let projected_schema = projections
             .map(|p| {
@@ -42,19 +70,77 @@ impl LogTableExec {
             })
             .unwrap_or_else(|| schema.clone());
 
+        let eq_properties = match Self::time_ordering(&provider, &projected_schema) {
+            Some(sort_expr) => {
+                EquivalenceProperties::new_with_orderings(projected_schema.clone(), [[sort_expr]])
+            }
+            None => EquivalenceProperties::new(projected_schema.clone()),
+        };
+
+        let partition_count = if provider.merge_by_time {
+            files.len().max(1)
+        } else {
+            1
+        };
+
         let plan_properties = PlanProperties::new(
-            EquivalenceProperties::new(projected_schema.clone()),
-            Partitioning::UnknownPartitioning(1),
+            eq_properties,
+            Partitioning::UnknownPartitioning(partition_count),
             EmissionType::Final,
             Boundedness::Bounded,
         );
 
         Self {
             provider,
+            files,
+            tokens: Vec::new(),
+            raw_filters: Vec::new(),
             projected_schema,
             plan_properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
+
+    /// Sort expression on the first datetime field, if declared time ordered and projected.
+    fn time_ordering(
+        provider: &LogTableProvider,
+        projected_schema: &SchemaRef,
+    ) -> Option<PhysicalSortExpr> {
+        if !provider.time_ordered && !provider.merge_by_time {
+            return None;
+        }
+        let time_field = provider.extractor.field_names().iter().find(|name| {
+            matches!(
+                provider.extractor.type_hints().get(*name),
+                Some(FieldType::DateTime(_))
+            )
+        })?;
+        let index = projected_schema.index_of(time_field).ok()?;
+        Some(PhysicalSortExpr::new(
+            Arc::new(Column::new(time_field, index)),
+            SortOptions {
+                descending: false,
+                nulls_first: false,
+            },
+        ))
+    }
+
+    /// Only scan index blocks that may contain all of `tokens`.
+    pub fn with_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    /// Only emit lines passing all of `raw_filters`.
+    pub fn with_raw_filters(mut self, raw_filters: Vec<RawFilter>) -> Self {
+        self.raw_filters = raw_filters;
+        self
+    }
+
+    /// The files scanned by this plan, in read order.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
 }
 
 impl DisplayAs for LogTableExec {
@@ -63,7 +149,22 @@ impl DisplayAs for LogTableExec {
         _t: datafusion::physical_plan::DisplayFormatType,
         _f: &mut std::fmt::Formatter,
     ) -> std::fmt::Result {
-        write!(_f, "LogTableExec")
+        write!(_f, "LogTableExec")?;
+        if !self.tokens.is_empty() {
+            write!(_f, ": index_tokens=[{}]", self.tokens.join(", "))?;
+        }
+        if !self.raw_filters.is_empty() {
+            let filters: Vec<String> = self
+                .raw_filters
+                .iter()
+                .map(|filter| match filter {
+                    RawFilter::Contains(needle) => format!("raw_contains({:?})", needle),
+                    RawFilter::Match(re) => format!("raw_match({:?})", re.as_str()),
+                })
+                .collect();
+            write!(_f, " raw_filters=[{}]", filters.join(", "))?;
+        }
+        Ok(())
     }
 }
 
@@ -93,11 +194,16 @@ impl ExecutionPlan for LogTableExec {
 
     fn execute(
         &self,
-        _partition: usize,
-        _context: Arc<datafusion::execution::TaskContext>,
+        partition: usize,
+        context: Arc<datafusion::execution::TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
         // Get field types in the same order as field_names, defaulting to String
         let default_string = FieldType::String;
+        // File mtime is passed as "<secs>.<micros>" and file size as a plain integer
+        let mtime_type = FieldType::DateTime(DateTime::new(Some(vec!["%s%.f".to_string()])));
+        let size_type = FieldType::Int64;
+        let line_type = FieldType::Int64;
+        let pattern_type = FieldType::Int64;
 
         let projected_fields = self.projected_schema.fields().iter().collect::<Vec<_>>();
         let field_names: Vec<&str> = projected_fields
@@ -106,61 +212,185 @@ impl ExecutionPlan for LogTableExec {
             .collect::<Vec<_>>();
         let field_types: Vec<&FieldType> = projected_fields
             .iter()
-            .map(|f| {
-                self.provider
-                    .scanner
-                    .type_hints
-                    .get(f.name())
-                    .unwrap_or(&default_string)
+            .map(|f| match f.name().as_str() {
+                "__FILE_MTIME__" if self.provider.add_file_mtime => &mtime_type,
+                "__FILE_SIZE__" if self.provider.add_file_size => &size_type,
+                "__LINE__" if self.provider.unmatched => &line_type,
+                "__PATTERN__" if self.provider.add_pattern => &pattern_type,
+                name => self
+                    .provider
+                    .extractor
+                    .type_hints()
+                    .get(name)
+                    .unwrap_or(&default_string),
             })
             .collect();
 
-        // Handle when provider.file_path is a glob path
-        let glb = glob(&self.provider.file_path)
-            .map_err(|e| datafusion_common::DataFusionError::External(Box::new(e)))?;
-        let files = glb
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| datafusion_common::DataFusionError::External(Box::new(e)))?;
-
-        if files.is_empty() {
-            return Err(datafusion_common::DataFusionError::External(
-                "No files found".into(),
-            ));
-        }
-
         let add_file_path = self.provider.add_file_path
+            && !self.provider.unmatched
             && self.projected_schema.column_with_name("__FILE__").is_some();
-        let add_raw =
-            self.provider.add_raw && self.projected_schema.column_with_name("__RAW__").is_some();
+        let add_raw = self.provider.add_raw
+            && !self.provider.unmatched
+            && self.projected_schema.column_with_name("__RAW__").is_some();
+        let add_file_mtime = self.provider.add_file_mtime
+            && !self.provider.unmatched
+            && self
+                .projected_schema
+                .column_with_name("__FILE_MTIME__")
+                .is_some();
+        let add_file_size = self.provider.add_file_size
+            && !self.provider.unmatched
+            && self
+                .projected_schema
+                .column_with_name("__FILE_SIZE__")
+                .is_some();
+        let add_pattern = self.provider.add_pattern
+            && !self.provider.unmatched
+            && self
+                .projected_schema
+                .column_with_name("__PATTERN__")
+                .is_some();
+
+        // In merge mode each partition reads a single file
+        let files = if self.provider.merge_by_time {
+            &self.files[partition..partition + 1]
+        } else {
+            &self.files[..]
+        };
+
+        // Set per statement by `LfLog::query_with_token`
+        let cancel = context
+            .session_config()
+            .get_extension::<CancellationToken>();
+
+        // Parsed batches are held in memory until consumed, so they are reserved from the
+        // session's memory pool; exceeding its limit fails the scan instead of the process.
+        let reservation = Mutex::new(
+            MemoryConsumer::new(format!("LogTableExec[{}]", partition))
+                .register(context.memory_pool()),
+        );
+        let output_rows = MetricBuilder::new(&self.metrics).output_rows(partition);
+        let invalid_utf8_lines =
+            MetricBuilder::new(&self.metrics).counter("invalid_utf8_lines", partition);
+        let truncated_files =
+            MetricBuilder::new(&self.metrics).counter("truncated_files", partition);
+        let mapped_files = MetricBuilder::new(&self.metrics).counter("mapped_files", partition);
+        let long_lines = MetricBuilder::new(&self.metrics).counter("long_lines", partition);
+        let peak_mem_used = MetricBuilder::new(&self.metrics).gauge("peak_mem_used", partition);
+        let elapsed_compute = MetricBuilder::new(&self.metrics).elapsed_compute(partition);
+        let timer = elapsed_compute.timer();
+        let _span = tracing::info_span!("log_scan", partition, files = files.len()).entered();
 
-        let files = files
-            .iter()
-            .map(|f| f.to_string_lossy().to_string())
-            .collect::<Vec<_>>();
         let partitions = files
-            .into_iter()
+            .iter()
             .map(|file| {
                 let ctx = ParseContext {
-                    file: &file,
-                    scanner: &self.provider.scanner,
+                    cancel: cancel.as_deref(),
+                    reservation: &reservation,
+                    file,
+                    extractor: self.provider.extractor.as_ref(),
                     field_names: &field_names,
                     field_types: &field_types,
                     schema: self.projected_schema.clone(),
                     add_file_path,
                     add_raw,
-                    thread_count: self.provider.num_threads,
+                    add_file_mtime,
+                    add_file_size,
+                    add_pattern,
+                    unmatched: self.provider.unmatched,
+                    tokens: &self.tokens,
+                    raw_filters: &self.raw_filters,
+                    count_filtered: self.provider.min_match_rate.is_some(),
+                    skip_lines: self.provider.skip_lines,
+                    skip_footer: self.provider.skip_footer,
+                    encoding: self.provider.encoding,
+                    line_limit: self
+                        .provider
+                        .max_line_length
+                        .map(|max| (max, self.provider.long_lines)),
+                    year: self.provider.year,
+                    timestamp_unit: self.provider.timestamp_unit,
+                    duration_unit: self.provider.duration_unit,
+                };
+                let parsed = match &self.provider.thread_pool {
+                    Some(pool) => pool.install(|| parse(ctx)),
+                    None => parse(ctx),
                 };
-                parse(ctx).map_err(|e| datafusion_common::DataFusionError::External(Box::new(e)))
+                parsed
+                    .map(|(batches, counts)| (file, batches, counts))
+                    .map_err(|e| match e {
+                        // Keep e.g. ResourcesExhausted from the memory pool as is
+                        LfError::DataFusion(e) => e,
+                        e => datafusion_common::DataFusionError::External(Box::new(e)),
+                    })
             })
             .collect::<Result<Vec<_>, _>>()?;
 
-        let partitions = partitions.into_iter().flatten().collect::<Vec<_>>();
+        for (file, _, counts) in &partitions {
+            if counts.invalid_utf8 > 0 {
+                log::warn!(
+                    "{}: {} lines with invalid UTF-8 were decoded lossily",
+                    file,
+                    counts.invalid_utf8
+                );
+                invalid_utf8_lines.add(counts.invalid_utf8);
+            }
+            if counts.truncated {
+                log::warn!(
+                    "{}: file was truncated or rotated while it was read; results stop at the truncation point",
+                    file
+                );
+                truncated_files.add(1);
+            }
+            if counts.mapped {
+                mapped_files.add(1);
+            }
+            if counts.long_lines > 0 {
+                log::warn!(
+                    "{}: {} lines longer than {} bytes were {}",
+                    file,
+                    counts.long_lines,
+                    self.provider.max_line_length.unwrap_or_default(),
+                    match self.provider.long_lines {
+                        LongLines::Skip => "skipped",
+                        LongLines::Truncate => "truncated",
+                    }
+                );
+                long_lines.add(counts.long_lines);
+            }
+        }
+
+        if let Some(min_rate) = self.provider.min_match_rate
+            && !self.provider.unmatched
+        {
+            check_match_rate(
+                partitions
+                    .iter()
+                    .map(|(file, _, counts)| (file.as_str(), counts)),
+                min_rate,
+            )
+            .map_err(|e| datafusion_common::DataFusionError::External(Box::new(e)))?;
+        }
+
+        let partitions = partitions
+            .into_iter()
+            .flat_map(|(_, batches, _)| batches)
+            .collect::<Vec<_>>();
+
+        let reservation = reservation.into_inner().unwrap();
+        let rows = partitions.iter().map(|b| b.num_rows()).sum();
+        tracing::debug!(rows, peak_mem = reservation.size(), "scan finished");
+        output_rows.add(rows);
+        peak_mem_used.set_max(reservation.size());
+        timer.done();
+
+        Ok(Box::pin(
+            MemoryStream::try_new(partitions, self.schema(), None)?.with_reservation(reservation),
+        ))
+    }
 
-        Ok(Box::pin(MemoryStream::try_new(
-            partitions,
-            self.schema(),
-            None,
-        )?))
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
     }
 
     fn schema(&self) -> SchemaRef {
@@ -169,45 +399,225 @@ impl ExecutionPlan for LogTableExec {
 }
 
 struct ParseContext<'a> {
+    cancel: Option<&'a CancellationToken>,
+    reservation: &'a Mutex<MemoryReservation>,
     file: &'a str,
-    scanner: &'a Scanner,
+    extractor: &'a dyn RecordExtractor,
     field_names: &'a [&'a str],
     field_types: &'a [&'a FieldType],
     schema: SchemaRef,
     add_file_path: bool,
     add_raw: bool,
-    thread_count: Option<usize>,
+    add_file_mtime: bool,
+    add_file_size: bool,
+    add_pattern: bool,
+    unmatched: bool,
+    tokens: &'a [String],
+    raw_filters: &'a [RawFilter],
+    /// Count lines rejected by `raw_filters` towards the match rate.
+    count_filtered: bool,
+    /// Leading lines of the file to ignore.
+    skip_lines: usize,
+    /// Trailing lines of the file to ignore.
+    skip_footer: usize,
+    /// Encoding the file is transcoded from, if it is not UTF-8.
+    encoding: Option<&'static Encoding>,
+    /// Maximum line length in bytes, and what to do with longer lines.
+    line_limit: Option<(usize, LongLines)>,
+    /// How timestamps without a year get one from the file's modification time.
+    year: YearStrategy,
+    /// Unit of `DateTime` columns that do not choose their own.
+    timestamp_unit: TimestampUnit,
+    /// Unit of `Duration` columns.
+    duration_unit: TimestampUnit,
 }
 
-fn parse(ctx: ParseContext) -> LfResult<Vec<RecordBatch>> {
+/// Line counts gathered while parsing a file.
+#[derive(Debug, Default, Clone, Copy)]
+struct LineCounts {
+    /// Non-empty lines.
+    lines: usize,
+    /// Lines matching the pattern.
+    matched: usize,
+    /// Lines with invalid UTF-8, decoded lossily.
+    invalid_utf8: usize,
+    /// Lines over the maximum line length, skipped or truncated.
+    long_lines: usize,
+    /// Non-empty lines dropped without counting towards `lines`: excluded, skipped
+    /// for their length or filtered out before matching.
+    skipped: usize,
+    /// Bytes of the parsed chunks.
+    bytes: usize,
+    /// The file was truncated while it was read, so trailing lines may be missing.
+    truncated: bool,
+    /// The file was parsed from a memory map rather than a buffer.
+    mapped: bool,
+}
+
+impl std::ops::Add for LineCounts {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            lines: self.lines + other.lines,
+            matched: self.matched + other.matched,
+            invalid_utf8: self.invalid_utf8 + other.invalid_utf8,
+            long_lines: self.long_lines + other.long_lines,
+            skipped: self.skipped + other.skipped,
+            bytes: self.bytes + other.bytes,
+            truncated: self.truncated || other.truncated,
+            mapped: self.mapped || other.mapped,
+        }
+    }
+}
+
+/// Fail if any file's share of matching non-empty lines is below `min_rate`.
+fn check_match_rate<'a>(
+    files: impl Iterator<Item = (&'a str, &'a LineCounts)>,
+    min_rate: f64,
+) -> LfResult<()> {
+    let failures: Vec<String> = files
+        .filter(|(_, counts)| {
+            counts.lines > 0 && (counts.matched as f64) < min_rate * counts.lines as f64
+        })
+        .map(|(file, counts)| {
+            format!(
+                "{}: {}/{} lines matched ({:.1}%)",
+                file,
+                counts.matched,
+                counts.lines,
+                100.0 * counts.matched as f64 / counts.lines as f64
+            )
+        })
+        .collect();
+    if failures.is_empty() {
+        return Ok(());
+    }
+    Err(LfError::MatchRate(format!(
+        "below the required {:.1}% in {}",
+        100.0 * min_rate,
+        failures.join("; ")
+    )))
+}
+
+fn parse(ctx: ParseContext) -> LfResult<(Vec<RecordBatch>, LineCounts)> {
     let ParseContext {
+        cancel,
+        reservation,
         file,
-        scanner,
+        extractor,
         field_names,
         field_types,
         schema,
         add_file_path,
         add_raw,
-        thread_count,
+        add_file_mtime,
+        add_file_size,
+        add_pattern,
+        unmatched,
+        tokens,
+        raw_filters,
+        count_filtered,
+        skip_lines,
+        skip_footer,
+        encoding,
+        line_limit,
+        year,
+        timestamp_unit,
+        duration_unit,
     } = ctx;
 
+    let file_span = tracing::debug_span!("parse_file", file);
+    let _enter = file_span.enter();
     let f = File::open(file)?;
+    let metadata = f.metadata()?;
+    let file_size = metadata.len().to_string();
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok());
+    let file_mtime = modified
+        .map(|d| format!("{}.{:06}", d.as_secs(), d.subsec_micros()))
+        .unwrap_or_default();
+    let mtime_micros = modified.and_then(|d| i64::try_from(d.as_micros()).ok());
+
+    // Files read into memory are reserved like the batches, so `--memory-limit` bounds
+    // them too; the reservation is freed once the file is parsed
+    let mut buffer_reservation = reservation.lock().unwrap().new_empty();
+    let gzip = is_gzip(file);
+    let (data, short_read) = match gzip {
+        true => {
+            let data = read_reserved(MultiGzDecoder::new(&f), &mut buffer_reservation)?;
+            (FileData::Buffered(data), false)
+        }
+        false => FileData::open(&f, &metadata, &mut buffer_reservation)?,
+    };
+    let mapped = matches!(data, FileData::Mapped(_));
+    // Transcoding and decompression change the length, so keep the one on disk for
+    // the truncation check
+    let read_len = match gzip {
+        true => metadata.len(),
+        false => data.len() as u64,
+    };
+    let data = match encoding {
+        Some(encoding) => {
+            let text = transcode(&data, encoding, &mut buffer_reservation)?;
+            if let FileData::Buffered(read) = &data {
+                buffer_reservation.shrink(read.len());
+            }
+            drop(data);
+            FileData::Buffered(text)
+        }
+        None => data,
+    };
 
-    let mmap = unsafe { Mmap::map(&f)? };
-
-    // If thread_count is not provided, use LFLOGTHREADS environment variable
-    let thread_count = thread_count.or_else(|| {
-        std::env::var("LFLOGTHREADS")
-            .ok()
-            .and_then(|s| s.parse().ok())
-    });
+    // Header and footer lines are never parsed, so they count neither as rows nor
+    // towards the match rate
+    let (body_start, body_end) = body_range(&data, skip_lines, skip_footer);
 
-    let chunk_count = thread_count
-        .unwrap_or_else(rayon::current_num_threads)
-        .clamp(1, rayon::current_num_threads());
-    let total_size = mmap.len();
-    let chunk_size = total_size / chunk_count;
+    // One chunk per thread of the pool the parse runs in
+    let chunk_count = rayon::current_num_threads();
+    let bounds: Vec<(usize, usize)> = if tokens.is_empty() {
+        chunk_bounds(&data[body_start..body_end], chunk_count)
+            .into_iter()
+            .map(|(start, end)| (body_start + start, body_start + end))
+            .collect()
+    } else {
+        BlockIndex::load_or_build(file, &metadata, &data)?
+            .candidate_ranges(tokens)
+            .into_iter()
+            .map(|(start, end)| (start.max(body_start), end.min(body_end)))
+            .filter(|(start, end)| start < end)
+            .collect()
+    };
+    tracing::debug!(
+        bytes = metadata.len(),
+        chunks = bounds.len(),
+        indexed = !tokens.is_empty(),
+        "file opened"
+    );
+
+    // Line numbers are only needed for unmatched rows; count the lines in each chunk
+    // to find the number of the first line of every chunk.
+    let first_lines: Vec<usize> = if unmatched {
+        let counts: Vec<usize> = bounds
+            .par_iter()
+            .map(|&(start, end)| memchr::memchr_iter(b'\n', &data[start..end]).count())
+            .collect();
+        let header_lines = memchr::memchr_iter(b'\n', &data[..body_start]).count();
+        counts
+            .iter()
+            .scan(1 + header_lines, |next, count| {
+                let first = *next;
+                *next += count;
+                Some(first)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
 
+    // Metadata columns are appended after the extracted values of each line
     let mut additional_columns = Vec::new();
     if add_file_path {
         additional_columns.push("__FILE__");
@@ -215,40 +625,439 @@ fn parse(ctx: ParseContext) -> LfResult<Vec<RecordBatch>> {
     if add_raw {
         additional_columns.push("__RAW__");
     }
+    if add_file_mtime {
+        additional_columns.push("__FILE_MTIME__");
+    }
+    if add_file_size {
+        additional_columns.push("__FILE_SIZE__");
+    }
+    if add_pattern {
+        additional_columns.push("__PATTERN__");
+    }
 
-    let file_path_index = if add_file_path {
+    // Unmatched rows only carry metadata columns, which are not extracted
+    let extracted = extractor.field_names();
+    let field_indices = if unmatched {
+        Vec::new()
+    } else {
         field_names
             .iter()
-            .position(|&name| name == "__FILE__")
-            .unwrap()
-    } else {
-        0
+            .map(|&name| {
+                extracted
+                    .iter()
+                    .position(|field| field == name)
+                    .or_else(|| {
+                        additional_columns
+                            .iter()
+                            .position(|&column| column == name)
+                            .map(|i| extracted.len() + i)
+                    })
+                    .ok_or_else(|| LfError::other(format!("Field name not found: {}", name)))
+            })
+            .collect::<LfResult<Vec<usize>>>()?
     };
 
-    let raw_index = if add_raw {
-        field_names
-            .iter()
-            .position(|&name| name == "__RAW__")
+    let finish_batch = |fields_builder: &mut FieldsBuilder, row_count: usize| {
+        let columns = fields_builder.finish();
+        let options = RecordBatchOptions::new().with_row_count(Some(row_count));
+        let batch = RecordBatch::try_new_with_options(schema.clone(), columns, &options)?;
+        reservation
+            .lock()
             .unwrap()
-    } else {
-        0
+            .try_grow(batch.get_array_memory_size())?;
+        tracing::trace!(
+            rows = row_count,
+            bytes = batch.get_array_memory_size(),
+            "batch emitted"
+        );
+        Ok::<_, LfError>(batch)
     };
 
-    let field_indices = scanner.prepare_indices(field_names, &additional_columns)?;
-
-    let partitions: std::result::Result<Vec<RecordBatch>, LfError> = (0..chunk_count)
+    let partitions: std::result::Result<Vec<(RecordBatch, LineCounts)>, LfError> = bounds
         .into_par_iter()
-        .map(|i| {
-            let mut fields_builder = FieldsBuilder::new(field_types);
+        .enumerate()
+        .map(|(i, (actual_start, actual_end))| {
+            // Chunks run on pool threads, so parent their spans explicitly
+            let _span = tracing::trace_span!(
+                parent: &file_span,
+                "parse_chunk",
+                chunk = i,
+                bytes = actual_end.saturating_sub(actual_start)
+            )
+            .entered();
+            let mut fields_builder =
+                FieldsBuilder::with_units(field_types, timestamp_unit, duration_unit)
+                    .with_year(year, mtime_micros);
+            let mut counts = LineCounts::default();
+
+            if actual_start >= actual_end {
+                // Empty chunk, return empty batch
+                return Ok((finish_batch(&mut fields_builder, 0)?, counts));
+            }
+
+            let section = &data[actual_start..actual_end];
+            counts.bytes = section.len();
+            let mut values = Vec::with_capacity(extracted.len() + additional_columns.len());
+            let mut row_count = 0;
+
+            // Without raw filters or the raw line, lines go to the extractor as bytes and
+            // only the values it captures are decoded
+            if !unmatched && raw_filters.is_empty() && !add_raw {
+                for (line_offset, line) in byte_lines(section).enumerate() {
+                    check_cancelled(cancel, line_offset)?;
+                    let Some(line) = limit_line(line, line_limit, &mut counts) else {
+                        continue;
+                    };
+                    let mut pattern = extractor.extract_bytes(line, &mut values);
+                    if pattern.is_none() && std::str::from_utf8(line).is_err() {
+                        // Decoded lossily, the line may still match
+                        counts.invalid_utf8 += 1;
+                        let decoded = String::from_utf8_lossy(line);
+                        let mut owned = Vec::new();
+                        pattern = extractor.extract_pattern(&decoded, &mut owned);
+                        values.clear();
+                        values.extend(owned.into_iter().map(|v| Cow::Owned(v.into_owned())));
+                    }
+                    let Some(pattern) = pattern else {
+                        if line.trim_ascii().is_empty() {
+                            continue;
+                        }
+                        if extractor.is_excluded(&String::from_utf8_lossy(line)) {
+                            counts.skipped += 1;
+                        } else {
+                            counts.lines += 1;
+                        }
+                        continue;
+                    };
+                    if add_file_path {
+                        values.push(Cow::Borrowed(file));
+                    }
+                    if add_file_mtime {
+                        values.push(Cow::Borrowed(&file_mtime));
+                    }
+                    if add_file_size {
+                        values.push(Cow::Borrowed(&file_size));
+                    }
+                    if add_pattern {
+                        values.push(Cow::Owned(pattern.to_string()));
+                    }
+                    fields_builder.push(
+                        field_types,
+                        field_indices.iter().map(|&i| values[i].as_ref()),
+                    );
+                    row_count += 1;
+                }
+                counts.lines += row_count;
+                counts.matched += row_count;
+                tracing::trace!(lines = counts.lines, rows = row_count, "chunk parsed");
+                return Ok((finish_batch(&mut fields_builder, row_count)?, counts));
+            }
+
+            // A corrupt byte must not abort the scan: invalid sequences in a line are
+            // replaced with U+FFFD and the line is counted.
+            let section_str = match std::str::from_utf8(section) {
+                Ok(s) => Cow::Borrowed(s),
+                Err(_) => {
+                    counts.invalid_utf8 = section
+                        .split(|&b| b == b'\n')
+                        .filter(|line| std::str::from_utf8(line).is_err())
+                        .count();
+                    String::from_utf8_lossy(section)
+                }
+            };
+
+            if unmatched {
+                for (line_offset, line) in lines(&section_str).enumerate() {
+                    check_cancelled(cancel, line_offset)?;
+                    let Some(line) = limit_str_line(line, line_limit, &mut counts) else {
+                        continue;
+                    };
+                    if line.trim().is_empty()
+                        || !raw_filters.iter().all(|filter| filter.matches(line))
+                        || extractor.is_match(line)
+                        || extractor.is_excluded(line)
+                    {
+                        continue;
+                    }
+                    let line_number = (first_lines[i] + line_offset).to_string();
+                    let row: Vec<&str> = field_names
+                        .iter()
+                        .map(|&name| match name {
+                            "__FILE__" => file,
+                            "__LINE__" => line_number.as_str(),
+                            _ => line,
+                        })
+                        .collect();
+                    fields_builder.push(field_types, row);
+                    row_count += 1;
+                }
+                return Ok((finish_batch(&mut fields_builder, row_count)?, counts));
+            }
 
-            let start = i * chunk_size;
+            for (line_offset, line) in lines(&section_str).enumerate() {
+                check_cancelled(cancel, line_offset)?;
+                let Some(line) = limit_str_line(line, line_limit, &mut counts) else {
+                    continue;
+                };
+                if !raw_filters.iter().all(|filter| filter.matches(line)) {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    if count_filtered && !extractor.is_excluded(line) {
+                        counts.lines += 1;
+                        counts.matched += extractor.is_match(line) as usize;
+                    } else {
+                        counts.skipped += 1;
+                    }
+                    continue;
+                }
+                if let Some(pattern) = extractor.extract_pattern(line, &mut values) {
+                    if add_file_path {
+                        values.push(Cow::Borrowed(file));
+                    }
+                    if add_raw {
+                        values.push(Cow::Borrowed(line));
+                    }
+                    if add_file_mtime {
+                        values.push(Cow::Borrowed(&file_mtime));
+                    }
+                    if add_file_size {
+                        values.push(Cow::Borrowed(&file_size));
+                    }
+                    if add_pattern {
+                        values.push(Cow::Owned(pattern.to_string()));
+                    }
+                    fields_builder.push(
+                        field_types,
+                        field_indices.iter().map(|&i| values[i].as_ref()),
+                    );
+                    row_count += 1;
+                } else if line.trim().is_empty() {
+                    continue;
+                } else if extractor.is_excluded(line) {
+                    counts.skipped += 1;
+                } else {
+                    counts.lines += 1;
+                }
+            }
+            counts.lines += row_count;
+            counts.matched += row_count;
+            tracing::trace!(lines = counts.lines, rows = row_count, "chunk parsed");
+
+            Ok((finish_batch(&mut fields_builder, row_count)?, counts))
+        })
+        .collect();
+
+    let (batches, counts): (Vec<_>, Vec<_>) = partitions?.into_iter().unzip();
+    let mut counts = counts.into_iter().fold(LineCounts::default(), |a, b| a + b);
+    // Appends are expected while reading a live log; shrinking means it was truncated
+    counts.truncated = short_read || f.metadata()?.len() < read_len;
+    counts.mapped = mapped;
+    tracing::debug!(
+        lines = counts.lines,
+        matched = counts.matched,
+        invalid_utf8 = counts.invalid_utf8,
+        truncated = counts.truncated,
+        "file parsed"
+    );
+    // `lines` of the unmatched table's scan are the same lines again
+    if let Some(stats) = extractor.scan_stats().filter(|_| !unmatched) {
+        stats.add(ScanCounts {
+            matched: counts.matched as u64,
+            unmatched: (counts.lines - counts.matched) as u64,
+            skipped: counts.skipped as u64,
+            bytes: counts.bytes as u64,
+        });
+    }
+    Ok((batches, counts))
+}
+
+/// Files modified this recently may still be written, truncated or rotated.
+const VOLATILE_WINDOW: Duration = Duration::from_secs(60);
+
+/// The bytes of a log file, memory-mapped or read into a buffer.
+enum FileData {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl FileData {
+    /// Map `f`, or read it into memory when it is volatile.
+    ///
+    /// Truncating a mapped file makes reads past the new end fault, so files that were
+    /// modified within [`VOLATILE_WINDOW`], and anything that is not a regular file, are
+    /// read into a buffer instead, grown from `reservation` so that reading fails
+    /// cleanly past the memory limit. Older files are mapped on the bet that they are
+    /// not truncated during the scan, which can still crash it if they are. Also
+    /// returns whether the read came up short.
+    fn open(
+        f: &File,
+        metadata: &Metadata,
+        reservation: &mut MemoryReservation,
+    ) -> LfResult<(Self, bool)> {
+        let recently_modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_none_or(|age| age < VOLATILE_WINDOW);
+        if !metadata.is_file() {
+            let data = read_reserved(f, reservation)?;
+            return Ok((FileData::Buffered(data), false));
+        }
+        if recently_modified {
+            let len = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+            reservation.try_grow(len)?;
+            let (data, short) = read_snapshot(f, metadata.len())?;
+            reservation.shrink(len - data.len());
+            return Ok((FileData::Buffered(data), short));
+        }
+        Ok((FileData::Mapped(unsafe { Mmap::map(f)? }), false))
+    }
+}
+
+impl std::ops::Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Mapped(mmap) => mmap,
+            FileData::Buffered(data) => data,
+        }
+    }
+}
+
+/// Decode `data` from `encoding` (or the encoding its byte order mark names) to UTF-8,
+/// growing `reservation` by the size of the result.
+///
+/// Malformed sequences become U+FFFD.
+fn transcode(
+    data: &[u8],
+    encoding: &'static Encoding,
+    reservation: &mut MemoryReservation,
+) -> LfResult<Vec<u8>> {
+    // Reserve the most the text can take before decoding, then give back the rest
+    let bound = encoding
+        .new_decoder()
+        .max_utf8_buffer_length(data.len())
+        .unwrap_or(usize::MAX);
+    reservation.try_grow(bound)?;
+    let (text, _, malformed) = encoding.decode(data);
+    if malformed {
+        tracing::debug!(encoding = encoding.name(), "malformed input replaced");
+    }
+    let text = text.into_owned().into_bytes();
+    reservation.shrink(bound.saturating_sub(text.len()));
+    reservation.try_grow(text.len().saturating_sub(bound))?;
+    Ok(text)
+}
+
+/// Read all of `reader`, a pipe, decompressor or other source of unknown size,
+/// growing `reservation` as the data comes in.
+fn read_reserved(mut reader: impl Read, reservation: &mut MemoryReservation) -> LfResult<Vec<u8>> {
+    const CHUNK: usize = 1 << 20;
+    let mut data = Vec::new();
+    loop {
+        reservation.try_grow(CHUNK)?;
+        let read = (&mut reader).take(CHUNK as u64).read_to_end(&mut data)?;
+        reservation.shrink(CHUNK - read);
+        if read == 0 {
+            return Ok(data);
+        }
+    }
+}
+
+/// Read the first `len` bytes of `reader`, and whether fewer were available.
+///
+/// A short read means the file was truncated while it was read; the partial line at
+/// the end is dropped so it does not become a corrupt row.
+fn read_snapshot(reader: impl Read, len: u64) -> LfResult<(Vec<u8>, bool)> {
+    let mut data = Vec::with_capacity(len as usize);
+    reader.take(len).read_to_end(&mut data)?;
+    let short = (data.len() as u64) < len;
+    if short {
+        let end = memchr::memrchr(b'\n', &data).map_or(0, |pos| pos + 1);
+        data.truncate(end);
+    }
+    Ok((data, short))
+}
+
+/// `line` within `limit`: cut to the maximum length, or `None` when it is skipped.
+/// Long lines are counted either way.
+fn limit_line<'l>(
+    line: &'l [u8],
+    limit: Option<(usize, LongLines)>,
+    counts: &mut LineCounts,
+) -> Option<&'l [u8]> {
+    match limit {
+        Some((max, policy)) if line.len() > max => {
+            counts.long_lines += 1;
+            match policy {
+                LongLines::Skip => {
+                    counts.skipped += 1;
+                    None
+                }
+                LongLines::Truncate => Some(&line[..max]),
+            }
+        }
+        _ => Some(line),
+    }
+}
 
+/// Like [`limit_line`], cutting at a character boundary.
+fn limit_str_line<'l>(
+    line: &'l str,
+    limit: Option<(usize, LongLines)>,
+    counts: &mut LineCounts,
+) -> Option<&'l str> {
+    let mut end = limit_line(line.as_bytes(), limit, counts)?.len();
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(&line[..end])
+}
+
+/// Check the cancellation token every [`CHECK_INTERVAL`] lines.
+fn check_cancelled(cancel: Option<&CancellationToken>, line_offset: usize) -> LfResult<()> {
+    match cancel {
+        Some(token) if line_offset.is_multiple_of(CHECK_INTERVAL) => token.check(),
+        _ => Ok(()),
+    }
+}
+
+/// Byte range of `data` left after dropping its first `skip_lines` and last
+/// `skip_footer` lines.
+fn body_range(data: &[u8], skip_lines: usize, skip_footer: usize) -> (usize, usize) {
+    let start = match skip_lines {
+        0 => 0,
+        n => memchr::memchr_iter(b'\n', data)
+            .nth(n - 1)
+            .map_or(data.len(), |newline| newline + 1),
+    };
+    // The newline ending the last line does not start another
+    let content = data.strip_suffix(b"\n").unwrap_or(data);
+    let end = match skip_footer {
+        0 => data.len(),
+        n => memchr::memrchr_iter(b'\n', content)
+            .nth(n - 1)
+            .map_or(0, |newline| newline + 1),
+    };
+    (start, end.max(start))
+}
+
+/// Split `data` into `chunk_count` byte ranges that end at line boundaries.
+///
+/// Ranges may be empty when the data has fewer lines than chunks.
+fn chunk_bounds(data: &[u8], chunk_count: usize) -> Vec<(usize, usize)> {
+    let total_size = data.len();
+    let chunk_size = total_size / chunk_count;
+    (0..chunk_count)
+        .map(|i| {
             // Find actual chunk boundaries at newline positions
             let actual_start = if i == 0 {
                 0
             } else {
                 // Start after the newline that ends the previous chunk's last line
-                find_next_newline(&mmap, start, total_size).unwrap_or(total_size)
+                find_next_newline(data, i * chunk_size, total_size).unwrap_or(total_size)
             };
 
             let actual_end = if i == chunk_count - 1 {
@@ -257,60 +1066,958 @@ fn parse(ctx: ParseContext) -> LfResult<Vec<RecordBatch>> {
             } else {
                 // Find the newline at or after the nominal end position
                 let nominal_end = (i + 1) * chunk_size;
-                find_next_newline(&mmap, nominal_end, total_size).unwrap_or(total_size)
+                find_next_newline(data, nominal_end, total_size).unwrap_or(total_size)
+            };
+            (actual_start, actual_end)
+        })
+        .collect()
+}
+
+/// Helper to find the index of the next newline character
+fn find_next_newline(mmap: &[u8], start: usize, end: usize) -> Option<usize> {
+    memchr::memchr(b'\n', &mmap[start..end]).map(|pos| start + pos + 1) // +1 to include the newline itself
+}
+
+/// Iterate over the lines of raw `data` like [`lines`], without decoding them.
+fn byte_lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut start = 0;
+    memchr::memchr_iter(b'\n', data)
+        .map(Some)
+        .chain(std::iter::once(None))
+        .filter_map(move |newline| match newline {
+            Some(end) => {
+                let line = &data[start..end];
+                start = end + 1;
+                Some(line.strip_suffix(b"\r").unwrap_or(line))
+            }
+            None => (start < data.len()).then(|| &data[start..]),
+        })
+}
+
+/// Iterate over the lines of `text` like [`str::lines`], finding newlines with memchr.
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+    let mut start = 0;
+    memchr::memchr_iter(b'\n', text.as_bytes())
+        .map(Some)
+        .chain(std::iter::once(None))
+        .filter_map(move |newline| match newline {
+            Some(end) => {
+                let line = &text[start..end];
+                start = end + 1;
+                Some(line.strip_suffix('\r').unwrap_or(line))
+            }
+            None => (start < text.len()).then(|| &text[start..]),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafusion::LogTableProvider;
+    use crate::scanner::Scanner;
+    use datafusion::arrow::datatypes::DataType;
+    use datafusion::prelude::SessionContext;
+
+    /// Subscriber recording span names and event messages.
+    struct Recorder {
+        seen: Arc<Mutex<Vec<String>>>,
+        next_id: std::sync::atomic::AtomicU64,
+    }
+
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            self.seen
+                .lock()
+                .unwrap()
+                .push(span.metadata().name().to_string());
+            let id = self
+                .next_id
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            tracing::span::Id::from_u64(id + 1)
+        }
+
+        fn record(&self, _span: &tracing::span::Id, _values: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            struct Message<'a>(&'a Mutex<Vec<String>>);
+            impl tracing::field::Visit for Message<'_> {
+                fn record_debug(
+                    &mut self,
+                    field: &tracing::field::Field,
+                    value: &dyn std::fmt::Debug,
+                ) {
+                    if field.name() == "message" {
+                        self.0.lock().unwrap().push(format!("{:?}", value));
+                    }
+                }
+            }
+            event.record(&mut Message(&self.seen));
+        }
+
+        fn enter(&self, _span: &tracing::span::Id) {}
+
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[tokio::test]
+    async fn test_log_table_tracing() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "1 a\n2 b\nnoise").unwrap();
+        temp_file.flush().unwrap();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let _guard = tracing::subscriber::set_default(Recorder {
+            seen: seen.clone(),
+            next_id: Default::default(),
+        });
+
+        let scanner = Scanner::new(r"^(?P<id>\d+) (?P<name>\w+)$".to_string()).unwrap();
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(temp_file.path().to_string_lossy())
+            .build();
+        let ctx = SessionContext::new();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+        ctx.sql("SELECT * FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        // Chunk spans and events may run on pool threads without the default set
+        let seen = seen.lock().unwrap();
+        for expected in [
+            "compiled scanner",
+            "log_scan",
+            "parse_file",
+            "file opened",
+            "file parsed",
+            "scan finished",
+        ] {
+            assert!(
+                seen.iter().any(|s| s == expected),
+                "{} in {:?}",
+                expected,
+                seen
+            );
+        }
+    }
+
+    #[test]
+    fn test_lines_matches_str_lines() {
+        for text in [
+            "",
+            "\n",
+            "a",
+            "a\n",
+            "a\nb",
+            "a\r\nb\r\n",
+            "a\n\n\nb\n",
+            "trailing\r",
+            "\r\n\r\n",
+            "é\nü\r\n",
+        ] {
+            assert_eq!(
+                lines(text).collect::<Vec<_>>(),
+                text.lines().collect::<Vec<_>>(),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_chunk_bounds_end_at_newlines() {
+        let data = b"first line\nsecond\nthird line here\nx\n";
+        let bounds = chunk_bounds(data, 3);
+        assert_eq!(bounds.first().unwrap().0, 0);
+        assert_eq!(bounds.last().unwrap().1, data.len());
+        for window in bounds.windows(2) {
+            assert_eq!(window[0].1, window[1].0);
+            assert_eq!(data[window[0].1 - 1], b'\n');
+        }
+    }
+
+    #[test]
+    fn test_body_range() {
+        let data = b"banner\n1 a\n2 b\ntotal 2\n";
+        assert_eq!(body_range(data, 0, 0), (0, data.len()));
+        assert_eq!(&data[7..15], b"1 a\n2 b\n");
+        assert_eq!(body_range(data, 1, 1), (7, 15));
+        // Without a final newline the last line is still one line
+        assert_eq!(body_range(&data[..data.len() - 1], 1, 1), (7, 15));
+        assert_eq!(body_range(data, 3, 2), (15, 15));
+        assert_eq!(body_range(data, 9, 0), (data.len(), data.len()));
+        assert_eq!(body_range(data, 0, 9), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_log_table_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let utf16 = dir.path().join("service.log");
+        let text = "1 démarré\r\n2 arrêté\r\n";
+        let mut bytes = vec![0xff, 0xfe];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&utf16, bytes).unwrap();
+        let latin1 = dir.path().join("legacy.log");
+        std::fs::write(&latin1, b"3 d\xe9j\xe0\n").unwrap();
+
+        let scan = |path: &std::path::Path, encoding| {
+            let log_table = LogTableProvider::builder(
+                Scanner::new(r"^(?P<id>\d+) (?P<msg>\S+)$".to_string()).unwrap(),
+            )
+            .with_file_path(path.to_string_lossy().to_string())
+            .with_encoding(encoding)
+            .build();
+            async move {
+                let ctx = SessionContext::new();
+                ctx.register_table("log", Arc::new(log_table)).unwrap();
+                let results = ctx
+                    .sql("SELECT msg FROM log ORDER BY id")
+                    .await
+                    .unwrap()
+                    .collect()
+                    .await
+                    .unwrap();
+                let plan = ctx
+                    .sql("EXPLAIN ANALYZE SELECT msg FROM log")
+                    .await
+                    .unwrap()
+                    .collect()
+                    .await
+                    .unwrap();
+                let plan = datafusion::arrow::util::pretty::pretty_format_batches(&plan)
+                    .unwrap()
+                    .to_string();
+                // Transcoding grows the text, which must not read as a truncation
+                assert!(plan.contains("truncated_files=0"), "{}", plan);
+                // Just written, so read into memory
+                assert!(plan.contains("mapped_files=0"), "{}", plan);
+                datafusion::arrow::util::pretty::pretty_format_batches(&results)
+                    .unwrap()
+                    .to_string()
+            }
+        };
+        let formatted = scan(&utf16, encoding_rs::UTF_16LE).await;
+        assert!(
+            formatted.contains("| démarré |") && formatted.contains("| arrêté  |"),
+            "{}",
+            formatted
+        );
+        let formatted = scan(&latin1, encoding_rs::WINDOWS_1252).await;
+        assert!(formatted.contains("| déjà |"), "{}", formatted);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_max_line_length() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "1 short").unwrap();
+        writeln!(file, "2 {}", "é".repeat(10)).unwrap();
+        writeln!(file, "3 ok").unwrap();
+
+        let scan = |long_lines| {
+            let log_table = LogTableProvider::builder(
+                Scanner::new(r"^(?P<id>\d+) (?P<msg>\S+)$".to_string()).unwrap(),
+            )
+            .with_file_path(file.path().to_string_lossy().to_string())
+            .with_max_line_length(Some(12), long_lines)
+            .build();
+            async move {
+                let ctx = SessionContext::new();
+                ctx.register_table("log", Arc::new(log_table)).unwrap();
+                let results = ctx
+                    .sql("SELECT msg FROM log ORDER BY id")
+                    .await
+                    .unwrap()
+                    .collect()
+                    .await
+                    .unwrap();
+                datafusion::arrow::util::pretty::pretty_format_batches(&results)
+                    .unwrap()
+                    .to_string()
+            }
+        };
+        let formatted = scan(LongLines::Skip).await;
+        assert!(!formatted.contains("é"), "{}", formatted);
+        assert!(formatted.contains("| ok"), "{}", formatted);
+        // Cut at 12 bytes, backing off to the end of the fifth `é`
+        let formatted = scan(LongLines::Truncate).await;
+        assert!(formatted.contains("| ééééé "), "{}", formatted);
+        assert!(!formatted.contains("éééééé"), "{}", formatted);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_list_column() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tags.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"1 tags=web,prod\n2 tags=\n3 tags=db\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new("^{{id:number}} tags={{tags:list}}$".to_string()).unwrap(),
+        )
+        .with_file_path(path.to_string_lossy().to_string())
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql("SELECT id, unnest(tags) AS tag FROM log ORDER BY id, tag")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            formatted,
+            "+----+------+\n\
+             | id | tag  |\n\
+             +----+------+\n\
+             | 1  | prod |\n\
+             | 1  | web  |\n\
+             | 3  | db   |\n\
+             +----+------+",
+        );
+
+        let results = ctx
+            .sql("SELECT SUM(array_length(tags)) FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(formatted.contains("| 3 "), "{}", formatted);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_yearless_timestamps() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("syslog");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"Dec 31 23:59:59 host a\nJan  1 00:00:01 host b\n")
+            .unwrap();
+        // Modified on 2024-01-02, so December belongs to 2023
+        file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_182_400))
+            .unwrap();
+        drop(file);
+
+        let scanner =
+            Scanner::new(r#"^{{ts:datetime("%b %e %H:%M:%S")}} host {{msg:any}}$"#.to_string())
+                .unwrap();
+        let (scanner, path) = (&scanner, &path);
+        let query = |year| async move {
+            let ctx = SessionContext::new();
+            let log_table = LogTableProvider::builder(scanner.clone())
+                .with_file_path(path.to_string_lossy().to_string())
+                .with_year(year)
+                .build();
+            ctx.register_table("log", Arc::new(log_table)).unwrap();
+            let results = ctx
+                .sql("SELECT CAST(ts AS VARCHAR) AS ts FROM log")
+                .await
+                .unwrap()
+                .collect()
+                .await
+                .unwrap();
+            datafusion::arrow::util::pretty::pretty_format_batches(&results)
+                .unwrap()
+                .to_string()
+        };
+        let formatted = query(YearStrategy::Rollover).await;
+        assert!(formatted.contains("2023-12-31T23:59:59"), "{}", formatted);
+        assert!(formatted.contains("2024-01-01T00:00:01"), "{}", formatted);
+        let formatted = query(YearStrategy::Fixed(2019)).await;
+        assert!(formatted.contains("2019-12-31T23:59:59"), "{}", formatted);
+        assert!(formatted.contains("2019-01-01T00:00:01"), "{}", formatted);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_timestamp_units() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "2024-01-02 03:04:05.678 1704164645.9 ok").unwrap();
+        file.flush().unwrap();
+
+        let scanner = Scanner::new(
+            r#"^{{ts:datetime("%Y-%m-%d %H:%M:%S%.f")}} {{sent:datetime("%s%.f", unit=s)}} {{msg:any}}$"#
+                .to_string(),
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(file.path().to_string_lossy().to_string())
+            .with_add_file_mtime(true)
+            .with_timestamp_unit(TimestampUnit::Millis)
+            .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql(
+                "SELECT arrow_typeof(ts) AS ts_type, arrow_typeof(sent) AS sent_type, \
+                 arrow_typeof(\"__FILE_MTIME__\") AS mtime_type, CAST(ts AS BIGINT) AS ts_ms, \
+                 CAST(sent AS BIGINT) AS sent_s FROM log",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(
+            formatted.contains(
+                "| Timestamp(ms) | Timestamp(s) | Timestamp(ms) | 1704164645678 | 1704164645 |"
+            ),
+            "{}",
+            formatted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_table_decimal_column() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"charge 0.1\ncharge 0.2\nrefund -0.105\ncharge 123456789.5\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new("^{{kind:var_name}} {{amount:decimal(10,2)}}$".to_string()).unwrap(),
+        )
+        .with_file_path(file.path().to_string_lossy().to_string())
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql(
+                "SELECT arrow_typeof(amount) AS t, CAST(SUM(amount) AS VARCHAR) AS total, \
+                 COUNT(amount) AS n FROM log GROUP BY t",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        // Exactly 0.30 - 0.11, where Float64 would give 0.19000000000000003; the last
+        // amount has more than 10 digits and is NULL
+        assert!(
+            formatted.contains("| Decimal128(10, 2) | 0.19  | 3 |"),
+            "{}",
+            formatted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_table_binary_columns() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"md5=0xD41D8CD98F00B204E9800998ECF8427E payload=aGVsbG8=\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new("^md5={{md5:hex(bytes)}} payload={{payload:base64(bytes)}}$".to_string())
+                .unwrap(),
+        )
+        .with_file_path(file.path().to_string_lossy().to_string())
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql(
+                "SELECT arrow_typeof(md5) AS t, encode(md5, 'hex') AS md5, \
+                 CAST(payload AS VARCHAR) AS payload FROM log",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(
+            formatted.contains("| Binary | d41d8cd98f00b204e9800998ecf8427e | hello   |"),
+            "{}",
+            formatted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_table_duration_column() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"2024-01-02 03:04:05 took 1.5s\n2024-01-02 03:04:06 took 2m\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let table = |duration_unit: Option<TimestampUnit>| {
+            let builder = LogTableProvider::builder(
+                Scanner::new(
+                    r#"^{{ts:datetime("%Y-%m-%d %H:%M:%S")}} took {{took:duration(typed)}}$"#
+                        .to_string(),
+                )
+                .unwrap(),
+            )
+            .with_file_path(file.path().to_string_lossy().to_string());
+            let builder = match duration_unit {
+                Some(unit) => builder.with_duration_unit(unit),
+                None => builder,
             };
+            Arc::new(builder.build())
+        };
+        let ctx = SessionContext::new();
+        // Milliseconds by default, whatever the timestamp unit
+        ctx.register_table("log", table(None)).unwrap();
+        // The timestamps' unit, so they add up
+        ctx.register_table("log_us", table(Some(TimestampUnit::Micros)))
+            .unwrap();
+
+        let results = ctx
+            .sql(
+                "SELECT arrow_typeof(took) AS t, CAST(took AS BIGINT) AS ms FROM log \
+                 WHERE took > INTERVAL '1 minute'",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(
+            formatted.contains("| Duration(ms) | 120000 |"),
+            "{}",
+            formatted
+        );
+        assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+
+        let results = ctx
+            .sql(
+                "SELECT arrow_typeof(took) AS t, CAST(ts + took AS VARCHAR) AS done FROM log_us \
+                 WHERE took > INTERVAL '1 minute'",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(
+            formatted.contains("| Duration(µs) | 2024-01-02T03:06:06 |"),
+            "{}",
+            formatted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_table_zoned_timestamps() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"2024-07-15 12:00:00 ok\n").unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new(
+                r#"^{{ts:datetime("%Y-%m-%d %H:%M:%S", tz=Europe/Berlin)}} {{msg:any}}$"#
+                    .to_string(),
+            )
+            .unwrap(),
+        )
+        .with_file_path(file.path().to_string_lossy().to_string())
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql("SELECT arrow_typeof(ts) AS t, CAST(ts AS BIGINT) AS micros FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        // 10:00 UTC
+        assert!(
+            formatted.contains("| Timestamp(µs, \"Europe/Berlin\") | 1721037600000000 |"),
+            "{}",
+            formatted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_table_dictionary_column() {
+        use datafusion::arrow::array::{Array, DictionaryArray, StringArray};
+        use datafusion::arrow::datatypes::Int8Type;
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(b"WARN disk\nWARN disk\nERROR down\nDEBUG! noise\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            // A fallback pattern lets a level outside the set through
+            Scanner::with_patterns(
+                vec![
+                    "^{{level:enum(INFO,WARN,ERROR)}} {{msg:any}}$".to_string(),
+                    r"^(?P<level>\w+)! {{msg:any}}$".to_string(),
+                ],
+                None,
+            )
+            .unwrap(),
+        )
+        .with_file_path(file.path().to_string_lossy().to_string())
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let batches = ctx
+            .sql("SELECT level FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let column = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<DictionaryArray<Int8Type>>()
+            .unwrap();
+        // The dictionary is the declared set, used or not
+        let dictionary = column
+            .values()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            dictionary.iter().flatten().collect::<Vec<_>>(),
+            ["INFO", "WARN", "ERROR"]
+        );
+
+        let results = ctx
+            .sql("SELECT level, COUNT(*) AS n FROM log WHERE level = 'WARN' GROUP BY level")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(formatted.contains("| WARN  | 2 |"), "{}", formatted);
+        assert_eq!(column.len(), 4);
+        assert_eq!(column.null_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_reads_gzip_rotations() {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut gz = GzEncoder::new(
+            File::create(dir.path().join("app.log.2.gz")).unwrap(),
+            Compression::default(),
+        );
+        gz.write_all(b"1 oldest\n2 older\n").unwrap();
+        gz.finish().unwrap();
+        std::fs::write(dir.path().join("app.log.1"), "3 old\n").unwrap();
+        std::fs::write(dir.path().join("app.log"), "4 live\n").unwrap();
+        // Compressed in a format that cannot be read, so skipped
+        std::fs::write(dir.path().join("app.log.3.zst"), b"\x28\xb5\x2f\xfd").unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new(r"^(?P<id>\d+) (?P<msg>\S+)$".to_string()).unwrap(),
+        )
+        .with_file_path(dir.path().join("app.log*").to_string_lossy().to_string())
+        .with_min_match_rate(Some(1.0))
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+        let results = ctx
+            .sql("SELECT msg FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            formatted,
+            "+--------+\n\
+             | msg    |\n\
+             +--------+\n\
+             | oldest |\n\
+             | older  |\n\
+             | old    |\n\
+             | live   |\n\
+             +--------+",
+        );
+
+        let plan = ctx
+            .sql("EXPLAIN ANALYZE SELECT msg FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let plan = datafusion::arrow::util::pretty::pretty_format_batches(&plan)
+            .unwrap()
+            .to_string();
+        // Decompressing grows the data, which must not read as a truncation
+        assert!(plan.contains("truncated_files=0"), "{}", plan);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_json_functions() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("json.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(
+            b"done {\"user\":{\"id\":7},\"took\":12}\ndone {\"user\":{},\"took\":3}\ndone [1,2]\n",
+        )
+        .unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        crate::datafusion::register_udfs(&ctx);
+        let log_table =
+            LogTableProvider::builder(Scanner::new("^done {{payload:json}}$".to_string()).unwrap())
+                .with_file_path(path.to_string_lossy().to_string())
+                .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql(
+                "SELECT json_get(payload, 'user', 'id') AS id, \
+                 CAST(json_extract(payload, '$.took') AS BIGINT) + 1 AS took, \
+                 json_get(payload, 1) AS second FROM log",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            formatted,
+            "+----+------+--------+\n\
+             | id | took | second |\n\
+             +----+------+--------+\n\
+             | 7  | 13   |        |\n\
+             |    | 4    |        |\n\
+             |    |      | 2      |\n\
+             +----+------+--------+",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_log_table_map_column() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("attrs.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"info user=alice took=12\nwarn user=bob msg=\"slow disk\"\ninfo took=3\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new("^{{level:var_name}} {{attrs:kv}}$".to_string()).unwrap(),
+        )
+        .with_file_path(path.to_string_lossy().to_string())
+        .build();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql("SELECT level, attrs['user'] AS user, attrs['msg'] AS msg FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            formatted,
+            "+-------+-------+-----------+\n\
+             | level | user  | msg       |\n\
+             +-------+-------+-----------+\n\
+             | info  | alice |           |\n\
+             | warn  | bob   | slow disk |\n\
+             | info  |       |           |\n\
+             +-------+-------+-----------+",
+        );
+    }
 
-            if actual_start >= actual_end {
-                // Empty chunk, return empty batch
-                let columns = fields_builder.finish();
-                let options = RecordBatchOptions::new().with_row_count(Some(0));
-                return RecordBatch::try_new_with_options(schema.clone(), columns, &options)
-                    .map_err(LfError::from);
-            }
+    #[tokio::test]
+    async fn test_log_table_exclude_pattern() {
+        use std::io::Write;
 
-            let section = &mmap[actual_start..actual_end];
-            let section_str = std::str::from_utf8(section)?;
-            let mut values = Vec::with_capacity(field_indices.len());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"1 start\nGET /health 200\nnoise\nGET /health 200\n2 stop\n")
+            .unwrap();
+        file.flush().unwrap();
+
+        let make_table = || {
+            LogTableProvider::builder(
+                Scanner::new(r"^(?P<id>\d+) (?P<msg>\w+)$".to_string())
+                    .unwrap()
+                    .with_exclude_pattern("^GET /health ", None)
+                    .unwrap(),
+            )
+            .with_file_path(path.to_string_lossy().to_string())
+        };
+        let ctx = SessionContext::new();
+        // 2 of the 3 lines left after dropping health checks match
+        let table = make_table().with_min_match_rate(Some(0.6)).build();
+        ctx.register_table("log", Arc::new(table)).unwrap();
+        let unmatched = make_table().build().unmatched_table();
+        ctx.register_table("log__unmatched", Arc::new(unmatched))
+            .unwrap();
 
-            let mut row_count = 0;
-            for line in section_str.lines() {
-                if scanner.scan_direct(line, &field_indices, &mut values) {
-                    if add_file_path {
-                        values[file_path_index] = file;
-                    }
-                    if add_raw {
-                        values[raw_index] = line;
-                    }
-                    fields_builder.push(field_types, &values);
-                    row_count += 1;
-                }
-            }
+        let results = ctx
+            .sql("SELECT COUNT(*) FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(formatted.contains("| 2 "), "{}", formatted);
 
-            let columns = fields_builder.finish();
-            let options = RecordBatchOptions::new().with_row_count(Some(row_count));
-            RecordBatch::try_new_with_options(schema.clone(), columns, &options)
-                .map_err(LfError::from)
-        })
-        .collect();
+        let results = ctx
+            .sql(r#"SELECT "__LINE__", "__RAW__" FROM log__unmatched"#)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(
+            formatted.contains("| 3        | noise   |"),
+            "{}",
+            formatted
+        );
+        assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+    }
 
-    partitions
-}
+    #[tokio::test]
+    async fn test_log_table_skip_header_and_footer() {
+        use std::io::Write;
 
-/// Helper to find the index of the next newline character
-fn find_next_newline(mmap: &[u8], start: usize, end: usize) -> Option<usize> {
-    mmap[start..end]
-        .iter()
-        .position(|&b| b == b'\n')
-        .map(|pos| start + pos + 1) // +1 to include the newline itself
-}
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"Report v2\n=========\n1 start\nnoise\n2 stop\n---\n2 entries\n")
+            .unwrap();
+        file.flush().unwrap();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::datafusion::LogTableProvider;
-    use crate::scanner::Scanner;
-    use datafusion::arrow::datatypes::DataType;
-    use datafusion::prelude::SessionContext;
+        let ctx = SessionContext::new();
+        let log_table = LogTableProvider::builder(
+            Scanner::new(r"^(?P<id>\d+) (?P<msg>\w+)$".to_string()).unwrap(),
+        )
+        .with_file_path(path.to_string_lossy().to_string())
+        .with_skip_lines(2)
+        .with_skip_footer(2)
+        .build();
+        ctx.register_table("log__unmatched", Arc::new(log_table.unmatched_table()))
+            .unwrap();
+        ctx.register_table("log", Arc::new(log_table)).unwrap();
+
+        let results = ctx
+            .sql("SELECT msg FROM log ORDER BY id")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(formatted.contains("start") && formatted.contains("stop"));
+        assert!(!formatted.contains("entries"), "{}", formatted);
+
+        // Only the body line that failed to match is unmatched, with its line number
+        let results = ctx
+            .sql(r#"SELECT "__LINE__", "__RAW__" FROM log__unmatched"#)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let formatted = datafusion::arrow::util::pretty::pretty_format_batches(&results)
+            .unwrap()
+            .to_string();
+        assert!(
+            formatted.contains("| 4        | noise   |"),
+            "{}",
+            formatted
+        );
+        assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+    }
 
     #[tokio::test]
     async fn test_log_table_provider() {
@@ -318,13 +2025,10 @@ mod tests {
 
         let pattern = r"^\[(?P<time>\w{3} \w{3} \d{1,2} \d{2}:\d{2}:\d{2} \d{4})\] \[(?P<level>[^\]]+)\] (?P<message>.*)$";
         let scanner = Scanner::new(pattern.to_string()).unwrap();
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: String::from("loghub/Apache/Apache_2k.log"),
-            add_file_path: false,
-            add_raw: false,
-            num_threads: Some(8),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(String::from("loghub/Apache/Apache_2k.log"))
+            .with_num_threads(Some(8))
+            .build();
 
         let _ = ctx.register_table("log", Arc::new(log_table));
         let df = ctx.sql("SELECT * FROM log").await.unwrap();
@@ -337,13 +2041,10 @@ mod tests {
 
         let pattern = r#"^\[{{time:datetime("%a %b %d %H:%M:%S %Y")}}\] \[{{level:var_name}}\] {{message:any}}$"#;
         let scanner = Scanner::new(pattern.to_string()).unwrap();
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: String::from("loghub/Apache/Apache_2k.log"),
-            add_file_path: false,
-            add_raw: false,
-            num_threads: Some(8),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(String::from("loghub/Apache/Apache_2k.log"))
+            .with_num_threads(Some(8))
+            .build();
 
         let _ = ctx.register_table("log_mac", Arc::new(log_table));
         let df = ctx
@@ -360,13 +2061,10 @@ mod tests {
         // Pattern for jk2_init() messages: "jk2_init() Found child 6725 in scoreboard slot 10"
         let pattern = r#"^\[{{time:datetime("%a %b %d %H:%M:%S %Y")}}\] \[{{level:var_name}}\] jk2_init\(\) Found child {{child_pid:number}} in scoreboard slot {{slot:number}}$"#;
         let scanner = Scanner::new(pattern.to_string()).unwrap();
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: String::from("loghub/Apache/Apache_2k.log"),
-            add_file_path: false,
-            add_raw: false,
-            num_threads: Some(8),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(String::from("loghub/Apache/Apache_2k.log"))
+            .with_num_threads(Some(8))
+            .build();
 
         let _ = ctx.register_table("log_int", Arc::new(log_table));
 
@@ -409,13 +2107,10 @@ mod tests {
         assert_eq!(scanner.field_names.len(), 3);
         assert_eq!(scanner.field_names, vec!["time", "level", "message"]);
 
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: String::from("loghub/Apache/Apache_2k.log"),
-            add_file_path: false,
-            add_raw: false,
-            num_threads: Some(8),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(String::from("loghub/Apache/Apache_2k.log"))
+            .with_num_threads(Some(8))
+            .build();
 
         let _ = ctx.register_table("log_proj", Arc::new(log_table));
 
@@ -484,13 +2179,10 @@ mod tests {
 
         let pattern = r#"^\[{{time:datetime("%a %b %d %H:%M:%S %Y")}}\] \[{{level:var_name}}\] {{message:any}}$"#;
         let scanner = Scanner::new(pattern.to_string()).unwrap();
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: temp_file.path().to_string_lossy().to_string(),
-            add_file_path: false,
-            add_raw: false,
-            num_threads: Some(8),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(temp_file.path().to_string_lossy().to_string())
+            .with_num_threads(Some(8))
+            .build();
 
         let _ = ctx.register_table("log_small", Arc::new(log_table));
         let df = ctx
@@ -511,13 +2203,10 @@ mod tests {
 
         let pattern = r#"^\[{{time:datetime("%a %b %d %H:%M:%S %Y")}}\] \[{{level:var_name}}\] {{message:any}}$"#;
         let scanner = Scanner::new(pattern.to_string()).unwrap();
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: String::from("/nonexistent/path/to/file.log"),
-            add_file_path: false,
-            add_raw: false,
-            num_threads: Some(8),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(String::from("/nonexistent/path/to/file.log"))
+            .with_num_threads(Some(8))
+            .build();
 
         let _ = ctx.register_table("log_missing", Arc::new(log_table));
         let df = ctx.sql("SELECT * FROM log_missing").await.unwrap();
@@ -543,13 +2232,12 @@ mod tests {
         // Pattern with mixed unnamed (\d+) and named groups
         let pattern = r"^(\d+) (?P<name>\w+)$";
         let scanner = Scanner::new(pattern.to_string()).unwrap();
-        let log_table = LogTableProvider {
-            scanner,
-            file_path: path.clone(),
-            add_file_path: true, // Request __FILE__
-            add_raw: true,       // Request __RAW__
-            num_threads: Some(1),
-        };
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(path.clone())
+            .with_add_file_path(true)
+            .with_add_raw(true)
+            .with_num_threads(Some(1))
+            .build();
 
         let _ = ctx.register_table("log_mixed", Arc::new(log_table));
 
@@ -588,4 +2276,516 @@ mod tests {
             .unwrap();
         assert_eq!(raw_col.value(0), "123 test_val");
     }
+
+    #[tokio::test]
+    async fn test_log_table_file_metadata_columns() {
+        use datafusion::arrow::array::{Int64Array, TimestampMicrosecondArray};
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "123 first").unwrap();
+        writeln!(temp_file, "456 second").unwrap();
+        temp_file.flush().unwrap();
+        let metadata = temp_file.as_file().metadata().unwrap();
+        let expected_mtime = metadata
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros() as i64;
+
+        let ctx = SessionContext::new();
+        let scanner = Scanner::new(r"^(?P<id>\d+) (?P<name>\w+)$".to_string()).unwrap();
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(temp_file.path().to_string_lossy().to_string())
+            .with_add_file_mtime(true)
+            .with_add_file_size(true)
+            .with_num_threads(Some(2))
+            .build();
+
+        let _ = ctx.register_table("log_meta", Arc::new(log_table));
+        let df = ctx
+            .sql("SELECT name, \"__FILE_MTIME__\", \"__FILE_SIZE__\" FROM log_meta")
+            .await
+            .unwrap();
+        let results = df.collect().await.unwrap();
+        let batch =
+            datafusion::arrow::compute::concat_batches(&results[0].schema(), &results).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        let mtime_col = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(mtime_col.value(0), expected_mtime);
+
+        let size_col = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(size_col.value(0), metadata.len() as i64);
+        assert_eq!(size_col.value(1), metadata.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_time_ordered_skips_sort() {
+        use datafusion::physical_plan::displayable;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, lines) in [
+            (
+                "app.log",
+                ["2024-01-03 00:00:00 c", "2024-01-03 00:00:01 d"],
+            ),
+            (
+                "app.log.1",
+                ["2024-01-01 00:00:00 a", "2024-01-02 00:00:00 b"],
+            ),
+        ] {
+            let mut f = std::fs::File::create(dir.path().join(name)).unwrap();
+            for line in lines {
+                writeln!(f, "{}", line).unwrap();
+            }
+        }
+
+        let pattern = r#"^{{time:datetime("%Y-%m-%d %H:%M:%S")}} {{msg:any}}$"#;
+        let scanner = Scanner::new(pattern.to_string()).unwrap();
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(dir.path().join("app.log*").to_string_lossy().to_string())
+            .with_time_ordered(true)
+            .with_num_threads(Some(1))
+            .build();
+
+        let ctx = SessionContext::new();
+        let _ = ctx.register_table("log_sorted", Arc::new(log_table));
+        let df = ctx
+            .sql("SELECT time, msg FROM log_sorted ORDER BY time")
+            .await
+            .unwrap();
+        let plan = df.clone().create_physical_plan().await.unwrap();
+        let plan_str = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(
+            !plan_str.contains("SortExec"),
+            "unexpected sort:\n{}",
+            plan_str
+        );
+
+        let batches = df.collect().await.unwrap();
+        let msgs: Vec<String> = batches
+            .iter()
+            .flat_map(|b| {
+                let col = b
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<datafusion::arrow::array::StringArray>()
+                    .unwrap();
+                col.iter()
+                    .map(|v| v.unwrap_or_default().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(msgs, vec!["a", "b", "c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_merge_by_time() {
+        use datafusion::arrow::array::StringArray;
+        use datafusion::physical_plan::displayable;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, lines) in [
+            (
+                "a.log",
+                ["2024-01-01 00:00:00 a1", "2024-01-01 00:00:02 a2"],
+            ),
+            (
+                "b.log",
+                ["2024-01-01 00:00:01 b1", "2024-01-01 00:00:03 b2"],
+            ),
+        ] {
+            let mut f = std::fs::File::create(dir.path().join(name)).unwrap();
+            for line in lines {
+                writeln!(f, "{}", line).unwrap();
+            }
+        }
+
+        let pattern = r#"^{{time:datetime("%Y-%m-%d %H:%M:%S")}} {{msg:any}}$"#;
+        let scanner = Scanner::new(pattern.to_string()).unwrap();
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(dir.path().join("*.log").to_string_lossy().to_string())
+            .with_merge_by_time(true)
+            .with_num_threads(Some(1))
+            .build();
+
+        let ctx = SessionContext::new();
+        let _ = ctx.register_table("log_merge", Arc::new(log_table));
+        let df = ctx
+            .sql("SELECT time, msg FROM log_merge ORDER BY time")
+            .await
+            .unwrap();
+        let plan = df.clone().create_physical_plan().await.unwrap();
+        let plan_str = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(plan_str.contains("SortPreservingMergeExec"), "{}", plan_str);
+        assert!(
+            !plan_str.contains("SortExec:"),
+            "unexpected sort:\n{}",
+            plan_str
+        );
+
+        let batches = df.collect().await.unwrap();
+        let msgs: Vec<String> = batches
+            .iter()
+            .flat_map(|b| {
+                let col = b.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+                col.iter()
+                    .map(|v| v.unwrap_or_default().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(msgs, vec!["a1", "b1", "a2", "b2"]);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_unmatched() {
+        use datafusion::arrow::array::{Int64Array, StringArray};
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        for i in 1..=100 {
+            if i % 10 == 0 {
+                writeln!(temp_file, "garbage line {}", i).unwrap();
+            } else if i % 25 == 1 {
+                writeln!(temp_file).unwrap();
+            } else {
+                writeln!(temp_file, "{} ok", i).unwrap();
+            }
+        }
+        temp_file.flush().unwrap();
+        let path = temp_file.path().to_string_lossy().to_string();
+
+        let scanner = Scanner::new(r"^(?P<id>\d+) (?P<msg>\w+)$".to_string()).unwrap();
+        let log_table = LogTableProvider::builder(scanner)
+            .with_file_path(path.clone())
+            .with_num_threads(Some(4))
+            .build();
+
+        let ctx = SessionContext::new();
+        let _ = ctx.register_table("log_um", Arc::new(log_table.unmatched_table()));
+        let df = ctx
+            .sql("SELECT \"__FILE__\", \"__LINE__\", \"__RAW__\" FROM log_um ORDER BY \"__LINE__\"")
+            .await
+            .unwrap();
+        let results = df.collect().await.unwrap();
+        let batch =
+            datafusion::arrow::compute::concat_batches(&results[0].schema(), &results).unwrap();
+
+        // Empty lines are not reported
+        assert_eq!(batch.num_rows(), 10);
+        let files = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let lines = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        let raws = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        for row in 0..10 {
+            let line_number = (row as i64 + 1) * 10;
+            assert_eq!(files.value(row), path);
+            assert_eq!(lines.value(row), line_number);
+            assert_eq!(raws.value(row), format!("garbage line {}", line_number));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_table_min_match_rate() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        for i in 0..20 {
+            if i % 4 == 0 {
+                writeln!(temp_file, "not a match").unwrap();
+            } else {
+                writeln!(temp_file, "{} ok", i).unwrap();
+            }
+            writeln!(temp_file).unwrap();
+        }
+        temp_file.flush().unwrap();
+
+        let make_table = |min_match_rate| {
+            LogTableProvider::builder(
+                Scanner::new(r"^(?P<id>\d+) (?P<msg>\w+)$".to_string()).unwrap(),
+            )
+            .with_file_path(temp_file.path().to_string_lossy().to_string())
+            .with_min_match_rate(min_match_rate)
+            .with_num_threads(Some(2))
+            .build()
+        };
+
+        // 15 of 20 non-empty lines match (75%)
+        let ctx = SessionContext::new();
+        let _ = ctx.register_table("log_lenient", Arc::new(make_table(Some(0.7))));
+        let results = ctx
+            .sql("SELECT * FROM log_lenient")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 15);
+
+        let _ = ctx.register_table("log_strict", Arc::new(make_table(Some(0.95))));
+        let err = ctx
+            .sql("SELECT * FROM log_strict")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("15/20 lines matched"), "{}", err);
+
+        // A percentage instead of a fraction is rejected before any file is read
+        let _ = ctx.register_table("log_percent", Arc::new(make_table(Some(95.0))));
+        let err = ctx
+            .sql("SELECT * FROM log_percent")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("outside 0.0-1.0"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_log_table_token_index() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        for i in 0..2000 {
+            writeln!(file, "{} GET /items request_id=r{:05}", i, i).unwrap();
+        }
+        file.flush().unwrap();
+
+        let log_table = LogTableProvider::builder(
+            Scanner::new(r"^(?P<id>\d+) (?P<method>\w+) (?P<path>\S+)".to_string()).unwrap(),
+        )
+        .with_file_path(path.to_string_lossy().to_string())
+        .with_add_raw(true)
+        .with_index(true)
+        .with_num_threads(Some(2))
+        .build();
+
+        let ctx = SessionContext::new();
+        let _ = ctx.register_table("log", Arc::new(log_table));
+        let sql = r#"SELECT id FROM log WHERE "__RAW__" LIKE '% request_id=r01234'"#;
+
+        let plan = ctx
+            .sql(&format!("EXPLAIN {}", sql))
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let plan = datafusion::arrow::util::pretty::pretty_format_batches(&plan)
+            .unwrap()
+            .to_string();
+        assert!(plan.contains("index_tokens=[r01234]"), "{}", plan);
+
+        let results = ctx.sql(sql).await.unwrap().collect().await.unwrap();
+        let ids: Vec<String> = results
+            .iter()
+            .flat_map(|batch| {
+                let col = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<datafusion::arrow::array::StringArray>()
+                    .unwrap();
+                col.iter()
+                    .map(|v| v.unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(ids, vec!["1234"]);
+        assert!(crate::datafusion::index::index_path(&path.to_string_lossy()).exists());
+    }
+
+    #[tokio::test]
+    async fn test_log_table_raw_search() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        for i in 0..500 {
+            let level = if i % 100 == 7 { "ERROR" } else { "INFO" };
+            writeln!(file, "{} {} job=j{} done", i, level, i).unwrap();
+        }
+        file.flush().unwrap();
+
+        for index in [false, true] {
+            let log_table = LogTableProvider::builder(
+                Scanner::new(r"^(?P<id>\d+) (?P<level>\w+)".to_string()).unwrap(),
+            )
+            .with_file_path(path.to_string_lossy().to_string())
+            .with_index(index)
+            .with_num_threads(Some(2))
+            .build();
+            let ctx = SessionContext::new();
+            crate::datafusion::register_udfs(&ctx);
+            let _ = ctx.register_table("log", Arc::new(log_table));
+
+            let ids = |sql: &'static str| {
+                let ctx = ctx.clone();
+                async move {
+                    let results = ctx.sql(sql).await.unwrap().collect().await.unwrap();
+                    results
+                        .iter()
+                        .flat_map(|batch| {
+                            let col = batch
+                                .column(0)
+                                .as_any()
+                                .downcast_ref::<datafusion::arrow::array::StringArray>()
+                                .unwrap();
+                            col.iter()
+                                .map(|v| v.unwrap().to_string())
+                                .collect::<Vec<_>>()
+                        })
+                        .collect::<Vec<_>>()
+                }
+            };
+
+            assert_eq!(
+                ids("SELECT id FROM log WHERE raw_contains('j42')").await,
+                vec!["42"]
+            );
+            assert_eq!(
+                ids("SELECT id FROM log WHERE raw_match('ERROR job=j[0-2]?0?7 ') ORDER BY id")
+                    .await,
+                vec!["107", "207", "7"]
+            );
+            assert_eq!(
+                ids("SELECT id FROM log WHERE raw_contains('ERROR') AND id LIKE '4%'").await,
+                vec!["407"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_table_invalid_utf8() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("corrupt.log");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"1 ok\n2 bad\xff\xfebyte\n3 ok\n").unwrap();
+        file.flush().unwrap();
+
+        let log_table = LogTableProvider::builder(
+            Scanner::new(r"^(?P<id>\d+) (?P<msg>.*)$".to_string()).unwrap(),
+        )
+        .with_file_path(path.to_string_lossy().to_string())
+        .with_num_threads(Some(1))
+        .build();
+        let ctx = SessionContext::new();
+        let _ = ctx.register_table("log", Arc::new(log_table));
+
+        let results = ctx
+            .sql("SELECT msg FROM log ORDER BY id")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let col = results[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<datafusion::arrow::array::StringArray>()
+            .unwrap();
+        let msgs: Vec<&str> = col.iter().map(|v| v.unwrap()).collect();
+        assert_eq!(msgs, vec!["ok", "bad\u{FFFD}\u{FFFD}byte", "ok"]);
+
+        let plan = ctx
+            .sql("EXPLAIN ANALYZE SELECT msg FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let plan = datafusion::arrow::util::pretty::pretty_format_batches(&plan)
+            .unwrap()
+            .to_string();
+        assert!(plan.contains("invalid_utf8_lines=1"), "{}", plan);
+    }
+
+    #[test]
+    fn test_read_snapshot_drops_partial_line_when_short() {
+        let (data, short) = read_snapshot(&b"1 ok\n2 ok\n"[..], 10).unwrap();
+        assert_eq!((data.as_slice(), short), (&b"1 ok\n2 ok\n"[..], false));
+
+        // Appended bytes past the snapshot length are not read
+        let (data, short) = read_snapshot(&b"1 ok\n2 ok\n3 new\n"[..], 10).unwrap();
+        assert_eq!((data.as_slice(), short), (&b"1 ok\n2 ok\n"[..], false));
+
+        // A file truncated mid-read ends in a partial line, which is dropped
+        let (data, short) = read_snapshot(&b"1 ok\n2 o"[..], 10).unwrap();
+        assert_eq!((data.as_slice(), short), (&b"1 ok\n"[..], true));
+    }
+
+    #[test]
+    fn test_file_data_buffers_recently_modified_files() {
+        use datafusion::execution::memory_pool::{GreedyMemoryPool, MemoryPool};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "1 ok\n").unwrap();
+
+        let pool: Arc<dyn MemoryPool> = Arc::new(GreedyMemoryPool::new(8));
+        let mut reservation = MemoryConsumer::new("test").register(&pool);
+        let open = |reservation: &mut MemoryReservation| {
+            let f = File::open(&path).unwrap();
+            let metadata = f.metadata().unwrap();
+            FileData::open(&f, &metadata, reservation).map(|(data, _)| data)
+        };
+        let data = open(&mut reservation).unwrap();
+        assert!(matches!(data, FileData::Buffered(ref data) if data == b"1 ok\n"));
+        assert_eq!(reservation.size(), 5);
+
+        // A snapshot past the memory limit fails instead of being read
+        std::fs::write(&path, "1 ok\n2 ok\n").unwrap();
+        let mut other = reservation.new_empty();
+        let err = open(&mut other).err().unwrap();
+        assert!(err.to_string().contains("Resources exhausted"), "{}", err);
+        assert_eq!(other.size(), 0);
+        drop(data);
+        reservation.free();
+        std::fs::write(&path, "1 ok\n").unwrap();
+
+        let old = std::time::SystemTime::now() - 2 * VOLATILE_WINDOW;
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let data = open(&mut reservation).unwrap();
+        assert!(matches!(data, FileData::Mapped(_)));
+        assert_eq!(&*data, b"1 ok\n");
+        assert_eq!(reservation.size(), 0);
+    }
 }
@@ -0,0 +1,299 @@
+//! Log file resolution and ordering.
+
+use glob::glob;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::error::{Error, Result};
+
+/// Compression extensions ignored when reading a rotation suffix.
+const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "bz2", "xz", "zst"];
+
+/// Whether `path` is gzip-compressed, and so is decompressed before it is parsed.
+pub fn is_gzip(path: impl AsRef<Path>) -> bool {
+    path.as_ref().extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Whether `path` is compressed in a format that cannot be read.
+fn is_unreadable_compressed(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext != "gz" && COMPRESSED_EXTENSIONS.contains(&ext))
+}
+
+/// Expand a path or glob pattern into the matching files, in rotation order.
+///
+/// Besides `*`, `?`, `[...]` and recursive `**` directories, `{a,b}` alternatives
+/// are expanded (see [`expand_braces`]). Gzip files are read decompressed; files
+/// compressed otherwise (`.bz2`, `.xz`, `.zst`) are skipped with a warning. Returns
+/// [`Error::NoFiles`] if nothing matches.
+pub fn resolve_files(pattern: &str) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for expanded in expand_braces(pattern) {
+        for file in glob(&expanded)? {
+            let file = file?;
+            if is_unreadable_compressed(&file) {
+                log::warn!(
+                    "{}: skipped, only gzip-compressed files can be read",
+                    file.display()
+                );
+                continue;
+            }
+            if seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+    if files.is_empty() {
+        return Err(Error::NoFiles(pattern.to_string()));
+    }
+    sort_by_rotation(&mut files);
+    Ok(files
+        .iter()
+        .map(|f| f.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Expand `{a,b}` alternatives in a glob pattern, shell-style.
+///
+/// Alternatives may be nested (`{app,{worker,cron}-*}.log`) or empty (`app{,.1}.log`).
+/// Braces without a top-level comma are kept literally.
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let bytes = pattern.as_bytes();
+    let mut depth = 0;
+    let mut open = None;
+    let mut commas = Vec::new();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'{' => {
+                if depth == 0 {
+                    open = Some(i);
+                    commas.clear();
+                }
+                depth += 1;
+            }
+            b',' if depth == 1 => commas.push(i),
+            b'}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = open.unwrap();
+                    if commas.is_empty() {
+                        // Literal braces; expand the rest of the pattern
+                        let prefix = &pattern[..=i];
+                        return expand_braces(&pattern[i + 1..])
+                            .into_iter()
+                            .flat_map(|rest| {
+                                expand_braces(&pattern[start + 1..i]).into_iter().map(
+                                    move |inner| {
+                                        format!("{}{{{}}}{}", &prefix[..start], inner, rest)
+                                    },
+                                )
+                            })
+                            .collect();
+                    }
+                    let mut bounds = vec![start];
+                    bounds.extend(&commas);
+                    bounds.push(i);
+                    let suffix = &pattern[i + 1..];
+                    return bounds
+                        .windows(2)
+                        .flat_map(|w| {
+                            let alternative = &pattern[w[0] + 1..w[1]];
+                            expand_braces(&format!(
+                                "{}{}{}",
+                                &pattern[..start],
+                                alternative,
+                                suffix
+                            ))
+                        })
+                        .collect();
+                }
+            }
+            _ => {}
+        }
+    }
+    vec![pattern.to_string()]
+}
+
+/// Expand several paths or glob patterns into one file list.
+///
+/// Each pattern's matches are kept in rotation order and appended in the order the
+/// patterns are given; files matched by more than one pattern are read once.
+/// Returns [`Error::NoFiles`] if any pattern matches nothing.
+pub fn resolve_file_list(patterns: &[String]) -> Result<Vec<String>> {
+    if patterns.is_empty() {
+        return Err(Error::NoFiles(String::new()));
+    }
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for pattern in patterns {
+        for file in resolve_files(pattern)? {
+            if seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Sort files so rotated logs come out oldest first.
+///
+/// Files sharing a base name (`app.log`, `app.log.1`, `app.log.2.gz`) are ordered
+/// by descending rotation number, so the live file comes last. Ties are broken by
+/// modification time and then by path.
+pub fn sort_by_rotation(files: &mut [PathBuf]) {
+    files.sort_by_cached_key(|path| {
+        let (base, index) = rotation_key(path);
+        let mtime = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        (base, Reverse(index), mtime, path.clone())
+    });
+}
+
+/// Split a path into its rotation base and rotation number (0 for the live file).
+fn rotation_key(path: &Path) -> (PathBuf, u64) {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut stem = name.as_str();
+    if let Some((rest, ext)) = stem.rsplit_once('.')
+        && COMPRESSED_EXTENSIONS.contains(&ext)
+    {
+        stem = rest;
+    }
+
+    let (base, index) = match stem.rsplit_once('.') {
+        Some((rest, suffix))
+            if !rest.is_empty()
+                && !suffix.is_empty()
+                && suffix.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            (rest, suffix.parse().unwrap_or(u64::MAX))
+        }
+        _ => (stem, 0),
+    };
+    (path.with_file_name(base), index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_by_rotation() {
+        let mut files: Vec<PathBuf> = [
+            "logs/app.log",
+            "logs/app.log.10",
+            "logs/app.log.2.gz",
+            "logs/app.log.1",
+            "logs/worker.log.1",
+            "logs/worker.log",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        sort_by_rotation(&mut files);
+
+        let sorted: Vec<&str> = files.iter().map(|p| p.to_str().unwrap()).collect();
+        assert_eq!(
+            sorted,
+            vec![
+                "logs/app.log.10",
+                "logs/app.log.2.gz",
+                "logs/app.log.1",
+                "logs/app.log",
+                "logs/worker.log.1",
+                "logs/worker.log",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_file_list() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("archive")).unwrap();
+        for name in [
+            "access.log",
+            "access.log.1",
+            "archive/access.log",
+            "other.log",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        let files = resolve_file_list(&[
+            path("archive/*.log"),
+            path("access.log*"),
+            path("access.log"),
+        ])
+        .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                path("archive/access.log"),
+                path("access.log.1"),
+                path("access.log")
+            ]
+        );
+
+        let err = resolve_file_list(&[path("access.log"), path("missing.log")]).unwrap_err();
+        assert!(matches!(err, Error::NoFiles(p) if p.ends_with("missing.log")));
+    }
+
+    #[test]
+    fn test_expand_braces() {
+        assert_eq!(
+            expand_braces("logs/**/{app,worker}-*.log"),
+            vec!["logs/**/app-*.log", "logs/**/worker-*.log"]
+        );
+        assert_eq!(
+            expand_braces("{a,b}/{x,y}.log"),
+            vec!["a/x.log", "a/y.log", "b/x.log", "b/y.log"]
+        );
+        assert_eq!(
+            expand_braces("{app,{worker,cron}-*}.log"),
+            vec!["app.log", "worker-*.log", "cron-*.log"]
+        );
+        assert_eq!(expand_braces("app{,.1}.log"), vec!["app.log", "app.1.log"]);
+        assert_eq!(
+            expand_braces("{single}/{a,b}"),
+            vec!["{single}/a", "{single}/b"]
+        );
+        assert_eq!(expand_braces("unclosed{a,b"), vec!["unclosed{a,b"]);
+    }
+
+    #[test]
+    fn test_resolve_files_recursive_braces() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [
+            "app-1.log",
+            "2024/01/app-2.log",
+            "2024/02/worker-1.log",
+            "2024/02/cron-1.log",
+        ] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let pattern = dir.path().join("**/{app,worker}-*.log");
+        let mut files = resolve_files(&pattern.to_string_lossy()).unwrap();
+        files.sort();
+        let expected: Vec<String> = ["2024/01/app-2.log", "2024/02/worker-1.log", "app-1.log"]
+            .iter()
+            .map(|name| dir.path().join(name).to_string_lossy().to_string())
+            .collect();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn test_resolve_files_no_match() {
+        let err = resolve_files("/nonexistent/dir/*.log").unwrap_err();
+        assert!(matches!(err, Error::NoFiles(_)));
+    }
+}
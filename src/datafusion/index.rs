@@ -0,0 +1,464 @@
+//! Sidecar block index with per-block token bloom filters.
+//!
+//! A file is split into blocks of roughly [`DEFAULT_BLOCK_SIZE`] bytes ending at line
+//! boundaries. Each block stores a bloom filter over the tokens of its lines, so a
+//! search for exact tokens only has to scan the blocks that may contain all of them.
+//!
+//! The index is saved next to the log file as `<file>.lflogidx` and rebuilt when the
+//! file's size or modification time changes.
+
+use datafusion::logical_expr::expr::{Like, ScalarFunction};
+use datafusion::logical_expr::{BinaryExpr, Expr, Operator};
+use datafusion::scalar::ScalarValue;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::path::PathBuf;
+
+use crate::datafusion::search::RAW_CONTAINS;
+use crate::error::Result;
+
+/// Target size of an indexed block in bytes.
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// Extension appended to the log file path for its index.
+pub const INDEX_EXTENSION: &str = "lflogidx";
+
+const MAGIC: &[u8; 8] = b"LFLGIDX1";
+const BITS_PER_TOKEN: usize = 10;
+const HASH_COUNT: u32 = 7;
+
+/// Whether `b` is part of a token. Bytes of non-ASCII characters are token bytes.
+pub(crate) fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b >= 0x80
+}
+
+/// Split `data` into tokens: maximal runs of ASCII alphanumerics, `_` and non-ASCII characters.
+pub fn tokens(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    data.split(|&b| !is_token_byte(b))
+        .filter(|token| !token.is_empty())
+}
+
+/// A fixed-size bloom filter over byte strings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Create a filter sized for `items` distinct entries.
+    pub fn with_capacity(items: usize) -> Self {
+        let words = (items * BITS_PER_TOKEN).max(64).div_ceil(64);
+        Self {
+            bits: vec![0; words],
+            hashes: HASH_COUNT,
+        }
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        let bit_count = self.bits.len() as u64 * 64;
+        for bit in bit_positions(item, self.hashes, bit_count) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Whether `item` may have been inserted. False positives are possible, false negatives are not.
+    pub fn contains(&self, item: &[u8]) -> bool {
+        let bit_count = self.bits.len() as u64 * 64;
+        bit_positions(item, self.hashes, bit_count)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// Bit positions of `item` using double hashing over a stable 64-bit hash.
+fn bit_positions(item: &[u8], hashes: u32, bit_count: u64) -> impl Iterator<Item = u64> {
+    // FNV-1a followed by the splitmix64 finalizer; stable across builds, unlike std's hasher
+    let mut hash = 0xcbf29ce484222325u64;
+    for &b in item {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xbf58476d1ce4e5b9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94d049bb133111eb);
+    hash ^= hash >> 31;
+    let h1 = hash;
+    let h2 = hash.rotate_left(32) | 1;
+    (0..hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+}
+
+/// A byte range of the file and the bloom filter of its tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub filter: BloomFilter,
+}
+
+/// Block index of one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockIndex {
+    file_len: u64,
+    mtime: (u64, u32),
+    pub blocks: Vec<Block>,
+}
+
+impl BlockIndex {
+    /// Index `data` in blocks of about `block_size` bytes.
+    pub fn build(data: &[u8], block_size: usize) -> Self {
+        let mut bounds = Vec::new();
+        let mut start = 0;
+        while start < data.len() {
+            let nominal_end = (start + block_size.max(1)).min(data.len());
+            let end = memchr::memchr(b'\n', &data[nominal_end..])
+                .map_or(data.len(), |pos| nominal_end + pos + 1);
+            bounds.push((start, end));
+            start = end;
+        }
+
+        let blocks = bounds
+            .into_par_iter()
+            .map(|(start, end)| {
+                let distinct: HashSet<&[u8]> = tokens(&data[start..end]).collect();
+                let mut filter = BloomFilter::with_capacity(distinct.len());
+                for token in distinct {
+                    filter.insert(token);
+                }
+                Block { start, end, filter }
+            })
+            .collect();
+
+        Self {
+            file_len: data.len() as u64,
+            mtime: (0, 0),
+            blocks,
+        }
+    }
+
+    /// Load the sidecar index of `file`, building and saving it if missing or stale.
+    ///
+    /// Failing to save is not an error; the index is then only used for this scan.
+    pub fn load_or_build(file: &str, metadata: &Metadata, data: &[u8]) -> Result<Self> {
+        let path = index_path(file);
+        let mtime = mtime(metadata);
+        if let Ok(bytes) = fs::read(&path)
+            && let Some(index) = Self::from_bytes(&bytes)
+            && index.file_len == metadata.len()
+            && index.mtime == mtime
+        {
+            return Ok(index);
+        }
+
+        let mut index = Self::build(data, DEFAULT_BLOCK_SIZE);
+        index.mtime = mtime;
+        let tmp_path = path.with_extension(format!("{}.tmp", INDEX_EXTENSION));
+        if let Err(e) =
+            fs::write(&tmp_path, index.to_bytes()).and_then(|_| fs::rename(&tmp_path, &path))
+        {
+            log::warn!("Could not save index {}: {}", path.display(), e);
+            let _ = fs::remove_file(&tmp_path);
+        }
+        Ok(index)
+    }
+
+    /// Byte ranges of the blocks that may contain every token of `needles`.
+    pub fn candidate_ranges(&self, needles: &[String]) -> Vec<(usize, usize)> {
+        let needle_tokens: Vec<&[u8]> = needles.iter().flat_map(|n| tokens(n.as_bytes())).collect();
+        self.blocks
+            .iter()
+            .filter(|block| {
+                needle_tokens
+                    .iter()
+                    .all(|token| block.filter.contains(token))
+            })
+            .map(|block| (block.start, block.end))
+            .collect()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.file_len.to_le_bytes());
+        out.extend_from_slice(&self.mtime.0.to_le_bytes());
+        out.extend_from_slice(&self.mtime.1.to_le_bytes());
+        out.extend_from_slice(&(self.blocks.len() as u64).to_le_bytes());
+        for block in &self.blocks {
+            out.extend_from_slice(&(block.start as u64).to_le_bytes());
+            out.extend_from_slice(&(block.end as u64).to_le_bytes());
+            out.extend_from_slice(&block.filter.hashes.to_le_bytes());
+            out.extend_from_slice(&(block.filter.bits.len() as u64).to_le_bytes());
+            for word in &block.filter.bits {
+                out.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len())? != MAGIC {
+            return None;
+        }
+        let file_len = reader.u64()?;
+        let mtime = (reader.u64()?, reader.u32()?);
+        let block_count = reader.u64()?;
+        let mut blocks = Vec::new();
+        for _ in 0..block_count {
+            let start = reader.u64()? as usize;
+            let end = reader.u64()? as usize;
+            let hashes = reader.u32()?;
+            let words = reader.u64()? as usize;
+            if words == 0 || words > reader.0.len() / 8 {
+                return None;
+            }
+            let bits = (0..words).map(|_| reader.u64()).collect::<Option<_>>()?;
+            blocks.push(Block {
+                start,
+                end,
+                filter: BloomFilter { bits, hashes },
+            });
+        }
+        reader.0.is_empty().then_some(Self {
+            file_len,
+            mtime,
+            blocks,
+        })
+    }
+}
+
+/// Little-endian reader over a byte slice.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+/// Path of the sidecar index of `file`.
+pub fn index_path(file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.{}", file, INDEX_EXTENSION))
+}
+
+fn mtime(metadata: &Metadata) -> (u64, u32) {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or((0, 0), |d| (d.as_secs(), d.subsec_nanos()))
+}
+
+/// Strings whose tokens every line matching `filters` contains as whole tokens.
+///
+/// Recognizes `__RAW__ = '...'`, `__RAW__ LIKE '...'` and `raw_contains('...')` predicates.
+pub fn filter_tokens(filters: &[Expr]) -> Vec<String> {
+    filters.iter().flat_map(expr_tokens).collect()
+}
+
+/// Whether `filter` yields tokens for the index.
+pub fn supports_filter(filter: &Expr) -> bool {
+    !expr_tokens(filter).is_empty()
+}
+
+fn expr_tokens(expr: &Expr) -> Vec<String> {
+    match expr {
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        }) => match (raw_column(left), string_literal(right)) {
+            (true, Some(value)) => token_strings(value),
+            _ => match (raw_column(right), string_literal(left)) {
+                (true, Some(value)) => token_strings(value),
+                _ => Vec::new(),
+            },
+        },
+        Expr::Like(Like {
+            negated: false,
+            expr,
+            pattern,
+            escape_char: None,
+            case_insensitive: false,
+        }) if raw_column(expr) => string_literal(pattern).map(like_tokens).unwrap_or_default(),
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::And,
+            right,
+        }) => {
+            let mut tokens = expr_tokens(left);
+            tokens.extend(expr_tokens(right));
+            tokens
+        }
+        Expr::ScalarFunction(ScalarFunction { func, args }) if func.name() == RAW_CONTAINS => {
+            match args.as_slice() {
+                [needle] => string_literal(needle).map(token_strings),
+                [haystack, needle] if raw_column(haystack) => {
+                    string_literal(needle).map(token_strings)
+                }
+                _ => None,
+            }
+            .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn raw_column(expr: &Expr) -> bool {
+    matches!(expr, Expr::Column(column) if column.name == "__RAW__")
+}
+
+fn string_literal(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Literal(
+            ScalarValue::Utf8(Some(s))
+            | ScalarValue::LargeUtf8(Some(s))
+            | ScalarValue::Utf8View(Some(s)),
+            _,
+        ) => Some(s),
+        _ => None,
+    }
+}
+
+fn token_strings(value: &str) -> Vec<String> {
+    tokens(value.as_bytes())
+        .map(|token| String::from_utf8_lossy(token).into_owned())
+        .collect()
+}
+
+/// Tokens of a LIKE pattern that are whole tokens of any matching line.
+///
+/// A token next to a `%` or `_` wildcard may be part of a longer token in the line,
+/// unless the pattern starts or ends there (the line starts or ends with it).
+fn like_tokens(pattern: &str) -> Vec<String> {
+    if pattern.contains('\\') {
+        return Vec::new();
+    }
+    let bytes = pattern.as_bytes();
+    let is_wildcard = |b: u8| b == b'%' || b == b'_';
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if !is_token_byte(bytes[i]) || is_wildcard(bytes[i]) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && is_token_byte(bytes[i]) && !is_wildcard(bytes[i]) {
+            i += 1;
+        }
+        let bounded_left = start == 0 || !is_wildcard(bytes[start - 1]);
+        let bounded_right = i == bytes.len() || !is_wildcard(bytes[i]);
+        if bounded_left && bounded_right {
+            result.push(pattern[start..i].to_string());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::prelude::{ident, lit};
+
+    #[test]
+    fn test_tokens() {
+        let line = b"GET /api/v1?id=req-3f2a_b user=\xc3\xa9mile";
+        let found: Vec<&[u8]> = tokens(line).collect();
+        assert_eq!(
+            found,
+            vec![
+                &b"GET"[..],
+                b"api",
+                b"v1",
+                b"id",
+                b"req",
+                b"3f2a_b",
+                b"user",
+                "émile".as_bytes()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_index_candidates() {
+        let mut data = String::new();
+        for i in 0..1000 {
+            data.push_str(&format!("line {} request id-{}\n", i, i * 7));
+        }
+        let index = BlockIndex::build(data.as_bytes(), 1024);
+        assert!(index.blocks.len() > 10);
+        assert_eq!(index.blocks.last().unwrap().end, data.len());
+        for block in &index.blocks {
+            assert!(block.end == data.len() || data.as_bytes()[block.end - 1] == b'\n');
+        }
+
+        let ranges = index.candidate_ranges(&["id-3500".to_string()]);
+        assert!(!ranges.is_empty() && ranges.len() < 3, "{:?}", ranges);
+        assert!(
+            ranges
+                .iter()
+                .any(|&(start, end)| data[start..end].contains("request id-3500\n"))
+        );
+        assert_eq!(index.candidate_ranges(&[]).len(), index.blocks.len());
+    }
+
+    #[test]
+    fn test_load_or_build_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("app.log");
+        fs::write(&file, "alpha beta\ngamma delta\n").unwrap();
+        let file = file.to_string_lossy().to_string();
+
+        let data = fs::read(&file).unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        let built = BlockIndex::load_or_build(&file, &metadata, &data).unwrap();
+        assert!(index_path(&file).exists());
+
+        let loaded = BlockIndex::from_bytes(&fs::read(index_path(&file)).unwrap()).unwrap();
+        assert_eq!(loaded, built);
+        assert_eq!(loaded.candidate_ranges(&["gamma".to_string()]).len(), 1);
+
+        // A changed file invalidates the index
+        fs::write(&file, "alpha beta\ngamma delta\nepsilon\n").unwrap();
+        let data = fs::read(&file).unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        let rebuilt = BlockIndex::load_or_build(&file, &metadata, &data).unwrap();
+        assert_eq!(rebuilt.file_len, data.len() as u64);
+        assert!(BlockIndex::from_bytes(b"garbage").is_none());
+    }
+
+    #[test]
+    fn test_filter_tokens() {
+        let filters = vec![
+            ident("__RAW__").eq(lit("req-42")),
+            ident("__RAW__").like(lit("%user alice logged%")),
+            ident("level").eq(lit("ERROR")),
+        ];
+        assert_eq!(filter_tokens(&filters), vec!["req", "42", "alice"]);
+        assert!(!supports_filter(&filters[2]));
+        let search = crate::datafusion::search::raw_contains_udf();
+        assert_eq!(
+            filter_tokens(&[search.call(vec![lit("user=bob")])]),
+            vec!["user", "bob"]
+        );
+        assert_eq!(like_tokens("GET /a%"), vec!["GET"]);
+        assert_eq!(like_tokens("%error at line 12"), vec!["at", "line", "12"]);
+        assert_eq!(like_tokens("%ab_cd%"), Vec::<String>::new());
+        assert_eq!(like_tokens("%x\\%y%"), Vec::<String>::new());
+    }
+}
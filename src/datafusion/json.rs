@@ -0,0 +1,296 @@
+//! Functions reading into JSON text, such as the columns of `json` fields.
+//!
+//! `json_get(payload, 'user', 'name')` follows object keys and array indexes given as
+//! arguments, `json_extract(payload, '$.items[0].id')` a path. Both return the value
+//! found as text: strings without their quotes, numbers and booleans as written and
+//! objects and arrays as JSON. Missing values, JSON `null` and text that is not JSON
+//! give NULL; `CAST` the result for numbers, e.g.
+//! `CAST(json_get(payload, 'took') AS DOUBLE)`.
+
+use datafusion::arrow::array::{Array, ArrayRef, StringArray, UInt64Array};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{Result, exec_err, plan_err};
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion::prelude::SessionContext;
+use serde_json::Value;
+use std::any::Any;
+use std::sync::Arc;
+
+/// Name of the function following keys and indexes.
+pub const JSON_GET: &str = "json_get";
+/// Name of the function following a path.
+pub const JSON_EXTRACT: &str = "json_extract";
+
+/// A step into a JSON value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// The member of an object.
+    Key(String),
+    /// The item of an array, from 0.
+    Index(usize),
+}
+
+/// Parse a path like `$.items[0].id` or `$['odd key']`; the leading `$` is optional.
+///
+/// Returns `None` for malformed paths.
+pub fn parse_path(path: &str) -> Option<Vec<Step>> {
+    let bare;
+    let mut rest = match path.strip_prefix('$') {
+        Some(rest) => rest,
+        // A bare first key, as in `user.name`
+        None => {
+            bare = format!(".{}", path);
+            &bare
+        }
+    };
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return None;
+            }
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']')?;
+            let inner = &after[..end];
+            let quoted = inner
+                .strip_prefix('\'')
+                .and_then(|s| s.strip_suffix('\''))
+                .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')));
+            steps.push(match quoted {
+                Some(key) => Step::Key(key.to_string()),
+                None => Step::Index(inner.trim().parse().ok()?),
+            });
+            rest = &after[end + 1..];
+        } else {
+            return None;
+        }
+    }
+    Some(steps)
+}
+
+/// The value `steps` lead to in `json`, as text.
+pub fn lookup(json: &str, steps: &[Step]) -> Option<String> {
+    let value: Value = serde_json::from_str(json).ok()?;
+    let mut value = &value;
+    for step in steps {
+        value = match (step, value) {
+            (Step::Key(key), Value::Object(object)) => object.get(key)?,
+            (Step::Index(index), Value::Array(array)) => array.get(*index)?,
+            _ => return None,
+        };
+    }
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// `json_get` / `json_extract` scalar function.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct JsonAccess {
+    name: &'static str,
+    signature: Signature,
+}
+
+impl JsonAccess {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            signature: Signature::variadic_any(Volatility::Immutable),
+        }
+    }
+
+    /// The steps of row `row` given by the arguments after the JSON.
+    fn steps(&self, args: &[ArrayRef], row: usize) -> Result<Option<Vec<Step>>> {
+        let mut steps = Vec::with_capacity(args.len());
+        for arg in args {
+            if arg.is_null(row) {
+                return Ok(None);
+            }
+            if arg.data_type().is_integer() {
+                let index = cast(&arg.slice(row, 1), &DataType::UInt64)?;
+                let index = index.as_any().downcast_ref::<UInt64Array>().unwrap();
+                match index.is_null(0) {
+                    true => return Ok(None),
+                    false => steps.push(Step::Index(index.value(0) as usize)),
+                }
+                continue;
+            }
+            let text = arg
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(row);
+            match self.name {
+                JSON_EXTRACT => match parse_path(text) {
+                    Some(path) => steps.extend(path),
+                    None => return exec_err!("{}: invalid path '{}'", self.name, text),
+                },
+                _ => steps.push(Step::Key(text.to_string())),
+            }
+        }
+        Ok(Some(steps))
+    }
+}
+
+impl ScalarUDFImpl for JsonAccess {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        match (self.name, arg_types.len()) {
+            (JSON_EXTRACT, 2) | (JSON_GET, 2..) => Ok(DataType::Utf8),
+            (JSON_EXTRACT, _) => plan_err!("{}(json, path) takes two arguments", self.name),
+            _ => plan_err!("{}(json, key, ...) takes at least one key", self.name),
+        }
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        let rows = args.number_rows;
+        let args = args
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let array = arg.to_array(rows)?;
+                // Integers are indexes after the JSON; the JSON itself is read as text
+                match i > 0 && array.data_type().is_integer() {
+                    true => Ok(array),
+                    false => Ok(cast(&array, &DataType::Utf8)?),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (json, steps) = args.split_first().unwrap();
+        let json = json.as_any().downcast_ref::<StringArray>().unwrap();
+        let mut values = Vec::with_capacity(rows);
+        for row in 0..rows {
+            let value = match (json.is_null(row), self.steps(steps, row)?) {
+                (false, Some(steps)) => lookup(json.value(row), &steps),
+                _ => None,
+            };
+            values.push(value);
+        }
+        Ok(ColumnarValue::Array(Arc::new(StringArray::from(values))))
+    }
+}
+
+/// The `json_get` function.
+pub fn json_get_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(JsonAccess::new(JSON_GET))
+}
+
+/// The `json_extract` function.
+pub fn json_extract_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(JsonAccess::new(JSON_EXTRACT))
+}
+
+/// Register the JSON functions with a session.
+pub fn register_udfs(ctx: &SessionContext) {
+    ctx.register_udf(json_get_udf());
+    ctx.register_udf(json_extract_udf());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("$.items[0].id"),
+            Some(vec![
+                Step::Key("items".into()),
+                Step::Index(0),
+                Step::Key("id".into())
+            ])
+        );
+        assert_eq!(
+            parse_path("$['odd key']"),
+            Some(vec![Step::Key("odd key".into())])
+        );
+        assert_eq!(
+            parse_path("user.name"),
+            Some(vec![Step::Key("user".into()), Step::Key("name".into())])
+        );
+        assert_eq!(parse_path("$"), Some(vec![]));
+        for invalid in ["$.", "$..a", "$[x]", "$[0", "$x"] {
+            assert_eq!(parse_path(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_functions() {
+        let ctx = SessionContext::new();
+        register_udfs(&ctx);
+        let batches = ctx
+            .sql(
+                "SELECT json_get(s, 'user', 'name') AS name, json_get(s, 'tags', 1) AS tag, \
+                 json_extract(s, '$.user') AS user, json_extract(s, '$.took') AS took \
+                 FROM (VALUES ('{\"user\": {\"name\": \"ann\"}, \"tags\": [\"a\", \"b\"], \"took\": 1.5}'), \
+                 ('{\"user\": null}'), ('not json'), (NULL)) AS t(s)",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let column = |i: usize| {
+            batches[0]
+                .column(i)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .iter()
+                .map(|v| v.map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column(0), [Some("ann".to_string()), None, None, None]);
+        assert_eq!(column(1), [Some("b".to_string()), None, None, None]);
+        assert_eq!(
+            column(2),
+            [Some(r#"{"name":"ann"}"#.to_string()), None, None, None]
+        );
+        assert_eq!(column(3), [Some("1.5".to_string()), None, None, None]);
+
+        // Other types are read as JSON text
+        let batches = ctx
+            .sql("SELECT json_get(n, 'a') AS a, json_extract(n, '$') AS n FROM (VALUES (1), (NULL)) AS t(n)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let column = |i: usize| {
+            batches[0]
+                .column(i)
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .iter()
+                .map(|v| v.map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column(0), [None, None]);
+        assert_eq!(column(1), [Some("1".to_string()), None]);
+
+        assert!(ctx.sql("SELECT json_get('{}')").await.is_err());
+        let invalid = ctx.sql("SELECT json_extract('{}', '$[x]')").await;
+        assert!(invalid.unwrap().collect().await.is_err());
+    }
+}
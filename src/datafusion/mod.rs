@@ -5,8 +5,22 @@
 
 mod builder;
 mod exec;
+mod files;
+pub mod index;
+pub mod json;
 mod provider;
+pub mod search;
 
 pub use builder::FieldsBuilder;
 pub use exec::LogTableExec;
-pub use provider::LogTableProvider;
+pub(crate) use files::resolve_file_list;
+pub(crate) use provider::check_min_match_rate;
+pub use provider::{LogTableProvider, LogTableProviderBuilder, LongLines, STRICT_MATCH_RATE};
+
+use datafusion::prelude::SessionContext;
+
+/// Register the search and JSON functions with a session.
+pub fn register_udfs(ctx: &SessionContext) {
+    search::register_udfs(ctx);
+    json::register_udfs(ctx);
+}
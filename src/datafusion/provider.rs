@@ -1,43 +1,202 @@
 //! LogTableProvider implementation for DataFusion.
 
 use async_trait::async_trait;
-use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 use datafusion::catalog::{Session, TableProvider};
 use datafusion::common::Result;
-use datafusion::logical_expr::{Expr, TableType};
+use datafusion::logical_expr::{Expr, TableProviderFilterPushDown, TableType};
 use datafusion::physical_plan::ExecutionPlan;
+use encoding_rs::Encoding;
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::any::Any;
 use std::sync::Arc;
 
+use crate::datafusion::builder::{
+    decimal_data_type, dictionary_data_type, duration_data_type, map_data_type, timestamp_data_type,
+};
 use crate::datafusion::exec::LogTableExec;
-use crate::scanner::Scanner;
-use crate::types::FieldType;
+use crate::datafusion::files::resolve_file_list;
+use crate::datafusion::index;
+use crate::datafusion::search::{self, RawFilter};
+use crate::extractor::RecordExtractor;
+use crate::stats::{ScanCounts, ScanStats};
+use crate::types::{FieldType, TimestampUnit, YearStrategy};
+
+/// Match rate required by strict mode.
+pub const STRICT_MATCH_RATE: f64 = 0.95;
+
+/// What to do with lines longer than [`LogTableProvider::max_line_length`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LongLines {
+    /// Drop the line: it is neither a row nor unmatched, and not in the match rate.
+    #[default]
+    Skip,
+    /// Parse only the first `max_line_length` bytes of the line.
+    Truncate,
+}
+
+impl std::str::FromStr for LongLines {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> crate::error::Result<Self> {
+        match s {
+            "skip" => Ok(LongLines::Skip),
+            "truncate" => Ok(LongLines::Truncate),
+            other => Err(crate::error::Error::Config(format!(
+                "unknown long line policy '{}', expected skip or truncate",
+                other
+            ))),
+        }
+    }
+}
+
+/// Check that a minimum match rate is a fraction, so `95` is not read as 9500%.
+pub(crate) fn check_min_match_rate(min_match_rate: Option<f64>) -> crate::error::Result<()> {
+    match min_match_rate {
+        Some(rate) if !(0.0..=1.0).contains(&rate) => Err(crate::error::Error::Config(format!(
+            "minimum match rate {} is outside 0.0-1.0, e.g. use 0.95 for 95%",
+            rate
+        ))),
+        _ => Ok(()),
+    }
+}
 
 /// A DataFusion TableProvider that reads and parses log files.
+///
+/// Construct it with [`LogTableProvider::builder`]; new options are added as builder
+/// setters, so the struct cannot be built with a literal outside this crate.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct LogTableProvider {
-    pub scanner: Scanner,
-    pub file_path: String,
+    /// Turns each line into field values.
+    pub extractor: Arc<dyn RecordExtractor>,
+    /// Paths or glob patterns of the files to read, unioned into one table.
+    pub file_paths: Vec<String>,
     pub add_file_path: bool,
     pub add_raw: bool,
+    pub add_file_mtime: bool,
+    pub add_file_size: bool,
+    /// Add a `__PATTERN__` column with the index of the pattern each line matched.
+    pub add_pattern: bool,
+    /// Rows are ordered by the first datetime field across files in rotation order.
+    ///
+    /// When set, the scan declares this ordering so DataFusion can skip sorts.
+    pub time_ordered: bool,
+    /// Each file is ordered by the first datetime field.
+    ///
+    /// When set, every file is scanned as its own partition so `ORDER BY` on that
+    /// field becomes a streaming k-way merge instead of a full sort.
+    pub merge_by_time: bool,
+    /// Expose the lines the pattern rejects instead of parsed rows.
+    ///
+    /// The schema is then fixed to `__FILE__`, `__LINE__` and `__RAW__`.
+    pub unmatched: bool,
+    /// Minimum fraction (0.0-1.0) of non-empty lines that must match in each file.
+    ///
+    /// When set, a scan where any file falls below it fails with per-file counts
+    /// instead of silently returning partial results.
+    pub min_match_rate: Option<f64>,
+    /// Use per-file token bloom indexes (`<file>.lflogidx`, built on first use) to skip
+    /// blocks that cannot match `__RAW__` equality or `LIKE` filters.
+    ///
+    /// Not applied to strict scans, whose match rate must cover every line, nor to
+    /// files read with an `encoding`.
+    pub index: bool,
+    /// Leading lines of each file to ignore, e.g. a banner header.
+    pub skip_lines: usize,
+    /// Trailing lines of each file to ignore, e.g. a summary footer.
+    pub skip_footer: usize,
+    /// Lines longer than this many bytes are handled by `long_lines` instead of being
+    /// matched whole, so one huge line cannot stall the regex engine.
+    pub max_line_length: Option<usize>,
+    /// Policy for lines longer than `max_line_length`.
+    pub long_lines: LongLines,
+    /// Encoding files are transcoded from before scanning, e.g. UTF-16LE; `None` reads
+    /// them as UTF-8.
+    pub encoding: Option<&'static Encoding>,
+    /// Number of parse threads; a dedicated pool of this size is built with the provider.
     pub num_threads: Option<usize>,
+    /// Pool files are parsed in, shared by clones of the provider.
+    ///
+    /// When unset, rayon's global pool is used.
+    pub thread_pool: Option<Arc<ThreadPool>>,
+    /// How timestamps whose format has no year get one from the file's modification
+    /// time.
+    pub year: YearStrategy,
+    /// Unit of `DateTime` columns that do not choose their own, and of `__FILE_MTIME__`.
+    pub timestamp_unit: TimestampUnit,
+    /// Unit of `Duration` columns.
+    pub duration_unit: TimestampUnit,
 }
 
 impl LogTableProvider {
-    /// Create a new LogTableProvider.
+    /// Create a new LogTableProvider over one path or glob pattern.
+    ///
+    /// Shorthand for the common options; use [`LogTableProvider::builder`] for the rest.
     pub fn new(
-        scanner: Scanner,
+        extractor: impl RecordExtractor + 'static,
         file_path: String,
         add_file_path: bool,
         add_raw: bool,
         num_threads: Option<usize>,
     ) -> Self {
+        Self::builder(extractor)
+            .with_file_path(file_path)
+            .with_add_file_path(add_file_path)
+            .with_add_raw(add_raw)
+            .with_num_threads(num_threads)
+            .build()
+    }
+
+    /// Start building a provider for `extractor` (usually a [`Scanner`]), with no
+    /// files and all options off.
+    ///
+    /// [`Scanner`]: crate::scanner::Scanner
+    pub fn builder(extractor: impl RecordExtractor + 'static) -> LogTableProviderBuilder {
+        Self::builder_with_extractor(Arc::new(extractor))
+    }
+
+    /// Like [`LogTableProvider::builder`], for an extractor that is already shared.
+    pub fn builder_with_extractor(extractor: Arc<dyn RecordExtractor>) -> LogTableProviderBuilder {
+        LogTableProviderBuilder {
+            provider: Self {
+                extractor,
+                file_paths: Vec::new(),
+                add_file_path: false,
+                add_raw: false,
+                add_file_mtime: false,
+                add_file_size: false,
+                add_pattern: false,
+                time_ordered: false,
+                merge_by_time: false,
+                unmatched: false,
+                min_match_rate: None,
+                index: false,
+                skip_lines: 0,
+                skip_footer: 0,
+                max_line_length: None,
+                long_lines: LongLines::Skip,
+                encoding: None,
+                num_threads: None,
+                thread_pool: None,
+                year: YearStrategy::default(),
+                timestamp_unit: TimestampUnit::default(),
+                duration_unit: TimestampUnit::Millis,
+            },
+        }
+    }
+
+    /// Counts of the scans of this provider's extractor so far, if it keeps them, as
+    /// the regex [`Scanner`](crate::scanner::Scanner) does.
+    pub fn scan_stats(&self) -> Option<ScanCounts> {
+        self.extractor.scan_stats().map(ScanStats::counts)
+    }
+
+    /// Companion provider exposing the non-empty lines this provider's pattern rejects.
+    pub fn unmatched_table(&self) -> Self {
         Self {
-            scanner,
-            file_path,
-            add_file_path,
-            add_raw,
-            num_threads,
+            unmatched: true,
+            ..self.clone()
         }
     }
 
@@ -47,11 +206,212 @@ impl LogTableProvider {
         projections: Option<&Vec<usize>>,
         schema: SchemaRef,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(LogTableExec::new(
-            projections,
-            schema,
-            self.clone(),
-        )))
+        self.create_exec(projections, schema, Vec::new(), Vec::new())
+    }
+
+    fn create_exec(
+        &self,
+        projections: Option<&Vec<usize>>,
+        schema: SchemaRef,
+        tokens: Vec<String>,
+        raw_filters: Vec<RawFilter>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        check_min_match_rate(self.min_match_rate)
+            .map_err(|e| datafusion::error::DataFusionError::External(Box::new(e)))?;
+        let files = resolve_file_list(&self.file_paths)
+            .map_err(|e| datafusion::error::DataFusionError::External(Box::new(e)))?;
+        Ok(Arc::new(
+            LogTableExec::new(projections, schema, self.clone(), files)
+                .with_tokens(tokens)
+                .with_raw_filters(raw_filters),
+        ))
+    }
+
+    /// Whether scans may skip blocks using the token index.
+    fn uses_index(&self) -> bool {
+        self.index && !self.unmatched && self.min_match_rate.is_none() && self.encoding.is_none()
+    }
+}
+
+/// Builder for [`LogTableProvider`], created by [`LogTableProvider::builder`].
+#[derive(Debug, Clone)]
+pub struct LogTableProviderBuilder {
+    provider: LogTableProvider,
+}
+
+impl LogTableProviderBuilder {
+    /// Add a path or glob pattern to read.
+    pub fn with_file_path(mut self, file_path: impl Into<String>) -> Self {
+        self.provider.file_paths.push(file_path.into());
+        self
+    }
+
+    /// Replace the paths or glob patterns to read.
+    pub fn with_file_paths<I, S>(mut self, file_paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.provider.file_paths = file_paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Add the `__FILE__` column.
+    pub fn with_add_file_path(mut self, add_file_path: bool) -> Self {
+        self.provider.add_file_path = add_file_path;
+        self
+    }
+
+    /// Add the `__RAW__` column.
+    pub fn with_add_raw(mut self, add_raw: bool) -> Self {
+        self.provider.add_raw = add_raw;
+        self
+    }
+
+    /// Add the `__FILE_MTIME__` column.
+    pub fn with_add_file_mtime(mut self, add_file_mtime: bool) -> Self {
+        self.provider.add_file_mtime = add_file_mtime;
+        self
+    }
+
+    /// Add the `__FILE_SIZE__` column.
+    pub fn with_add_file_size(mut self, add_file_size: bool) -> Self {
+        self.provider.add_file_size = add_file_size;
+        self
+    }
+
+    /// Add the `__PATTERN__` column.
+    pub fn with_add_pattern(mut self, add_pattern: bool) -> Self {
+        self.provider.add_pattern = add_pattern;
+        self
+    }
+
+    /// Declare rows ordered by the first datetime field.
+    pub fn with_time_ordered(mut self, time_ordered: bool) -> Self {
+        self.provider.time_ordered = time_ordered;
+        self
+    }
+
+    /// Scan each file as a partition ordered by the first datetime field.
+    pub fn with_merge_by_time(mut self, merge_by_time: bool) -> Self {
+        self.provider.merge_by_time = merge_by_time;
+        self
+    }
+
+    /// Expose the lines the pattern rejects instead of parsed rows.
+    pub fn with_unmatched(mut self, unmatched: bool) -> Self {
+        self.provider.unmatched = unmatched;
+        self
+    }
+
+    /// Fail scans where a file's match rate is below this fraction; scans with a rate
+    /// outside 0.0-1.0 fail up front.
+    pub fn with_min_match_rate(mut self, min_match_rate: Option<f64>) -> Self {
+        self.provider.min_match_rate = min_match_rate;
+        self
+    }
+
+    /// Skip blocks using per-file token bloom indexes.
+    pub fn with_index(mut self, index: bool) -> Self {
+        self.provider.index = index;
+        self
+    }
+
+    /// Ignore the first `skip_lines` lines of each file.
+    ///
+    /// Skipped lines are neither rows, unmatched lines nor counted in the match rate.
+    pub fn with_skip_lines(mut self, skip_lines: usize) -> Self {
+        self.provider.skip_lines = skip_lines;
+        self
+    }
+
+    /// Ignore the last `skip_footer` lines of each file, like
+    /// [`with_skip_lines`](Self::with_skip_lines).
+    pub fn with_skip_footer(mut self, skip_footer: usize) -> Self {
+        self.provider.skip_footer = skip_footer;
+        self
+    }
+
+    /// Skip or truncate lines longer than `max_line_length` bytes, per `long_lines`.
+    ///
+    /// Such lines are counted in the scan's `long_lines` metric.
+    pub fn with_max_line_length(
+        mut self,
+        max_line_length: Option<usize>,
+        long_lines: LongLines,
+    ) -> Self {
+        self.provider.max_line_length = max_line_length;
+        self.provider.long_lines = long_lines;
+        self
+    }
+
+    /// Transcode files from `encoding`, e.g. `encoding_rs::WINDOWS_1252` for latin-1
+    /// or `encoding_rs::UTF_16LE`, before scanning.
+    ///
+    /// A byte order mark at the start of a file takes precedence. Each file is decoded
+    /// into memory in full, and the token index is not used.
+    pub fn with_encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.provider.encoding = (encoding != encoding_rs::UTF_8).then_some(encoding);
+        self
+    }
+
+    /// Choose how timestamps whose format has no year, like syslog's `%b %e %T`, get
+    /// one; see [`YearStrategy`].
+    pub fn with_year(mut self, year: YearStrategy) -> Self {
+        self.provider.year = year;
+        self
+    }
+
+    /// Store timestamps in `unit` rather than microseconds, e.g. milliseconds for
+    /// tools that expect them. A `datetime(..., unit=...)` field keeps its own unit.
+    pub fn with_timestamp_unit(mut self, unit: TimestampUnit) -> Self {
+        self.provider.timestamp_unit = unit;
+        self
+    }
+
+    /// Store `duration(typed)` fields in `unit` rather than milliseconds. DataFusion
+    /// only adds durations to timestamps of the same unit, so set it to the timestamp
+    /// unit for arithmetic such as `ts + took`.
+    pub fn with_duration_unit(mut self, unit: TimestampUnit) -> Self {
+        self.provider.duration_unit = unit;
+        self
+    }
+
+    /// Set the number of threads to use for parsing.
+    ///
+    /// Unless a pool is given with [`with_thread_pool`](Self::with_thread_pool), a
+    /// dedicated pool of this many threads is built for the provider.
+    pub fn with_num_threads(mut self, num_threads: Option<usize>) -> Self {
+        self.provider.num_threads = num_threads;
+        self
+    }
+
+    /// Parse files in `thread_pool`, e.g. one shared by several providers.
+    pub fn with_thread_pool(mut self, thread_pool: Option<Arc<ThreadPool>>) -> Self {
+        self.provider.thread_pool = thread_pool;
+        self
+    }
+
+    /// Finish the provider.
+    ///
+    /// Without a thread pool or thread count, the `LFLOGTHREADS` environment variable
+    /// is consulted as a last resort before falling back to rayon's global pool.
+    pub fn build(mut self) -> LogTableProvider {
+        let provider = &mut self.provider;
+        if provider.thread_pool.is_none() {
+            let num_threads = provider.num_threads.or_else(|| {
+                std::env::var("LFLOGTHREADS")
+                    .ok()
+                    .and_then(|s| s.parse().ok())
+            });
+            if let Some(num_threads) = num_threads {
+                match ThreadPoolBuilder::new().num_threads(num_threads).build() {
+                    Ok(pool) => provider.thread_pool = Some(Arc::new(pool)),
+                    Err(e) => log::warn!("Using the global thread pool: {}", e),
+                }
+            }
+        }
+        self.provider
     }
 }
 
@@ -61,19 +421,39 @@ impl TableProvider for LogTableProvider {
         self
     }
 
-    /// Generate schema dynamically from the scanner's field names and type hints.
+    /// Generate schema dynamically from the extractor's field names and type hints.
     fn schema(&self) -> SchemaRef {
+        if self.unmatched {
+            return SchemaRef::new(Schema::new(vec![
+                Field::new("__FILE__", DataType::Utf8, true),
+                Field::new("__LINE__", DataType::Int64, true),
+                Field::new("__RAW__", DataType::Utf8, true),
+            ]));
+        }
+
         let mut fields: Vec<Field> = self
-            .scanner
-            .field_names
+            .extractor
+            .field_names()
             .iter()
             .map(|name| {
-                let data_type = match self.scanner.type_hints.get(name) {
+                let data_type = match self.extractor.type_hints().get(name) {
                     Some(FieldType::Int) => DataType::Int32,
+                    Some(FieldType::Int64) => DataType::Int64,
                     Some(FieldType::Float) => DataType::Float64,
-                    Some(FieldType::DateTime(_)) => {
-                        DataType::Timestamp(TimeUnit::Microsecond, None)
+                    Some(FieldType::Decimal(precision, scale)) => {
+                        decimal_data_type(*precision, *scale)
+                    }
+                    Some(FieldType::DateTime(d)) => timestamp_data_type(
+                        d.unit.unwrap_or(self.timestamp_unit),
+                        d.timezone.as_deref(),
+                    ),
+                    Some(FieldType::List(_)) => {
+                        DataType::List(Arc::new(Field::new_list_field(DataType::Utf8, true)))
                     }
+                    Some(FieldType::Map) => map_data_type(),
+                    Some(FieldType::Duration) => duration_data_type(self.duration_unit),
+                    Some(FieldType::Binary(_)) => DataType::Binary,
+                    Some(FieldType::Dictionary(_)) => dictionary_data_type(),
                     _ => DataType::Utf8,
                 };
                 Field::new(name, data_type, true)
@@ -85,6 +465,19 @@ impl TableProvider for LogTableProvider {
         if self.add_raw {
             fields.push(Field::new("__RAW__", DataType::Utf8, true));
         }
+        if self.add_file_mtime {
+            fields.push(Field::new(
+                "__FILE_MTIME__",
+                timestamp_data_type(self.timestamp_unit, None),
+                true,
+            ));
+        }
+        if self.add_file_size {
+            fields.push(Field::new("__FILE_SIZE__", DataType::Int64, true));
+        }
+        if self.add_pattern {
+            fields.push(Field::new("__PATTERN__", DataType::Int64, true));
+        }
         SchemaRef::new(Schema::new(fields))
     }
 
@@ -92,13 +485,84 @@ impl TableProvider for LogTableProvider {
         TableType::Base
     }
 
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| {
+                if search::is_raw_filter(filter) {
+                    // raw_contains / raw_match are evaluated on each line by the scan
+                    TableProviderFilterPushDown::Exact
+                } else if self.uses_index() && index::supports_filter(filter) {
+                    // Blocks are skipped, but rows in scanned blocks are not filtered
+                    TableProviderFilterPushDown::Inexact
+                } else {
+                    TableProviderFilterPushDown::Unsupported
+                }
+            })
+            .collect())
+    }
+
     async fn scan(
         &self,
         _state: &dyn Session,
         projection: Option<&Vec<usize>>,
-        _filters: &[Expr],
+        filters: &[Expr],
         _limit: Option<usize>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        self.create_physical_plan(projection, self.schema())
+        let tokens = if self.uses_index() {
+            index::filter_tokens(filters)
+        } else {
+            Vec::new()
+        };
+        let raw_filters = filters
+            .iter()
+            .filter_map(|filter| RawFilter::from_expr(filter).transpose())
+            .collect::<Result<Vec<_>>>()?;
+        self.create_exec(projection, self.schema(), tokens, raw_filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn test_provider_builder() {
+        let scanner = Scanner::new(r"^(?P<id>\d+) (?P<msg>.*)$".to_string()).unwrap();
+        let provider = LogTableProvider::builder(scanner.clone())
+            .with_file_path("a.log")
+            .with_file_path("logs/*.log")
+            .with_add_raw(true)
+            .with_add_file_size(true)
+            .with_min_match_rate(Some(0.5))
+            .build();
+        assert_eq!(provider.file_paths, vec!["a.log", "logs/*.log"]);
+        assert!(provider.add_raw && provider.add_file_size && !provider.add_file_path);
+        assert_eq!(provider.min_match_rate, Some(0.5));
+
+        let names: Vec<String> = provider
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(names, vec!["id", "msg", "__RAW__", "__FILE_SIZE__"]);
+
+        let provider = LogTableProvider::new(scanner.clone(), "a.log".into(), true, false, Some(2));
+        assert_eq!(provider.file_paths, vec!["a.log"]);
+        assert!(provider.add_file_path && !provider.index);
+        assert_eq!(provider.num_threads, Some(2));
+        assert_eq!(provider.thread_pool.unwrap().current_num_threads(), 2);
+
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(3).build().unwrap());
+        let provider = LogTableProvider::builder(scanner)
+            .with_num_threads(Some(2))
+            .with_thread_pool(Some(pool.clone()))
+            .build();
+        assert!(Arc::ptr_eq(provider.thread_pool.as_ref().unwrap(), &pool));
     }
 }
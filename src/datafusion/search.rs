@@ -0,0 +1,272 @@
+//! Full-text search functions over raw log lines.
+//!
+//! `raw_contains('token')` keeps lines containing `token` at token boundaries and
+//! `raw_match('regex')` keeps lines matching a regex. Used in `WHERE` on a log table
+//! they are evaluated by the scan on every raw line, whether or not `__RAW__` is
+//! selected, and `raw_contains` skips blocks using the token index when enabled.
+//!
+//! Both also accept the text to search as a first argument, e.g.
+//! `raw_contains(message, 'timeout')`, which works on any string column.
+
+use datafusion::arrow::array::{Array, ArrayRef, BooleanArray, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{Result, ScalarValue, plan_err};
+use datafusion::logical_expr::expr::ScalarFunction;
+use datafusion::logical_expr::{
+    ColumnarValue, Expr, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, TypeSignature,
+    Volatility,
+};
+use datafusion::prelude::SessionContext;
+use regex::Regex;
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::datafusion::index::is_token_byte;
+
+/// Name of the token search function.
+pub const RAW_CONTAINS: &str = "raw_contains";
+/// Name of the regex search function.
+pub const RAW_MATCH: &str = "raw_match";
+
+/// Whether `needle` occurs in `haystack` at token boundaries.
+///
+/// An occurrence counts if it does not extend a token of the surrounding text, so
+/// `req-42` is found in `id=req-42 ok` but not in `id=req-420`.
+pub fn contains_token(haystack: &str, needle: &str) -> bool {
+    if needle.is_empty() {
+        return true;
+    }
+    let bytes = haystack.as_bytes();
+    let first = needle.as_bytes()[0];
+    let last = needle.as_bytes()[needle.len() - 1];
+    haystack.match_indices(needle).any(|(start, _)| {
+        let end = start + needle.len();
+        let bounded_left = start == 0 || !is_token_byte(first) || !is_token_byte(bytes[start - 1]);
+        let bounded_right =
+            end == bytes.len() || !is_token_byte(last) || !is_token_byte(bytes[end]);
+        bounded_left && bounded_right
+    })
+}
+
+/// A search pushed down into the scan and tested on each raw line.
+#[derive(Debug, Clone)]
+pub enum RawFilter {
+    Contains(String),
+    Match(Regex),
+}
+
+impl RawFilter {
+    /// Parse a single-argument `raw_contains` / `raw_match` call with a literal argument.
+    ///
+    /// Returns `None` for other expressions and an error for invalid regexes.
+    pub fn from_expr(expr: &Expr) -> Result<Option<Self>> {
+        let Expr::ScalarFunction(ScalarFunction { func, args }) = expr else {
+            return Ok(None);
+        };
+        let [arg] = args.as_slice() else {
+            return Ok(None);
+        };
+        let Some(value) = string_literal(arg) else {
+            return Ok(None);
+        };
+        match func.name() {
+            RAW_CONTAINS => Ok(Some(Self::Contains(value.to_string()))),
+            RAW_MATCH => Regex::new(value)
+                .map(|re| Some(Self::Match(re)))
+                .map_err(|e| {
+                    datafusion::error::DataFusionError::Plan(format!("{}: {}", RAW_MATCH, e))
+                }),
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether `line` passes this filter.
+    pub fn matches(&self, line: &str) -> bool {
+        match self {
+            Self::Contains(needle) => contains_token(line, needle),
+            Self::Match(re) => re.is_match(line),
+        }
+    }
+}
+
+/// Whether `expr` is a search the scan evaluates itself.
+pub fn is_raw_filter(expr: &Expr) -> bool {
+    matches!(RawFilter::from_expr(expr), Ok(Some(_)))
+}
+
+fn string_literal(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Literal(
+            ScalarValue::Utf8(Some(s))
+            | ScalarValue::LargeUtf8(Some(s))
+            | ScalarValue::Utf8View(Some(s)),
+            _,
+        ) => Some(s),
+        _ => None,
+    }
+}
+
+/// `raw_contains` / `raw_match` scalar function.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct RawSearch {
+    name: &'static str,
+    signature: Signature,
+}
+
+impl RawSearch {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            signature: Signature::one_of(
+                vec![TypeSignature::String(1), TypeSignature::String(2)],
+                Volatility::Immutable,
+            ),
+        }
+    }
+}
+
+impl ScalarUDFImpl for RawSearch {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Boolean)
+    }
+
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        let [haystack, pattern] = args.args.as_slice() else {
+            return plan_err!(
+                "{}('...') searches raw lines and must be a top-level WHERE condition on a log \
+                 table; use {}(<column>, '...') elsewhere",
+                self.name,
+                self.name
+            );
+        };
+
+        let haystack = haystack.to_array(args.number_rows)?;
+        let haystack = cast(&haystack, &DataType::Utf8)?;
+        let haystack = haystack.as_any().downcast_ref::<StringArray>().unwrap();
+
+        let result: BooleanArray = match pattern {
+            ColumnarValue::Scalar(scalar) => {
+                let Some(pattern) = scalar_string(scalar)? else {
+                    return Ok(ColumnarValue::Scalar(ScalarValue::Boolean(None)));
+                };
+                let filter = match self.name {
+                    RAW_MATCH => RawFilter::Match(Regex::new(&pattern).map_err(|e| {
+                        datafusion::error::DataFusionError::Execution(format!(
+                            "{}: {}",
+                            self.name, e
+                        ))
+                    })?),
+                    _ => RawFilter::Contains(pattern),
+                };
+                haystack
+                    .iter()
+                    .map(|v| v.map(|line| filter.matches(line)))
+                    .collect()
+            }
+            ColumnarValue::Array(patterns) => {
+                let patterns: ArrayRef = cast(patterns, &DataType::Utf8)?;
+                let patterns = patterns.as_any().downcast_ref::<StringArray>().unwrap();
+                haystack
+                    .iter()
+                    .zip(patterns.iter())
+                    .map(|(line, pattern)| match (line, pattern) {
+                        (Some(line), Some(pattern)) => match self.name {
+                            RAW_MATCH => Regex::new(pattern).ok().map(|re| re.is_match(line)),
+                            _ => Some(contains_token(line, pattern)),
+                        },
+                        _ => None,
+                    })
+                    .collect()
+            }
+        };
+        Ok(ColumnarValue::Array(Arc::new(result)))
+    }
+}
+
+fn scalar_string(scalar: &ScalarValue) -> Result<Option<String>> {
+    match scalar.cast_to(&DataType::Utf8)? {
+        ScalarValue::Utf8(value) => Ok(value),
+        _ => Ok(None),
+    }
+}
+
+/// The `raw_contains` function.
+pub fn raw_contains_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(RawSearch::new(RAW_CONTAINS))
+}
+
+/// The `raw_match` function.
+pub fn raw_match_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(RawSearch::new(RAW_MATCH))
+}
+
+/// Register the search functions with a session.
+pub fn register_udfs(ctx: &SessionContext) {
+    ctx.register_udf(raw_contains_udf());
+    ctx.register_udf(raw_match_udf());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_token() {
+        assert!(contains_token("id=req-42 ok", "req-42"));
+        assert!(contains_token("req-42", "req-42"));
+        assert!(!contains_token("id=req-420", "req-42"));
+        assert!(!contains_token("xreq-42", "req-42"));
+        assert!(contains_token("xreq-42 req-42", "req-42"));
+        assert!(contains_token("path=/api/v1", "/api"));
+        assert!(contains_token("anything", ""));
+    }
+
+    #[tokio::test]
+    async fn test_search_functions_on_columns() {
+        let ctx = SessionContext::new();
+        register_udfs(&ctx);
+        let batches = ctx
+            .sql(
+                "SELECT raw_contains(s, 'timeout') AS c, raw_match(s, '^ERR') AS m \
+                 FROM (VALUES ('ERR timeout'), ('ok timeouts'), (NULL)) AS t(s)",
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let column = |i: usize| {
+            batches[0]
+                .column(i)
+                .as_any()
+                .downcast_ref::<BooleanArray>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column(0), vec![Some(true), Some(false), None]);
+        assert_eq!(column(1), vec![Some(true), Some(false), None]);
+
+        let err = ctx
+            .sql("SELECT raw_contains('x')")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("top-level WHERE"), "{}", err);
+    }
+}
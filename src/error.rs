@@ -27,12 +27,24 @@ pub enum Error {
     #[error("TOML parse error: {0}")]
     Toml(#[from] toml::de::Error),
 
+    #[error("TOML serialize error: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
     #[error("DataFusion error: {0}")]
     DataFusion(#[from] datafusion_common::DataFusionError),
 
     #[error("Arrow error: {0}")]
     Arrow(#[from] datafusion::arrow::error::ArrowError),
 
+    #[error("HTTP error: {0}")]
+    Http(#[from] ureq::Error),
+
     #[error("Rustyline error: {0}")]
     Readline(#[from] rustyline::error::ReadlineError),
 
@@ -46,6 +58,22 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
 
+    #[error("Checksum mismatch for {name}: expected {expected}, got {actual}")]
+    Checksum {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Match rate too low: {0}")]
+    MatchRate(String),
+
+    #[error("Query cancelled")]
+    Cancelled,
+
+    #[error("Query timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
     #[error("No files found for path: {0}")]
     NoFiles(String),
 
@@ -56,9 +84,41 @@ pub enum Error {
 /// Convenience result alias using the crate's `Error`.
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl From<lflog_core::Error> for Error {
+    fn from(error: lflog_core::Error) -> Self {
+        match error {
+            lflog_core::Error::Io(e) => Error::Io(e),
+            lflog_core::Error::Utf8(e) => Error::Utf8(e),
+            lflog_core::Error::Regex(e) => Error::Regex(e),
+            lflog_core::Error::Toml(e) => Error::Toml(e),
+            lflog_core::Error::TomlSerialize(e) => Error::TomlSerialize(e),
+            lflog_core::Error::Json(e) => Error::Json(e),
+            lflog_core::Error::Yaml(e) => Error::Yaml(e),
+            lflog_core::Error::MacroParse(s) => Error::MacroParse(s),
+            lflog_core::Error::Macro(s) => Error::Macro(s),
+            lflog_core::Error::Config(s) => Error::Config(s),
+            lflog_core::Error::Other(s) => Error::Other(s),
+        }
+    }
+}
+
 impl Error {
     /// Convenience constructor for `Other` variant.
     pub fn other<S: Into<String>>(s: S) -> Self {
         Error::Other(s.into())
     }
+
+    /// Convert a DataFusion error, unwrapping cancellation and timeout raised by a scan.
+    pub fn from_datafusion(error: datafusion_common::DataFusionError) -> Self {
+        if let datafusion_common::DataFusionError::External(inner) = error.find_root()
+            && let Some(lf_error) = inner.downcast_ref::<Error>()
+        {
+            match lf_error {
+                Error::Cancelled => return Error::Cancelled,
+                Error::Timeout(timeout) => return Error::Timeout(*timeout),
+                _ => {}
+            }
+        }
+        Error::DataFusion(error)
+    }
 }
@@ -0,0 +1,280 @@
+//! Export helpers for plotting query results.
+//!
+//! [`downsample`] reduces a timestamped result to at most N time buckets per series,
+//! keeping the min, max and time-weighted average of every numeric column, and
+//! [`write_csv`] saves a result to a CSV file.
+
+use datafusion::arrow::array::{Array, TimestampMicrosecondArray};
+use datafusion::arrow::datatypes::{DataType, IntervalMonthDayNano, TimeUnit};
+use datafusion::dataframe::DataFrameWriteOptions;
+use datafusion::functions::expr_fn::{coalesce, date_bin, nullif};
+use datafusion::functions_aggregate::expr_fn::{avg, count, max, min, sum};
+use datafusion::functions_window::expr_fn::lead;
+use datafusion::logical_expr::{ExprFunctionExt, when};
+use datafusion::prelude::{DataFrame, Expr, cast, ident, lit};
+use datafusion::scalar::ScalarValue;
+
+use crate::error::{Error, Result};
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+
+/// Column holding how long each sample lasts, in microseconds, while downsampling.
+const WEIGHT_COLUMN: &str = "__lflog_weight";
+
+/// Downsample `df` to at most `max_points` rows per series.
+///
+/// Rows are grouped into equal-width buckets of `time_column` (the first timestamp
+/// column when `None`), and by every other non-numeric column, which identify the
+/// series. Each numeric column `v` becomes `v_min`, `v_max` and `v_avg`, and `count`
+/// holds the number of rows in the bucket. Buckets are labelled with their start
+/// time and sorted by series, then time.
+///
+/// `v_avg` is weighted by time: each sample counts for the time until the next
+/// sample of its series, so a burst of samples doesn't outweigh a long steady
+/// stretch. The last sample of a series has no known duration and counts for
+/// nothing, unless no sample in the bucket has a duration, when `v_avg` is the
+/// plain average.
+pub async fn downsample(
+    df: DataFrame,
+    time_column: Option<&str>,
+    max_points: usize,
+) -> Result<DataFrame> {
+    if max_points == 0 {
+        return Err(Error::Config("max_points must be at least 1".into()));
+    }
+    let schema = df.schema().clone();
+    let time_column = match time_column {
+        Some(name) => schema
+            .fields()
+            .iter()
+            .find(|f| f.name() == name)
+            .ok_or_else(|| Error::Config(format!("No column '{}' to downsample by", name)))?,
+        None => schema
+            .fields()
+            .iter()
+            .find(|f| matches!(f.data_type(), DataType::Timestamp(_, _)))
+            .ok_or_else(|| Error::Config("No timestamp column to downsample by".into()))?,
+    }
+    .name()
+    .clone();
+
+    // The query runs once; bounds and buckets are computed from the cached result
+    let df = df.cache().await?;
+    let time = cast(
+        ident(&time_column),
+        DataType::Timestamp(TimeUnit::Microsecond, None),
+    );
+    let bounds = df
+        .clone()
+        .aggregate(vec![], vec![min(time.clone()), max(time.clone())])?
+        .collect()
+        .await?;
+    let bound = |i: usize| {
+        bounds.first().and_then(|batch| {
+            let array = batch
+                .column(i)
+                .as_any()
+                .downcast_ref::<TimestampMicrosecondArray>()?;
+            (!array.is_null(0)).then(|| array.value(0))
+        })
+    };
+    let (Some(first), Some(last)) = (bound(0), bound(1)) else {
+        return Ok(df);
+    };
+
+    // Width such that (last - first) / width < max_points, in whole seconds if longer
+    let mut width = (last - first) / max_points as i64 + 1;
+    if width > MICROS_PER_SECOND {
+        width = (width + MICROS_PER_SECOND - 1) / MICROS_PER_SECOND * MICROS_PER_SECOND;
+    }
+    let bucket = date_bin(
+        lit(ScalarValue::IntervalMonthDayNano(Some(
+            IntervalMonthDayNano::new(0, 0, width * 1000),
+        ))),
+        time.clone(),
+        lit(ScalarValue::TimestampMicrosecond(Some(first), None)),
+    )
+    .alias(&time_column);
+
+    let (numeric, series): (Vec<_>, Vec<_>) = schema
+        .fields()
+        .iter()
+        .filter(|f| *f.name() != time_column)
+        .partition(|f| f.data_type().is_numeric());
+    let series: Vec<Expr> = series.into_iter().map(|f| ident(f.name())).collect();
+
+    // Each sample lasts until the next one of its series
+    let micros = cast(time.clone(), DataType::Int64);
+    let next = lead(micros.clone(), Some(1), None)
+        .partition_by(series.clone())
+        .order_by(vec![time.sort(true, false)])
+        .build()?;
+    let df = df.window(vec![next.alias(WEIGHT_COLUMN)])?.with_column(
+        WEIGHT_COLUMN,
+        cast(ident(WEIGHT_COLUMN) - micros, DataType::Float64),
+    )?;
+
+    // The weighted sums are aggregated first, then divided
+    let hidden = |name: &str, part: &str| format!("__lflog_{}_{}", name, part);
+    let mut aggregates = Vec::new();
+    let mut columns = vec![ident(&time_column)];
+    columns.extend(series.iter().cloned());
+    for field in numeric {
+        let name = field.name();
+        let weight = when(ident(name).is_not_null(), ident(WEIGHT_COLUMN)).end()?;
+        let value = cast(ident(name), DataType::Float64) * ident(WEIGHT_COLUMN);
+        aggregates.push(min(ident(name)).alias(format!("{}_min", name)));
+        aggregates.push(max(ident(name)).alias(format!("{}_max", name)));
+        aggregates.push(sum(value).alias(hidden(name, "weighted")));
+        aggregates.push(sum(weight).alias(hidden(name, "duration")));
+        aggregates.push(avg(ident(name)).alias(hidden(name, "avg")));
+        let weighted =
+            ident(hidden(name, "weighted")) / nullif(ident(hidden(name, "duration")), lit(0.0));
+        columns.push(ident(format!("{}_min", name)));
+        columns.push(ident(format!("{}_max", name)));
+        columns.push(
+            coalesce(vec![weighted, ident(hidden(name, "avg"))]).alias(format!("{}_avg", name)),
+        );
+    }
+    aggregates.push(count(lit(1)).alias("count"));
+    columns.push(ident("count"));
+
+    let mut group = vec![bucket];
+    group.extend(series.iter().cloned());
+    let sort = series
+        .into_iter()
+        .chain(std::iter::once(ident(&time_column)))
+        .map(|e: Expr| e.sort(true, false))
+        .collect();
+    Ok(df
+        .aggregate(group, aggregates)?
+        .select(columns)?
+        .sort(sort)?)
+}
+
+/// Write the rows of `df` to a CSV file with a header line.
+pub async fn write_csv(df: DataFrame, path: &str) -> Result<()> {
+    df.write_csv(
+        path,
+        DataFrameWriteOptions::new().with_single_file_output(true),
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int64Array, StringArray};
+    use datafusion::prelude::SessionContext;
+
+    #[tokio::test]
+    async fn test_downsample_per_series() {
+        let ctx = SessionContext::new();
+        let df = ctx
+            .sql(
+                "SELECT to_timestamp_seconds(value) AS ts, \
+                 CASE WHEN value % 2 = 0 THEN 'even' ELSE 'odd' END AS host, value AS v \
+                 FROM generate_series(0, 999)",
+            )
+            .await
+            .unwrap();
+        let batches = downsample(df, None, 10)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch =
+            datafusion::arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+        let names: Vec<&str> = batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["ts", "host", "v_min", "v_max", "v_avg", "count"]
+        );
+
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let hosts = column("host");
+        let hosts = hosts.as_any().downcast_ref::<StringArray>().unwrap();
+        for host in ["even", "odd"] {
+            let rows = hosts.iter().filter(|h| *h == Some(host)).count();
+            assert!((9..=10).contains(&rows), "{} has {} rows", host, rows);
+        }
+        let counts = column("count");
+        let counts = counts.as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(counts.iter().flatten().sum::<i64>(), 1000);
+        let mins = column("v_min");
+        let mins = mins.as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(mins.iter().flatten().min(), Some(0));
+        let maxes = column("v_max");
+        let maxes = maxes.as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(maxes.iter().flatten().max(), Some(999));
+        let avgs = column("v_avg");
+        assert!(avgs.as_any().downcast_ref::<Float64Array>().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_downsample_weights_by_time() {
+        // 10 for one second, then 0 for nine: a plain average would be 3.33
+        let ctx = SessionContext::new();
+        let df = ctx
+            .sql(
+                "SELECT to_timestamp_seconds(column1) AS ts, column2 AS v \
+                 FROM (VALUES (0, 10), (1, 0), (10, 0))",
+            )
+            .await
+            .unwrap();
+        let batches = downsample(df, None, 1)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let avgs = batches[0].column_by_name("v_avg").unwrap();
+        let avgs = avgs.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(avgs.len(), 1);
+        assert_eq!(avgs.value(0), 1.0);
+
+        // A lone sample has no duration and keeps its value
+        let df = ctx
+            .sql("SELECT to_timestamp_seconds(5) AS ts, 7 AS v")
+            .await
+            .unwrap();
+        let batches = downsample(df, None, 10)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let avgs = batches[0].column_by_name("v_avg").unwrap();
+        let avgs = avgs.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(avgs.value(0), 7.0);
+    }
+
+    #[tokio::test]
+    async fn test_downsample_errors_and_csv() {
+        let ctx = SessionContext::new();
+        let df = ctx.sql("SELECT 1 AS v").await.unwrap();
+        let err = downsample(df, None, 10).await.unwrap_err();
+        assert!(err.to_string().contains("No timestamp column"), "{}", err);
+
+        let df = ctx
+            .sql("SELECT to_timestamp_seconds(value) AS ts, value AS v FROM generate_series(1, 4)")
+            .await
+            .unwrap();
+        let df = downsample(df, Some("ts"), 2).await.unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.csv");
+        write_csv(df, &path.to_string_lossy()).await.unwrap();
+        let csv = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "ts,v_min,v_max,v_avg,count");
+        assert_eq!(lines.len(), 3);
+    }
+}
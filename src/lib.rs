@@ -4,15 +4,27 @@
 //! macro expansions and querying them using SQL via DataFusion.
 
 pub mod app;
+pub mod auth;
+pub mod builtin;
+pub mod cancel;
+pub mod chart;
+pub mod coverage;
 pub mod datafusion;
 pub mod error;
-pub mod macros;
-pub mod scanner;
-pub mod types;
+pub mod export;
+pub mod plugin;
+pub mod registry;
+
+// The parser lives in lflog-core; keep its modules at their original paths
+pub use lflog_core::{
+    dl, extractor, macros, record, redact, rules, scanner, stats, transform, types, validate,
+};
 
 // Re-export commonly used items for convenience
 pub use app::{LfLog, QueryOptions};
+pub use cancel::CancellationToken;
 pub use datafusion::LogTableProvider;
-pub use macros::parser::Profiles;
+pub use extractor::{RecordExtractor, RecordFormat};
+pub use macros::parser::{ProfileFormat, Profiles};
 pub use scanner::Scanner;
-pub use types::FieldType;
+pub use types::{BinaryEncoding, FieldType, TimestampUnit, YearStrategy};
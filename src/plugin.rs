@@ -0,0 +1,342 @@
+//! Native plugins loaded at startup.
+//!
+//! A plugin is a shared library (`cdylib`) exporting two C symbols:
+//!
+//! - `lflog_plugin_abi_version() -> u32`, returning [`PLUGIN_ABI_VERSION`];
+//! - `lflog_plugin_manifest() -> *const c_char`, returning a NUL-terminated TOML
+//!   manifest that lives as long as the library.
+//!
+//! The manifest uses the profiles file layout, so a plugin can ship `custom_macros`
+//! and `profiles` (log sources), plus `[[functions]]` naming SQL functions the
+//! library implements:
+//!
+//! ```toml
+//! [[functions]]
+//! name = "mask_card"
+//! symbol = "lflog_mask_card"
+//! ```
+//!
+//! A function maps one string to a string or NULL and has the signature
+//! [`PluginFn`]: it reads `len` bytes of UTF-8 at `input`, passes its result to
+//! `write(ctx, data, len)` (which may be called several times to append) and returns
+//! 0, or returns 1 for NULL. Any other return value fails the query. NULL inputs give
+//! NULL without calling the plugin.
+
+use datafusion::arrow::array::{Array, StringArray, StringBuilder};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use serde::Deserialize;
+use std::any::Any;
+use std::ffi::{CStr, c_char, c_void};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::dl::Library;
+use crate::error::{Error, Result};
+use crate::macros::parser::{CustomMacro, Profile, Profiles};
+
+/// Version of the plugin ABI described in the module docs.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+const ABI_VERSION_SYMBOL: &str = "lflog_plugin_abi_version";
+const MANIFEST_SYMBOL: &str = "lflog_plugin_manifest";
+
+/// Callback a [`PluginFn`] passes its output to.
+pub type WriteFn = unsafe extern "C" fn(ctx: *mut c_void, data: *const u8, len: usize);
+
+/// A string function exported by a plugin.
+pub type PluginFn =
+    unsafe extern "C" fn(input: *const u8, len: usize, write: WriteFn, ctx: *mut c_void) -> i32;
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type ManifestFn = unsafe extern "C" fn() -> *const c_char;
+
+/// The manifest a plugin returns from `lflog_plugin_manifest`.
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    custom_macros: Vec<CustomMacro>,
+    #[serde(default)]
+    profiles: Vec<Profile>,
+    #[serde(default)]
+    functions: Vec<FunctionSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionSpec {
+    /// SQL name of the function.
+    name: String,
+    /// Exported symbol implementing it.
+    symbol: String,
+}
+
+/// A loaded plugin: the macros and profiles it adds and its SQL functions.
+pub struct Plugin {
+    pub path: String,
+    pub profiles: Profiles,
+    pub functions: Vec<ScalarUDF>,
+}
+
+impl Plugin {
+    /// Load the plugin library at `path` and read its manifest.
+    ///
+    /// The library stays loaded while any of its functions is registered.
+    pub fn load(path: &str) -> Result<Self> {
+        let library = Library::open(path)
+            .map_err(|e| Error::Config(format!("Cannot load plugin {}: {}", path, e)))?;
+        let library = Arc::new(library);
+        // Safety: the types are those the ABI documents, and every function keeps
+        // the library loaded
+        fn symbol<F: Copy>(library: &Library, path: &str, name: &str) -> Result<F> {
+            unsafe { library.symbol(name) }
+                .map_err(|e| Error::Config(format!("Plugin {}: {}", path, e)))
+        }
+        let abi_version: AbiVersionFn = symbol(&library, path, ABI_VERSION_SYMBOL)?;
+        let version = unsafe { abi_version() };
+        if version != PLUGIN_ABI_VERSION {
+            return Err(Error::Config(format!(
+                "Plugin {} uses ABI version {}, expected {}",
+                path, version, PLUGIN_ABI_VERSION
+            )));
+        }
+        let manifest: ManifestFn = symbol(&library, path, MANIFEST_SYMBOL)?;
+        let manifest = unsafe { manifest() };
+        if manifest.is_null() {
+            return Err(Error::Config(format!("Plugin {} has no manifest", path)));
+        }
+        let manifest: Manifest = toml::from_str(unsafe { CStr::from_ptr(manifest) }.to_str()?)?;
+
+        let functions = manifest
+            .functions
+            .iter()
+            .map(|spec| {
+                let function: PluginFn = symbol(&library, path, &spec.symbol)?;
+                Ok(ScalarUDF::new_from_impl(PluginFunction {
+                    name: spec.name.clone(),
+                    signature: Signature::string(1, Volatility::Immutable),
+                    function,
+                    _library: library.clone(),
+                }))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut profiles = Profiles {
+            custom_macros: manifest.custom_macros,
+            profiles: manifest.profiles,
+            plugins: Vec::new(),
+        };
+        for profile in profiles.profiles.iter_mut() {
+            profile.custom_macros.extend(profiles.custom_macros.clone());
+        }
+        Ok(Self {
+            path: path.to_string(),
+            profiles,
+            functions,
+        })
+    }
+}
+
+/// A plugin function registered as a SQL scalar function.
+#[derive(Debug)]
+struct PluginFunction {
+    name: String,
+    signature: Signature,
+    function: PluginFn,
+    /// Keeps the library mapped while the function can be called.
+    _library: Arc<Library>,
+}
+
+impl PartialEq for PluginFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && std::ptr::fn_addr_eq(self.function, other.function)
+    }
+}
+
+impl Eq for PluginFunction {}
+
+impl Hash for PluginFunction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        (self.function as usize).hash(state);
+    }
+}
+
+impl ScalarUDFImpl for PluginFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> datafusion::common::Result<DataType> {
+        Ok(DataType::Utf8)
+    }
+
+    fn invoke_with_args(
+        &self,
+        args: ScalarFunctionArgs,
+    ) -> datafusion::common::Result<ColumnarValue> {
+        let input = args.args[0].to_array(args.number_rows)?;
+        let input = cast(&input, &DataType::Utf8)?;
+        let input = input.as_any().downcast_ref::<StringArray>().unwrap();
+
+        let mut output = StringBuilder::with_capacity(input.len(), input.value_data().len());
+        let mut buffer = Vec::new();
+        for value in input.iter() {
+            let Some(value) = value else {
+                output.append_null();
+                continue;
+            };
+            buffer.clear();
+            let status = unsafe {
+                (self.function)(
+                    value.as_ptr(),
+                    value.len(),
+                    append,
+                    &mut buffer as *mut Vec<u8> as *mut c_void,
+                )
+            };
+            match status {
+                0 => output.append_value(String::from_utf8_lossy(&buffer)),
+                1 => output.append_null(),
+                status => {
+                    return Err(datafusion::error::DataFusionError::Execution(format!(
+                        "Plugin function {} failed with status {}",
+                        self.name, status
+                    )));
+                }
+            }
+        }
+        Ok(ColumnarValue::Array(Arc::new(output.finish())))
+    }
+}
+
+/// [`WriteFn`] appending to the `Vec<u8>` behind `ctx`.
+unsafe extern "C" fn append(ctx: *mut c_void, data: *const u8, len: usize) {
+    if data.is_null() || len == 0 {
+        return;
+    }
+    let buffer = unsafe { &mut *(ctx as *mut Vec<u8>) };
+    buffer.extend_from_slice(unsafe { std::slice::from_raw_parts(data, len) });
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::LfLog;
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+
+    const PLUGIN_SOURCE: &str = r#"
+use std::ffi::{c_char, c_void};
+
+#[unsafe(no_mangle)]
+pub extern "C" fn lflog_plugin_abi_version() -> u32 {
+    1
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn lflog_plugin_manifest() -> *const c_char {
+    concat!(
+        "custom_macros = [{ name = \"ticket\", pattern = '[A-Z]+-\\d+' }]\n",
+        "[[profiles]]\n",
+        "name = \"tickets\"\n",
+        "pattern = '^{{id:ticket}} {{msg:any}}$'\n",
+        "[[functions]]\n",
+        "name = \"shout\"\n",
+        "symbol = \"shout\"\n",
+        "\0"
+    )
+    .as_ptr() as *const c_char
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn shout(
+    input: *const u8,
+    len: usize,
+    write: unsafe extern "C" fn(*mut c_void, *const u8, usize),
+    ctx: *mut c_void,
+) -> i32 {
+    let input = unsafe { std::slice::from_raw_parts(input, len) };
+    if input.is_empty() {
+        return 1;
+    }
+    let upper = input.to_ascii_uppercase();
+    unsafe {
+        write(ctx, upper.as_ptr(), upper.len());
+        write(ctx, b"!".as_ptr(), 1);
+    }
+    0
+}
+"#;
+
+    /// Compile the test plugin with rustc, or `None` if rustc is unavailable.
+    fn build_plugin(dir: &std::path::Path) -> Option<String> {
+        let source = dir.join("plugin.rs");
+        std::fs::write(&source, PLUGIN_SOURCE).unwrap();
+        let output = dir.join(format!(
+            "{}plugin{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ));
+        let status = std::process::Command::new(
+            std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()),
+        )
+        .args(["--edition", "2024", "--crate-type", "cdylib", "-o"])
+        .arg(&output)
+        .arg(&source)
+        .status()
+        .ok()?;
+        assert!(status.success(), "test plugin failed to compile");
+        Some(output.to_string_lossy().into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(path) = build_plugin(dir.path()) else {
+            eprintln!("rustc not found, skipping plugin test");
+            return;
+        };
+        let plugin = Plugin::load(&path).unwrap();
+        assert!(plugin.profiles.get_macro("ticket").is_some());
+        assert_eq!(plugin.functions[0].name(), "shout");
+
+        let log = dir.path().join("app.log");
+        std::fs::write(&log, "OPS-1 disk full\nOPS-22 ok\n").unwrap();
+        let lflog = LfLog::new().with_plugin(&path).unwrap();
+        lflog
+            .register(crate::QueryOptions::new(log.to_string_lossy()).with_profile("tickets"))
+            .unwrap();
+        let batches = lflog
+            .query("SELECT id, shout(msg) AS loud FROM log")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let table = pretty_format_batches(&batches).unwrap().to_string();
+        assert!(table.contains("| OPS-1  | DISK FULL! |"), "{}", table);
+        assert!(table.contains("| OPS-22 | OK!        |"), "{}", table);
+        // The plugin returns NULL for empty input
+        let batches = lflog
+            .query("SELECT shout('') IS NULL AS empty")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let table = pretty_format_batches(&batches).unwrap().to_string();
+        assert!(table.contains("| true  |"), "{}", table);
+
+        assert!(Plugin::load(&dir.path().join("missing.so").to_string_lossy()).is_err());
+    }
+}
@@ -0,0 +1,373 @@
+//! Community profile registry.
+//!
+//! Downloads profile packs by registry name or direct URL, verifies their SHA-256
+//! checksum and installs them into the local profiles directory
+//! (`~/.config/lflog/profiles.d` by default). The default registry is the `registry/`
+//! directory of the repository. Only profile packs are fetched: lflog does not update
+//! itself.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::macros::parser::{ProfileFormat, Profiles};
+
+/// Default registry index, overridable with the `LFLOG_REGISTRY` environment variable.
+pub const DEFAULT_REGISTRY_URL: &str =
+    "https://raw.githubusercontent.com/WeiNyn/lflog/main/registry/index.json";
+
+/// A profile pack listed in the registry index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryEntry {
+    pub name: String,
+    pub url: String,
+    pub sha256: String,
+    pub description: Option<String>,
+}
+
+/// The registry index: the list of vetted profile packs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistryIndex {
+    #[serde(default)]
+    pub packs: Vec<RegistryEntry>,
+}
+
+impl RegistryIndex {
+    /// Download the registry index from `url`.
+    pub fn fetch(url: &str) -> Result<Self> {
+        let body = download(url)?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegistryEntry> {
+        self.packs.iter().find(|p| p.name == name)
+    }
+}
+
+/// Directory where fetched profile packs are installed.
+pub fn profiles_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("lflog").join("profiles.d"))
+}
+
+/// Resolve the registry index URL from `LFLOG_REGISTRY` or the default.
+pub fn registry_url() -> String {
+    std::env::var("LFLOG_REGISTRY").unwrap_or_else(|_| DEFAULT_REGISTRY_URL.to_string())
+}
+
+/// Fetch a profile pack and install it into `dest_dir`.
+///
+/// `source` is either a registry pack name or an `http(s)://` URL. Registry packs are
+/// verified against the checksum in the index. URLs need `expected_sha256`: a checksum
+/// served next to the pack would come from the same host, so it could not vouch for it.
+///
+/// The pack must parse as valid profiles before it is written. Returns the installed path.
+pub fn fetch_pack(source: &str, expected_sha256: Option<&str>, dest_dir: &Path) -> Result<PathBuf> {
+    let (name, url, checksum) = if is_url(source) {
+        let checksum = expected_sha256.ok_or_else(|| {
+            Error::Config(format!(
+                "Fetching {} needs the pack's SHA-256 checksum, use --sha256",
+                source
+            ))
+        })?;
+        (
+            pack_name_from_url(source)?,
+            source.to_string(),
+            checksum.to_string(),
+        )
+    } else {
+        let index = RegistryIndex::fetch(&registry_url())?;
+        let entry = index.get(source).ok_or_else(|| {
+            Error::Config(format!("Profile pack '{}' not found in registry", source))
+        })?;
+        if !is_pack_stem(&entry.name) {
+            return Err(Error::Config(format!(
+                "invalid pack name '{}' in registry, expected letters, digits, '-' and '_'",
+                entry.name
+            )));
+        }
+        let checksum = expected_sha256.unwrap_or(&entry.sha256).to_string();
+        let ext = Path::new(&entry.url)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("toml");
+        (
+            format!("{}.{}", entry.name, ext),
+            entry.url.clone(),
+            checksum,
+        )
+    };
+
+    let body = download(&url)?;
+    install_pack(&name, &body, &checksum, dest_dir)
+}
+
+/// Verify and write a downloaded pack into `dest_dir` as `file_name`.
+///
+/// `file_name` must be a plain name like `nginx.toml`, so the pack cannot land
+/// outside `dest_dir`.
+pub fn install_pack(
+    file_name: &str,
+    content: &[u8],
+    expected_sha256: &str,
+    dest_dir: &Path,
+) -> Result<PathBuf> {
+    let is_plain = file_name
+        .rsplit_once('.')
+        .is_some_and(|(stem, ext)| is_pack_stem(stem) && is_pack_extension(ext));
+    if !is_plain {
+        return Err(Error::Config(format!(
+            "invalid pack file name '{}', expected e.g. nginx.toml",
+            file_name
+        )));
+    }
+    verify_checksum(file_name, content, expected_sha256)?;
+
+    let text = std::str::from_utf8(content)?;
+    Profiles::from_str_with_format(text, ProfileFormat::from_path(file_name))?;
+
+    std::fs::create_dir_all(dest_dir)?;
+    let path = dest_dir.join(file_name);
+    std::fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// Load and merge every profile pack installed in `dir`.
+///
+/// Packs that fail to load are skipped with a warning, so one broken pack does not
+/// break every command. Returns `None` if the directory does not exist or holds no
+/// packs.
+pub fn load_installed(dir: &Path) -> Result<Option<Profiles>> {
+    if !dir.is_dir() {
+        return Ok(None);
+    }
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    paths.sort();
+
+    let mut merged: Option<Profiles> = None;
+    for path in paths {
+        let is_pack = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(is_pack_extension);
+        if !is_pack {
+            continue;
+        }
+        let mut profiles = match Profiles::from_file(&path.to_string_lossy()) {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                log::warn!("Skipping profile pack {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        // Downloaded packs may not load native code
+        profiles.plugins.clear();
+        match merged.as_mut() {
+            Some(m) => m.merge(profiles),
+            None => merged = Some(profiles),
+        }
+    }
+    Ok(merged)
+}
+
+/// Compute the lowercase hex SHA-256 digest of `content`.
+pub fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn verify_checksum(name: &str, content: &[u8], expected: &str) -> Result<()> {
+    let actual = sha256_hex(content);
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(Error::Checksum {
+            name: name.to_string(),
+            expected: expected.trim().to_string(),
+            actual,
+        });
+    }
+    Ok(())
+}
+
+/// Whether `stem` is a plain pack name: letters, digits, `-` and `_`, so no path
+/// separators or `..`.
+fn is_pack_stem(stem: &str) -> bool {
+    !stem.is_empty()
+        && stem
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn is_pack_extension(ext: &str) -> bool {
+    matches!(ext, "toml" | "json" | "yaml" | "yml")
+}
+
+fn pack_name_from_url(url: &str) -> Result<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let name = path.rsplit('/').next().unwrap_or_default();
+    if name.is_empty() {
+        return Err(Error::Config(format!(
+            "cannot derive a pack file name from URL: {}",
+            url
+        )));
+    }
+    if Path::new(name).extension().is_none() {
+        return Ok(format!("{}.toml", name));
+    }
+    Ok(name.to_string())
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("https://") || source.starts_with("http://")
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let mut response = ureq::get(url).call()?;
+    Ok(response.body_mut().read_to_vec()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"
+[[profiles]]
+name = "nginx"
+pattern = '{{client:string}} - {{msg:any}}'
+"#;
+
+    #[test]
+    fn test_install_pack_verifies_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let good = sha256_hex(PACK.as_bytes());
+
+        let err = install_pack("nginx.toml", PACK.as_bytes(), &"0".repeat(64), dir.path());
+        assert!(matches!(err, Err(Error::Checksum { .. })));
+        assert!(!dir.path().join("nginx.toml").exists());
+
+        let path = install_pack("nginx.toml", PACK.as_bytes(), &good, dir.path()).unwrap();
+        assert!(path.exists());
+
+        let installed = load_installed(dir.path()).unwrap().unwrap();
+        assert!(installed.get_profile("nginx").is_some());
+    }
+
+    #[test]
+    fn test_load_installed_skips_broken_packs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a-broken.toml"), "not = [valid").unwrap();
+        std::fs::write(dir.path().join("nginx.toml"), PACK).unwrap();
+        let installed = load_installed(dir.path()).unwrap().unwrap();
+        assert!(installed.get_profile("nginx").is_some());
+
+        std::fs::remove_file(dir.path().join("nginx.toml")).unwrap();
+        assert!(load_installed(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_install_pack_rejects_invalid_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = b"not = [valid";
+        let sum = sha256_hex(content);
+        assert!(install_pack("bad.toml", content, &sum, dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_install_pack_rejects_unsafe_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("profiles.d");
+        let sum = sha256_hex(PACK.as_bytes());
+        for name in [
+            "../../.bashrc.toml",
+            "../evil.toml",
+            "a/b.toml",
+            "a\\b.toml",
+            ".toml",
+            "nginx",
+            "nginx.sh",
+            "ng inx.toml",
+        ] {
+            assert!(
+                install_pack(name, PACK.as_bytes(), &sum, &sub).is_err(),
+                "{}",
+                name
+            );
+        }
+        assert!(!dir.path().join("evil.toml").exists());
+        assert!(
+            install_pack(
+                "my-pack_2.yaml",
+                b"profiles: []\n",
+                &sha256_hex(b"profiles: []\n"),
+                &sub
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_registry_index_matches_packs() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("registry");
+        let index: RegistryIndex =
+            serde_json::from_slice(&std::fs::read(root.join("index.json")).unwrap()).unwrap();
+        let base = DEFAULT_REGISTRY_URL.strip_suffix("index.json").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut installed = Vec::new();
+        for entry in &index.packs {
+            let file_name = entry.url.strip_prefix(base).unwrap();
+            let content = std::fs::read(root.join(file_name)).unwrap();
+            let name = format!("{}.toml", entry.name);
+            install_pack(&name, &content, &entry.sha256, dir.path())
+                .unwrap_or_else(|e| panic!("registry pack {}: {}", entry.name, e));
+            installed.push(entry.name.as_str());
+        }
+        assert_eq!(installed, ["nginx", "redis"]);
+
+        // Every profile matches the sample line above it
+        let profiles = load_installed(dir.path()).unwrap().unwrap();
+        for (name, line) in [
+            (
+                "nginx_access",
+                r#"93.180.71.3 - - [17/May/2015:08:05:32 +0000] "GET /downloads/product_1 HTTP/1.1" 304 0 "-" "Debian APT-HTTP/1.3 (0.8.16~exp12ubuntu10.21)""#,
+            ),
+            (
+                "nginx_error",
+                r#"2015/05/17 08:05:32 [error] 1234#1234: *7 open() "/usr/share/nginx/html/favicon.ico" failed (2: No such file or directory), client: 10.0.0.1, server: localhost, request: "GET /favicon.ico HTTP/1.1", host: "example.com""#,
+            ),
+            (
+                "redis",
+                "1:M 07 Oct 2024 12:00:00.123 * Ready to accept connections tcp",
+            ),
+        ] {
+            let profile = profiles.get_profile(name).unwrap();
+            let scanner = crate::scanner::Scanner::with_custom_macros(
+                profile.pattern.clone(),
+                Some(&profile.custom_macros),
+            )
+            .unwrap();
+            assert!(scanner.scan(line).is_some(), "{}: {}", name, line);
+        }
+    }
+
+    #[test]
+    fn test_url_pack_needs_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = fetch_pack("https://example.invalid/kafka.toml", None, dir.path()).unwrap_err();
+        assert!(err.to_string().contains("--sha256"), "{}", err);
+    }
+
+    #[test]
+    fn test_pack_name_from_url() {
+        assert_eq!(
+            pack_name_from_url("https://example.com/packs/kafka.yaml?raw=1").unwrap(),
+            "kafka.yaml"
+        );
+        assert_eq!(
+            pack_name_from_url("https://example.com/packs/kafka").unwrap(),
+            "kafka.toml"
+        );
+    }
+}